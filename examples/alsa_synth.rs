@@ -0,0 +1,58 @@
+// An example of a software synthesizer using the ALSA back-end.
+// The code that is shared between all backends is in the `example_synth.rs` file.
+//
+// Compiling
+// =========
+// You can compile this example with
+// ```
+// cargo build --release --examples --features backend-alsa
+// ```
+// This generates a standalone application that you can find
+// in `target/release/examples/alsa_synth`.
+// Note: ALSA is only available under Linux.
+//
+// Running
+// =======
+//
+// 1. Start the application generated during compiling.
+//    It plays on the "default" ALSA device and registers a client with the ALSA sequencer.
+// 2. (Optionally) start a midi keyboard simulator, e.g. `vkeybd`.
+// 3. Connect the midi keyboard output to the synthesizer midi input, e.g. with `aconnect`
+//    (`aconnect -l` lists the clients and their ports).
+// 4. Press keys on the midi keyboard.
+//
+// ## Logging
+// In order to enable logging, set the environment variable `RSYNTH_LOG_LEVEL` to
+// one of the supported log levels.
+// Recognized log levels are: 'off', 'error', 'warning', 'info', 'debug' and 'trace'.
+//
+// You can set the environment variable `RSYNTH_LOG_FILE` to the file name of the file in which
+// you want to log.
+//
+// Note that the example is also logging to a file in the realtime thread, which may cause clipping.
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+extern crate rsynth;
+
+mod example_synth;
+use example_synth::*;
+
+#[cfg(feature = "backend-alsa")]
+use rsynth::backend::alsa_backend::run;
+
+#[cfg(feature = "backend-alsa")]
+fn main() {
+    if let Err(e) = run(NoisePlayer::new()) {
+        println!("Unexpected error: {}", e);
+    }
+}
+
+#[cfg(not(feature = "backend-alsa"))]
+fn main() {
+    println!("This example was compiled without support for alsa.");
+    println!("Compile with passing `--features backend-alsa`");
+    println!("as parameter to `cargo`.");
+}
@@ -0,0 +1,74 @@
+// An example of a software synthesizer using the CLAP back-end.
+// The code that is shared between all backends is in the `example_synth` file.
+//
+// Compiling
+// =========
+// You can compile this example with
+// ```
+// cargo build --release --examples --features backend-clap
+// ```
+// This generates a library that you can find
+//
+// * under `target/release/examples/libclap_synth.so` for linux
+// * in the `target/release/examples/` folder for other operating systems.
+//
+// Running
+// =======
+//
+// ## Under Linux
+// Copy the `.so` file to `~/.clap/` and rename it so that its extension is `.clap`,
+// e.g. `~/.clap/clap_synth.clap`.
+//
+// ## Under Windows
+// Copy the `.dll` file to `C:\Program Files\Common Files\CLAP` and rename it so that its
+// extension is `.clap`.
+//
+// ## Under MacOs
+// TODO
+// Note: CLAP plugins need to be packaged as a bundle under MacOs.
+//
+// ## Logging
+// In order to enable logging, set the environment variable `RSYNTH_LOG_LEVEL` to
+// one of the supported log levels.
+// Recognized log levels are: 'off', 'error', 'warning', 'info', 'debug' and 'trace'.
+//
+// You can set the environment variable `RSYNTH_LOG_FILE` to the file name of the file in which
+// you want to log.
+//
+// Note that these environment variables need to be visible to the host.
+// Note that the example is also logging to a file in the realtime thread, which may cause clipping.
+
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+#[macro_use]
+extern crate rsynth;
+
+mod example_synth;
+use example_synth::*;
+
+#[cfg(feature = "backend-clap")]
+use rsynth::backend::clap_backend::ClapPluginMeta;
+
+#[cfg(feature = "backend-clap")]
+impl ClapPluginMeta for NoisePlayer {
+    fn clap_id(&self) -> &str {
+        "rsynth.example.noise"
+    }
+    fn vendor(&self) -> &str {
+        "rsynth"
+    }
+    fn features(&self) -> Vec<&str> {
+        vec!["instrument", "synthesizer"]
+    }
+}
+
+#[rustfmt::skip::macros(clap_init)]
+#[cfg(feature = "backend-clap")]
+clap_init!(
+    fn init() -> NoisePlayer {
+        NoisePlayer::new()
+    }
+);
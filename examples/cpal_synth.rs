@@ -0,0 +1,85 @@
+// An example of a software synthesizer using the cpal back-end.
+// The code that is shared between all backends is in the `example_synth.rs` file.
+//
+// Compiling
+// =========
+// You can compile this example with
+// ```
+// cargo build --release --examples --features backend-cpal
+// ```
+// This generates a standalone application that you can find
+//
+// * in `target/release/examples/cpal_synth` when you're using Linux
+// * under the `target/release/examples/` folder when you're using Windows or MacOs
+//
+// Running
+// =======
+//
+// Start the application generated during compiling. It plays on the default output device.
+// cpal does not support midi, so this example plays a few notes by sending midi events from
+// the main thread.
+//
+// ## Logging
+// In order to enable logging, set the environment variable `RSYNTH_LOG_LEVEL` to
+// one of the supported log levels.
+// Recognized log levels are: 'off', 'error', 'warning', 'info', 'debug' and 'trace'.
+//
+// You can set the environment variable `RSYNTH_LOG_FILE` to the file name of the file in which
+// you want to log.
+//
+// Note that the example is also logging to a file in the realtime thread, which may cause clipping.
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+extern crate rsynth;
+
+mod example_synth;
+use example_synth::*;
+
+#[cfg(feature = "backend-cpal")]
+use rsynth::backend::cpal_backend::{midi_queue, start};
+#[cfg(feature = "backend-cpal")]
+use rsynth::event::{Indexed, RawMidiEvent};
+#[cfg(feature = "backend-cpal")]
+use std::thread;
+#[cfg(feature = "backend-cpal")]
+use std::time::Duration;
+
+#[cfg(feature = "backend-cpal")]
+fn main() {
+    let (mut sender, receiver) = midi_queue(1024);
+    let handle = match start(NoisePlayer::new(), receiver) {
+        Ok(handle) => handle,
+        Err(e) => {
+            println!("Unexpected error: {}", e);
+            return;
+        }
+    };
+    for note in &[60, 64, 67, 72] {
+        let note_on = RawMidiEvent::new(&[0x90, *note, 100]);
+        sender
+            .try_send(Indexed {
+                index: 0,
+                event: note_on,
+            })
+            .ok();
+        thread::sleep(Duration::from_millis(500));
+        let note_off = RawMidiEvent::new(&[0x80, *note, 0]);
+        sender
+            .try_send(Indexed {
+                index: 0,
+                event: note_off,
+            })
+            .ok();
+    }
+    handle.stop();
+}
+
+#[cfg(not(feature = "backend-cpal"))]
+fn main() {
+    println!("This example was compiled without support for cpal.");
+    println!("Compile with passing `--features backend-cpal`");
+    println!("as parameter to `cargo`.");
+}
@@ -13,14 +13,17 @@ use polyphony::{
 };
 use rand::{thread_rng, Rng};
 use rsynth::event::{
-    ContextualEventHandler, EventHandler, Indexed, RawMidiEvent, SysExEvent, Timed,
+    ContextualEventHandler, EventHandler, Indexed, MetaEvent, ParameterChange, RawMidiEvent,
+    SysExEvent, Timed,
 };
-use rsynth::{AudioHandler, ContextualAudioRenderer};
+use rsynth::{AudioHandler, ContextualAudioRenderer, ParameterHandler};
 
 use midi_consts::channel_event::*;
 use rsynth::backend::HostInterface;
 use rsynth::buffer::AudioBufferInOut;
 use rsynth::meta::{InOut, Meta, MetaData};
+use rsynth::parameters::{ParameterInfo, ParameterRange, ParameterStore, Parameters};
+use std::sync::Arc;
 
 // The total number of samples to pre-calculate.
 // This is like recording a sample of white noise and then
@@ -29,6 +32,8 @@ use rsynth::meta::{InOut, Meta, MetaData};
 static SAMPLE_SIZE: usize = 65536;
 static NUMBER_OF_VOICES: usize = 6;
 static AMPLIFY_MULTIPLIER: f32 = 1.0 / NUMBER_OF_VOICES as f32;
+// The index of the "volume" parameter.
+const VOLUME_PARAMETER: usize = 0;
 
 // This struct defines the data that we will need to play one "noise"
 pub struct Noise {
@@ -65,7 +70,7 @@ impl Noise {
     // Here, we use one implementation over all floating point types.
     // If you want to use SIMD optimization, you can have separate implementations
     // for `f32` and `f64`.
-    fn render_audio_buffer<S>(&mut self, buffer: &mut AudioBufferInOut<S>)
+    fn render_audio_buffer<S>(&mut self, buffer: &mut AudioBufferInOut<S>, volume: f32)
     where
         S: AsPrim + Float,
     {
@@ -78,8 +83,8 @@ impl Noise {
             for sample in output_channel.iter_mut() {
                 // We "add" to the output.
                 // In this way, various noises can be heard together.
-                *sample =
-                    *sample + self.white_noise[self.position].as_::<S>() * self.amplitude.as_();
+                *sample = *sample
+                    + self.white_noise[self.position].as_::<S>() * (self.amplitude * volume).as_();
                 // Increment the position of our sound sample.
                 // We loop this easily by using modulo.
                 self.position = (self.position + 1) % self.white_noise.len();
@@ -120,6 +125,7 @@ impl EventHandler<Timed<RawMidiEvent>> for Noise {
 
 pub struct NoisePlayer {
     meta_data: MetaData<&'static str, &'static str, &'static str>,
+    parameters: Arc<ParameterStore<&'static str>>,
     voices: Vec<Noise>,
 }
 
@@ -138,6 +144,15 @@ impl NoisePlayer {
         }
     }
 
+    fn parameters() -> ParameterStore<&'static str> {
+        ParameterStore::new(vec![ParameterInfo {
+            name: "volume",
+            label: "",
+            range: ParameterRange::new(0.0, 1.0),
+            default_value: 1.0,
+        }])
+    }
+
     pub fn new() -> Self {
         let mut voices = Vec::new();
         for _ in 0..NUMBER_OF_VOICES {
@@ -145,11 +160,20 @@ impl NoisePlayer {
         }
         Self {
             meta_data: Self::meta_data(),
+            parameters: Arc::new(Self::parameters()),
             voices: voices,
         }
     }
 }
 
+impl ParameterHandler for NoisePlayer {
+    type Parameters = ParameterStore<&'static str>;
+
+    fn parameters(&self) -> Arc<Self::Parameters> {
+        self.parameters.clone()
+    }
+}
+
 impl Meta for NoisePlayer {
     type MetaData = MetaData<&'static str, &'static str, &'static str>;
 
@@ -176,8 +200,9 @@ where
             // Initialize the output buffer.
             buffer.outputs().set(S::zero());
         }
+        let volume = self.parameters.plain_value(VOLUME_PARAMETER);
         for noise in self.voices.iter_mut() {
-            noise.render_audio_buffer(buffer);
+            noise.render_audio_buffer(buffer, volume);
         }
     }
 }
@@ -217,3 +242,17 @@ impl<'a, Context> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Context
         // We don't do anything with SysEx events
     }
 }
+
+// Only needed for the standalone application, when rendering a midi file.
+impl<'a, Context> ContextualEventHandler<Indexed<Timed<MetaEvent<'a>>>, Context> for NoisePlayer {
+    fn handle_event(&mut self, _event: Indexed<Timed<MetaEvent<'a>>>, _context: &mut Context) {
+        // We don't do anything with meta events.
+    }
+}
+
+impl<Context> ContextualEventHandler<Timed<ParameterChange>, Context> for NoisePlayer {
+    fn handle_event(&mut self, _event: Timed<ParameterChange>, _context: &mut Context) {
+        // We don't need to do anything here: the new value is read from the parameters
+        // when rendering the audio.
+    }
+}
@@ -0,0 +1,38 @@
+// Create the LV2 bundle for the `lv2_synth` example.
+//
+// Usage
+// =====
+// First compile the `lv2_synth` example, then run
+// ```
+// cargo run --release --example lv2_bundle --features backend-lv2 -- \
+//     target/release/examples/liblv2_synth.so ~/.lv2/rsynth_lv2_synth.lv2
+// ```
+// The first argument is the plugin library, the second argument is the directory of the bundle
+// that is created.
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+extern crate rsynth;
+
+mod example_synth;
+mod lv2_synth_meta;
+use example_synth::*;
+
+use rsynth::backend::lv2_backend::ttl::create_bundle;
+use std::path::Path;
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().collect();
+    if arguments.len() != 3 {
+        eprintln!(
+            "Usage: {} <plugin library> <bundle directory>",
+            arguments[0]
+        );
+        std::process::exit(1);
+    }
+    let plugin = NoisePlayer::new();
+    create_bundle(&plugin, Path::new(&arguments[1]), Path::new(&arguments[2]))
+        .expect("Failed to create the bundle.");
+}
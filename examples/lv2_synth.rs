@@ -0,0 +1,61 @@
+// An example of a software synthesizer using the LV2 back-end.
+// The code that is shared between all backends is in the `example_synth.rs` file,
+// the LV2-specific meta-data is in the `lv2_synth_meta.rs` file.
+//
+// Compiling
+// =========
+// You can compile this example with
+// ```
+// cargo build --release --examples --features backend-lv2
+// ```
+// This generates a library that you can find
+//
+// * under `target/release/examples/liblv2_synth.so` for linux
+// * in the `target/release/examples/` folder for other operating systems.
+//
+// Running
+// =======
+//
+// ## Under Linux
+// LV2 hosts load plugins from a "bundle": a directory that contains the library together with
+// some Turtle files that describe the plugin. The `lv2_bundle` example creates the bundle:
+// ```
+// cargo run --release --example lv2_bundle --features backend-lv2 -- \
+//     target/release/examples/liblv2_synth.so ~/.lv2/rsynth_lv2_synth.lv2
+// ```
+//
+// ## Under Windows
+// TODO
+//
+// ## Under MacOs
+// TODO
+//
+// ## Logging
+// In order to enable logging, set the environment variable `RSYNTH_LOG_LEVEL` to
+// one of the supported log levels.
+// Recognized log levels are: 'off', 'error', 'warning', 'info', 'debug' and 'trace'.
+//
+// You can set the environment variable `RSYNTH_LOG_FILE` to the file name of the file in which
+// you want to log.
+//
+// Note that these environment variables need to be visible to the host.
+// Note that the example is also logging to a file in the realtime thread, which may cause clipping.
+
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+#[macro_use]
+extern crate rsynth;
+
+mod example_synth;
+mod lv2_synth_meta;
+use example_synth::*;
+
+#[rustfmt::skip::macros(lv2_init)]
+lv2_init!(
+    fn init() -> NoisePlayer {
+        NoisePlayer::new()
+    }
+);
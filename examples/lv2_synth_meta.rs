@@ -0,0 +1,14 @@
+// The LV2-specific meta-data of the example synthesizer.
+// This is shared between the `lv2_synth` and the `lv2_bundle` examples, so that the plugin
+// library and the generated bundle describe the same plugin.
+use crate::example_synth::NoisePlayer;
+use rsynth::backend::lv2_backend::Lv2PluginMeta;
+
+impl Lv2PluginMeta for NoisePlayer {
+    fn uri(&self) -> &str {
+        "https://github.com/PieterPenninckx/rsynth/examples/lv2_synth"
+    }
+    fn plugin_class(&self) -> Option<&str> {
+        Some("InstrumentPlugin")
+    }
+}
@@ -32,9 +32,9 @@ use rsynth::backend::combined::dummy::{AudioDummy, MidiDummy};
 #[cfg(feature = "backend-combined")]
 use rsynth::backend::combined::memory::AudioBufferWriter;
 #[cfg(feature = "backend-combined-midly")]
-use rsynth::backend::combined::midly::MidlyMidiReader;
+use rsynth::backend::combined::midly::MidlyMultiTrackReader;
 #[cfg(feature = "backend-combined")]
-use rsynth::backend::combined::run;
+use rsynth::backend::combined::{run, RunOptions};
 use rsynth::buffer::AudioChunk;
 use std::fs::OpenOptions;
 use std::{env, fs};
@@ -63,7 +63,7 @@ fn main() {
             samplerate,
             number_of_seconds * samplerate as usize,
         );
-        let midi_event_reader = MidlyMidiReader::new(smf.header, &smf.tracks[1]);
+        let midi_event_reader = MidlyMultiTrackReader::try_new(smf.header, &smf.tracks).unwrap();
         let midi_out = MidiDummy::new();
         println!("Rendering {} tracks of audio.", number_of_seconds);
         run(
@@ -71,8 +71,8 @@ fn main() {
             buffer_size_in_frames,
             audio_in,
             audio_buffer_writer,
-            midi_event_reader,
             midi_out,
+            RunOptions::new().midi_input(midi_event_reader),
         )
         .unwrap();
 
@@ -0,0 +1,60 @@
+// An example of a software synthesizer as a standalone application.
+// The code that is shared between all backends is in the `example_synth.rs` file.
+//
+// Compiling
+// =========
+// You can compile this example with
+// ```
+// cargo build --release --examples --features standalone
+// ```
+// Add the `backend-jack` feature to be able to run the synthesizer live:
+// ```
+// cargo build --release --examples --features standalone,backend-jack
+// ```
+// This generates a standalone application that you can find
+// in `target/release/examples/standalone_synth`.
+//
+// Running
+// =======
+//
+// Render a midi file offline:
+// ```
+// target/release/examples/standalone_synth --midi song.mid --output song.wav --sample-rate 48000
+// ```
+// Run the synthesizer live with Jack:
+// ```
+// target/release/examples/standalone_synth --client-name standalone_synth
+// ```
+// Pass `--help` to see all command line arguments.
+//
+// ## Logging
+// In order to enable logging, set the environment variable `RSYNTH_LOG_LEVEL` to
+// one of the supported log levels.
+// Recognized log levels are: 'off', 'error', 'warning', 'info', 'debug' and 'trace'.
+//
+// You can set the environment variable `RSYNTH_LOG_FILE` to the file name of the file in which
+// you want to log.
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+#[cfg_attr(feature = "standalone", macro_use)]
+extern crate rsynth;
+
+mod example_synth;
+use example_synth::*;
+
+#[cfg(feature = "standalone")]
+standalone_main!(
+    fn init() -> NoisePlayer {
+        NoisePlayer::new()
+    }
+);
+
+#[cfg(not(feature = "standalone"))]
+fn main() {
+    println!("This example was compiled without support for standalone applications.");
+    println!("Compile with passing `--features standalone`");
+    println!("as parameter to `cargo`.");
+}
@@ -0,0 +1,69 @@
+// An example of a software synthesizer using the VST3 back-end.
+// The code that is shared between all backends is in the `example_synth` file.
+//
+// Compiling
+// =========
+// You can compile this example with
+// ```
+// cargo build --release --examples --features backend-vst3
+// ```
+// This generates a library that you can find
+// under `target/release/examples/libvst3_synth.so`.
+// Note: the VST3 backend currently only supports Linux.
+//
+// Running
+// =======
+//
+// ## Under Linux
+// VST3 plugins are bundles. Create the following directory structure under `~/.vst3/`:
+// ```
+// ~/.vst3/vst3_synth.vst3/Contents/x86_64-linux/vst3_synth.so
+// ```
+// where `vst3_synth.so` is the `.so` file that was generated (renamed so that it has the
+// same name as the bundle).
+//
+// ## Logging
+// In order to enable logging, set the environment variable `RSYNTH_LOG_LEVEL` to
+// one of the supported log levels.
+// Recognized log levels are: 'off', 'error', 'warning', 'info', 'debug' and 'trace'.
+//
+// You can set the environment variable `RSYNTH_LOG_FILE` to the file name of the file in which
+// you want to log.
+//
+// Note that these environment variables need to be visible to the host.
+// Note that the example is also logging to a file in the realtime thread, which may cause clipping.
+
+#[macro_use]
+extern crate log;
+extern crate asprim;
+extern crate num_traits;
+extern crate rand;
+#[macro_use]
+extern crate rsynth;
+
+mod example_synth;
+use example_synth::*;
+
+#[cfg(feature = "backend-vst3")]
+use rsynth::backend::vst3_backend::Vst3PluginMeta;
+
+#[cfg(feature = "backend-vst3")]
+impl Vst3PluginMeta for NoisePlayer {
+    fn class_id(&self) -> [u8; 16] {
+        *b"rsynth.noise.vst"
+    }
+    fn vendor(&self) -> &str {
+        "rsynth"
+    }
+    fn sub_categories(&self) -> &str {
+        "Instrument|Synth"
+    }
+}
+
+#[rustfmt::skip::macros(vst3_init)]
+#[cfg(feature = "backend-vst3")]
+vst3_init!(
+    fn init() -> NoisePlayer {
+        NoisePlayer::new()
+    }
+);
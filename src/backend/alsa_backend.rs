@@ -0,0 +1,1120 @@
+//! Wrapper for the [ALSA] backend (behind the `backend-alsa` feature).
+//!
+//! Support is only enabled if you compile with the "backend-alsa" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//!
+//! This backend is meant for Linux systems where [JACK] is not available: audio is read from
+//! and written to an ALSA PCM device and midi is received from and sent to the
+//! ALSA sequencer.
+//!
+//! For an example, see `alsa_synth.rs` in the `examples` folder.
+//! `examples/example_synth` contains the code that is shared for all backends and
+//! `examples/alsa_synth.rs` contains the alsa-specific code.
+//!
+//! # Usage
+//! See the documentation of the [`run`] function.
+//! The [`run`] function blocks until the user presses a key or until the plugin calls
+//! [`stop`] on the [`AlsaHost`]. If you want to keep control, e.g. in a GUI application
+//! or a daemon, use the [`start`] function instead, which returns an [`AlsaHandle`] that can be
+//! used to stop the plugin.
+//!
+//! # Configuration
+//! Use an [`AlsaConfig`] with the [`run_with_config`] or [`start_with_config`] functions to
+//! choose the PCM devices, the sample rate, the period size and the name of the sequencer client.
+//! Sample rate and period size are requests: the plugin is informed of the sample rate that
+//! the device actually uses.
+//!
+//! # Audio
+//! The audio outputs of the plugin are written to the channels of the playback device.
+//! If the plugin has audio inputs, a capture device is opened as well and its channels are
+//! passed to the plugin. When the plugin has no audio outputs, silence is written to a single
+//! playback channel, so that the playback device still paces the processing.
+//!
+//! # Midi
+//! If the plugin has midi inputs or midi outputs, a client is registered with the ALSA
+//! sequencer and a sequencer port is created for every midi port, using the names from the
+//! [`CommonMidiPortMeta`] trait.
+//! Use a tool like `aconnect` to connect these ports to other clients.
+//!
+//! Incoming events are timestamped by the sequencer and delivered to the plugin as
+//! `Indexed<Timed<RawMidiEvent>>` (or `Indexed<Timed<SysExEvent>>` for longer messages)
+//! at the start of the next period, so that midi input is delayed by one period.
+//!
+//! The [`AlsaHost`] implements the [`MidiOutput`] trait. Events are scheduled on the queue of
+//! the sequencer client, relative to the start of the period that is being rendered.
+//!
+//! [`CommonMidiPortMeta`]: ../../trait.CommonMidiPortMeta.html
+//! [`MidiOutput`]: ../trait.MidiOutput.html
+//! [`stop`]: ../trait.HostInterface.html#method.stop
+//! [`start`]: ./fn.start.html
+//! [`AlsaHandle`]: ./struct.AlsaHandle.html
+//! [`AlsaHost`]: ./struct.AlsaHost.html
+//! [`AlsaConfig`]: ./struct.AlsaConfig.html
+//! [`run_with_config`]: ./fn.run_with_config.html
+//! [`start_with_config`]: ./fn.start_with_config.html
+//! [ALSA]: https://www.alsa-project.org/
+//! [JACK]: http://www.jackaudio.org/
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+//! [`run`]: ./fn.run.html
+use crate::backend::{HostInterface, MidiOutput, MidiOutputError, Stop};
+use crate::buffer::AudioBufferInOut;
+use crate::event::{
+    ContextualEventHandler, EventHandler, Indexed, RawMidiEvent, SysExEvent, Timed,
+};
+use crate::{
+    AudioHandler, CommonAudioPortMeta, CommonMidiPortMeta, CommonPluginMeta,
+    ContextualAudioRenderer,
+};
+use core::cmp;
+use std::ffi::CString;
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use vecstorage::VecStorage;
+
+/// Re-exports of the [`alsa`](https://crates.io/crates/alsa) crate.
+/// Use this so that your code doesn't break when `rsynth` upgrades its dependency on `alsa`.
+pub mod alsa {
+    pub use alsa::*;
+}
+
+use self::alsa::pcm::{Access, Format, Frames, HwParams, IO, PCM};
+use self::alsa::seq::{self, EventType, PortCap, PortInfo, PortType, Seq};
+use self::alsa::{Direction, ValueOr};
+
+/// The size of the buffers used to convert between sequencer events and midi bytes.
+/// This is the maximum length of a SysEx message that can be received or sent.
+const MIDI_BUFFER_SIZE: usize = 4096;
+
+/// Used to communicate with ALSA.
+///
+/// You don't need to instantiate this yourself: it is passed as the `context`
+/// parameter to the [`render_audio`] method when using the [`run`] function.
+///
+/// [`render_audio`]: ../../trait.ContextualAudioRenderer.html#tymethod.render_buffer
+/// [`run`]: ./fn.run.html
+pub struct AlsaHost<'a> {
+    midi_output: Option<SequencerOutput<'a>>,
+    sample_rate: u32,
+    stop_requested: bool,
+}
+
+impl<'a> AlsaHost<'a> {
+    /// Get access to the underlying [`Seq`] so that you can use sequencer-specific features,
+    /// or `None` if the plugin has no midi ports.
+    ///
+    /// [`Seq`]: ./alsa/seq/struct.Seq.html
+    pub fn sequencer(&self) -> Option<&'a Seq> {
+        self.midi_output.as_ref().map(|output| output.seq)
+    }
+
+    fn write_raw_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        bytes: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        let sample_rate = self.sample_rate;
+        match self.midi_output {
+            Some(ref mut midi_output) => {
+                midi_output.write(port_index, time_in_frames, sample_rate, bytes)
+            }
+            None => Err(MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports: 0,
+            }),
+        }
+    }
+}
+
+impl<'a> HostInterface for AlsaHost<'a> {
+    fn output_initialized(&self) -> bool {
+        false
+    }
+
+    fn stop(&mut self) {
+        self.stop_requested = true;
+    }
+}
+
+impl<'a> Stop for AlsaHost<'a> {}
+
+impl<'a> MidiOutput for AlsaHost<'a> {
+    fn number_of_midi_output_ports(&self) -> usize {
+        self.midi_output
+            .as_ref()
+            .map(|output| output.ports.len())
+            .unwrap_or(0)
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        self.write_raw_midi(port_index, time_in_frames, event.bytes())
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        self.write_raw_midi(port_index, time_in_frames, data)
+    }
+}
+
+impl<'a> EventHandler<Indexed<Timed<RawMidiEvent>>> for AlsaHost<'a> {
+    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_midi(index, event.time_in_frames, event.event) {
+            error!("Failed to write midi event: {}", e);
+        }
+    }
+}
+
+impl<'a, 'e> EventHandler<Indexed<Timed<SysExEvent<'e>>>> for AlsaHost<'a> {
+    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_sysex(index, event.time_in_frames, event.event.data()) {
+            error!("Failed to write SysEx event: {}", e);
+        }
+    }
+}
+
+/// The part of the [`Sequencer`] that is used by the [`AlsaHost`] to send midi events.
+struct SequencerOutput<'a> {
+    seq: &'a Seq,
+    encoder: &'a mut seq::MidiEvent,
+    queue: i32,
+    ports: &'a [i32],
+}
+
+impl<'a> SequencerOutput<'a> {
+    fn write(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        sample_rate: u32,
+        bytes: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        let port = *self
+            .ports
+            .get(port_index)
+            .ok_or(MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports: self.ports.len(),
+            })?;
+        self.encoder.reset_encode();
+        let mut event = match self.encoder.encode(bytes) {
+            Ok((_, Some(event))) => event,
+            // The bytes do not form a complete midi message.
+            Ok((_, None)) | Err(_) => return Err(MidiOutputError::Unsupported),
+        };
+        event.set_source(port);
+        event.set_subs();
+        event.schedule_real(
+            self.queue,
+            true,
+            frames_to_duration(time_in_frames, sample_rate),
+        );
+        self.seq.event_output(&mut event).map_err(|e| {
+            warn!("Failed to send event to the sequencer: {}", e);
+            MidiOutputError::BufferFull
+        })?;
+        Ok(())
+    }
+}
+
+/// Convert a number of frames to a duration.
+fn frames_to_duration(frames: u32, sample_rate: u32) -> Duration {
+    Duration::from_nanos(frames as u64 * 1_000_000_000 / cmp::max(sample_rate, 1) as u64)
+}
+
+/// Compute the position within the current buffer of an event that the sequencer has
+/// received at `event_time`.
+///
+/// The events that are handled before rendering a buffer have been received during the
+/// previous period, which started at `previous_period_start`. In order to keep the
+/// timing between the events, they are delayed by one period and clamped to the buffer.
+fn time_in_frames(
+    event_time: Duration,
+    previous_period_start: Duration,
+    sample_rate: u32,
+    number_of_frames: usize,
+) -> u32 {
+    if number_of_frames == 0 {
+        return 0;
+    }
+    let elapsed = event_time
+        .checked_sub(previous_period_start)
+        .unwrap_or_default();
+    let frames = elapsed.as_nanos() * sample_rate as u128 / 1_000_000_000;
+    cmp::min(frames, number_of_frames as u128 - 1) as u32
+}
+
+/// A client of the ALSA sequencer with a port for every midi input and midi output.
+struct Sequencer {
+    seq: Seq,
+    queue: i32,
+    input_ports: Vec<i32>,
+    output_ports: Vec<i32>,
+    encoder: seq::MidiEvent,
+    decoder: seq::MidiEvent,
+    decode_buffer: Vec<u8>,
+    previous_period_start: Duration,
+}
+
+impl Sequencer {
+    fn new<P>(client_name: &str, plugin: &P) -> Result<Self, alsa::Error>
+    where
+        P: CommonMidiPortMeta,
+    {
+        let seq = Seq::open(None, None, true)?;
+        seq.set_client_name(&c_string(client_name))?;
+        let queue = seq.alloc_queue()?;
+
+        let mut input_ports = Vec::with_capacity(plugin.max_number_of_midi_inputs());
+        for index in 0..plugin.max_number_of_midi_inputs() {
+            let mut name = String::new();
+            if let Err(e) = plugin.input_name(&mut name, index) {
+                error!(
+                    "Failed to get the name of midi input port with index {}: {}.",
+                    index, e
+                );
+                continue;
+            }
+            info!("Creating midi input port with name {}", name);
+            let capability = PortCap::WRITE | PortCap::SUBS_WRITE;
+            input_ports.push(create_port(&seq, &name, capability, Some(queue))?);
+        }
+
+        let mut output_ports = Vec::with_capacity(plugin.max_number_of_midi_outputs());
+        for index in 0..plugin.max_number_of_midi_outputs() {
+            let mut name = String::new();
+            if let Err(e) = plugin.output_name(&mut name, index) {
+                error!(
+                    "Failed to get the name of midi output port with index {}: {}.",
+                    index, e
+                );
+                continue;
+            }
+            info!("Creating midi output port with name {}", name);
+            let capability = PortCap::READ | PortCap::SUBS_READ;
+            output_ports.push(create_port(&seq, &name, capability, None)?);
+        }
+
+        seq.control_queue(queue, EventType::Start, 0, None)?;
+        seq.drain_output()?;
+
+        let decoder = seq::MidiEvent::new(MIDI_BUFFER_SIZE as u32)?;
+        decoder.enable_running_status(false);
+        Ok(Sequencer {
+            seq,
+            queue,
+            input_ports,
+            output_ports,
+            encoder: seq::MidiEvent::new(MIDI_BUFFER_SIZE as u32)?,
+            decoder,
+            decode_buffer: vec![0; MIDI_BUFFER_SIZE],
+            previous_period_start: Duration::default(),
+        })
+    }
+
+    fn queue_time(&self) -> Result<Duration, alsa::Error> {
+        Ok(self.seq.get_queue_status(self.queue)?.get_real_time())
+    }
+}
+
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).expect("nul bytes have been removed")
+}
+
+fn create_port(
+    seq: &Seq,
+    name: &str,
+    capability: PortCap,
+    timestamp_queue: Option<i32>,
+) -> Result<i32, alsa::Error> {
+    let mut port_info = PortInfo::empty()?;
+    port_info.set_name(&c_string(name));
+    port_info.set_capability(capability);
+    port_info.set_type(PortType::MIDI_GENERIC | PortType::APPLICATION);
+    if let Some(queue) = timestamp_queue {
+        port_info.set_timestamping(true);
+        port_info.set_timestamp_real(true);
+        port_info.set_timestamp_queue(queue);
+    }
+    seq.create_port(&port_info)?;
+    Ok(port_info.get_port())
+}
+
+/// Open a PCM device with the given number of channels and try to configure it with the
+/// sample rate and period size from the config.
+fn open_pcm(
+    device: &str,
+    direction: Direction,
+    channels: u32,
+    config: &AlsaConfig,
+) -> Result<PCM, alsa::Error> {
+    info!(
+        "Opening {:?} device {} with {} channels.",
+        direction, device, channels
+    );
+    let pcm = PCM::new(device, direction, false)?;
+    {
+        let hw_params = HwParams::any(&pcm)?;
+        hw_params.set_channels(channels)?;
+        hw_params.set_rate(config.sample_rate, ValueOr::Nearest)?;
+        hw_params.set_format(Format::float())?;
+        hw_params.set_access(Access::RWInterleaved)?;
+        let period_size =
+            hw_params.set_period_size_near(config.period_size_in_frames, ValueOr::Nearest)?;
+        hw_params.set_buffer_size_near(period_size * config.number_of_periods as Frames)?;
+        pcm.hw_params(&hw_params)?;
+    }
+    pcm.prepare()?;
+    Ok(pcm)
+}
+
+struct AlsaProcessHandler<P> {
+    plugin: P,
+    playback: PCM,
+    capture: Option<PCM>,
+    sequencer: Option<Sequencer>,
+    sample_rate: u32,
+    number_of_frames: usize,
+    interleaved_inputs: Vec<f32>,
+    interleaved_outputs: Vec<f32>,
+    input_buffers: Vec<Vec<f32>>,
+    output_buffers: Vec<Vec<f32>>,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static mut [f32]>,
+}
+
+impl<P> AlsaProcessHandler<P>
+where
+    P: CommonAudioPortMeta + CommonMidiPortMeta + CommonPluginMeta + AudioHandler,
+    for<'a> P: ContextualAudioRenderer<f32, AlsaHost<'a>>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, AlsaHost<'a>>,
+    for<'a, 'e> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, AlsaHost<'a>>,
+{
+    fn new(mut plugin: P, config: &AlsaConfig) -> Result<Self, alsa::Error> {
+        trace!("AlsaProcessHandler::new()");
+        let number_of_inputs = plugin.max_number_of_audio_inputs();
+        let number_of_outputs = plugin.max_number_of_audio_outputs();
+        // When the plugin has no audio outputs, the playback device is still used to pace
+        // the processing.
+        let playback_channels = cmp::max(number_of_outputs, 1);
+        let playback = open_pcm(
+            &config.playback_device,
+            Direction::Playback,
+            playback_channels as u32,
+            config,
+        )?;
+        let (sample_rate, period_size) = {
+            let hw_params = playback.hw_params_current()?;
+            (hw_params.get_rate()?, hw_params.get_period_size()?)
+        };
+        let number_of_frames = period_size as usize;
+        info!(
+            "Using a sample rate of {} Hz and a period size of {} frames.",
+            sample_rate, number_of_frames
+        );
+
+        let capture = if number_of_inputs > 0 {
+            let capture = open_pcm(
+                &config.capture_device,
+                Direction::Capture,
+                number_of_inputs as u32,
+                config,
+            )?;
+            capture.start()?;
+            Some(capture)
+        } else {
+            None
+        };
+
+        let sequencer =
+            if plugin.max_number_of_midi_inputs() > 0 || plugin.max_number_of_midi_outputs() > 0 {
+                let client_name = match config.client_name {
+                    Some(ref client_name) => client_name.clone(),
+                    None => plugin.name().to_string(),
+                };
+                Some(Sequencer::new(&client_name, &plugin)?)
+            } else {
+                None
+            };
+
+        plugin.set_sample_rate(sample_rate as f64);
+
+        Ok(AlsaProcessHandler {
+            plugin,
+            playback,
+            capture,
+            sequencer,
+            sample_rate,
+            number_of_frames,
+            interleaved_inputs: vec![0.0; number_of_inputs * number_of_frames],
+            interleaved_outputs: vec![0.0; playback_channels * number_of_frames],
+            input_buffers: vec![vec![0.0; number_of_frames]; number_of_inputs],
+            output_buffers: vec![vec![0.0; number_of_frames]; number_of_outputs],
+            inputs: VecStorage::with_capacity(number_of_inputs),
+            outputs: VecStorage::with_capacity(number_of_outputs),
+        })
+    }
+
+    fn handle_events(
+        sequencer: &mut Sequencer,
+        plugin: &mut P,
+        period_start: Duration,
+        number_of_frames: usize,
+        alsa_host: &mut AlsaHost,
+    ) -> Result<(), alsa::Error> {
+        // No tracing here, because this is called in the `process` function,
+        // and we do not want to trace that.
+        let Sequencer {
+            ref seq,
+            ref input_ports,
+            ref decoder,
+            ref mut decode_buffer,
+            ref mut previous_period_start,
+            ..
+        } = *sequencer;
+        let sample_rate = alsa_host.sample_rate;
+        let mut input = seq.input();
+        while input.event_input_pending(true)? > 0 {
+            let mut input_event = input.event_input()?;
+            let index = match input_ports
+                .iter()
+                .position(|port| *port == input_event.get_dest().port)
+            {
+                Some(index) => index,
+                None => continue,
+            };
+            let time_in_frames = match input_event.get_time() {
+                Some(time) => {
+                    time_in_frames(time, *previous_period_start, sample_rate, number_of_frames)
+                }
+                None => 0,
+            };
+            let length = match decoder.decode(decode_buffer, &mut input_event) {
+                Ok(length) => length,
+                // Not a midi event, e.g. a notification that a port has been connected.
+                Err(_) => continue,
+            };
+            let bytes = &decode_buffer[..length];
+            trace!("handle_events found event: {:?}", bytes);
+            if bytes.len() <= 3 {
+                if let Some(raw_event) = RawMidiEvent::try_new(bytes) {
+                    let event = Indexed {
+                        index,
+                        event: Timed {
+                            time_in_frames,
+                            event: raw_event,
+                        },
+                    };
+                    plugin.handle_event(event, alsa_host);
+                } else {
+                    warn!(
+                        "Strange event of length {}; ignoring this event.",
+                        bytes.len()
+                    );
+                }
+            } else {
+                let event = Indexed {
+                    index,
+                    event: Timed {
+                        time_in_frames,
+                        event: SysExEvent::new(bytes),
+                    },
+                };
+                plugin.handle_event(event, alsa_host);
+            }
+        }
+        *previous_period_start = period_start;
+        Ok(())
+    }
+
+    fn read_inputs(&mut self) -> Result<(), alsa::Error> {
+        let capture = match self.capture {
+            Some(ref capture) => capture,
+            None => return Ok(()),
+        };
+        let io = capture.io_f32()?;
+        if let Err(e) = read_all(capture, &io, &mut self.interleaved_inputs) {
+            warn!("Failed to read from the capture device: {}", e);
+            for sample in self.interleaved_inputs.iter_mut() {
+                *sample = 0.0;
+            }
+        }
+        let number_of_channels = self.input_buffers.len();
+        for (channel_index, channel) in self.input_buffers.iter_mut().enumerate() {
+            for (frame_index, sample) in channel.iter_mut().enumerate() {
+                *sample = self.interleaved_inputs[frame_index * number_of_channels + channel_index];
+            }
+        }
+        Ok(())
+    }
+
+    fn write_outputs(&mut self) -> Result<(), alsa::Error> {
+        let number_of_channels =
+            self.interleaved_outputs.len() / cmp::max(self.number_of_frames, 1);
+        for (frame_index, frame) in self
+            .interleaved_outputs
+            .chunks_mut(number_of_channels)
+            .enumerate()
+        {
+            for (channel_index, sample) in frame.iter_mut().enumerate() {
+                *sample = self
+                    .output_buffers
+                    .get(channel_index)
+                    .map(|channel| channel[frame_index])
+                    .unwrap_or(0.0);
+            }
+        }
+        let io = self.playback.io_f32()?;
+        write_all(
+            &self.playback,
+            &io,
+            &self.interleaved_outputs,
+            number_of_channels,
+        )
+    }
+
+    /// Process one period. Returns `false` when the plugin has requested to stop.
+    fn process(&mut self) -> Result<bool, alsa::Error> {
+        self.read_inputs()?;
+        let keep_running = self.render()?;
+        self.write_outputs()?;
+        Ok(keep_running)
+    }
+
+    fn render(&mut self) -> Result<bool, alsa::Error> {
+        let AlsaProcessHandler {
+            ref mut plugin,
+            ref mut sequencer,
+            ref input_buffers,
+            ref mut output_buffers,
+            ref mut inputs,
+            ref mut outputs,
+            sample_rate,
+            number_of_frames,
+            ..
+        } = *self;
+
+        let mut alsa_host = AlsaHost {
+            midi_output: None,
+            sample_rate,
+            stop_requested: false,
+        };
+        if let Some(ref mut sequencer) = *sequencer {
+            let period_start = sequencer.queue_time()?;
+            Self::handle_events(
+                sequencer,
+                plugin,
+                period_start,
+                number_of_frames,
+                &mut alsa_host,
+            )?;
+            alsa_host.midi_output = Some(SequencerOutput {
+                seq: &sequencer.seq,
+                encoder: &mut sequencer.encoder,
+                queue: sequencer.queue,
+                ports: &sequencer.output_ports,
+            });
+        }
+
+        let mut inputs = inputs.vec_guard();
+        for input_buffer in input_buffers.iter() {
+            inputs.push(input_buffer.as_slice());
+        }
+        let mut outputs = outputs.vec_guard();
+        for output_buffer in output_buffers.iter_mut() {
+            outputs.push(output_buffer.as_mut_slice());
+        }
+        let mut buffer =
+            AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
+        plugin.render_buffer(&mut buffer, &mut alsa_host);
+
+        let stop_requested = alsa_host.stop_requested;
+        if let Some(midi_output) = alsa_host.midi_output {
+            midi_output.seq.drain_output()?;
+        }
+        Ok(!stop_requested)
+    }
+}
+
+/// Read a full period from the capture device, recovering from overruns.
+fn read_all(pcm: &PCM, io: &IO<f32>, buffer: &mut [f32]) -> Result<(), alsa::Error> {
+    match io.readi(buffer) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            pcm.try_recover(e, true)?;
+            Err(e)
+        }
+    }
+}
+
+/// Write a full period to the playback device, recovering from underruns.
+fn write_all(
+    pcm: &PCM,
+    io: &IO<f32>,
+    buffer: &[f32],
+    number_of_channels: usize,
+) -> Result<(), alsa::Error> {
+    let mut position = 0;
+    while position < buffer.len() {
+        match io.writei(&buffer[position..]) {
+            Ok(frames) => position += frames * number_of_channels,
+            Err(e) => {
+                warn!("Underrun of the playback device: {}", e);
+                pcm.try_recover(e, true)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// State that is shared between the [`AlsaHandle`] and the processing thread.
+#[derive(Default)]
+struct SharedState {
+    stop_requested: AtomicBool,
+    stopped: AtomicBool,
+}
+
+/// A handle to a plugin that is running with ALSA, as returned by the [`start`] function.
+///
+/// The plugin is running on a separate thread as long as processing has not stopped.
+/// Call [`stop`] to stop processing or [`join`] to wait until processing stops.
+/// Both return the plugin.
+///
+/// [`start`]: ./fn.start.html
+/// [`stop`]: #method.stop
+/// [`join`]: #method.join
+pub struct AlsaHandle<P> {
+    thread: JoinHandle<Result<P, alsa::Error>>,
+    shared_state: Arc<SharedState>,
+}
+
+impl<P> AlsaHandle<P> {
+    /// Return `true` when processing has stopped, either because the plugin called
+    /// [`stop`] on the [`AlsaHost`], or because an error occurred.
+    ///
+    /// [`stop`]: ../trait.HostInterface.html#method.stop
+    /// [`AlsaHost`]: ./struct.AlsaHost.html
+    pub fn is_stopped(&self) -> bool {
+        self.shared_state.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Stop processing after the current period, close the devices and return the plugin.
+    pub fn stop(self) -> Result<P, alsa::Error> {
+        info!("Stopping processing...");
+        self.shared_state
+            .stop_requested
+            .store(true, Ordering::SeqCst);
+        self.join()
+    }
+
+    /// Block until processing has stopped (see [`is_stopped`]), then close the devices
+    /// and return the plugin.
+    ///
+    /// [`is_stopped`]: #method.is_stopped
+    pub fn join(self) -> Result<P, alsa::Error> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(e) => panic::resume_unwind(e),
+        }
+    }
+}
+
+/// Configuration for the ALSA backend, to be used with the [`run_with_config`] and
+/// [`start_with_config`] functions.
+///
+/// # Example
+/// ```
+/// use rsynth::backend::alsa_backend::AlsaConfig;
+/// let config = AlsaConfig::new()
+///     .playback_device("hw:0")
+///     .sample_rate(48000)
+///     .period_size_in_frames(128)
+///     .client_name("my_synth");
+/// ```
+///
+/// [`run_with_config`]: ./fn.run_with_config.html
+/// [`start_with_config`]: ./fn.start_with_config.html
+#[derive(Clone, Debug)]
+pub struct AlsaConfig {
+    playback_device: String,
+    capture_device: String,
+    sample_rate: u32,
+    period_size_in_frames: Frames,
+    number_of_periods: u32,
+    client_name: Option<String>,
+}
+
+impl Default for AlsaConfig {
+    fn default() -> Self {
+        AlsaConfig {
+            playback_device: "default".to_string(),
+            capture_device: "default".to_string(),
+            sample_rate: 44100,
+            period_size_in_frames: 256,
+            number_of_periods: 2,
+            client_name: None,
+        }
+    }
+}
+
+impl AlsaConfig {
+    /// Create a new `AlsaConfig` with the default configuration:
+    /// the "default" devices are used for playback and capture, with a sample rate of
+    /// 44100 Hz and two periods of 256 frames, and the name of the plugin is used as the
+    /// name of the sequencer client.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the PCM device with the given name for playback, e.g. `"hw:0"` or `"null"`.
+    pub fn playback_device<S: Into<String>>(mut self, device: S) -> Self {
+        self.playback_device = device.into();
+        self
+    }
+
+    /// Use the PCM device with the given name for capture.
+    /// The capture device is only opened if the plugin has audio inputs.
+    pub fn capture_device<S: Into<String>>(mut self, device: S) -> Self {
+        self.capture_device = device.into();
+        self
+    }
+
+    /// Request the given sample rate (44100 by default).
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Request the given number of frames per period (256 by default).
+    /// This is the number of frames that is rendered at a time.
+    pub fn period_size_in_frames(mut self, period_size_in_frames: u32) -> Self {
+        self.period_size_in_frames = period_size_in_frames as Frames;
+        self
+    }
+
+    /// Request the given number of periods in the buffer of the device (2 by default).
+    pub fn number_of_periods(mut self, number_of_periods: u32) -> Self {
+        self.number_of_periods = number_of_periods;
+        self
+    }
+
+    /// Use the given name for the sequencer client instead of the name of the plugin.
+    pub fn client_name<S: Into<String>>(mut self, client_name: S) -> Self {
+        self.client_name = Some(client_name.into());
+        self
+    }
+}
+
+/// Start running the plugin with ALSA without blocking, using the default [`AlsaConfig`].
+///
+/// The plugin keeps running until [`stop`] is called on the returned [`AlsaHandle`].
+/// Use [`join`] to wait until the plugin calls [`stop`] on the [`AlsaHost`].
+///
+/// # Example
+/// ```no_run
+/// # use rsynth::backend::alsa_backend::AlsaHandle;
+/// # fn start<P>(plugin: P) -> Result<AlsaHandle<P>, ()> { unimplemented!() }
+/// # fn example<P>(plugin: P) -> Result<(), ()> {
+/// let handle = start(plugin)?;
+/// // Do something else, e.g. run a GUI.
+/// let plugin = handle.stop();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`AlsaConfig`]: ./struct.AlsaConfig.html
+/// [`stop`]: ./struct.AlsaHandle.html#method.stop
+/// [`join`]: ./struct.AlsaHandle.html#method.join
+/// [`AlsaHandle`]: ./struct.AlsaHandle.html
+/// [`AlsaHost`]: ./struct.AlsaHost.html
+pub fn start<P>(plugin: P) -> Result<AlsaHandle<P>, alsa::Error>
+where
+    P: CommonPluginMeta + AudioHandler + CommonAudioPortMeta + CommonMidiPortMeta + Send + 'static,
+    for<'a> P: ContextualAudioRenderer<f32, AlsaHost<'a>>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, AlsaHost<'a>>,
+    for<'a, 'e> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, AlsaHost<'a>>,
+{
+    start_with_config(plugin, AlsaConfig::default())
+}
+
+/// Start running the plugin with ALSA without blocking, using the given configuration.
+///
+/// The devices are opened on the processing thread; errors that occur while opening them
+/// are returned by this function.
+/// See the [`start`] function for more information.
+///
+/// [`start`]: ./fn.start.html
+pub fn start_with_config<P>(plugin: P, config: AlsaConfig) -> Result<AlsaHandle<P>, alsa::Error>
+where
+    P: CommonPluginMeta + AudioHandler + CommonAudioPortMeta + CommonMidiPortMeta + Send + 'static,
+    for<'a> P: ContextualAudioRenderer<f32, AlsaHost<'a>>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, AlsaHost<'a>>,
+    for<'a, 'e> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, AlsaHost<'a>>,
+{
+    let shared_state = Arc::new(SharedState::default());
+    let thread_state = shared_state.clone();
+    let (setup_sender, setup_receiver) = mpsc::sync_channel(1);
+    let thread = thread::spawn(move || {
+        // `PCM` is not `Send`, so the devices are opened on the processing thread.
+        let mut process_handler = match AlsaProcessHandler::new(plugin, &config) {
+            Ok(process_handler) => {
+                setup_sender.send(Ok(())).ok();
+                process_handler
+            }
+            Err(e) => {
+                thread_state.stopped.store(true, Ordering::SeqCst);
+                setup_sender.send(Err(e)).ok();
+                return Err(e);
+            }
+        };
+        let mut result = Ok(());
+        while !thread_state.stop_requested.load(Ordering::SeqCst) {
+            match process_handler.process() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    error!("Stopping because of an ALSA error: {}", e);
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if let Err(e) = process_handler.playback.drain() {
+            warn!("Failed to drain the playback device: {}", e);
+        }
+        thread_state.stopped.store(true, Ordering::SeqCst);
+        result.map(|_| process_handler.plugin)
+    });
+
+    match setup_receiver.recv() {
+        Ok(Ok(())) => Ok(AlsaHandle {
+            thread,
+            shared_state,
+        }),
+        Ok(Err(e)) => {
+            thread.join().ok();
+            Err(e)
+        }
+        // The thread panicked before it could report the result of the setup.
+        Err(_) => match thread.join() {
+            Err(e) => panic::resume_unwind(e),
+            Ok(result) => result.map(|_| unreachable!("setup succeeded without reporting")),
+        },
+    }
+}
+
+/// The interval with which [`run_with_config`] checks if processing has stopped.
+///
+/// [`run_with_config`]: ./fn.run_with_config.html
+const STOP_POLLING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Run the plugin until the user presses a key on the computer keyboard or the plugin
+/// requests the `AlsaHost` to stop.
+///
+/// This is a thin wrapper around the [`start`] function.
+///
+/// [`start`]: ./fn.start.html
+pub fn run<P>(plugin: P) -> Result<P, alsa::Error>
+where
+    P: CommonPluginMeta + AudioHandler + CommonAudioPortMeta + CommonMidiPortMeta + Send + 'static,
+    for<'a> P: ContextualAudioRenderer<f32, AlsaHost<'a>>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, AlsaHost<'a>>,
+    for<'a, 'e> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, AlsaHost<'a>>,
+{
+    run_with_config(plugin, AlsaConfig::default())
+}
+
+/// Same as the [`run`] function, but with the given configuration.
+///
+/// # Example
+/// ```no_run
+/// # use rsynth::backend::alsa_backend::AlsaConfig;
+/// # fn run_with_config<P>(plugin: P, config: AlsaConfig) -> Result<P, ()> { Ok(plugin) }
+/// # fn example<P>(plugin: P) -> Result<(), ()> {
+/// let config = AlsaConfig::new().playback_device("hw:1").period_size_in_frames(128);
+/// let plugin = run_with_config(plugin, config)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`run`]: ./fn.run.html
+pub fn run_with_config<P>(plugin: P, config: AlsaConfig) -> Result<P, alsa::Error>
+where
+    P: CommonPluginMeta + AudioHandler + CommonAudioPortMeta + CommonMidiPortMeta + Send + 'static,
+    for<'a> P: ContextualAudioRenderer<f32, AlsaHost<'a>>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, AlsaHost<'a>>,
+    for<'a, 'e> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, AlsaHost<'a>>,
+{
+    let handle = start_with_config(plugin, config)?;
+
+    // Wait for a key press on a separate thread, so that we also return when the plugin stops.
+    let (key_sender, key_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        println!("Press any key to quit");
+        let mut user_input = String::new();
+        io::stdin().read_line(&mut user_input).ok();
+        key_sender.send(()).ok();
+    });
+    while !handle.is_stopped() {
+        if key_receiver.recv_timeout(STOP_POLLING_INTERVAL).is_ok() {
+            break;
+        }
+    }
+
+    handle.stop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::{InOut, Meta, MetaData};
+
+    struct TestPlugin {
+        meta: MetaData<&'static str, &'static str, &'static str>,
+        sample_rate: f64,
+        number_of_buffers: usize,
+        number_of_frames: usize,
+    }
+
+    impl TestPlugin {
+        fn new(midi_port_meta: InOut<&'static str>) -> Self {
+            TestPlugin {
+                meta: MetaData {
+                    general_meta: "test plugin",
+                    audio_port_meta: InOut {
+                        inputs: vec![],
+                        outputs: vec!["out left", "out right"],
+                    },
+                    midi_port_meta,
+                },
+                sample_rate: 0.0,
+                number_of_buffers: 0,
+                number_of_frames: 0,
+            }
+        }
+    }
+
+    impl Meta for TestPlugin {
+        type MetaData = MetaData<&'static str, &'static str, &'static str>;
+        fn meta(&self) -> &Self::MetaData {
+            &self.meta
+        }
+    }
+
+    impl AudioHandler for TestPlugin {
+        fn set_sample_rate(&mut self, sample_rate: f64) {
+            self.sample_rate = sample_rate;
+        }
+    }
+
+    impl<'a> ContextualAudioRenderer<f32, AlsaHost<'a>> for TestPlugin {
+        fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut AlsaHost) {
+            self.number_of_frames += buffer.number_of_frames();
+            self.number_of_buffers += 1;
+            if self.number_of_buffers == 3 {
+                context.stop();
+            }
+        }
+    }
+
+    impl<'a> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, AlsaHost<'a>> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut AlsaHost) {
+            context.handle_event(event);
+        }
+    }
+
+    impl<'a, 'e> ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, AlsaHost<'a>> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'e>>>, context: &mut AlsaHost) {
+            context.handle_event(event);
+        }
+    }
+
+    #[test]
+    fn time_in_frames_is_relative_to_the_previous_period() {
+        let start = Duration::from_millis(1000);
+        assert_eq!(time_in_frames(start, start, 48000, 256), 0);
+        assert_eq!(
+            time_in_frames(start + Duration::from_millis(1), start, 48000, 256),
+            48
+        );
+        assert_eq!(
+            time_in_frames(start + Duration::from_micros(2500), start, 44100, 256),
+            110
+        );
+    }
+
+    #[test]
+    fn time_in_frames_is_clamped_to_the_buffer() {
+        let start = Duration::from_millis(1000);
+        assert_eq!(
+            time_in_frames(start - Duration::from_millis(1), start, 48000, 256),
+            0
+        );
+        assert_eq!(
+            time_in_frames(start + Duration::from_millis(10), start, 48000, 256),
+            255
+        );
+        assert_eq!(time_in_frames(start, start, 48000, 0), 0);
+    }
+
+    #[test]
+    fn frames_to_duration_uses_the_sample_rate() {
+        assert_eq!(frames_to_duration(48, 48000), Duration::from_millis(1));
+        assert_eq!(frames_to_duration(0, 44100), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn writing_midi_without_sequencer_returns_an_error() {
+        let mut host = AlsaHost {
+            midi_output: None,
+            sample_rate: 44100,
+            stop_requested: false,
+        };
+        assert_eq!(host.number_of_midi_output_ports(), 0);
+        let event = RawMidiEvent::new(&[0x90, 60, 100]);
+        assert_eq!(
+            host.write_midi(0, 0, event),
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index: 0,
+                number_of_ports: 0
+            })
+        );
+        host.stop();
+        assert!(host.stop_requested);
+    }
+
+    #[test]
+    #[ignore = "requires the ALSA null device; run with `cargo test -- --ignored`"]
+    fn plugin_renders_to_the_null_device_until_it_stops() {
+        let plugin = TestPlugin::new(InOut {
+            inputs: vec![],
+            outputs: vec![],
+        });
+        let config = AlsaConfig::new()
+            .playback_device("null")
+            .sample_rate(48000)
+            .period_size_in_frames(64);
+        let handle = start_with_config(plugin, config).expect("cannot open the ALSA null device");
+        let plugin = handle
+            .join()
+            .expect("processing should stop without errors");
+        assert_eq!(plugin.number_of_buffers, 3);
+        assert!(plugin.number_of_frames > 0);
+        assert!(plugin.sample_rate > 0.0);
+    }
+}
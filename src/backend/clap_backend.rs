@@ -0,0 +1,1499 @@
+//! Wrapper for the [CLAP] plugin backend (behind the `backend-clap` feature).
+//!
+//! Support is only enabled if you compile with the "backend-clap" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//!
+//! For an example, see `clap_synth.rs` in the `examples` folder.
+//! `examples/example_synth` contains the code that is shared for all backends and
+//! `examples/clap_synth.rs` contains the CLAP-specific code.
+//!
+//! # Usage
+//! See the documentation of the [`clap_init`] macro.
+//!
+//! # Audio ports
+//! The audio inputs of the plugin are exposed to the host as one CLAP audio input port with one
+//! channel per audio input of the plugin; the same holds for the audio outputs.
+//! Only 32-bit audio is supported.
+//!
+//! # Note ports
+//! Each midi input and each midi output of the plugin (as defined by the
+//! [`CommonMidiPortMeta`] trait) is exposed to the host as a CLAP note port.
+//! The note ports prefer the midi dialect. Note on and note off events in the CLAP dialect are
+//! converted to midi events.
+//!
+//! # Events
+//! Events are delivered to the plugin before the audio of the buffer is rendered, with the
+//! timestamp that was given by the host, so plugins can handle them sample-accurately.
+//! The index of the note port is used as the index of the [`Indexed`] event.
+//!
+//! # Limitations
+//! Parameters, saving and restoring the state and GUI's are not supported (yet).
+//!
+//! [CLAP]: https://cleveraudio.org/
+//! [`clap_init`]: ../../macro.clap_init.html
+//! [`CommonMidiPortMeta`]: ../../trait.CommonMidiPortMeta.html
+//! [`Indexed`]: ../../event/struct.Indexed.html
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+use crate::backend::{
+    HostInterface, MidiOutput, MidiOutputError, PortNames, TimeSignature, Transport, TransportInfo,
+};
+use crate::buffer::AudioBufferInOut;
+use crate::event::{
+    ContextualEventHandler, EventHandler, Indexed, RawMidiEvent, SysExEvent, Timed,
+};
+use crate::{
+    AudioHandler, CommonAudioPortMeta, CommonMidiPortMeta, CommonPluginMeta,
+    ContextualAudioRenderer,
+};
+use core::cmp;
+use midi_consts::channel_event::{NOTE_OFF, NOTE_ON};
+use std::cell::UnsafeCell;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use vecstorage::VecStorage;
+
+/// Re-exports from the [`clap-sys`](https://crates.io/crates/clap-sys) crate.
+/// Use this in libraries so that your library does not break when `rsynth` upgrades to another
+/// version of the `clap-sys` crate.
+pub mod clap_sys {
+    pub use clap_sys::*;
+}
+
+use self::clap_sys::{
+    audio_buffer::clap_audio_buffer,
+    entry::clap_plugin_entry,
+    events::{
+        clap_event_header, clap_event_midi, clap_event_midi_sysex, clap_event_note,
+        clap_event_transport, clap_input_events, clap_output_events, CLAP_CORE_EVENT_SPACE_ID,
+        CLAP_EVENT_MIDI, CLAP_EVENT_MIDI_SYSEX, CLAP_EVENT_NOTE_OFF, CLAP_EVENT_NOTE_ON,
+        CLAP_TRANSPORT_HAS_BEATS_TIMELINE, CLAP_TRANSPORT_HAS_SECONDS_TIMELINE,
+        CLAP_TRANSPORT_HAS_TEMPO, CLAP_TRANSPORT_HAS_TIME_SIGNATURE, CLAP_TRANSPORT_IS_PLAYING,
+    },
+    ext::audio_ports::{
+        clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN,
+        CLAP_EXT_AUDIO_PORTS, CLAP_PORT_MONO, CLAP_PORT_STEREO,
+    },
+    ext::latency::{clap_plugin_latency, CLAP_EXT_LATENCY},
+    ext::note_ports::{
+        clap_note_port_info, clap_plugin_note_ports, CLAP_EXT_NOTE_PORTS, CLAP_NOTE_DIALECT_CLAP,
+        CLAP_NOTE_DIALECT_MIDI,
+    },
+    factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID},
+    fixedpoint::{CLAP_BEATTIME_FACTOR, CLAP_SECTIME_FACTOR},
+    host::clap_host,
+    id::CLAP_INVALID_ID,
+    plugin::{clap_plugin, clap_plugin_descriptor},
+    process::{clap_process, clap_process_status, CLAP_PROCESS_CONTINUE, CLAP_PROCESS_ERROR},
+    version::CLAP_VERSION,
+};
+
+/// The maximum total size (in bytes) of the SysEx events that can be sent to the host per buffer.
+const MAX_SYSEX_DATA_SIZE: usize = 64 * 1024;
+
+/// Define some CLAP-specific meta-data for a CLAP plugin.
+pub trait ClapPluginMeta: CommonPluginMeta {
+    /// The unique identifier of the plugin, in reverse domain name notation,
+    /// e.g. `"com.example.my-synth"`.
+    fn clap_id(&self) -> &str;
+
+    /// The name of the vendor of the plugin.
+    fn vendor(&self) -> &str {
+        ""
+    }
+
+    /// The version of the plugin, e.g. `"1.0.0"`.
+    fn version(&self) -> &str {
+        ""
+    }
+
+    /// A short description of the plugin.
+    fn description(&self) -> &str {
+        ""
+    }
+
+    /// The features of the plugin, e.g. `vec!["instrument", "synthesizer"]`.
+    /// See the [`plugin_features`] module for the standard features.
+    ///
+    /// [`plugin_features`]: ./clap_sys/plugin_features/index.html
+    fn features(&self) -> Vec<&str> {
+        Vec::new()
+    }
+}
+
+/// Used to communicate with the CLAP host.
+///
+/// This is the "context" that is passed to the plugin when rendering audio and handling events.
+/// It can be used to send midi events and SysEx events to the note output ports of the plugin,
+/// see the [`MidiOutput`] trait.
+/// The events are passed to the host immediately, so they must be written in chronological order.
+///
+/// [`MidiOutput`]: ../trait.MidiOutput.html
+pub struct ClapHost {
+    host: *const clap_host,
+    out_events: *const clap_output_events,
+    transport: Transport,
+    number_of_midi_outputs: usize,
+    sysex_data: Vec<u8>,
+}
+
+impl ClapHost {
+    fn new(host: *const clap_host, number_of_midi_outputs: usize) -> Self {
+        Self {
+            host,
+            out_events: ptr::null(),
+            transport: Transport::default(),
+            number_of_midi_outputs,
+            sysex_data: Vec::with_capacity(MAX_SYSEX_DATA_SIZE),
+        }
+    }
+
+    /// Get access to the underlying [`clap_host`] so that you can use CLAP-specific features.
+    ///
+    /// [`clap_host`]: ./clap_sys/host/struct.clap_host.html
+    pub fn clap_host(&self) -> *const clap_host {
+        self.host
+    }
+
+    fn check_port_index(&self, port_index: usize) -> Result<u16, MidiOutputError> {
+        if port_index < self.number_of_midi_outputs {
+            Ok(port_index as u16)
+        } else {
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports: self.number_of_midi_outputs,
+            })
+        }
+    }
+
+    // `out_events` is only set during the `process` call of the host.
+    fn check_processing(&self) -> Result<(), MidiOutputError> {
+        if self.out_events.is_null() {
+            Err(MidiOutputError::NotProcessing)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn push_event(&mut self, header: &clap_event_header) -> Result<(), MidiOutputError> {
+        self.check_processing()?;
+        let out_events = unsafe { &*self.out_events };
+        match out_events.try_push {
+            Some(try_push) if unsafe { try_push(out_events, header) } => Ok(()),
+            _ => Err(MidiOutputError::BufferFull),
+        }
+    }
+}
+
+fn event_header<E>(time_in_frames: u32, type_: u16) -> clap_event_header {
+    clap_event_header {
+        size: mem::size_of::<E>() as u32,
+        time: time_in_frames,
+        space_id: CLAP_CORE_EVENT_SPACE_ID,
+        type_,
+        flags: 0,
+    }
+}
+
+impl HostInterface for ClapHost {
+    fn output_initialized(&self) -> bool {
+        false
+    }
+}
+
+/// The transport is the transport that the host passed for the current buffer.
+impl TransportInfo for ClapHost {
+    fn transport(&self) -> Transport {
+        self.transport
+    }
+}
+
+impl MidiOutput for ClapHost {
+    fn number_of_midi_output_ports(&self) -> usize {
+        self.number_of_midi_outputs
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        let port_index = self.check_port_index(port_index)?;
+        let event = clap_event_midi {
+            header: event_header::<clap_event_midi>(time_in_frames, CLAP_EVENT_MIDI),
+            port_index,
+            data: *event.data(),
+        };
+        self.push_event(&event.header)
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        let port_index = self.check_port_index(port_index)?;
+        self.check_processing()?;
+        let start = self.sysex_data.len();
+        let end = start + data.len();
+        if end > self.sysex_data.capacity() {
+            // Do not allocate in the real-time thread.
+            return Err(MidiOutputError::BufferFull);
+        }
+        // The data must remain valid until the end of the `process` call, so it is copied.
+        self.sysex_data.extend_from_slice(data);
+        let event = clap_event_midi_sysex {
+            header: event_header::<clap_event_midi_sysex>(time_in_frames, CLAP_EVENT_MIDI_SYSEX),
+            port_index,
+            buffer: self.sysex_data[start..end].as_ptr(),
+            size: data.len() as u32,
+        };
+        self.push_event(&event.header)
+    }
+}
+
+impl EventHandler<Indexed<Timed<RawMidiEvent>>> for ClapHost {
+    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_midi(index, event.time_in_frames, event.event) {
+            error!("Failed to send midi event: {}", e);
+        }
+    }
+}
+
+impl<'a> EventHandler<Indexed<Timed<SysExEvent<'a>>>> for ClapHost {
+    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_sysex(index, event.time_in_frames, event.event.data()) {
+            error!("Failed to send SysEx event: {}", e);
+        }
+    }
+}
+
+fn convert_transport(transport: &clap_event_transport, sample_rate: f64) -> Transport {
+    let flags = transport.flags;
+    let has = |flag| flags & flag != 0;
+    let beats = |beat_time| beat_time as f64 / CLAP_BEATTIME_FACTOR as f64;
+    let has_beats = has(CLAP_TRANSPORT_HAS_BEATS_TIMELINE);
+    Transport {
+        is_playing: has(CLAP_TRANSPORT_IS_PLAYING),
+        tempo_in_beats_per_minute: if has(CLAP_TRANSPORT_HAS_TEMPO) {
+            Some(transport.tempo)
+        } else {
+            None
+        },
+        time_signature: if has(CLAP_TRANSPORT_HAS_TIME_SIGNATURE)
+            && transport.tsig_num > 0
+            && transport.tsig_denom > 0
+        {
+            Some(TimeSignature::new(
+                transport.tsig_num as u32,
+                transport.tsig_denom as u32,
+            ))
+        } else {
+            None
+        },
+        position_in_frames: if has(CLAP_TRANSPORT_HAS_SECONDS_TIMELINE)
+            && transport.song_pos_seconds >= 0
+        {
+            let seconds = transport.song_pos_seconds as f64 / CLAP_SECTIME_FACTOR as f64;
+            Some((seconds * sample_rate).round() as u64)
+        } else {
+            None
+        },
+        position_in_quarter_notes: if has_beats {
+            Some(beats(transport.song_pos_beats))
+        } else {
+            None
+        },
+        bar_start_in_quarter_notes: if has_beats {
+            Some(beats(transport.bar_start))
+        } else {
+            None
+        },
+    }
+}
+
+/// Convert a note on or note off event in the CLAP dialect to a midi event.
+///
+/// Returns `None` for events that do not correspond to one midi note, e.g. because the key or
+/// the channel is a wildcard (`-1`).
+fn note_to_midi(event: &clap_event_note) -> Option<Indexed<Timed<RawMidiEvent>>> {
+    if event.port_index < 0 || !(0..16).contains(&event.channel) || !(0..128).contains(&event.key) {
+        return None;
+    }
+    let velocity = (event.velocity.clamp(0.0, 1.0) * 127.0).round() as u8;
+    let (status, velocity) = match event.header.type_ {
+        // A velocity of `0` would be interpreted as a note off event.
+        CLAP_EVENT_NOTE_ON => (NOTE_ON, cmp::max(velocity, 1)),
+        CLAP_EVENT_NOTE_OFF => (NOTE_OFF, velocity),
+        _ => return None,
+    };
+    Some(Indexed {
+        index: event.port_index as usize,
+        event: Timed {
+            time_in_frames: event.header.time,
+            event: RawMidiEvent::new(&[status | event.channel as u8, event.key as u8, velocity]),
+        },
+    })
+}
+
+/// Copy `name` to `buffer`, truncating it if needed, and add a terminating `0`.
+fn copy_name(buffer: &mut [c_char], name: &str) {
+    let length = cmp::min(name.len(), buffer.len() - 1);
+    for (target, source) in buffer.iter_mut().zip(&name.as_bytes()[..length]) {
+        *target = *source as c_char;
+    }
+    buffer[length] = 0;
+}
+
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).expect("all NUL characters have been removed")
+}
+
+/// The plugin descriptor, together with the strings it points to.
+struct ClapDescriptor {
+    id: CString,
+    _strings: Vec<CString>,
+    _features: Vec<CString>,
+    _feature_pointers: Vec<*const c_char>,
+    raw: clap_plugin_descriptor,
+}
+
+// The raw pointers only point to the (immutable) strings that are owned by the descriptor.
+unsafe impl Send for ClapDescriptor {}
+unsafe impl Sync for ClapDescriptor {}
+
+impl ClapDescriptor {
+    fn new<P: ClapPluginMeta>(plugin: &P) -> Self {
+        let id = c_string(plugin.clap_id());
+        let strings: Vec<CString> = [
+            plugin.name(),
+            plugin.vendor(),
+            plugin.version(),
+            plugin.description(),
+            "",
+        ]
+        .iter()
+        .map(|s| c_string(s))
+        .collect();
+        let features: Vec<CString> = plugin.features().iter().map(|f| c_string(f)).collect();
+        let mut feature_pointers: Vec<*const c_char> =
+            features.iter().map(|f| f.as_ptr()).collect();
+        feature_pointers.push(ptr::null());
+        let empty = strings[4].as_ptr();
+        let raw = clap_plugin_descriptor {
+            clap_version: CLAP_VERSION,
+            id: id.as_ptr(),
+            name: strings[0].as_ptr(),
+            vendor: strings[1].as_ptr(),
+            url: empty,
+            manual_url: empty,
+            support_url: empty,
+            version: strings[2].as_ptr(),
+            description: strings[3].as_ptr(),
+            features: feature_pointers.as_ptr(),
+        };
+        Self {
+            id,
+            _strings: strings,
+            _features: features,
+            _feature_pointers: feature_pointers,
+            raw,
+        }
+    }
+}
+
+/// Used internally by the [`clap_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`clap_init`]: ../../macro.clap_init.html
+pub trait ClapFactory: 'static {
+    type Plugin: ClapPluginMeta
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, ClapHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, ClapHost>
+        + for<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, ClapHost>
+        + Send
+        + 'static;
+
+    /// Create a new instance of the plugin.
+    fn create_plugin() -> Self::Plugin;
+
+    /// The statics for this plugin.
+    fn statics() -> &'static ClapStatics;
+}
+
+/// The data that the host accesses via static pointers: the plugin factory, the extensions
+/// and the plugin descriptor.
+///
+/// Used internally by the [`clap_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`clap_init`]: ../../macro.clap_init.html
+pub struct ClapStatics {
+    descriptor: OnceLock<ClapDescriptor>,
+    factory: clap_plugin_factory,
+    audio_ports: clap_plugin_audio_ports,
+    note_ports: clap_plugin_note_ports,
+    latency: clap_plugin_latency,
+}
+
+impl ClapStatics {
+    /// Create the statics for the plugin created by the given factory.
+    pub const fn new<F: ClapFactory>() -> Self {
+        Self {
+            descriptor: OnceLock::new(),
+            factory: clap_plugin_factory {
+                get_plugin_count: Some(factory_get_plugin_count),
+                get_plugin_descriptor: Some(factory_get_plugin_descriptor::<F>),
+                create_plugin: Some(factory_create_plugin::<F>),
+            },
+            audio_ports: clap_plugin_audio_ports {
+                count: Some(audio_ports_count::<F>),
+                get: Some(audio_ports_get::<F>),
+            },
+            note_ports: clap_plugin_note_ports {
+                count: Some(note_ports_count::<F>),
+                get: Some(note_ports_get::<F>),
+            },
+            latency: clap_plugin_latency {
+                get: Some(latency_get::<F>),
+            },
+        }
+    }
+}
+
+/// Create the `clap_entry` for the plugin created by the given factory.
+///
+/// Used internally by the [`clap_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`clap_init`]: ../../macro.clap_init.html
+pub const fn entry<F: ClapFactory>() -> clap_plugin_entry {
+    clap_plugin_entry {
+        clap_version: CLAP_VERSION,
+        init: Some(entry_init),
+        deinit: Some(entry_deinit),
+        get_factory: Some(entry_get_factory::<F>),
+    }
+}
+
+// The plugin is only created to read its meta-data; this is documented in `clap_init`.
+fn descriptor<F: ClapFactory>() -> &'static ClapDescriptor {
+    F::statics()
+        .descriptor
+        .get_or_init(|| ClapDescriptor::new(&F::create_plugin()))
+}
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    trace!("entry_init");
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {
+    trace!("entry_deinit");
+}
+
+unsafe extern "C" fn entry_get_factory<F: ClapFactory>(factory_id: *const c_char) -> *const c_void {
+    if !factory_id.is_null() && CStr::from_ptr(factory_id) == CLAP_PLUGIN_FACTORY_ID {
+        &F::statics().factory as *const clap_plugin_factory as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn factory_get_plugin_descriptor<F: ClapFactory>(
+    _factory: *const clap_plugin_factory,
+    index: u32,
+) -> *const clap_plugin_descriptor {
+    if index == 0 {
+        &descriptor::<F>().raw
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_create_plugin<F: ClapFactory>(
+    _factory: *const clap_plugin_factory,
+    host: *const clap_host,
+    plugin_id: *const c_char,
+) -> *const clap_plugin {
+    let descriptor = descriptor::<F>();
+    if plugin_id.is_null() || CStr::from_ptr(plugin_id) != descriptor.id.as_c_str() {
+        return ptr::null();
+    }
+    let instance = Box::into_raw(Box::new(ClapInstance::<F>::new(
+        F::create_plugin(),
+        host,
+        descriptor,
+    )));
+    (*instance).raw.plugin_data = instance as *mut c_void;
+    &(*instance).raw
+}
+
+/// Does the audio processing.
+struct ClapPluginWrapper<P> {
+    plugin: P,
+    host: ClapHost,
+    sample_rate: f64,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static [f32]>,
+}
+
+impl<P> ClapPluginWrapper<P>
+where
+    P: CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + ContextualAudioRenderer<f32, ClapHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, ClapHost>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, ClapHost>,
+{
+    fn new(plugin: P, host: *const clap_host) -> Self {
+        Self {
+            inputs: VecStorage::with_capacity(plugin.max_number_of_audio_inputs()),
+            outputs: VecStorage::with_capacity(plugin.max_number_of_audio_outputs()),
+            host: ClapHost::new(host, plugin.max_number_of_midi_outputs()),
+            sample_rate: 0.0,
+            plugin,
+        }
+    }
+
+    unsafe fn handle_events(&mut self, in_events: &clap_input_events) {
+        let (size, get) = match (in_events.size, in_events.get) {
+            (Some(size), Some(get)) => (size, get),
+            _ => return,
+        };
+        for index in 0..size(in_events) {
+            let header = match get(in_events, index).as_ref() {
+                Some(header) => header,
+                None => continue,
+            };
+            if header.space_id != CLAP_CORE_EVENT_SPACE_ID {
+                continue;
+            }
+            let header_pointer = header as *const clap_event_header;
+            match header.type_ {
+                CLAP_EVENT_MIDI => {
+                    let event = &*(header_pointer as *const clap_event_midi);
+                    let event = Indexed {
+                        index: event.port_index as usize,
+                        event: Timed {
+                            time_in_frames: header.time,
+                            event: RawMidiEvent::new(&event.data),
+                        },
+                    };
+                    self.plugin.handle_event(event, &mut self.host);
+                }
+                CLAP_EVENT_MIDI_SYSEX => {
+                    let event = &*(header_pointer as *const clap_event_midi_sysex);
+                    if event.buffer.is_null() {
+                        continue;
+                    }
+                    let event = Indexed {
+                        index: event.port_index as usize,
+                        event: Timed {
+                            time_in_frames: header.time,
+                            event: SysExEvent::new(slice::from_raw_parts(
+                                event.buffer,
+                                event.size as usize,
+                            )),
+                        },
+                    };
+                    self.plugin.handle_event(event, &mut self.host);
+                }
+                CLAP_EVENT_NOTE_ON | CLAP_EVENT_NOTE_OFF => {
+                    if let Some(event) = note_to_midi(&*(header_pointer as *const clap_event_note))
+                    {
+                        self.plugin.handle_event(event, &mut self.host);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    unsafe fn process(&mut self, process: &clap_process) -> clap_process_status {
+        let number_of_frames = process.frames_count as usize;
+        self.host.transport = match process.transport.as_ref() {
+            Some(transport) => convert_transport(transport, self.sample_rate),
+            None => Transport::default(),
+        };
+        self.host.sysex_data.clear();
+        self.host.out_events = process.out_events;
+
+        if let Some(in_events) = process.in_events.as_ref() {
+            self.handle_events(in_events);
+        }
+
+        let mut inputs = self.inputs.vec_guard();
+        if process.audio_inputs_count > 0 {
+            let buffer: &clap_audio_buffer = &*process.audio_inputs;
+            let number_of_channels = cmp::min(buffer.channel_count as usize, inputs.capacity());
+            for channel in 0..number_of_channels {
+                let data = *buffer.data32.add(channel);
+                if data.is_null() {
+                    return CLAP_PROCESS_ERROR;
+                }
+                inputs.push(slice::from_raw_parts(data, number_of_frames));
+            }
+        }
+
+        let mut outputs = self.outputs.vec_guard();
+        if process.audio_outputs_count > 0 {
+            let buffer: &clap_audio_buffer = &*process.audio_outputs;
+            let number_of_channels = cmp::min(buffer.channel_count as usize, outputs.capacity());
+            for channel in 0..number_of_channels {
+                let data = *buffer.data32.add(channel);
+                if data.is_null() {
+                    return CLAP_PROCESS_ERROR;
+                }
+                outputs.push(slice::from_raw_parts_mut(data, number_of_frames));
+            }
+        }
+
+        let mut audio_buffer =
+            AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
+        self.plugin.render_buffer(&mut audio_buffer, &mut self.host);
+        self.host.out_events = ptr::null();
+        CLAP_PROCESS_CONTINUE
+    }
+}
+
+/// The plugin instance as seen by the host.
+///
+/// The host may call the functions of the extensions (e.g. to query the ports) on the main thread
+/// while the plugin is processing audio on the audio thread. For this reason, the information that
+/// these functions need is stored separately from the plugin itself.
+/// The plugin itself is only accessed in functions that the host does not call concurrently.
+struct ClapInstance<F: ClapFactory> {
+    raw: clap_plugin,
+    port_names: PortNames,
+    latency_in_frames: AtomicU32,
+    wrapper: UnsafeCell<ClapPluginWrapper<F::Plugin>>,
+}
+
+impl<F: ClapFactory> ClapInstance<F> {
+    fn new(plugin: F::Plugin, host: *const clap_host, descriptor: &ClapDescriptor) -> Self {
+        Self {
+            raw: clap_plugin {
+                desc: &descriptor.raw,
+                plugin_data: ptr::null_mut(),
+                init: Some(plugin_init),
+                destroy: Some(plugin_destroy::<F>),
+                activate: Some(plugin_activate::<F>),
+                deactivate: Some(plugin_deactivate),
+                start_processing: Some(plugin_start_processing),
+                stop_processing: Some(plugin_stop_processing),
+                reset: Some(plugin_reset),
+                process: Some(plugin_process::<F>),
+                get_extension: Some(plugin_get_extension::<F>),
+                on_main_thread: Some(plugin_on_main_thread),
+            },
+            port_names: PortNames::new(&plugin),
+            latency_in_frames: AtomicU32::new(plugin.latency_in_frames()),
+            wrapper: UnsafeCell::new(ClapPluginWrapper::new(plugin, host)),
+        }
+    }
+
+    unsafe fn from_raw<'a>(plugin: *const clap_plugin) -> &'a Self {
+        &*((*plugin).plugin_data as *const Self)
+    }
+
+    /// The caller must ensure that the host does not access the plugin concurrently.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn wrapper(&self) -> &mut ClapPluginWrapper<F::Plugin> {
+        &mut *self.wrapper.get()
+    }
+}
+
+unsafe extern "C" fn plugin_init(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_destroy<F: ClapFactory>(plugin: *const clap_plugin) {
+    let instance = (*plugin).plugin_data as *mut ClapInstance<F>;
+    drop(Box::from_raw(instance));
+}
+
+unsafe extern "C" fn plugin_activate<F: ClapFactory>(
+    plugin: *const clap_plugin,
+    sample_rate: f64,
+    _min_frames_count: u32,
+    _max_frames_count: u32,
+) -> bool {
+    trace!("activate(sample_rate: {})", sample_rate);
+    let instance = ClapInstance::<F>::from_raw(plugin);
+    let wrapper = instance.wrapper();
+    wrapper.sample_rate = sample_rate;
+    wrapper.plugin.set_sample_rate(sample_rate);
+    instance
+        .latency_in_frames
+        .store(wrapper.plugin.latency_in_frames(), Ordering::SeqCst);
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_reset(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_process<F: ClapFactory>(
+    plugin: *const clap_plugin,
+    process: *const clap_process,
+) -> clap_process_status {
+    match process.as_ref() {
+        Some(process) => ClapInstance::<F>::from_raw(plugin)
+            .wrapper()
+            .process(process),
+        None => CLAP_PROCESS_ERROR,
+    }
+}
+
+unsafe extern "C" fn plugin_get_extension<F: ClapFactory>(
+    _plugin: *const clap_plugin,
+    id: *const c_char,
+) -> *const c_void {
+    if id.is_null() {
+        return ptr::null();
+    }
+    let id = CStr::from_ptr(id);
+    let statics = F::statics();
+    if id == CLAP_EXT_AUDIO_PORTS {
+        &statics.audio_ports as *const clap_plugin_audio_ports as *const c_void
+    } else if id == CLAP_EXT_NOTE_PORTS {
+        &statics.note_ports as *const clap_plugin_note_ports as *const c_void
+    } else if id == CLAP_EXT_LATENCY {
+        &statics.latency as *const clap_plugin_latency as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn audio_ports_count<F: ClapFactory>(
+    plugin: *const clap_plugin,
+    is_input: bool,
+) -> u32 {
+    let port_names = &ClapInstance::<F>::from_raw(plugin).port_names;
+    let channels = if is_input {
+        &port_names.audio_in
+    } else {
+        &port_names.audio_out
+    };
+    if channels.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+unsafe extern "C" fn audio_ports_get<F: ClapFactory>(
+    plugin: *const clap_plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut clap_audio_port_info,
+) -> bool {
+    let port_names = &ClapInstance::<F>::from_raw(plugin).port_names;
+    let channels = if is_input {
+        &port_names.audio_in
+    } else {
+        &port_names.audio_out
+    };
+    let info = match info.as_mut() {
+        Some(info) => info,
+        None => return false,
+    };
+    if index != 0 || channels.is_empty() {
+        return false;
+    }
+    info.id = 0;
+    copy_name(&mut info.name, &channels.join(", "));
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = channels.len() as u32;
+    info.port_type = match channels.len() {
+        1 => CLAP_PORT_MONO.as_ptr(),
+        2 => CLAP_PORT_STEREO.as_ptr(),
+        _ => ptr::null(),
+    };
+    info.in_place_pair = CLAP_INVALID_ID;
+    true
+}
+
+unsafe extern "C" fn note_ports_count<F: ClapFactory>(
+    plugin: *const clap_plugin,
+    is_input: bool,
+) -> u32 {
+    let port_names = &ClapInstance::<F>::from_raw(plugin).port_names;
+    if is_input {
+        port_names.midi_in.len() as u32
+    } else {
+        port_names.midi_out.len() as u32
+    }
+}
+
+unsafe extern "C" fn note_ports_get<F: ClapFactory>(
+    plugin: *const clap_plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut clap_note_port_info,
+) -> bool {
+    let port_names = &ClapInstance::<F>::from_raw(plugin).port_names;
+    let names = if is_input {
+        &port_names.midi_in
+    } else {
+        &port_names.midi_out
+    };
+    match (names.get(index as usize), info.as_mut()) {
+        (Some(name), Some(info)) => {
+            info.id = index;
+            info.supported_dialects = CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI;
+            info.preferred_dialect = CLAP_NOTE_DIALECT_MIDI;
+            copy_name(&mut info.name, name);
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn latency_get<F: ClapFactory>(plugin: *const clap_plugin) -> u32 {
+    ClapInstance::<F>::from_raw(plugin)
+        .latency_in_frames
+        .load(Ordering::SeqCst)
+}
+
+/// Export a plugin as a CLAP plugin.
+/// You call this with one parameter, which is the function declaration of a function
+/// that creates your plugin.
+/// This function may also do some setup (e.g. initialize logging).
+/// _Note_: this function is also called once to read the meta-data, see
+/// [Creating the plugin for the meta-data].
+///
+/// The plugin is typically a custom data type and should implement
+/// the following traits:
+///
+/// **Traits for meta-data** (Note: you can use the [`Meta`] trait for this.
+/// * [`CommonPluginMeta`] (name of the plugin etc),
+/// * [`AudioHandlerMeta`] (number of audio ports),
+/// * [`CommonAudioPortMeta`] (names of the audio in and out ports),
+/// * [`MidiHandlerMeta`] (number of midi ports),
+/// * [`CommonMidiPortMeta`] (names of the midi in and out ports, these are used for the note ports) and
+/// * [`ClapPluginMeta`], (CLAP-specific meta-data)
+///
+/// **Traits for rendering audio**
+/// * [`AudioHandler`] and
+/// * [`ContextualAudioRenderer`]`<f32,`[`ClapHost`]`>`
+///
+/// **Traits for handling midi events**
+/// * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`ClapHost`]`>` and
+/// * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`ClapHost`]`>`.
+///
+/// **Generating midi events**
+///
+/// [`ClapHost`] implements [`MidiOutput`], so the plugin can send midi events
+/// and SysEx events to its note output ports.
+///
+/// # Example
+/// ```
+/// # #[macro_use] extern crate rsynth;
+/// use rsynth::{
+///     meta::{Meta, MetaData, InOut},
+///     event::{ContextualEventHandler, Indexed, Timed, RawMidiEvent, SysExEvent},
+///     backend::{HostInterface, clap_backend::ClapPluginMeta},
+///     buffer::AudioBufferInOut,
+///     ContextualAudioRenderer,
+///     AudioHandler,
+/// };
+///
+/// struct MyPlugin {
+///   meta: MetaData<&'static str, &'static str, &'static str>
+///   // Define other fields here
+/// }
+///
+/// impl Meta for MyPlugin {
+///    type MetaData = MetaData<&'static str, &'static str, &'static str>;
+///     fn meta(&self) -> &Self::MetaData {
+///         &self.meta
+///     }
+/// }
+///
+/// impl ClapPluginMeta for MyPlugin {
+///     fn clap_id(&self) -> &str { "com.example.my-plugin" }
+///     fn features(&self) -> Vec<&str> { vec!["instrument", "synthesizer"] }
+/// }
+///
+/// impl AudioHandler for MyPlugin {
+///     // Implementation omitted for brevity.
+/// #     fn set_sample_rate(&mut self, new_sample_rate: f64) {}
+/// }
+///
+/// impl<H> ContextualAudioRenderer<f32, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+///     // Implementation omitted for brevity.
+/// #    fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut H) {}
+/// }
+///
+/// impl<H> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
+/// impl<'a, H> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
+/// clap_init!(
+///    fn init() -> MyPlugin {
+///        MyPlugin {
+///             meta: MetaData {
+///                 general_meta: "my_plugin",
+///                 audio_port_meta: InOut {
+///                     inputs: vec![],
+///                     outputs: vec!["left", "right"],
+///                 },
+///                 midi_port_meta: InOut {
+///                     inputs: vec!["midi in"],
+///                     outputs: vec![],
+///                 },
+///             }
+///        }
+///    }
+/// );
+/// # fn main() {}
+/// ```
+/// [`RawMidiEvent`]: ./event/struct.RawMidiEvent.html
+/// [`SysExEvent`]: ./event/struct.SysExEvent.html
+/// [`Timed`]: ./event/struct.Timed.html
+/// [`Indexed`]: ./event/struct.Indexed.html
+/// [`CommonPluginMeta`]: ./trait.CommonPluginMeta.html
+/// [`AudioHandlerMeta`]: ./trait.AudioHandlerMeta.html
+/// [`MidiHandlerMeta`]: ./trait.MidiHandlerMeta.html
+/// [`CommonAudioPortMeta`]: ./trait.CommonAudioPortMeta.html
+/// [`CommonMidiPortMeta`]: ./trait.CommonMidiPortMeta.html
+/// [`Meta`]: ./meta/trait.Meta.html
+/// [`ContextualAudioRenderer`]: trait.ContextualAudioRenderer.html
+/// [`ContextualEventHandler`]: ./event/trait.ContextualEventHandler.html
+/// [`ClapHost`]: ./backend/clap_backend/struct.ClapHost.html
+/// [`ClapPluginMeta`]: ./backend/clap_backend/trait.ClapPluginMeta.html
+/// [`MidiOutput`]: ./backend/trait.MidiOutput.html
+/// [`AudioHandler`]: ./trait.AudioHandler.html
+/// [Creating the plugin for the meta-data]: ./backend/index.html#creating-the-plugin-for-the-meta-data
+//
+// As with `vst_init`, we use a macro so that plugins do not have to implement the `Default`
+// trait: the host creates the plugin via a C function to which we cannot supply an extra
+// parameter.
+#[macro_export]
+macro_rules! clap_init {
+    (fn $function_name:ident() -> $return_type:ty
+        $body:block
+    ) => {
+        fn $function_name () -> $return_type
+        $body
+
+        struct ClapPluginFactory;
+
+        static CLAP_STATICS: $crate::backend::clap_backend::ClapStatics =
+            $crate::backend::clap_backend::ClapStatics::new::<ClapPluginFactory>();
+
+        impl $crate::backend::clap_backend::ClapFactory for ClapPluginFactory {
+            type Plugin = $return_type;
+
+            fn create_plugin() -> $return_type {
+                $function_name()
+            }
+
+            fn statics() -> &'static $crate::backend::clap_backend::ClapStatics {
+                &CLAP_STATICS
+            }
+        }
+
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static clap_entry: $crate::backend::clap_backend::clap_sys::entry::clap_plugin_entry =
+            $crate::backend::clap_backend::entry::<ClapPluginFactory>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::{InOut, Meta, MetaData};
+    use std::os::raw::c_void;
+
+    struct TestPlugin {
+        meta: MetaData<&'static str, &'static str, &'static str>,
+    }
+
+    impl Meta for TestPlugin {
+        type MetaData = MetaData<&'static str, &'static str, &'static str>;
+        fn meta(&self) -> &Self::MetaData {
+            &self.meta
+        }
+    }
+
+    impl ClapPluginMeta for TestPlugin {
+        fn clap_id(&self) -> &str {
+            "rsynth.test"
+        }
+        fn features(&self) -> Vec<&str> {
+            vec!["instrument"]
+        }
+    }
+
+    impl AudioHandler for TestPlugin {
+        fn set_sample_rate(&mut self, _sample_rate: f64) {}
+
+        fn latency_in_frames(&self) -> u32 {
+            3
+        }
+    }
+
+    impl ContextualAudioRenderer<f32, ClapHost> for TestPlugin {
+        fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, _context: &mut ClapHost) {
+            let (inputs, mut outputs) = buffer.separate();
+            for (input, output) in inputs.channels().iter().zip(outputs.channel_iter_mut()) {
+                for (i, o) in input.iter().zip(output.iter_mut()) {
+                    *o = *i * 2.0;
+                }
+            }
+        }
+    }
+
+    // Echo the midi events to the midi output.
+    impl ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, ClapHost> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut ClapHost) {
+            context.handle_event(event);
+        }
+    }
+
+    impl<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, ClapHost> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut ClapHost) {
+            context.handle_event(event);
+        }
+    }
+
+    crate::clap_init!(
+        fn init() -> TestPlugin {
+            TestPlugin {
+                meta: MetaData {
+                    general_meta: "test plugin",
+                    audio_port_meta: InOut {
+                        inputs: vec!["in left", "in right"],
+                        outputs: vec!["out left", "out right"],
+                    },
+                    midi_port_meta: InOut {
+                        inputs: vec!["midi in"],
+                        outputs: vec!["midi out"],
+                    },
+                },
+            }
+        }
+    );
+
+    fn transport() -> clap_event_transport {
+        clap_event_transport {
+            header: event_header::<clap_event_transport>(0, 9),
+            flags: 0,
+            song_pos_beats: 0,
+            song_pos_seconds: 0,
+            tempo: 0.0,
+            tempo_inc: 0.0,
+            loop_start_beats: 0,
+            loop_end_beats: 0,
+            loop_start_seconds: 0,
+            loop_end_seconds: 0,
+            bar_start: 0,
+            bar_number: 0,
+            tsig_num: 0,
+            tsig_denom: 0,
+        }
+    }
+
+    #[test]
+    fn convert_transport_converts_the_fields_the_host_provides() {
+        let mut t = transport();
+        t.flags = CLAP_TRANSPORT_IS_PLAYING
+            | CLAP_TRANSPORT_HAS_TEMPO
+            | CLAP_TRANSPORT_HAS_BEATS_TIMELINE
+            | CLAP_TRANSPORT_HAS_SECONDS_TIMELINE
+            | CLAP_TRANSPORT_HAS_TIME_SIGNATURE;
+        t.tempo = 120.0;
+        t.song_pos_beats = 6 * CLAP_BEATTIME_FACTOR + CLAP_BEATTIME_FACTOR / 2;
+        t.bar_start = 6 * CLAP_BEATTIME_FACTOR;
+        t.song_pos_seconds = 3 * CLAP_SECTIME_FACTOR + CLAP_SECTIME_FACTOR / 4;
+        t.tsig_num = 3;
+        t.tsig_denom = 4;
+        assert_eq!(
+            convert_transport(&t, 48000.0),
+            Transport {
+                is_playing: true,
+                tempo_in_beats_per_minute: Some(120.0),
+                time_signature: Some(TimeSignature::new(3, 4)),
+                position_in_frames: Some(156000),
+                position_in_quarter_notes: Some(6.5),
+                bar_start_in_quarter_notes: Some(6.0),
+            }
+        );
+    }
+
+    #[test]
+    fn convert_transport_ignores_the_fields_the_host_does_not_provide() {
+        let mut t = transport();
+        t.tempo = 120.0;
+        t.tsig_num = 3;
+        t.tsig_denom = 4;
+        assert_eq!(convert_transport(&t, 48000.0), Transport::default());
+    }
+
+    fn note(type_: u16, channel: i16, key: i16, velocity: f64) -> clap_event_note {
+        clap_event_note {
+            header: event_header::<clap_event_note>(5, type_),
+            note_id: -1,
+            port_index: 0,
+            channel,
+            key,
+            velocity,
+        }
+    }
+
+    #[test]
+    fn note_to_midi_converts_note_on_and_note_off() {
+        let event = note_to_midi(&note(CLAP_EVENT_NOTE_ON, 2, 60, 1.0)).unwrap();
+        assert_eq!(event.index, 0);
+        assert_eq!(event.event.time_in_frames, 5);
+        assert_eq!(event.event.event.data(), &[NOTE_ON | 2, 60, 127]);
+        let event = note_to_midi(&note(CLAP_EVENT_NOTE_OFF, 2, 60, 0.0)).unwrap();
+        assert_eq!(event.event.event.data(), &[NOTE_OFF | 2, 60, 0]);
+    }
+
+    #[test]
+    fn note_to_midi_does_not_convert_note_on_to_note_off() {
+        let event = note_to_midi(&note(CLAP_EVENT_NOTE_ON, 0, 60, 0.0)).unwrap();
+        assert_eq!(event.event.event.data(), &[NOTE_ON, 60, 1]);
+    }
+
+    #[test]
+    fn note_to_midi_skips_wildcards() {
+        assert!(note_to_midi(&note(CLAP_EVENT_NOTE_ON, -1, 60, 1.0)).is_none());
+        assert!(note_to_midi(&note(CLAP_EVENT_NOTE_ON, 0, -1, 1.0)).is_none());
+    }
+
+    #[test]
+    fn writing_midi_fails_outside_of_process() {
+        let mut host = ClapHost::new(ptr::null(), 1);
+        assert_eq!(
+            host.write_midi(0, 0, RawMidiEvent::new(&[0x90, 60, 100])),
+            Err(MidiOutputError::NotProcessing)
+        );
+        assert_eq!(
+            host.write_sysex(0, 0, &[0xF0, 0x01, 0xF7]),
+            Err(MidiOutputError::NotProcessing)
+        );
+        assert!(host.sysex_data.is_empty());
+        assert_eq!(
+            host.write_midi(1, 0, RawMidiEvent::new(&[0x90, 60, 100])),
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index: 1,
+                number_of_ports: 1
+            })
+        );
+    }
+
+    // A minimal host that only uses the C interface.
+
+    unsafe extern "C" fn input_events_size(list: *const clap_input_events) -> u32 {
+        (*((*list).ctx as *const Vec<*const clap_event_header>)).len() as u32
+    }
+
+    unsafe extern "C" fn input_events_get(
+        list: *const clap_input_events,
+        index: u32,
+    ) -> *const clap_event_header {
+        let events = &*((*list).ctx as *const Vec<*const clap_event_header>);
+        events[index as usize]
+    }
+
+    unsafe extern "C" fn output_events_try_push(
+        list: *const clap_output_events,
+        event: *const clap_event_header,
+    ) -> bool {
+        let received = &mut *((*list).ctx as *mut Vec<(u32, u16, Vec<u8>)>);
+        let header = &*event;
+        let data = match header.type_ {
+            CLAP_EVENT_MIDI => (*(event as *const clap_event_midi)).data.to_vec(),
+            CLAP_EVENT_MIDI_SYSEX => {
+                let event = &*(event as *const clap_event_midi_sysex);
+                slice::from_raw_parts(event.buffer, event.size as usize).to_vec()
+            }
+            _ => return false,
+        };
+        received.push((header.time, header.type_, data));
+        true
+    }
+
+    unsafe fn c_str<'a>(s: *const c_char) -> &'a str {
+        CStr::from_ptr(s).to_str().unwrap()
+    }
+
+    #[test]
+    fn a_host_can_load_the_plugin_and_process_audio_and_events() {
+        unsafe {
+            let entry = &clap_entry;
+            assert!(entry.init.unwrap()(b"\0".as_ptr() as *const c_char));
+            assert!(entry.get_factory.unwrap()(b"unknown\0".as_ptr() as *const c_char).is_null());
+            let factory = &*(entry.get_factory.unwrap()(CLAP_PLUGIN_FACTORY_ID.as_ptr())
+                as *const clap_plugin_factory);
+
+            assert_eq!(factory.get_plugin_count.unwrap()(factory), 1);
+            let descriptor = &*factory.get_plugin_descriptor.unwrap()(factory, 0);
+            assert_eq!(c_str(descriptor.id), "rsynth.test");
+            assert_eq!(c_str(descriptor.name), "test plugin");
+            assert_eq!(c_str(*descriptor.features), "instrument");
+            assert!((*descriptor.features.add(1)).is_null());
+
+            let host = clap_host {
+                clap_version: CLAP_VERSION,
+                host_data: ptr::null_mut(),
+                name: b"test host\0".as_ptr() as *const c_char,
+                vendor: b"\0".as_ptr() as *const c_char,
+                url: b"\0".as_ptr() as *const c_char,
+                version: b"\0".as_ptr() as *const c_char,
+                get_extension: None,
+                request_restart: None,
+                request_process: None,
+                request_callback: None,
+            };
+            let create_plugin = factory.create_plugin.unwrap();
+            assert!(create_plugin(factory, &host, b"other\0".as_ptr() as *const c_char).is_null());
+            let plugin = &*create_plugin(factory, &host, descriptor.id);
+            assert!(plugin.init.unwrap()(plugin));
+
+            let audio_ports =
+                &*(plugin.get_extension.unwrap()(plugin, CLAP_EXT_AUDIO_PORTS.as_ptr())
+                    as *const clap_plugin_audio_ports);
+            assert_eq!(audio_ports.count.unwrap()(plugin, true), 1);
+            assert_eq!(audio_ports.count.unwrap()(plugin, false), 1);
+            let mut audio_port_info: clap_audio_port_info = mem::zeroed();
+            assert!(audio_ports.get.unwrap()(
+                plugin,
+                0,
+                false,
+                &mut audio_port_info
+            ));
+            assert_eq!(audio_port_info.channel_count, 2);
+            assert_eq!(c_str(audio_port_info.name.as_ptr()), "out left, out right");
+            assert_eq!(CStr::from_ptr(audio_port_info.port_type), CLAP_PORT_STEREO);
+
+            let note_ports = &*(plugin.get_extension.unwrap()(plugin, CLAP_EXT_NOTE_PORTS.as_ptr())
+                as *const clap_plugin_note_ports);
+            assert_eq!(note_ports.count.unwrap()(plugin, true), 1);
+            assert_eq!(note_ports.count.unwrap()(plugin, false), 1);
+            let mut note_port_info: clap_note_port_info = mem::zeroed();
+            assert!(note_ports.get.unwrap()(
+                plugin,
+                0,
+                true,
+                &mut note_port_info
+            ));
+            assert_eq!(c_str(note_port_info.name.as_ptr()), "midi in");
+            assert_eq!(note_port_info.preferred_dialect, CLAP_NOTE_DIALECT_MIDI);
+            assert!(!note_ports.get.unwrap()(
+                plugin,
+                1,
+                true,
+                &mut note_port_info
+            ));
+
+            assert!(plugin.activate.unwrap()(plugin, 44100.0, 1, 4));
+            let latency = &*(plugin.get_extension.unwrap()(plugin, CLAP_EXT_LATENCY.as_ptr())
+                as *const clap_plugin_latency);
+            assert_eq!(latency.get.unwrap()(plugin), 3);
+            assert!(plugin.start_processing.unwrap()(plugin));
+
+            let midi_event = clap_event_midi {
+                header: event_header::<clap_event_midi>(1, CLAP_EVENT_MIDI),
+                port_index: 0,
+                data: [0x90, 60, 100],
+            };
+            let note_event = note(CLAP_EVENT_NOTE_OFF, 0, 60, 0.0);
+            let sysex_data = [0xF0, 0x01, 0xF7];
+            let sysex_event = clap_event_midi_sysex {
+                header: event_header::<clap_event_midi_sysex>(3, CLAP_EVENT_MIDI_SYSEX),
+                port_index: 0,
+                buffer: sysex_data.as_ptr(),
+                size: sysex_data.len() as u32,
+            };
+            let input_event_pointers: Vec<*const clap_event_header> =
+                vec![&midi_event.header, &sysex_event.header, &note_event.header];
+            let in_events = clap_input_events {
+                ctx: &input_event_pointers as *const _ as *mut c_void,
+                size: Some(input_events_size),
+                get: Some(input_events_get),
+            };
+            let mut received: Vec<(u32, u16, Vec<u8>)> = Vec::new();
+            let out_events = clap_output_events {
+                ctx: &mut received as *mut _ as *mut c_void,
+                try_push: Some(output_events_try_push),
+            };
+
+            let mut left_in = [1.0f32, 2.0, 3.0, 4.0];
+            let mut right_in = [5.0f32, 6.0, 7.0, 8.0];
+            let mut left_out = [0.0f32; 4];
+            let mut right_out = [0.0f32; 4];
+            let mut input_channels = [left_in.as_mut_ptr(), right_in.as_mut_ptr()];
+            let mut output_channels = [left_out.as_mut_ptr(), right_out.as_mut_ptr()];
+            let audio_input = clap_audio_buffer {
+                data32: input_channels.as_mut_ptr(),
+                data64: ptr::null_mut(),
+                channel_count: 2,
+                latency: 0,
+                constant_mask: 0,
+            };
+            let mut audio_output = clap_audio_buffer {
+                data32: output_channels.as_mut_ptr(),
+                data64: ptr::null_mut(),
+                channel_count: 2,
+                latency: 0,
+                constant_mask: 0,
+            };
+            let process = clap_process {
+                steady_time: 0,
+                frames_count: 4,
+                transport: ptr::null(),
+                audio_inputs: &audio_input,
+                audio_outputs: &mut audio_output,
+                audio_inputs_count: 1,
+                audio_outputs_count: 1,
+                in_events: &in_events,
+                out_events: &out_events,
+            };
+            assert_eq!(
+                plugin.process.unwrap()(plugin, &process),
+                CLAP_PROCESS_CONTINUE
+            );
+
+            assert_eq!(left_out, [2.0, 4.0, 6.0, 8.0]);
+            assert_eq!(right_out, [10.0, 12.0, 14.0, 16.0]);
+            assert_eq!(
+                received,
+                vec![
+                    (1, CLAP_EVENT_MIDI, vec![0x90, 60, 100]),
+                    (3, CLAP_EVENT_MIDI_SYSEX, vec![0xF0, 0x01, 0xF7]),
+                    (5, CLAP_EVENT_MIDI, vec![0x80, 60, 0]),
+                ]
+            );
+
+            plugin.stop_processing.unwrap()(plugin);
+            plugin.deactivate.unwrap()(plugin);
+            plugin.destroy.unwrap()(plugin);
+            entry.deinit.unwrap()();
+        }
+    }
+
+    // This loads the `clap_synth` example in the same way as a host would do.
+    // Build the examples first, so that the library is not missing or outdated.
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "loads the example plugin; run `cargo build --examples --features backend-clap` and then `cargo test --features backend-clap -- --ignored`"]
+    fn a_host_can_load_the_example_plugin_from_a_clap_file() {
+        let executable = std::env::current_exe().unwrap();
+        let target_dir = executable.parent().unwrap().parent().unwrap();
+        let library = target_dir.join("examples").join("libclap_synth.so");
+        assert!(
+            library.exists(),
+            "{} does not exist; run `cargo build --examples` first.",
+            library.display()
+        );
+        let clap_file = std::env::temp_dir().join(format!("rsynth-{}.clap", std::process::id()));
+        std::fs::copy(&library, &clap_file).unwrap();
+
+        unsafe {
+            let library = libloading::Library::new(&clap_file).unwrap();
+            let entry: libloading::Symbol<*const clap_plugin_entry> =
+                library.get(b"clap_entry\0").unwrap();
+            let entry = &**entry;
+            let path = CString::new(clap_file.to_str().unwrap()).unwrap();
+            assert!(entry.init.unwrap()(path.as_ptr()));
+            let factory = &*(entry.get_factory.unwrap()(CLAP_PLUGIN_FACTORY_ID.as_ptr())
+                as *const clap_plugin_factory);
+            assert_eq!(factory.get_plugin_count.unwrap()(factory), 1);
+            let descriptor = &*factory.get_plugin_descriptor.unwrap()(factory, 0);
+            assert_eq!(c_str(descriptor.id), "rsynth.example.noise");
+
+            let host: clap_host = mem::zeroed();
+            let plugin = &*factory.create_plugin.unwrap()(factory, &host, descriptor.id);
+            assert!(plugin.init.unwrap()(plugin));
+            assert!(plugin.activate.unwrap()(plugin, 44100.0, 1, 16));
+            assert!(plugin.start_processing.unwrap()(plugin));
+
+            let note_on = clap_event_midi {
+                header: event_header::<clap_event_midi>(4, CLAP_EVENT_MIDI),
+                port_index: 0,
+                data: [0x90, 60, 100],
+            };
+            let input_event_pointers: Vec<*const clap_event_header> = vec![&note_on.header];
+            let in_events = clap_input_events {
+                ctx: &input_event_pointers as *const _ as *mut c_void,
+                size: Some(input_events_size),
+                get: Some(input_events_get),
+            };
+            let mut left_out = [0.0f32; 16];
+            let mut right_out = [0.0f32; 16];
+            let mut output_channels = [left_out.as_mut_ptr(), right_out.as_mut_ptr()];
+            let mut audio_output = clap_audio_buffer {
+                data32: output_channels.as_mut_ptr(),
+                data64: ptr::null_mut(),
+                channel_count: 2,
+                latency: 0,
+                constant_mask: 0,
+            };
+            let process = clap_process {
+                steady_time: 0,
+                frames_count: 16,
+                transport: ptr::null(),
+                audio_inputs: ptr::null(),
+                audio_outputs: &mut audio_output,
+                audio_inputs_count: 0,
+                audio_outputs_count: 1,
+                in_events: &in_events,
+                out_events: ptr::null(),
+            };
+            assert_eq!(
+                plugin.process.unwrap()(plugin, &process),
+                CLAP_PROCESS_CONTINUE
+            );
+            // The example plays noise after the note on event.
+            assert!(left_out[4..].iter().any(|sample| *sample != 0.0));
+
+            plugin.stop_processing.unwrap()(plugin);
+            plugin.deactivate.unwrap()(plugin);
+            plugin.destroy.unwrap()(plugin);
+            entry.deinit.unwrap()();
+        }
+        std::fs::remove_file(&clap_file).unwrap();
+    }
+}
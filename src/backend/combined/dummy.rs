@@ -1,5 +1,5 @@
 //! Dummy backend that does nothing, useful for testing.
-use super::{AudioReader, AudioWriter, MidiWriter};
+use super::{AudioReader, AudioWriter, EventSource, ExactTime, MidiWriter};
 use crate::buffer::{AudioBufferIn, AudioBufferOut};
 use crate::event::{DeltaEvent, RawMidiEvent};
 use core::cmp;
@@ -77,6 +77,18 @@ impl Iterator for MidiDummy {
     }
 }
 
+impl EventSource for MidiDummy {
+    type Event = RawMidiEvent;
+
+    fn next_event(&mut self) -> Option<(ExactTime, RawMidiEvent)> {
+        None
+    }
+}
+
 impl MidiWriter for MidiDummy {
     fn write_event(&mut self, _event: DeltaEvent<RawMidiEvent>) {}
+
+    fn supports_sysex(&self) -> bool {
+        true
+    }
 }
@@ -1,65 +1,322 @@
-//! Read midi files.
-use super::MICROSECONDS_PER_SECOND;
-use crate::event::{DeltaEvent, RawMidiEvent};
+//! Read and write midi files.
+use super::transport::SyntheticTransport;
+use super::{EventSource, ExactTime, MidiFileEvent, MidiWriter, MICROSECONDS_PER_SECOND};
+use crate::backend::TimeSignature;
+use crate::event::{DeltaEvent, Indexed, MetaEvent, RawMidiEvent, SysExEvent};
 
 /// Re-exports from the `midly` crate.
 pub mod midly {
     pub use midly::*;
 }
 
-use self::midly::Header;
-use self::midly::Timing;
-use self::midly::TrackEvent;
+use self::midly::live::LiveEvent;
+use self::midly::num::{u15, u24, u28};
 #[cfg(test)]
-use self::midly::{
-    num::{u15, u24, u28, u4, u7},
-    Format, MidiMessage,
-};
+use self::midly::num::{u4, u7};
+#[cfg(test)]
+use self::midly::MidiMessage;
+use self::midly::{Format, Fps, Header, Smf, Timing, TrackEvent};
 use self::midly::{MetaMessage, TrackEventKind};
 use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
 
 const SECONDS_PER_MINUTE: u64 = 60;
 const MICROSECONDS_PER_MINUTE: u64 = SECONDS_PER_MINUTE * MICROSECONDS_PER_SECOND;
 const DEFAULT_BEATS_PER_MINUTE: u64 = 120;
 
+/// The error type that represents the errors you can get from [`MidlyMidiReader::try_new`]
+/// and [`MidlyMultiTrackReader::try_new`].
+///
+/// [`MidlyMidiReader::try_new`]: ./struct.MidlyMidiReader.html#method.try_new
+/// [`MidlyMultiTrackReader::try_new`]: ./struct.MidlyMultiTrackReader.html#method.try_new
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidlyMidiReaderError {
+    /// The timing of the midi file is not supported, e.g. timecode-based timing
+    /// with zero subframes per frame.
+    UnsupportedTiming(Timing),
+}
+
+impl Display for MidlyMidiReaderError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            MidlyMidiReaderError::UnsupportedTiming(timing) => {
+                write!(f, "Unsupported midi file timing: {:?}", timing)
+            }
+        }
+    }
+}
+
+impl Error for MidlyMidiReaderError {}
+
+/// How the duration of a tick is determined.
+///
+/// The duration of a tick is a fraction of microseconds: [`scaled_tick_duration`] divided
+/// by [`denominator`], so that the time can be computed with exact integer arithmetic.
+///
+/// [`scaled_tick_duration`]: #method.scaled_tick_duration
+/// [`denominator`]: #method.denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TickTiming {
+    /// The duration of a tick depends on the tempo.
+    Metrical { ticks_per_beat: u64 },
+    /// The duration of a tick is fixed (SMPTE timecode); tempo changes are ignored.
+    /// The number of frames per second is `frames_per_second_numerator / frames_per_second_denominator`.
+    Timecode {
+        frames_per_second_numerator: u64,
+        frames_per_second_denominator: u64,
+        subframes_per_frame: u64,
+    },
+}
+
+impl TickTiming {
+    fn from_header(header: Header) -> Result<Self, MidlyMidiReaderError> {
+        match header.timing {
+            Timing::Metrical(t) if t.as_int() > 0 => Ok(TickTiming::Metrical {
+                ticks_per_beat: t.as_int() as u64,
+            }),
+            Timing::Timecode(fps, subframes) if subframes > 0 => {
+                let (frames_per_second_numerator, frames_per_second_denominator) =
+                    frames_per_second(fps);
+                Ok(TickTiming::Timecode {
+                    frames_per_second_numerator,
+                    frames_per_second_denominator,
+                    subframes_per_frame: subframes as u64,
+                })
+            }
+            timing => Err(MidlyMidiReaderError::UnsupportedTiming(timing)),
+        }
+    }
+
+    /// The duration of a tick in microseconds, multiplied by the `denominator`.
+    fn scaled_tick_duration(&self, tempo_in_micro_seconds_per_beat: u64) -> u64 {
+        match *self {
+            TickTiming::Metrical { .. } => tempo_in_micro_seconds_per_beat,
+            TickTiming::Timecode {
+                frames_per_second_denominator,
+                ..
+            } => MICROSECONDS_PER_SECOND * frames_per_second_denominator,
+        }
+    }
+
+    fn denominator(&self) -> u64 {
+        match *self {
+            TickTiming::Metrical { ticks_per_beat } => ticks_per_beat,
+            TickTiming::Timecode {
+                frames_per_second_numerator,
+                subframes_per_frame,
+                ..
+            } => frames_per_second_numerator * subframes_per_frame,
+        }
+    }
+}
+
+const DEFAULT_TEMPO_IN_MICRO_SECONDS_PER_BEAT: u64 =
+    MICROSECONDS_PER_MINUTE / DEFAULT_BEATS_PER_MINUTE;
+
+/// Keeps track of the time while reading the events of a midi file.
+///
+/// The absolute time is computed with exact integer arithmetic. The time between two events
+/// is derived from the absolute times, rounded to the microsecond, so that rounding errors
+/// do not accumulate, even in long midi files.
+struct MidiFileClock {
+    tick_timing: TickTiming,
+    tempo_in_micro_seconds_per_beat: u64,
+    /// The absolute time in microseconds, multiplied by `tick_timing.denominator()`.
+    scaled_time: u128,
+    last_event_time_in_microseconds: u64,
+}
+
+impl MidiFileClock {
+    fn new(tick_timing: TickTiming) -> Self {
+        Self {
+            tick_timing,
+            tempo_in_micro_seconds_per_beat: DEFAULT_TEMPO_IN_MICRO_SECONDS_PER_BEAT,
+            scaled_time: 0,
+            last_event_time_in_microseconds: 0,
+        }
+    }
+
+    fn advance(&mut self, ticks: u64) {
+        self.scaled_time += ticks as u128
+            * self
+                .tick_timing
+                .scaled_tick_duration(self.tempo_in_micro_seconds_per_beat) as u128;
+    }
+
+    fn handle_tempo_change(&mut self, kind: TrackEventKind) {
+        if let TrackEventKind::Meta(MetaMessage::Tempo(new_tempo_in_microseconds_per_beat)) = kind {
+            self.tempo_in_micro_seconds_per_beat =
+                new_tempo_in_microseconds_per_beat.as_int() as u64;
+        }
+    }
+
+    /// The exact absolute time.
+    fn time(&self) -> ExactTime {
+        ExactTime::new(self.scaled_time, self.tick_timing.denominator())
+    }
+
+    /// The absolute time, rounded to the nearest microsecond.
+    #[cfg(test)]
+    fn time_in_microseconds(&self) -> u64 {
+        self.time().in_microseconds()
+    }
+
+    /// Return the current time as the time of an event, together with the time since the
+    /// previous event, in microseconds.
+    fn event_time(&mut self) -> (ExactTime, u64) {
+        let time = self.time();
+        let time_in_microseconds = time.in_microseconds();
+        let microseconds_since_previous_event =
+            time_in_microseconds - self.last_event_time_in_microseconds;
+        self.last_event_time_in_microseconds = time_in_microseconds;
+        (time, microseconds_since_previous_event)
+    }
+}
+
 /// Read from midi events as parsed by the `midly` crate.
+///
+/// Both metrical timing (ticks per beat) and timecode-based timing
+/// (frames per second and subframes per frame) are supported.
+/// For 29.97 frames per second ("drop frame"), the exact frame rate of `30 / 1.001` frames per
+/// second is used.
+///
+/// Only midi events are returned. Use the [`events`] method of [`MidlyMultiTrackReader`] to also
+/// read system exclusive messages and meta events.
+///
+/// [`events`]: ./struct.MidlyMultiTrackReader.html#method.events
+/// [`MidlyMultiTrackReader`]: ./struct.MidlyMultiTrackReader.html
 pub struct MidlyMidiReader<'v, 'a> {
     events: &'v [TrackEvent<'a>],
     event_index: usize,
-    current_tempo_in_micro_seconds_per_beat: f64,
-    ticks_per_beat: f64,
+    clock: MidiFileClock,
 }
 
 impl<'v, 'a> MidlyMidiReader<'v, 'a> {
-    fn ticks_per_microsecond(&self) -> f64 {
-        self.ticks_per_beat / self.current_tempo_in_micro_seconds_per_beat
-    }
-
+    #[deprecated(
+        since = "0.1.2",
+        note = "Use `try_new` instead, which does not panic when the timing is not supported."
+    )]
     /// Create a new `MidlyMidiReader`.
+    ///
+    /// Panics
+    /// ------
+    /// Panics when the timing of the `header` is not supported.
+    /// Use [`try_new`] to get an error instead.
+    ///
+    /// [`try_new`]: #method.try_new
     pub fn new(header: Header, events: &'v [TrackEvent<'a>]) -> Self {
-        Self {
+        match Self::try_new(header, events) {
+            Ok(reader) => reader,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Try to create a new `MidlyMidiReader`.
+    /// Return an error when the timing of the `header` is not supported.
+    pub fn try_new(
+        header: Header,
+        events: &'v [TrackEvent<'a>],
+    ) -> Result<Self, MidlyMidiReaderError> {
+        Ok(Self {
             events,
             event_index: 0,
-            current_tempo_in_micro_seconds_per_beat: (MICROSECONDS_PER_MINUTE as f64
-                / DEFAULT_BEATS_PER_MINUTE as f64),
-            ticks_per_beat: match header.timing {
-                Timing::Metrical(t) => t.as_int() as f64,
-                Timing::Timecode(_, _) => unimplemented!(),
-            },
-        }
+            clock: MidiFileClock::new(TickTiming::from_header(header)?),
+        })
+    }
+}
+
+/// The number of frames per second, as a fraction (numerator, denominator).
+fn frames_per_second(fps: Fps) -> (u64, u64) {
+    match fps {
+        Fps::Fps24 => (24, 1),
+        Fps::Fps25 => (25, 1),
+        Fps::Fps29 => (30_000, 1001),
+        Fps::Fps30 => (30, 1),
     }
 }
 
 #[test]
 fn ticks_per_microsecond_works() {
     // 1 beat per second
-    let mr = MidlyMidiReader {
-        events: &[],
-        event_index: 0,
-        current_tempo_in_micro_seconds_per_beat: 1000_000.0,
-        ticks_per_beat: 100.0 * 1000_000.0,
+    let mut clock = MidiFileClock::new(TickTiming::Metrical {
+        ticks_per_beat: 100 * 1000_000,
+    });
+    clock.tempo_in_micro_seconds_per_beat = 1000_000;
+    // 100 ticks per microsecond.
+    clock.advance(100);
+    assert_eq!(clock.time_in_microseconds(), 1);
+}
+
+#[test]
+fn ticks_per_microsecond_works_for_timecode() {
+    let header = Header {
+        format: Format::SingleTrack,
+        timing: Timing::Timecode(Fps::Fps25, 40),
     };
-    assert_eq!(mr.ticks_per_microsecond(), 100.0);
+    let mut mr = MidlyMidiReader::try_new(header, &[]).expect("Unexpected error.");
+    // 25 frames per second with 40 subframes per frame is 1000 ticks per second.
+    mr.clock.advance(1);
+    assert_eq!(mr.clock.time_in_microseconds(), 1000);
+}
+
+#[test]
+fn try_new_returns_an_error_for_unsupported_timing() {
+    let timing = Timing::Timecode(Fps::Fps30, 0);
+    let header = Header {
+        format: Format::SingleTrack,
+        timing,
+    };
+    assert_eq!(
+        MidlyMidiReader::try_new(header, &[]).err(),
+        Some(MidlyMidiReaderError::UnsupportedTiming(timing))
+    );
+}
+
+#[test]
+fn iterator_uses_timecode_timing_and_ignores_tempo_changes() {
+    let note_on = TrackEventKind::Midi {
+        channel: u4::from(0),
+        message: MidiMessage::NoteOn {
+            key: u7::from(60),
+            vel: u7::from(90),
+        },
+    };
+    // (frames per second, subframes per frame, ticks, expected microseconds)
+    let cases = [
+        // One second.
+        (Fps::Fps24, 4, 96, 1_000_000),
+        (Fps::Fps25, 40, 1000, 1_000_000),
+        (Fps::Fps30, 80, 2400, 1_000_000),
+        // 30 frames at 29.97 frames per second take 1.001 seconds.
+        (Fps::Fps29, 10, 300, 1_001_000),
+    ];
+    for &(fps, subframes, ticks, expected_microseconds) in cases.iter() {
+        let events = vec![
+            TrackEvent {
+                delta: u28::from(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::from(1_000_000))),
+            },
+            TrackEvent {
+                delta: u28::from(ticks),
+                kind: note_on,
+            },
+        ];
+        let header = Header {
+            format: Format::SingleTrack,
+            timing: Timing::Timecode(fps, subframes),
+        };
+        let mut mr = MidlyMidiReader::try_new(header, &events).expect("Unexpected error.");
+        let observed = mr.next().expect("MidlyMidiReader should return one event.");
+        assert_eq!(
+            observed.microseconds_since_previous_event, expected_microseconds,
+            "{:?} with {} subframes",
+            fps, subframes
+        );
+        assert_eq!(mr.next(), None);
+    }
 }
 
 #[test]
@@ -68,9 +325,14 @@ fn new_works() {
         format: Format::SingleTrack,
         timing: Timing::Metrical(u15::from(12345)),
     };
-    let mr = MidlyMidiReader::new(header, &[]);
+    let mr = MidlyMidiReader::try_new(header, &[]).expect("Unexpected error.");
     assert_eq!(mr.event_index, 0);
-    assert_eq!(mr.ticks_per_beat, 12345.0);
+    assert_eq!(
+        mr.clock.tick_timing,
+        TickTiming::Metrical {
+            ticks_per_beat: 12345
+        }
+    );
     // 120 beats per minute
     // = 120 beats per 60 seconds
     // = 120 beats per 60 000 000 microseconds
@@ -78,36 +340,52 @@ fn new_works() {
     //   60 000 000 / 120 beats per microsecond
     //   = 10 000 000 / 20 beats per microsecond
     //   =    500 000 beats per microsecond
-    assert_eq!(mr.current_tempo_in_micro_seconds_per_beat, 500000.0);
+    assert_eq!(mr.clock.tempo_in_micro_seconds_per_beat, 500000);
 }
 
-impl<'e, 'a> Iterator for MidlyMidiReader<'e, 'a> {
-    type Item = DeltaEvent<RawMidiEvent>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut microseconds_since_previous_event = 0.0;
+impl<'e, 'a> MidlyMidiReader<'e, 'a> {
+    /// Return the next midi event, together with its exact time.
+    fn next_timed(&mut self) -> Option<(ExactTime, DeltaEvent<RawMidiEvent>)> {
         while let Some(event) = self.events.get(self.event_index) {
             self.event_index += 1;
-            microseconds_since_previous_event +=
-                (event.delta.as_int() as f64) / self.ticks_per_microsecond();
-            if let TrackEventKind::Meta(MetaMessage::Tempo(new_tempo_in_microseconds_per_beat)) =
-                event.kind
-            {
-                self.current_tempo_in_micro_seconds_per_beat =
-                    new_tempo_in_microseconds_per_beat.as_int() as f64;
-            }
+            self.clock.advance(event.delta.as_int() as u64);
+            self.clock.handle_tempo_change(event.kind);
             if let TrackEventKind::Midi { .. } = event.kind {
                 let raw_midi_event = RawMidiEvent::try_from(event.kind).ok()?;
-                return Some(DeltaEvent {
-                    microseconds_since_previous_event: microseconds_since_previous_event as u64,
-                    event: raw_midi_event,
-                });
+                let (time, microseconds_since_previous_event) = self.clock.event_time();
+                return Some((
+                    time,
+                    DeltaEvent {
+                        microseconds_since_previous_event,
+                        event: raw_midi_event,
+                    },
+                ));
             }
         }
         return None;
     }
 }
 
+impl<'e, 'a> Iterator for MidlyMidiReader<'e, 'a> {
+    type Item = DeltaEvent<RawMidiEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_timed().map(|(_, event)| event)
+    }
+}
+
+/// The midi events with their exact time, so that they are placed on the exact frame when
+/// rendering with the [`run`] function.
+///
+/// [`run`]: ../fn.run.html
+impl<'e, 'a> EventSource for MidlyMidiReader<'e, 'a> {
+    type Event = RawMidiEvent;
+
+    fn next_event(&mut self) -> Option<(ExactTime, RawMidiEvent)> {
+        self.next_timed().map(|(time, event)| (time, event.event))
+    }
+}
+
 #[test]
 fn iterator_correctly_returns_one_event() {
     // 120 beats per minute
@@ -144,12 +422,820 @@ fn iterator_correctly_returns_one_event() {
         timing: Timing::Metrical(u15::from(ticks_per_beat)),
         format: Format::SingleTrack,
     };
-    let mut mr = MidlyMidiReader::new(header, &events);
+    let mut mr = MidlyMidiReader::try_new(header, &events).expect("Unexpected error.");
     let observed = mr.next().expect("MidlyMidiReader should return one event.");
     assert_eq!(observed.microseconds_since_previous_event, 1000000);
     assert_eq!(mr.next(), None);
 }
 
+/// Read the midi events of all tracks of a midi file, as parsed by the `midly` crate.
+///
+/// The events of all tracks are merged by time. Tempo changes from any track (typically the
+/// first track of a format 1 file) apply to all tracks.
+/// When events of different tracks happen at the same tick, the event of the track with the
+/// lowest index comes first.
+///
+/// This iterator returns the midi events without the index of the track.
+/// Use the [`indexed`] method to get the index of the track with each event.
+///
+/// _Note_: the tracks of a format 2 ("sequential") midi file are independent sequences,
+/// but are merged in the same way.
+///
+/// Example
+/// -------
+/// ```no_run
+/// use rsynth::backend::combined::midly::{midly::Smf, MidlyMultiTrackReader};
+/// let data = std::fs::read("song.mid").expect("Failed to read the midi file.");
+/// let smf = Smf::parse(&data).expect("Failed to parse the midi file.");
+/// let reader = MidlyMultiTrackReader::try_new(smf.header, &smf.tracks)
+///     .expect("The timing of the midi file is not supported.");
+/// // Pass `reader` as the midi input to the `run` function ...
+/// # drop(reader);
+/// ```
+///
+/// [`indexed`]: #method.indexed
+pub struct MidlyMultiTrackReader<'v, 'a> {
+    tracks: &'v [Vec<TrackEvent<'a>>],
+    positions: Vec<TrackPosition>,
+    clock: MidiFileClock,
+    time_in_ticks: u64,
+}
+
+/// The position of the reader within one track.
+#[derive(Clone, Copy, Default)]
+struct TrackPosition {
+    event_index: usize,
+    previous_event_time_in_ticks: u64,
+}
+
+impl<'v, 'a> MidlyMultiTrackReader<'v, 'a> {
+    /// Try to create a new `MidlyMultiTrackReader`.
+    /// Return an error when the timing of the `header` is not supported.
+    pub fn try_new(
+        header: Header,
+        tracks: &'v [Vec<TrackEvent<'a>>],
+    ) -> Result<Self, MidlyMidiReaderError> {
+        Ok(Self {
+            tracks,
+            positions: vec![TrackPosition::default(); tracks.len()],
+            clock: MidiFileClock::new(TickTiming::from_header(header)?),
+            time_in_ticks: 0,
+        })
+    }
+
+    /// Return the midi events together with the index of the track they belong to.
+    pub fn indexed(self) -> IndexedMidlyMultiTrackReader<'v, 'a> {
+        IndexedMidlyMultiTrackReader { reader: self }
+    }
+
+    /// Return the events of all tracks as [`MidiFileEvent`]s: midi events, system exclusive
+    /// messages and, if enabled with [`with_meta_events`], meta events.
+    /// These events can be rendered by passing them as midi input in the [`RunOptions`].
+    ///
+    /// [`MidiFileEvent`]: ../enum.MidiFileEvent.html
+    /// [`with_meta_events`]: ./struct.MidlyMidiFileEventReader.html#method.with_meta_events
+    /// [`RunOptions`]: ../struct.RunOptions.html
+    pub fn events(self) -> MidlyMidiFileEventReader<'v, 'a> {
+        MidlyMidiFileEventReader {
+            reader: self,
+            include_meta_events: false,
+        }
+    }
+
+    fn next_indexed(&mut self) -> Option<(ExactTime, DeltaEvent<Indexed<RawMidiEvent>>)> {
+        self.next_converted(|kind| match kind {
+            TrackEventKind::Midi { .. } => RawMidiEvent::try_from(kind).ok(),
+            _ => None,
+        })
+    }
+
+    /// Return the next event for which `convert` returns `Some`, together with its exact time.
+    fn next_converted<E, F>(&mut self, convert: F) -> Option<(ExactTime, DeltaEvent<Indexed<E>>)>
+    where
+        F: Fn(TrackEventKind<'a>) -> Option<E>,
+    {
+        loop {
+            let tracks = self.tracks;
+            let (track_index, event_time_in_ticks) = self
+                .positions
+                .iter()
+                .zip(tracks.iter())
+                .enumerate()
+                .filter_map(|(track_index, (position, track))| {
+                    track.get(position.event_index).map(|event| {
+                        (
+                            track_index,
+                            position.previous_event_time_in_ticks + event.delta.as_int() as u64,
+                        )
+                    })
+                })
+                .min_by_key(|&(track_index, time_in_ticks)| (time_in_ticks, track_index))?;
+            let position = &mut self.positions[track_index];
+            let event = &tracks[track_index][position.event_index];
+            position.event_index += 1;
+            position.previous_event_time_in_ticks = event_time_in_ticks;
+
+            self.clock.advance(event_time_in_ticks - self.time_in_ticks);
+            self.time_in_ticks = event_time_in_ticks;
+            self.clock.handle_tempo_change(event.kind);
+            if let Some(converted) = convert(event.kind) {
+                let (time, microseconds_since_previous_event) = self.clock.event_time();
+                return Some((
+                    time,
+                    DeltaEvent {
+                        microseconds_since_previous_event,
+                        event: Indexed::new(track_index, converted),
+                    },
+                ));
+            }
+        }
+    }
+}
+
+impl<'v, 'a> Iterator for MidlyMultiTrackReader<'v, 'a> {
+    type Item = DeltaEvent<RawMidiEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_indexed().map(|(_, event)| DeltaEvent {
+            microseconds_since_previous_event: event.microseconds_since_previous_event,
+            event: event.event.event,
+        })
+    }
+}
+
+/// The midi events with their exact time, so that they are placed on the exact frame when
+/// rendering with the [`run`] function.
+///
+/// [`run`]: ../fn.run.html
+impl<'v, 'a> EventSource for MidlyMultiTrackReader<'v, 'a> {
+    type Event = RawMidiEvent;
+
+    fn next_event(&mut self) -> Option<(ExactTime, RawMidiEvent)> {
+        self.next_indexed()
+            .map(|(time, event)| (time, event.event.event))
+    }
+}
+
+/// Read the midi events of all tracks of a midi file, together with the index of the track.
+///
+/// This is created by the [`indexed`] method of [`MidlyMultiTrackReader`].
+///
+/// [`indexed`]: ./struct.MidlyMultiTrackReader.html#method.indexed
+/// [`MidlyMultiTrackReader`]: ./struct.MidlyMultiTrackReader.html
+pub struct IndexedMidlyMultiTrackReader<'v, 'a> {
+    reader: MidlyMultiTrackReader<'v, 'a>,
+}
+
+impl<'v, 'a> Iterator for IndexedMidlyMultiTrackReader<'v, 'a> {
+    type Item = DeltaEvent<Indexed<RawMidiEvent>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_indexed().map(|(_, event)| event)
+    }
+}
+
+/// Read the midi events, system exclusive messages and (optionally) meta events of all tracks
+/// of a midi file.
+///
+/// This is created by the [`events`] method of [`MidlyMultiTrackReader`].
+/// Render these events by passing them as midi input in the [`RunOptions`].
+///
+/// [`events`]: ./struct.MidlyMultiTrackReader.html#method.events
+/// [`MidlyMultiTrackReader`]: ./struct.MidlyMultiTrackReader.html
+/// [`RunOptions`]: ../struct.RunOptions.html
+pub struct MidlyMidiFileEventReader<'v, 'a> {
+    reader: MidlyMultiTrackReader<'v, 'a>,
+    include_meta_events: bool,
+}
+
+impl<'v, 'a> MidlyMidiFileEventReader<'v, 'a> {
+    /// Also return the meta events (text, markers, lyrics, key and time signatures, ...).
+    pub fn with_meta_events(mut self) -> Self {
+        self.include_meta_events = true;
+        self
+    }
+}
+
+impl<'v, 'a> MidlyMidiFileEventReader<'v, 'a> {
+    fn next_timed(&mut self) -> Option<(ExactTime, DeltaEvent<MidiFileEvent<'a>>)> {
+        let include_meta_events = self.include_meta_events;
+        self.reader
+            .next_converted(|kind| match kind {
+                TrackEventKind::Midi { .. } => {
+                    RawMidiEvent::try_from(kind).ok().map(MidiFileEvent::Midi)
+                }
+                TrackEventKind::SysEx(data) => Some(MidiFileEvent::SysEx(data)),
+                TrackEventKind::Meta(message) if include_meta_events => {
+                    meta_event(message).map(MidiFileEvent::Meta)
+                }
+                _ => None,
+            })
+            .map(|(time, event)| {
+                (
+                    time,
+                    DeltaEvent {
+                        microseconds_since_previous_event: event.microseconds_since_previous_event,
+                        event: event.event.event,
+                    },
+                )
+            })
+    }
+}
+
+impl<'v, 'a> Iterator for MidlyMidiFileEventReader<'v, 'a> {
+    type Item = DeltaEvent<MidiFileEvent<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_timed().map(|(_, event)| event)
+    }
+}
+
+/// The events with their exact time, so that they are placed on the exact frame when
+/// rendering with the [`run`] function.
+///
+/// [`run`]: ../fn.run.html
+impl<'v, 'a> EventSource for MidlyMidiFileEventReader<'v, 'a> {
+    type Event = MidiFileEvent<'a>;
+
+    fn next_event(&mut self) -> Option<(ExactTime, MidiFileEvent<'a>)> {
+        self.next_timed().map(|(time, event)| (time, event.event))
+    }
+}
+
+/// Convert a meta message from the `midly` crate to a `MetaEvent`, if it is meaningful for
+/// plugins.
+fn meta_event(message: MetaMessage) -> Option<MetaEvent> {
+    Some(match message {
+        MetaMessage::Text(text) => MetaEvent::Text(text),
+        MetaMessage::Copyright(text) => MetaEvent::Copyright(text),
+        MetaMessage::TrackName(text) => MetaEvent::TrackName(text),
+        MetaMessage::InstrumentName(text) => MetaEvent::InstrumentName(text),
+        MetaMessage::Lyric(text) => MetaEvent::Lyric(text),
+        MetaMessage::Marker(text) => MetaEvent::Marker(text),
+        MetaMessage::CuePoint(text) => MetaEvent::CuePoint(text),
+        MetaMessage::KeySignature(sharps, minor) => MetaEvent::KeySignature { sharps, minor },
+        MetaMessage::TimeSignature(numerator, denominator_power, _, _) => {
+            match 1u32.checked_shl(denominator_power as u32) {
+                Some(denominator) if numerator > 0 => {
+                    MetaEvent::TimeSignature(TimeSignature::new(numerator as u32, denominator))
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+fn note_on(channel: u8, key: u8) -> TrackEventKind<'static> {
+    TrackEventKind::Midi {
+        channel: u4::from(channel),
+        message: MidiMessage::NoteOn {
+            key: u7::from(key),
+            vel: u7::from(100),
+        },
+    }
+}
+
+#[test]
+fn multi_track_reader_merges_tracks_and_applies_tempo_changes_to_all_tracks() {
+    let ticks_per_beat = 10;
+    let tracks = vec![
+        // The tempo map: after one beat at 120 beats per minute (500 000 microseconds),
+        // change the tempo to 60 beats per minute.
+        vec![TrackEvent {
+            delta: u28::from(10),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::from(1_000_000))),
+        }],
+        // Events at beat 0 and at beat 2.
+        vec![
+            TrackEvent {
+                delta: u28::from(0),
+                kind: note_on(0, 60),
+            },
+            TrackEvent {
+                delta: u28::from(20),
+                kind: note_on(0, 62),
+            },
+        ],
+        // Events at beat 0 and at beat 1.5.
+        vec![
+            TrackEvent {
+                delta: u28::from(0),
+                kind: note_on(1, 64),
+            },
+            TrackEvent {
+                delta: u28::from(15),
+                kind: note_on(1, 66),
+            },
+        ],
+    ];
+    let header = Header {
+        format: Format::Parallel,
+        timing: Timing::Metrical(u15::from(ticks_per_beat)),
+    };
+    let expected = [
+        (0, 1, note_on(0, 60)),
+        (0, 2, note_on(1, 64)),
+        // One beat at 120 bpm and half a beat at 60 bpm.
+        (1_000_000, 2, note_on(1, 66)),
+        (500_000, 1, note_on(0, 62)),
+    ];
+    let observed: Vec<_> = MidlyMultiTrackReader::try_new(header, &tracks)
+        .expect("Unexpected error.")
+        .indexed()
+        .collect();
+    assert_eq!(
+        observed,
+        expected
+            .iter()
+            .map(|&(delta, index, kind)| DeltaEvent {
+                microseconds_since_previous_event: delta,
+                event: Indexed::new(index, RawMidiEvent::try_from(kind).unwrap()),
+            })
+            .collect::<Vec<_>>()
+    );
+    let without_index: Vec<_> = MidlyMultiTrackReader::try_new(header, &tracks)
+        .expect("Unexpected error.")
+        .map(|event| event.event)
+        .collect();
+    assert_eq!(
+        without_index,
+        observed
+            .iter()
+            .map(|event| event.event.event)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn midi_file_event_reader_returns_sysex_and_optionally_meta_events() {
+    let sysex_data = [0x7E, 0x7F, 0x09, 0x01, 0xF7];
+    let tracks = vec![vec![
+        TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::Marker(b"intro")),
+        },
+        TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::TimeSignature(6, 3, 24, 8)),
+        },
+        // The end of track meta event is never returned.
+        TrackEvent {
+            delta: u28::from(10),
+            kind: TrackEventKind::SysEx(&sysex_data),
+        },
+        TrackEvent {
+            delta: u28::from(10),
+            kind: note_on(0, 60),
+        },
+        TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        },
+    ]];
+    // One tick is 500 000 / 10 = 50 000 microseconds.
+    let header = Header {
+        format: Format::SingleTrack,
+        timing: Timing::Metrical(u15::from(10)),
+    };
+    let without_meta: Vec<_> = MidlyMultiTrackReader::try_new(header, &tracks)
+        .expect("Unexpected error.")
+        .events()
+        .collect();
+    assert_eq!(
+        without_meta,
+        vec![
+            DeltaEvent {
+                microseconds_since_previous_event: 500_000,
+                event: MidiFileEvent::SysEx(&sysex_data),
+            },
+            DeltaEvent {
+                microseconds_since_previous_event: 500_000,
+                event: MidiFileEvent::Midi(RawMidiEvent::new(&[0x90, 60, 100])),
+            },
+        ]
+    );
+    let with_meta: Vec<_> = MidlyMultiTrackReader::try_new(header, &tracks)
+        .expect("Unexpected error.")
+        .events()
+        .with_meta_events()
+        .collect();
+    assert_eq!(
+        with_meta[0..2],
+        [
+            DeltaEvent {
+                microseconds_since_previous_event: 0,
+                event: MidiFileEvent::Meta(MetaEvent::Marker(b"intro")),
+            },
+            DeltaEvent {
+                microseconds_since_previous_event: 0,
+                event: MidiFileEvent::Meta(MetaEvent::TimeSignature(TimeSignature::new(6, 8))),
+            },
+        ]
+    );
+    assert_eq!(with_meta[2..], without_meta[..]);
+}
+
+/// Write midi events to a standard midi file with the `midly` crate.
+///
+/// The events are collected in memory while rendering. After rendering, write the midi file
+/// with the [`save`] or [`write_std`] method, or get the [`Smf`] with the [`smf`] method.
+///
+/// The midi file uses a constant tempo (120 beats per minute by default) with
+/// 480 ticks per beat (by default). System exclusive events are stored as well.
+/// Other events that are not channel messages (e.g. system real-time messages)
+/// cannot be stored in a midi file and are skipped.
+///
+/// Example
+/// -------
+/// ```no_run
+/// use rsynth::backend::combined::midly::MidlyMidiWriter;
+/// use rsynth::backend::combined::midly::midly::{num::u15, Format};
+/// let mut writer = MidlyMidiWriter::new()
+///     .format(Format::Parallel)
+///     .ticks_per_beat(u15::from(960))
+///     .beats_per_minute(90.0);
+/// // Pass `&mut writer` as the midi output to the `run` function and render ...
+/// writer.save("output.mid").expect("Failed to write the midi file.");
+/// ```
+///
+/// [`save`]: #method.save
+/// [`write_std`]: #method.write_std
+/// [`smf`]: #method.smf
+/// [`Smf`]: ./midly/struct.Smf.html
+pub struct MidlyMidiWriter {
+    format: Format,
+    ticks_per_beat: u15,
+    microseconds_per_beat: u24,
+    time_in_microseconds: u64,
+    events: Vec<(u64, WrittenEvent)>,
+}
+
+enum WrittenEvent {
+    Midi(RawMidiEvent),
+    SysEx(Vec<u8>),
+}
+
+const DEFAULT_TICKS_PER_BEAT: u16 = 480;
+
+impl MidlyMidiWriter {
+    /// Create a new `MidlyMidiWriter` for a single-track (format 0) midi file
+    /// with 480 ticks per beat and a tempo of 120 beats per minute.
+    pub fn new() -> Self {
+        Self {
+            format: Format::SingleTrack,
+            ticks_per_beat: u15::from(DEFAULT_TICKS_PER_BEAT),
+            microseconds_per_beat: u24::from(
+                (MICROSECONDS_PER_MINUTE / DEFAULT_BEATS_PER_MINUTE) as u32,
+            ),
+            time_in_microseconds: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Set the format of the midi file.
+    ///
+    /// With `Format::SingleTrack` (format 0), the tempo and the midi events are written
+    /// in one track.
+    /// With `Format::Parallel` (format 1), the tempo is written in the first track and the
+    /// midi events are written in the second track.
+    /// `Format::Sequential` (format 2) is not supported and is treated as `Format::Parallel`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = match format {
+            Format::Sequential => Format::Parallel,
+            format => format,
+        };
+        self
+    }
+
+    /// Set the number of ticks per beat (the resolution of the midi file).
+    pub fn ticks_per_beat(mut self, ticks_per_beat: u15) -> Self {
+        self.ticks_per_beat = ticks_per_beat;
+        self
+    }
+
+    /// Set the tempo of the midi file.
+    ///
+    /// Panics
+    /// ------
+    /// Panics if `beats_per_minute` is not positive.
+    pub fn beats_per_minute(mut self, beats_per_minute: f64) -> Self {
+        assert!(beats_per_minute > 0.0);
+        let microseconds_per_beat = (MICROSECONDS_PER_MINUTE as f64 / beats_per_minute).round();
+        self.microseconds_per_beat =
+            u24::from(microseconds_per_beat.min(u24::max_value().as_int() as f64) as u32);
+        self
+    }
+
+    fn time_in_ticks(&self, time_in_microseconds: u64) -> u64 {
+        let ticks_per_beat = self.ticks_per_beat.as_int() as u128;
+        let microseconds_per_beat = self.microseconds_per_beat.as_int() as u128;
+        // Round to the nearest tick.
+        ((time_in_microseconds as u128 * ticks_per_beat + microseconds_per_beat / 2)
+            / microseconds_per_beat) as u64
+    }
+
+    fn track_events(&self) -> Vec<TrackEvent<'_>> {
+        let mut track = Vec::with_capacity(self.events.len() + 1);
+        // Compute the delta times from the absolute times, so that rounding errors
+        // do not accumulate.
+        let mut previous_time_in_ticks = 0;
+        for (time_in_microseconds, event) in self.events.iter() {
+            let kind = match event {
+                WrittenEvent::Midi(event) => match LiveEvent::parse(event.bytes()) {
+                    Ok(LiveEvent::Midi { channel, message }) => {
+                        TrackEventKind::Midi { channel, message }
+                    }
+                    _ => {
+                        warn!(
+                            "Skipping midi event {:?} that cannot be stored in a midi file.",
+                            event.bytes()
+                        );
+                        continue;
+                    }
+                },
+                // In a midi file, the leading `0xF0` byte is implied by the event kind.
+                WrittenEvent::SysEx(data) => match data.split_first() {
+                    Some((0xF0, data)) => TrackEventKind::SysEx(data),
+                    _ => {
+                        warn!(
+                            "Skipping system exclusive event {:?} that does not start with 0xF0.",
+                            data
+                        );
+                        continue;
+                    }
+                },
+            };
+            let time_in_ticks = self.time_in_ticks(*time_in_microseconds);
+            track.push(TrackEvent {
+                delta: u28::from((time_in_ticks - previous_time_in_ticks) as u32),
+                kind,
+            });
+            previous_time_in_ticks = time_in_ticks;
+        }
+        track.push(end_of_track());
+        track
+    }
+
+    /// Get the midi file with the events that have been written so far.
+    pub fn smf(&self) -> Smf<'_> {
+        let mut smf = Smf::new(Header::new(
+            self.format,
+            Timing::Metrical(self.ticks_per_beat),
+        ));
+        let tempo = TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(self.microseconds_per_beat)),
+        };
+        let mut events = self.track_events();
+        if self.format == Format::SingleTrack {
+            events.insert(0, tempo);
+            smf.tracks.push(events);
+        } else {
+            smf.tracks.push(vec![tempo, end_of_track()]);
+            smf.tracks.push(events);
+        }
+        smf
+    }
+
+    /// Write the midi file with the events that have been written so far.
+    pub fn write_std<W: io::Write>(&self, out: W) -> io::Result<()> {
+        self.smf().write_std(out)
+    }
+
+    /// Save the midi file with the events that have been written so far to the given path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.smf().save(path)
+    }
+}
+
+impl Default for MidlyMidiWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidiWriter for MidlyMidiWriter {
+    fn write_event(&mut self, event: DeltaEvent<RawMidiEvent>) {
+        self.time_in_microseconds += event.microseconds_since_previous_event;
+        self.events
+            .push((self.time_in_microseconds, WrittenEvent::Midi(event.event)));
+    }
+
+    fn supports_sysex(&self) -> bool {
+        true
+    }
+
+    fn write_sysex(&mut self, event: DeltaEvent<SysExEvent>) {
+        self.time_in_microseconds += event.microseconds_since_previous_event;
+        self.events.push((
+            self.time_in_microseconds,
+            WrittenEvent::SysEx(event.event.data().to_vec()),
+        ));
+    }
+}
+
+fn end_of_track() -> TrackEvent<'static> {
+    TrackEvent {
+        delta: u28::from(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    }
+}
+
+#[test]
+fn midly_midi_writer_writes_a_midi_file_that_can_be_read_back() {
+    let note_on = RawMidiEvent::new(&[0x90, 60, 90]);
+    let note_off = RawMidiEvent::new(&[0x80, 60, 0]);
+    // The default tempo is 120 beats per minute, so 500 000 microseconds per beat.
+    let mut writer = MidlyMidiWriter::new().ticks_per_beat(u15::from(100));
+    writer.write_event(DeltaEvent {
+        microseconds_since_previous_event: 500_000,
+        event: note_on,
+    });
+    // A system real-time event is skipped.
+    writer.write_event(DeltaEvent {
+        microseconds_since_previous_event: 0,
+        event: RawMidiEvent::new(&[0xF8]),
+    });
+    writer.write_event(DeltaEvent {
+        microseconds_since_previous_event: 250_000,
+        event: note_off,
+    });
+    let mut data = Vec::new();
+    writer.write_std(&mut data).unwrap();
+
+    let smf = Smf::parse(&data).unwrap();
+    assert_eq!(smf.header.format, Format::SingleTrack);
+    assert_eq!(smf.header.timing, Timing::Metrical(u15::from(100)));
+    assert_eq!(smf.tracks.len(), 1);
+    let deltas: Vec<u32> = smf.tracks[0].iter().map(|e| e.delta.as_int()).collect();
+    assert_eq!(deltas, vec![0, 100, 50, 0]);
+    let events: Vec<_> = MidlyMidiReader::try_new(smf.header, &smf.tracks[0])
+        .expect("Unexpected error.")
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            DeltaEvent {
+                microseconds_since_previous_event: 500_000,
+                event: note_on,
+            },
+            DeltaEvent {
+                microseconds_since_previous_event: 250_000,
+                event: note_off,
+            },
+        ]
+    );
+}
+
+#[test]
+fn midly_midi_writer_writes_sysex_events() {
+    let mut writer = MidlyMidiWriter::new().ticks_per_beat(u15::from(100));
+    writer.write_sysex(DeltaEvent {
+        microseconds_since_previous_event: 500_000,
+        event: SysExEvent::new(&[0xF0, 0x7E, 0x7F, 0xF7]),
+    });
+    // A system exclusive event without the leading `0xF0` is skipped.
+    writer.write_sysex(DeltaEvent {
+        microseconds_since_previous_event: 0,
+        event: SysExEvent::new(&[0x7E, 0xF7]),
+    });
+    let mut data = Vec::new();
+    writer.write_std(&mut data).unwrap();
+
+    let smf = Smf::parse(&data).unwrap();
+    assert_eq!(smf.tracks[0].len(), 3);
+    assert_eq!(smf.tracks[0][1].delta, u28::from(100));
+    assert_eq!(
+        smf.tracks[0][1].kind,
+        TrackEventKind::SysEx(&[0x7E, 0x7F, 0xF7])
+    );
+}
+
+#[test]
+fn midly_midi_writer_writes_the_tempo_in_a_separate_track_for_format_1() {
+    let mut writer = MidlyMidiWriter::new()
+        .format(Format::Parallel)
+        .ticks_per_beat(u15::from(96))
+        .beats_per_minute(60.0);
+    writer.write_event(DeltaEvent {
+        microseconds_since_previous_event: 1_500_000,
+        event: RawMidiEvent::new(&[0x91, 64, 100]),
+    });
+    let smf = writer.smf();
+    assert_eq!(smf.header.format, Format::Parallel);
+    assert_eq!(smf.tracks.len(), 2);
+    assert_eq!(
+        smf.tracks[0][0].kind,
+        TrackEventKind::Meta(MetaMessage::Tempo(u24::from(1_000_000)))
+    );
+    assert_eq!(smf.tracks[1][0].delta, u28::from(144));
+    assert_eq!(
+        smf.tracks[1][0].kind,
+        TrackEventKind::Midi {
+            channel: u4::from(1),
+            message: MidiMessage::NoteOn {
+                key: u7::from(64),
+                vel: u7::from(100),
+            },
+        }
+    );
+}
+
+/// Create a [`SyntheticTransport`] from the tempo and time signature meta-events
+/// in all tracks of a midi file.
+///
+/// The tracks are merged in the same way as by the [`MidlyMultiTrackReader`], so that e.g.
+/// the tempo map in the first track of a format 1 midi file applies to all tracks.
+///
+/// This can be used to provide transport information to the plugin when rendering a midi file
+/// with the [`transport`] method of [`RunOptions`].
+///
+/// _Note_: when the `header` specifies timecode-based timing, tempo meta-events are
+/// meaningless and the default transport is returned.
+///
+/// [`SyntheticTransport`]: ../transport/struct.SyntheticTransport.html
+/// [`MidlyMultiTrackReader`]: ./struct.MidlyMultiTrackReader.html
+/// [`transport`]: ../struct.RunOptions.html#method.transport
+/// [`RunOptions`]: ../struct.RunOptions.html
+pub fn synthetic_transport(
+    header: Header,
+    tracks: &[Vec<TrackEvent>],
+) -> Result<SyntheticTransport, MidlyMidiReaderError> {
+    let mut reader = MidlyMultiTrackReader::try_new(header, tracks)?;
+    let mut transport = SyntheticTransport::default();
+    if let Timing::Timecode(_, _) = header.timing {
+        return Ok(transport);
+    }
+    while let Some((time, event)) = reader.next_converted(|kind| match kind {
+        TrackEventKind::Meta(MetaMessage::Tempo(_))
+        | TrackEventKind::Meta(MetaMessage::TimeSignature(..)) => Some(kind),
+        _ => None,
+    }) {
+        let time_in_microseconds = time.in_microseconds();
+        match event.event.event {
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo_in_microseconds_per_beat)) => {
+                transport.add_tempo_change(
+                    time_in_microseconds,
+                    MICROSECONDS_PER_MINUTE as f64 / tempo_in_microseconds_per_beat.as_int() as f64,
+                );
+            }
+            TrackEventKind::Meta(MetaMessage::TimeSignature(
+                numerator,
+                denominator_power,
+                _,
+                _,
+            )) => match 1u32.checked_shl(denominator_power as u32) {
+                Some(denominator) if numerator > 0 => transport.add_time_signature_change(
+                    time_in_microseconds,
+                    TimeSignature::new(numerator as u32, denominator),
+                ),
+                _ => warn!(
+                    "Ignoring invalid time signature {}/2^{}.",
+                    numerator, denominator_power
+                ),
+            },
+            _ => {}
+        }
+    }
+    Ok(transport)
+}
+
+#[test]
+fn synthetic_transport_uses_tempo_and_time_signature_events_of_all_tracks() {
+    let ticks_per_beat = 32;
+    let tempo_track = vec![
+        // After two beats at the default tempo of 120 beats per minute (so after one second),
+        // change the tempo to 60 beats per minute.
+        TrackEvent {
+            delta: u28::from(2 * ticks_per_beat),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::from(1_000_000))),
+        },
+    ];
+    let note_track = vec![
+        TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::TimeSignature(3, 2, 24, 8)),
+        },
+        TrackEvent {
+            delta: u28::from(ticks_per_beat),
+            kind: note_on(0, 60),
+        },
+    ];
+    let header = Header {
+        timing: Timing::Metrical(u15::from(ticks_per_beat as u16)),
+        format: Format::Parallel,
+    };
+    let transport = synthetic_transport(header, &[tempo_track, note_track])
+        .expect("Unexpected error.")
+        .transport_at(2_000_000, 0);
+    assert_eq!(transport.tempo_in_beats_per_minute, Some(60.0));
+    assert_eq!(transport.position_in_quarter_notes, Some(3.0));
+    assert_eq!(transport.time_signature, Some(TimeSignature::new(3, 4)));
+    assert_eq!(transport.bar_start_in_quarter_notes, Some(3.0));
+}
+
 #[cfg(test)]
 fn iterator_correctly_returns_two_events() {
     // 120 beats per minute
@@ -196,7 +1282,7 @@ fn iterator_correctly_returns_two_events() {
         timing: Timing::Metrical(u15::from(ticks_per_beat)),
         format: Format::SingleTrack,
     };
-    let mut mr = MidlyMidiReader::new(header, &events);
+    let mut mr = MidlyMidiReader::try_new(header, &events).expect("Unexpected error.");
     let observed = mr.next().expect("MidlyMidiReader should return one event.");
     assert_eq!(observed.microseconds_since_previous_event, 1000000);
     let observed = mr
@@ -205,3 +1291,206 @@ fn iterator_correctly_returns_two_events() {
     assert_eq!(observed.microseconds_since_previous_event, 1000000);
     assert_eq!(mr.next(), None);
 }
+
+#[cfg(test)]
+struct FrameRecorder {
+    frames_rendered: u64,
+    event_times_in_frames: Vec<u64>,
+}
+
+#[cfg(test)]
+impl crate::AudioHandler for FrameRecorder {
+    fn set_sample_rate(&mut self, _sample_rate: f64) {}
+}
+
+#[cfg(test)]
+impl crate::event::EventHandler<crate::event::Timed<RawMidiEvent>> for FrameRecorder {
+    fn handle_event(&mut self, event: crate::event::Timed<RawMidiEvent>) {
+        self.event_times_in_frames
+            .push(self.frames_rendered + event.time_in_frames as u64);
+    }
+}
+
+#[cfg(test)]
+impl<C> crate::ContextualAudioRenderer<f32, C> for FrameRecorder {
+    fn render_buffer(
+        &mut self,
+        buffer: &mut crate::buffer::AudioBufferInOut<f32>,
+        _context: &mut C,
+    ) {
+        self.frames_rendered += buffer.number_of_frames() as u64;
+    }
+}
+
+#[cfg(test)]
+fn render_event_times_in_frames<M>(
+    midi_input: M,
+    sample_rate: u64,
+    length_in_seconds: u64,
+) -> Vec<u64>
+where
+    M: EventSource<Event = RawMidiEvent>,
+{
+    use super::dummy::{AudioDummy, MidiDummy};
+    let mut plugin = FrameRecorder {
+        frames_rendered: 0,
+        event_times_in_frames: Vec::new(),
+    };
+    super::run(
+        &mut plugin,
+        8192,
+        AudioDummy::<f32>::with_sample_rate_and_length(
+            sample_rate as u32,
+            (length_in_seconds * sample_rate) as usize,
+        ),
+        AudioDummy::<f32>::with_sample_rate_and_length(sample_rate as u32, 0),
+        MidiDummy::new(),
+        super::RunOptions::new().midi_input(midi_input),
+    )
+    .expect("Unexpected error.");
+    plugin.event_times_in_frames
+}
+
+#[test]
+fn rendering_a_one_hour_midi_file_places_every_event_on_the_expected_frame() {
+    // About 140 beats per minute and later about 133 beats per minute: a tick does not take
+    // an integer number of microseconds, nor an integer number of frames.
+    let first_tempo_in_microseconds_per_beat: u64 = 428_571;
+    let second_tempo_in_microseconds_per_beat: u64 = 451_127;
+    let ticks_per_beat: u64 = 960;
+    let event_delta_time_in_ticks: u64 = 97;
+    let number_of_events: u64 = 82_000;
+    // The tempo changes between two events, halfway the file.
+    let tempo_change_time_in_ticks: u64 = number_of_events / 2 * event_delta_time_in_ticks + 50;
+
+    let event_times_in_ticks: Vec<u64> = (1..=number_of_events)
+        .map(|event_index| event_index * event_delta_time_in_ticks)
+        .collect();
+    // The exact time of the event at the given tick, in microseconds, multiplied by the number
+    // of ticks per beat.
+    let scaled_time = |time_in_ticks: u64| -> u128 {
+        if time_in_ticks <= tempo_change_time_in_ticks {
+            time_in_ticks as u128 * first_tempo_in_microseconds_per_beat as u128
+        } else {
+            tempo_change_time_in_ticks as u128 * first_tempo_in_microseconds_per_beat as u128
+                + (time_in_ticks - tempo_change_time_in_ticks) as u128
+                    * second_tempo_in_microseconds_per_beat as u128
+        }
+    };
+    let round = |numerator: u128, denominator: u128| -> u64 {
+        ((2 * numerator + denominator) / (2 * denominator)) as u64
+    };
+
+    // A format 1 file with the tempo map in the first track and the notes in the second track.
+    let tempo_track = vec![
+        TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::from(
+                first_tempo_in_microseconds_per_beat as u32,
+            ))),
+        },
+        TrackEvent {
+            delta: u28::from(tempo_change_time_in_ticks as u32),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::from(
+                second_tempo_in_microseconds_per_beat as u32,
+            ))),
+        },
+    ];
+    let note_track: Vec<_> = event_times_in_ticks
+        .iter()
+        .map(|_| TrackEvent {
+            delta: u28::from(event_delta_time_in_ticks as u32),
+            kind: note_on(0, 60),
+        })
+        .collect();
+    let tracks = vec![tempo_track, note_track];
+    let header = Header {
+        format: Format::Parallel,
+        timing: Timing::Metrical(u15::from(ticks_per_beat as u16)),
+    };
+    // The same as a format 0 file.
+    let mut single_track = Vec::new();
+    let mut previous_time_in_ticks = 0;
+    for (index, &time_in_ticks) in event_times_in_ticks.iter().enumerate() {
+        if index == 0 {
+            single_track.push(tracks[0][0]);
+        }
+        if previous_time_in_ticks < tempo_change_time_in_ticks
+            && tempo_change_time_in_ticks < time_in_ticks
+        {
+            single_track.push(TrackEvent {
+                delta: u28::from((tempo_change_time_in_ticks - previous_time_in_ticks) as u32),
+                kind: tracks[0][1].kind,
+            });
+            previous_time_in_ticks = tempo_change_time_in_ticks;
+        }
+        single_track.push(TrackEvent {
+            delta: u28::from((time_in_ticks - previous_time_in_ticks) as u32),
+            kind: note_on(0, 60),
+        });
+        previous_time_in_ticks = time_in_ticks;
+    }
+    let single_track_header = Header {
+        format: Format::SingleTrack,
+        timing: header.timing,
+    };
+
+    let last_event_time_in_seconds = round(
+        scaled_time(*event_times_in_ticks.last().unwrap()),
+        ticks_per_beat as u128 * MICROSECONDS_PER_SECOND as u128,
+    );
+    assert!(last_event_time_in_seconds >= 3600);
+
+    // When iterating, the time of each event is rounded to the nearest microsecond.
+    let expected_times_in_microseconds: Vec<u64> = event_times_in_ticks
+        .iter()
+        .map(|&time_in_ticks| round(scaled_time(time_in_ticks), ticks_per_beat as u128))
+        .collect();
+    let mut time_in_microseconds = 0;
+    let observed_times_in_microseconds: Vec<u64> = MidlyMultiTrackReader::try_new(header, &tracks)
+        .expect("Unexpected error.")
+        .map(|event| {
+            time_in_microseconds += event.microseconds_since_previous_event;
+            time_in_microseconds
+        })
+        .collect();
+    assert_eq!(
+        observed_times_in_microseconds,
+        expected_times_in_microseconds
+    );
+
+    // When rendering, the exact time of each event is rounded to the nearest frame.
+    for &sample_rate in &[8000, 22050, 44100, 48000, 96000, 192000] {
+        let expected_times_in_frames: Vec<u64> = event_times_in_ticks
+            .iter()
+            .map(|&time_in_ticks| {
+                round(
+                    scaled_time(time_in_ticks) * sample_rate as u128,
+                    ticks_per_beat as u128 * MICROSECONDS_PER_SECOND as u128,
+                )
+            })
+            .collect();
+        let length_in_seconds = last_event_time_in_seconds + 1;
+        assert_eq!(
+            render_event_times_in_frames(
+                MidlyMultiTrackReader::try_new(header, &tracks).expect("Unexpected error."),
+                sample_rate,
+                length_in_seconds
+            ),
+            expected_times_in_frames,
+            "Events of MidlyMultiTrackReader are not on the expected frame at {} frames per second.",
+            sample_rate
+        );
+        assert_eq!(
+            render_event_times_in_frames(
+                MidlyMidiReader::try_new(single_track_header, &single_track)
+                    .expect("Unexpected error."),
+                sample_rate,
+                length_in_seconds
+            ),
+            expected_times_in_frames,
+            "Events of MidlyMidiReader are not on the expected frame at {} frames per second.",
+            sample_rate
+        );
+    }
+}
@@ -6,12 +6,20 @@
 //!
 //! The [`run`] function can be used to run a plugin and read audio and midi from the
 //! inputs and write audio and midi to the outputs.
+//! With [`RunOptions`], you can
+//! * read midi from one or more midi inputs, just like a plugin with more than one midi port
+//!   in Jack,
+//! * deliver system exclusive messages and meta events from midi files,
+//! * deliver parameter changes to the plugin and
+//! * specify the tempo and time signature (see the [`transport`] module).
+//!
+//! Use the [`restore_state`] function to restore a saved state before rendering.
 //!
 //! Currently, the following inputs and outputs are available:
 //!
 //! * Dummy: [`AudioDummy`]: dummy audio input (generates silence) and output and [`MidiDummy`]: dummy midi input (generates no events) and output
 //! * Hound: [`HoundAudioReader`] and [`HoundAudioWriter`]: read and write `.wav` files (behind the "backend-combined-hound" feature)
-//! * Midly: [`MidlyMidiReader`]: read `.mid` files (behind the "backend-combined-midly" feature)
+//! * Midly: [`MidlyMidiReader`], [`MidlyMultiTrackReader`] and [`MidlyMidiWriter`]: read and write `.mid` files (behind the "backend-combined-midly" feature)
 //! * Memory: [`AudioBufferReader`] and [`AudioBufferWriter`]: read and write audio from memory
 //! * Testing: [`TestAudioReader`] and [`TestAudioWriter`]: audio input and output, to be used in tests
 //!
@@ -24,25 +32,37 @@
 //! [`HoundAudioReader`]: ./hound/struct.HoundAudioReader.html
 //! [`HoundAudioWriter`]: ./hound/struct.HoundAudioWriter.html
 //! [`MidlyMidiReader`]: ./midly/struct.MidlyMidiReader.html
+//! [`MidlyMidiWriter`]: ./midly/struct.MidlyMidiWriter.html
+//! [`MidlyMultiTrackReader`]: ./midly/struct.MidlyMultiTrackReader.html
 //! [`TestAudioReader`]: ./struct.TestAudioReader.html
 //! [`TestAudioWriter`]: ./struct.TestAudioWriter.html
 //! [`AudioBufferReader`]: ./memory/struct.AudioBufferReader.html
 //! [`AudioBufferWriter`]: ./memory/struct.AudioBufferWriter.html
 //! [`run`]: ./fn.run.html
+//! [`RunOptions`]: ./struct.RunOptions.html
+//! [`restore_state`]: ./fn.restore_state.html
+//! [`transport`]: ./transport/index.html
 //! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
 //! [`AudioChunkReader`]: ./memory/struct.AudioChunkReader.html
 
-use crate::backend::{HostInterface, Stop};
+use crate::backend::{HostInterface, MidiOutput, MidiOutputError, Stop, Transport, TransportInfo};
 use crate::buffer::{
     buffers_as_mut_slice, buffers_as_slice, AudioBufferIn, AudioBufferInOut, AudioBufferOut,
     AudioChunk,
 };
 use crate::event::event_queue::{AlwaysInsertNewAfterOld, EventQueue};
-use crate::event::{DeltaEvent, EventHandler, RawMidiEvent, Timed};
-use crate::ContextualAudioRenderer;
+use crate::event::{
+    ContextualEventHandler, DeltaEvent, EventHandler, Indexed, MetaEvent, ParameterChange,
+    RawMidiEvent, SysExEvent, Timed,
+};
+use crate::state::{self, PluginState, StateError};
+use crate::{AudioHandler, ContextualAudioRenderer, ParameterHandler};
+use dummy::MidiDummy;
 use num_traits::Zero;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
+use std::ops::Range;
+use transport::SyntheticTransport;
 use vecstorage::VecStorage;
 
 pub mod dummy;
@@ -51,6 +71,7 @@ pub mod hound;
 pub mod memory;
 #[cfg(feature = "backend-combined-midly")]
 pub mod midly;
+pub mod transport;
 
 /// Define how audio is read.
 ///
@@ -121,12 +142,177 @@ where
 
 pub const MICROSECONDS_PER_SECOND: u64 = 1_000_000;
 
+/// Convert an absolute time in frames to microseconds, rounded to the nearest microsecond.
+///
+/// The conversion is exact (apart from the rounding), so converting back with
+/// `microseconds_to_frames` gives the original number of frames, as long as there are
+/// fewer than one million frames per second.
+fn frames_to_microseconds(time_in_frames: u64, frames_per_second: u64) -> u64 {
+    divide_rounded(
+        time_in_frames as u128 * MICROSECONDS_PER_SECOND as u128,
+        frames_per_second as u128,
+    )
+}
+
+/// Convert an absolute time in microseconds to frames, rounded to the nearest frame.
+#[cfg(test)]
+fn microseconds_to_frames(time_in_microseconds: u64, frames_per_second: u64) -> u64 {
+    ExactTime::from_microseconds(time_in_microseconds).in_frames(frames_per_second)
+}
+
+fn divide_rounded(numerator: u128, denominator: u128) -> u64 {
+    ((numerator + denominator / 2) / denominator) as u64
+}
+
+/// An exact point in time, relative to the start: `numerator / denominator` microseconds.
+///
+/// This allows an [`EventSource`] to specify the time of each event without rounding,
+/// e.g. the time of an event in a midi file, where a tick does not need to last an integer
+/// number of microseconds.
+/// The time is only rounded when it is converted to frames.
+///
+/// [`EventSource`]: ./trait.EventSource.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExactTime {
+    numerator: u128,
+    denominator: u64,
+}
+
+impl ExactTime {
+    /// Create a new `ExactTime` of `numerator / denominator` microseconds.
+    ///
+    /// # Panics
+    /// Panics if `denominator` is zero.
+    pub fn new(numerator: u128, denominator: u64) -> Self {
+        assert!(denominator > 0);
+        ExactTime {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Create a new `ExactTime` of the given number of microseconds.
+    pub fn from_microseconds(time_in_microseconds: u64) -> Self {
+        Self::new(time_in_microseconds as u128, 1)
+    }
+
+    /// The time in microseconds, rounded to the nearest microsecond.
+    pub fn in_microseconds(&self) -> u64 {
+        divide_rounded(self.numerator, self.denominator as u128)
+    }
+
+    /// The time in frames, rounded to the nearest frame.
+    pub fn in_frames(&self, frames_per_second: u64) -> u64 {
+        divide_rounded(
+            self.numerator * frames_per_second as u128,
+            self.denominator as u128 * MICROSECONDS_PER_SECOND as u128,
+        )
+    }
+}
+
+/// A source of events with their exact time, used for the midi inputs of the [`run`] function.
+///
+/// The readers of the [`midly`] module implement this trait, so that the events of a midi file
+/// are placed on the exact frame.
+/// Use [`DeltaEventSource`] to read the events from an iterator over [`DeltaEvent`]s.
+///
+/// [`run`]: ./fn.run.html
+/// [`midly`]: ./midly/index.html
+/// [`DeltaEventSource`]: ./struct.DeltaEventSource.html
+/// [`DeltaEvent`]: ../../event/struct.DeltaEvent.html
+pub trait EventSource {
+    type Event;
+    /// Return the next event together with its time, or `None` if there are no more events.
+    /// The time of an event must not be before the time of the previous event.
+    fn next_event(&mut self) -> Option<(ExactTime, Self::Event)>;
+}
+
+/// An [`EventSource`] that reads the events from an iterator over [`DeltaEvent`]s.
+///
+/// [`EventSource`]: ./trait.EventSource.html
+/// [`DeltaEvent`]: ../../event/struct.DeltaEvent.html
+pub struct DeltaEventSource<I> {
+    events: I,
+    time_in_microseconds: u64,
+}
+
+impl<I> DeltaEventSource<I> {
+    /// Create a new `DeltaEventSource` that reads the events from `events`.
+    pub fn new(events: I) -> Self {
+        DeltaEventSource {
+            events,
+            time_in_microseconds: 0,
+        }
+    }
+}
+
+impl<I, E> EventSource for DeltaEventSource<I>
+where
+    I: Iterator<Item = DeltaEvent<E>>,
+{
+    type Event = E;
+
+    fn next_event(&mut self) -> Option<(ExactTime, E)> {
+        let event = self.events.next()?;
+        self.time_in_microseconds += event.microseconds_since_previous_event;
+        Some((
+            ExactTime::from_microseconds(self.time_in_microseconds),
+            event.event,
+        ))
+    }
+}
+
 /// Define how midi is written.
-/// _Note_: there is no "`MidiReader`"; we use `Iterator<Item = DeltaEvent<RawMidiEvent>>` for that.
+/// _Note_: there is no "`MidiReader`"; we use [`EventSource`] for that.
+///
+/// [`EventSource`]: ./trait.EventSource.html
 pub trait MidiWriter {
     fn write_event(&mut self, event: DeltaEvent<RawMidiEvent>);
+
+    /// Return `true` if this midi writer can write system exclusive events.
+    ///
+    /// The default implementation returns `false`.
+    fn supports_sysex(&self) -> bool {
+        false
+    }
+
+    /// Write a system exclusive event.
+    ///
+    /// This is only called when [`supports_sysex`] returns `true`.
+    /// The default implementation ignores the event.
+    ///
+    /// [`supports_sysex`]: #method.supports_sysex
+    fn write_sysex(&mut self, event: DeltaEvent<SysExEvent>) {
+        let _ = event;
+    }
 }
 
+impl<W> MidiWriter for &mut W
+where
+    W: MidiWriter + ?Sized,
+{
+    fn write_event(&mut self, event: DeltaEvent<RawMidiEvent>) {
+        (**self).write_event(event)
+    }
+
+    fn supports_sysex(&self) -> bool {
+        (**self).supports_sysex()
+    }
+
+    fn write_sysex(&mut self, event: DeltaEvent<SysExEvent>) {
+        (**self).write_sysex(event)
+    }
+}
+
+/// An event that is queued by the [`MidiWriterWrapper`].
+/// The data of system exclusive events is stored separately, so that no allocation is needed.
+enum QueuedMidiEvent {
+    Midi(RawMidiEvent),
+    SysEx(Range<usize>),
+}
+
+const SYSEX_DATA_CAPACITY: usize = 65536;
+
 // TODO: find a better name for this.
 /// A wrapper around a midi writer that implements `EventHandler<Timed<RawMidiEvent>>` by queueing
 /// the events, which can then be written to the encapsulated `MidiWriter` by calling `step_frames`.
@@ -137,9 +323,12 @@ where
     inner: W,
     current_time_in_frames: u64,
     previous_time_in_microseconds: u64,
-    micro_seconds_per_frame: f64,
-    event_queue: EventQueue<RawMidiEvent>,
+    frames_per_second: u64,
+    event_queue: EventQueue<QueuedMidiEvent>,
+    sysex_data: Vec<u8>,
     must_stop: bool,
+    transport: SyntheticTransport,
+    latency_in_frames: u64,
 }
 
 impl<W> HostInterface for MidiWriterWrapper<W>
@@ -157,46 +346,175 @@ where
 
 impl<W> Stop for MidiWriterWrapper<W> where W: MidiWriter {}
 
+impl<W> TransportInfo for MidiWriterWrapper<W>
+where
+    W: MidiWriter,
+{
+    fn transport(&self) -> Transport {
+        let current_time_in_microseconds =
+            frames_to_microseconds(self.current_time_in_frames, self.frames_per_second);
+        self.transport
+            .transport_at(current_time_in_microseconds, self.current_time_in_frames)
+    }
+}
+
 impl<W> MidiWriterWrapper<W>
 where
     W: MidiWriter,
 {
+    /// Create a new `MidiWriterWrapper`.
+    ///
+    /// The number of frames per second is derived from `micro_seconds_per_frame`, rounded to
+    /// the nearest integer.
+    /// Prefer [`with_frames_per_second`] when the number of frames per second is known.
+    ///
+    /// [`with_frames_per_second`]: #method.with_frames_per_second
     pub fn new(inner: W, micro_seconds_per_frame: f64) -> Self {
+        Self::with_frames_per_second(
+            inner,
+            (MICROSECONDS_PER_SECOND as f64 / micro_seconds_per_frame).round() as u64,
+        )
+    }
+
+    /// Create a new `MidiWriterWrapper` for the given number of frames per second.
+    ///
+    /// The time of each event is computed from its absolute position in frames,
+    /// so that rounding errors do not accumulate.
+    ///
+    /// # Panics
+    /// Panics if `frames_per_second` is zero.
+    pub fn with_frames_per_second(inner: W, frames_per_second: u64) -> Self {
+        assert!(frames_per_second > 0);
         MidiWriterWrapper {
             inner,
             previous_time_in_microseconds: 0,
             current_time_in_frames: 0,
-            micro_seconds_per_frame,
+            frames_per_second,
             event_queue: EventQueue::new(1024),
+            sysex_data: Vec::with_capacity(SYSEX_DATA_CAPACITY),
             must_stop: false,
+            transport: SyntheticTransport::default(),
+            latency_in_frames: 0,
+        }
+    }
+
+    /// Use the given transport for the [`TransportInfo`] implementation.
+    /// By default, a transport with a constant tempo of 120 beats per minute in 4/4 is used.
+    ///
+    /// [`TransportInfo`]: ../trait.TransportInfo.html
+    pub fn set_transport(&mut self, transport: SyntheticTransport) {
+        self.transport = transport;
+    }
+
+    /// Write the midi events `latency_in_frames` frames earlier, so that they are aligned with
+    /// an audio output from which the first `latency_in_frames` frames are trimmed.
+    /// Events during these first frames are written at time `0`.
+    /// By default, the latency is `0`.
+    pub fn set_latency_in_frames(&mut self, latency_in_frames: u64) {
+        self.latency_in_frames = latency_in_frames;
+    }
+
+    fn check_can_queue(&self, port_index: usize) -> Result<(), MidiOutputError> {
+        if port_index != 0 {
+            return Err(MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports: 1,
+            });
         }
+        if self.event_queue.len() >= self.event_queue.capacity() {
+            return Err(MidiOutputError::BufferFull);
+        }
+        Ok(())
     }
 
     pub fn step_frames(&mut self, number_of_frames: u64) {
         for event in self.event_queue.iter() {
-            let current_time_in_frames =
-                self.current_time_in_frames + (event.time_in_frames as u64);
+            let current_time_in_frames = (self.current_time_in_frames
+                + (event.time_in_frames as u64))
+                .saturating_sub(self.latency_in_frames);
             let current_time_in_microseconds =
-                (current_time_in_frames as f64 * self.micro_seconds_per_frame) as u64;
-            let delta_event = DeltaEvent {
-                microseconds_since_previous_event: current_time_in_microseconds
-                    - self.previous_time_in_microseconds,
-                event: event.event,
-            };
-            self.inner.write_event(delta_event);
+                frames_to_microseconds(current_time_in_frames, self.frames_per_second);
+            let microseconds_since_previous_event =
+                current_time_in_microseconds - self.previous_time_in_microseconds;
+            match &event.event {
+                QueuedMidiEvent::Midi(event) => self.inner.write_event(DeltaEvent {
+                    microseconds_since_previous_event,
+                    event: *event,
+                }),
+                QueuedMidiEvent::SysEx(range) => self.inner.write_sysex(DeltaEvent {
+                    microseconds_since_previous_event,
+                    event: SysExEvent::new(&self.sysex_data[range.clone()]),
+                }),
+            }
             self.previous_time_in_microseconds = current_time_in_microseconds;
         }
         self.event_queue.clear();
+        self.sysex_data.clear();
         self.current_time_in_frames += number_of_frames;
     }
 }
 
+/// There is one midi output port, with index `0`.
+/// SysEx events are only supported if the encapsulated [`MidiWriter`] supports them.
+/// The total size of the SysEx events within one call to `step_frames` is limited to 64 KiB.
+///
+/// [`MidiWriter`]: ./trait.MidiWriter.html
+impl<W> MidiOutput for MidiWriterWrapper<W>
+where
+    W: MidiWriter,
+{
+    fn number_of_midi_output_ports(&self) -> usize {
+        1
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        self.check_can_queue(port_index)?;
+        self.event_queue.queue_event(
+            Timed::new(time_in_frames, QueuedMidiEvent::Midi(event)),
+            AlwaysInsertNewAfterOld,
+        );
+        Ok(())
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        self.check_can_queue(port_index)?;
+        if !self.inner.supports_sysex() {
+            return Err(MidiOutputError::Unsupported);
+        }
+        let start = self.sysex_data.len();
+        if start + data.len() > SYSEX_DATA_CAPACITY {
+            return Err(MidiOutputError::BufferFull);
+        }
+        self.sysex_data.extend_from_slice(data);
+        self.event_queue.queue_event(
+            Timed::new(
+                time_in_frames,
+                QueuedMidiEvent::SysEx(start..self.sysex_data.len()),
+            ),
+            AlwaysInsertNewAfterOld,
+        );
+        Ok(())
+    }
+}
+
 impl<W> EventHandler<Timed<RawMidiEvent>> for MidiWriterWrapper<W>
 where
     W: MidiWriter,
 {
     fn handle_event(&mut self, event: Timed<RawMidiEvent>) {
-        self.event_queue.queue_event(event, AlwaysInsertNewAfterOld);
+        if let Err(e) = self.write_midi(0, event.time_in_frames, event.event) {
+            error!("Failed to write midi event: {}", e);
+        }
     }
 }
 
@@ -237,7 +555,402 @@ where
     }
 }
 
-/// Run an audio renderer with the given audio input, audio output, midi input and midi output.
+/// Options for the [`run`] function: the midi inputs, the parameter automation, the transport
+/// and how the midi events are delivered to the plugin.
+///
+/// All options can be freely combined. [`RunOptions::new`] creates options without midi input,
+/// without parameter automation and with a transport with a constant tempo of 120 beats per
+/// minute in 4/4.
+///
+/// The type of the events of the midi inputs determines which events the plugin must handle:
+/// * for `DeltaEvent<`[`RawMidiEvent`]`>`, the plugin must implement
+///   [`EventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>`,
+/// * for `DeltaEvent<`[`MidiFileEvent`]`>` (e.g. from the [`events`] method of
+///   [`MidlyMultiTrackReader`]), the plugin must additionally implement
+///   [`EventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>>` and
+///   [`EventHandler`]`<`[`Timed`]`<`[`MetaEvent`]`>>`.
+///
+/// With [`with_input_index`], the events are delivered together with the index of the midi
+/// input instead, see the documentation of that method.
+///
+/// Example
+/// -------
+/// ```
+/// use rsynth::backend::TimeSignature;
+/// use rsynth::backend::combined::{DeltaEventSource, RunOptions};
+/// use rsynth::backend::combined::transport::SyntheticTransport;
+/// use rsynth::event::{DeltaEvent, ParameterChange, RawMidiEvent};
+/// let midi = vec![DeltaEvent {
+///     microseconds_since_previous_event: 1000,
+///     event: RawMidiEvent::new(&[0x90, 60, 100]),
+/// }];
+/// let automation = vec![DeltaEvent {
+///     microseconds_since_previous_event: 500,
+///     event: ParameterChange::new(0, 0.5),
+/// }];
+/// let options = RunOptions::new()
+///     .midi_input(DeltaEventSource::new(midi.into_iter()))
+///     .automation(automation.into_iter())
+///     .transport(SyntheticTransport::new(90.0, TimeSignature::new(3, 4)));
+/// // Pass `options` to the `run` function ...
+/// # drop(options);
+/// ```
+///
+/// [`run`]: ./fn.run.html
+/// [`RunOptions::new`]: #method.new
+/// [`with_input_index`]: #method.with_input_index
+/// [`RawMidiEvent`]: ../../event/struct.RawMidiEvent.html
+/// [`EventHandler`]: ../../event/trait.EventHandler.html
+/// [`Timed`]: ../../event/struct.Timed.html
+/// [`MidiFileEvent`]: ./enum.MidiFileEvent.html
+/// [`events`]: ./midly/struct.MidlyMultiTrackReader.html#method.events
+/// [`MidlyMultiTrackReader`]: ./midly/struct.MidlyMultiTrackReader.html
+/// [`SysExEvent`]: ../../event/struct.SysExEvent.html
+/// [`MetaEvent`]: ../../event/enum.MetaEvent.html
+pub struct RunOptions<MidiIn, Automation, Delivery> {
+    midi_inputs: Vec<MidiIn>,
+    automation: Automation,
+    transport: SyntheticTransport,
+    delivery: Delivery,
+}
+
+impl RunOptions<MidiDummy, NoAutomation, WithoutInputIndex> {
+    /// Create new `RunOptions` without midi input, without parameter automation and with
+    /// a constant tempo of 120 beats per minute in 4/4.
+    pub fn new() -> Self {
+        RunOptions {
+            midi_inputs: Vec::new(),
+            automation: NoAutomation,
+            transport: SyntheticTransport::default(),
+            delivery: WithoutInputIndex::default(),
+        }
+    }
+}
+
+impl Default for RunOptions<MidiDummy, NoAutomation, WithoutInputIndex> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<MidiIn, Automation, Delivery> RunOptions<MidiIn, Automation, Delivery> {
+    /// Read midi from the given midi input.
+    ///
+    /// This replaces any midi inputs that were set before.
+    pub fn midi_input<M>(self, midi_input: M) -> RunOptions<M, Automation, Delivery> {
+        self.midi_inputs(std::iter::once(midi_input))
+    }
+
+    /// Read midi from the given midi inputs, just like a plugin with more than one midi port
+    /// in Jack.
+    ///
+    /// The events of all midi inputs are merged by time.
+    /// When events of different midi inputs happen on the same frame, the event of the midi
+    /// input with the lowest index is delivered first.
+    /// This replaces any midi inputs that were set before.
+    pub fn midi_inputs<I>(self, midi_inputs: I) -> RunOptions<I::Item, Automation, Delivery>
+    where
+        I: IntoIterator,
+    {
+        RunOptions {
+            midi_inputs: midi_inputs.into_iter().collect(),
+            automation: self.automation,
+            transport: self.transport,
+            delivery: self.delivery,
+        }
+    }
+
+    /// Deliver the parameter changes from `automation` to the plugin as
+    /// [`Timed`]`<`[`ParameterChange`]`>` events, interleaved with the midi events.
+    /// The plugin must implement [`EventHandler`]`<`[`Timed`]`<`[`ParameterChange`]`>>`.
+    ///
+    /// When a midi event and a parameter change happen on the same frame, the parameter change
+    /// is delivered first.
+    ///
+    /// [`Timed`]: ../../event/struct.Timed.html
+    /// [`ParameterChange`]: ../../event/struct.ParameterChange.html
+    /// [`EventHandler`]: ../../event/trait.EventHandler.html
+    pub fn automation<A>(self, automation: A) -> RunOptions<MidiIn, WithAutomation<A>, Delivery>
+    where
+        A: Iterator<Item = DeltaEvent<ParameterChange>>,
+    {
+        RunOptions {
+            midi_inputs: self.midi_inputs,
+            automation: WithAutomation {
+                reader: EventSourceReader::new(DeltaEventSource::new(automation)),
+            },
+            transport: self.transport,
+            delivery: self.delivery,
+        }
+    }
+
+    /// Use the given transport for the [`TransportInfo`] implementation of the
+    /// [`MidiWriterWrapper`] that is passed as context to the plugin, instead of a transport
+    /// with a constant tempo of 120 beats per minute in 4/4.
+    /// When rendering a midi file, you can create the transport with the
+    /// [`synthetic_transport`] function.
+    ///
+    /// [`TransportInfo`]: ../trait.TransportInfo.html
+    /// [`MidiWriterWrapper`]: ./struct.MidiWriterWrapper.html
+    /// [`synthetic_transport`]: ./midly/fn.synthetic_transport.html
+    pub fn transport(mut self, transport: SyntheticTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Deliver the midi events as [`Indexed`]`<`[`Timed`]`<_>>`, where the index is the index of
+    /// the midi input, together with the [`MidiWriterWrapper`] as context.
+    /// This is the same type of event that the Jack backend delivers, so a plugin with more
+    /// than one midi port can be rendered offline without changes.
+    ///
+    /// The plugin must implement
+    /// [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`MidiWriterWrapper`]`>`
+    /// and, for [`MidiFileEvent`]s, the same for [`SysExEvent`] and [`MetaEvent`].
+    ///
+    /// [`Indexed`]: ../../event/struct.Indexed.html
+    /// [`Timed`]: ../../event/struct.Timed.html
+    /// [`MidiWriterWrapper`]: ./struct.MidiWriterWrapper.html
+    /// [`ContextualEventHandler`]: ../../event/trait.ContextualEventHandler.html
+    /// [`RawMidiEvent`]: ../../event/struct.RawMidiEvent.html
+    /// [`MidiFileEvent`]: ./enum.MidiFileEvent.html
+    /// [`SysExEvent`]: ../../event/struct.SysExEvent.html
+    /// [`MetaEvent`]: ../../event/enum.MetaEvent.html
+    pub fn with_input_index(self) -> RunOptions<MidiIn, Automation, WithInputIndex> {
+        RunOptions {
+            midi_inputs: self.midi_inputs,
+            automation: self.automation,
+            transport: self.transport,
+            delivery: WithInputIndex::default(),
+        }
+    }
+}
+
+/// Defines how the events of the midi inputs are delivered to the plugin by the [`run`] function.
+///
+/// This is implemented by [`WithoutInputIndex`] and [`WithInputIndex`], see [`RunOptions`].
+///
+/// [`run`]: ./fn.run.html
+/// [`WithoutInputIndex`]: ./struct.WithoutInputIndex.html
+/// [`WithInputIndex`]: ./struct.WithInputIndex.html
+/// [`RunOptions`]: ./struct.RunOptions.html
+pub trait MidiDelivery<R, C, E> {
+    /// Deliver an event of the midi input with the given index to the plugin.
+    fn deliver(&mut self, plugin: &mut R, context: &mut C, event: Indexed<Timed<E>>);
+}
+
+/// Deliver the midi events without the index of the midi input (the default),
+/// see [`RunOptions`].
+///
+/// [`RunOptions`]: ./struct.RunOptions.html
+#[derive(Default)]
+pub struct WithoutInputIndex {
+    sysex_buffer: Vec<u8>,
+}
+
+/// Deliver the midi events with the index of the midi input,
+/// see [`RunOptions::with_input_index`].
+///
+/// [`RunOptions::with_input_index`]: ./struct.RunOptions.html#method.with_input_index
+#[derive(Default)]
+pub struct WithInputIndex {
+    sysex_buffer: Vec<u8>,
+}
+
+/// Prepend the `0xF0` byte to the data of a system exclusive message from a midi file.
+fn sysex_with_start<'b>(sysex_buffer: &'b mut Vec<u8>, data: &[u8]) -> SysExEvent<'b> {
+    sysex_buffer.clear();
+    sysex_buffer.push(SYSEX_START);
+    sysex_buffer.extend_from_slice(data);
+    SysExEvent::new(sysex_buffer)
+}
+
+impl<R, C> MidiDelivery<R, C, RawMidiEvent> for WithoutInputIndex
+where
+    R: EventHandler<Timed<RawMidiEvent>>,
+{
+    fn deliver(&mut self, plugin: &mut R, _context: &mut C, event: Indexed<Timed<RawMidiEvent>>) {
+        plugin.handle_event(event.event)
+    }
+}
+
+impl<'a, R, C> MidiDelivery<R, C, MidiFileEvent<'a>> for WithoutInputIndex
+where
+    R: EventHandler<Timed<RawMidiEvent>>
+        + for<'e> EventHandler<Timed<SysExEvent<'e>>>
+        + EventHandler<Timed<MetaEvent<'a>>>,
+{
+    fn deliver(
+        &mut self,
+        plugin: &mut R,
+        _context: &mut C,
+        event: Indexed<Timed<MidiFileEvent<'a>>>,
+    ) {
+        let Timed {
+            time_in_frames,
+            event,
+        } = event.event;
+        match event {
+            MidiFileEvent::Midi(event) => plugin.handle_event(Timed::new(time_in_frames, event)),
+            MidiFileEvent::SysEx(data) => plugin.handle_event(Timed::new(
+                time_in_frames,
+                sysex_with_start(&mut self.sysex_buffer, data),
+            )),
+            MidiFileEvent::Meta(event) => plugin.handle_event(Timed::new(time_in_frames, event)),
+        }
+    }
+}
+
+impl<R, C> MidiDelivery<R, C, RawMidiEvent> for WithInputIndex
+where
+    R: ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, C>,
+{
+    fn deliver(&mut self, plugin: &mut R, context: &mut C, event: Indexed<Timed<RawMidiEvent>>) {
+        plugin.handle_event(event, context)
+    }
+}
+
+impl<'a, R, C> MidiDelivery<R, C, MidiFileEvent<'a>> for WithInputIndex
+where
+    R: ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, C>
+        + for<'e> ContextualEventHandler<Indexed<Timed<SysExEvent<'e>>>, C>
+        + ContextualEventHandler<Indexed<Timed<MetaEvent<'a>>>, C>,
+{
+    fn deliver(
+        &mut self,
+        plugin: &mut R,
+        context: &mut C,
+        event: Indexed<Timed<MidiFileEvent<'a>>>,
+    ) {
+        let Indexed { index, event } = event;
+        let Timed {
+            time_in_frames,
+            event,
+        } = event;
+        match event {
+            MidiFileEvent::Midi(event) => plugin.handle_event(
+                Indexed::new(index, Timed::new(time_in_frames, event)),
+                context,
+            ),
+            MidiFileEvent::SysEx(data) => plugin.handle_event(
+                Indexed::new(
+                    index,
+                    Timed::new(
+                        time_in_frames,
+                        sysex_with_start(&mut self.sysex_buffer, data),
+                    ),
+                ),
+                context,
+            ),
+            MidiFileEvent::Meta(event) => plugin.handle_event(
+                Indexed::new(index, Timed::new(time_in_frames, event)),
+                context,
+            ),
+        }
+    }
+}
+
+/// Parameter automation for the [`run`] function.
+///
+/// This is implemented by [`NoAutomation`] and [`WithAutomation`], see [`RunOptions::automation`].
+///
+/// [`run`]: ./fn.run.html
+/// [`NoAutomation`]: ./struct.NoAutomation.html
+/// [`WithAutomation`]: ./struct.WithAutomation.html
+/// [`RunOptions::automation`]: ./struct.RunOptions.html#method.automation
+pub trait AutomationInput<R> {
+    /// The time of the next parameter change, in frames, relative to `buffer_start_in_frames`.
+    fn peek_time_in_frames(
+        &mut self,
+        frames_per_second: u64,
+        buffer_start_in_frames: u64,
+    ) -> Option<u64>;
+
+    /// Deliver the next parameter change to the plugin.
+    fn deliver_next(&mut self, plugin: &mut R, time_in_frames: u32);
+}
+
+/// No parameter automation (the default), see [`RunOptions`].
+///
+/// [`RunOptions`]: ./struct.RunOptions.html
+pub struct NoAutomation;
+
+impl<R> AutomationInput<R> for NoAutomation {
+    fn peek_time_in_frames(
+        &mut self,
+        _frames_per_second: u64,
+        _buffer_start_in_frames: u64,
+    ) -> Option<u64> {
+        None
+    }
+
+    fn deliver_next(&mut self, _plugin: &mut R, _time_in_frames: u32) {}
+}
+
+/// Parameter automation from an iterator, see [`RunOptions::automation`].
+///
+/// [`RunOptions::automation`]: ./struct.RunOptions.html#method.automation
+pub struct WithAutomation<A>
+where
+    A: Iterator<Item = DeltaEvent<ParameterChange>>,
+{
+    reader: EventSourceReader<DeltaEventSource<A>>,
+}
+
+impl<R, A> AutomationInput<R> for WithAutomation<A>
+where
+    A: Iterator<Item = DeltaEvent<ParameterChange>>,
+    R: EventHandler<Timed<ParameterChange>>,
+{
+    fn peek_time_in_frames(
+        &mut self,
+        frames_per_second: u64,
+        buffer_start_in_frames: u64,
+    ) -> Option<u64> {
+        self.reader
+            .peek_time_in_frames(frames_per_second, buffer_start_in_frames)
+    }
+
+    fn deliver_next(&mut self, plugin: &mut R, time_in_frames: u32) {
+        if let Some(event) = self.reader.next_event() {
+            plugin.handle_event(Timed::new(time_in_frames, event));
+        }
+    }
+}
+
+/// An event from a midi file.
+///
+/// See [`RunOptions`] for how these events are delivered to the plugin.
+///
+/// [`RunOptions`]: ./struct.RunOptions.html
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MidiFileEvent<'a> {
+    /// A midi event.
+    Midi(RawMidiEvent),
+    /// A system exclusive message, as stored in midi files: without the leading `0xF0` byte.
+    /// It is delivered to the plugin as a [`SysExEvent`] that starts with the `0xF0` byte,
+    /// just like with Jack.
+    ///
+    /// [`SysExEvent`]: ../../event/struct.SysExEvent.html
+    SysEx(&'a [u8]),
+    /// A meta event.
+    Meta(MetaEvent<'a>),
+}
+
+const SYSEX_START: u8 = 0xF0;
+
+/// Run an audio renderer with the given audio input, audio output and midi output.
+///
+/// The midi inputs, parameter automation and transport are specified with the `options`,
+/// see [`RunOptions`].
+///
+/// Latency
+/// =======
+/// The first [`latency_in_frames`] frames of the output are not written to `audio_out`,
+/// so that the output is aligned with the input.
+/// After the audio input is exhausted, the plugin renders `latency_in_frames` frames of silence,
+/// so that the output has the same length as the input.
+/// The midi output is shifted by the same number of frames; midi events that the plugin writes
+/// during the trimmed frames are written at time `0`.
 ///
 /// Parameters
 /// ==========
@@ -246,23 +959,71 @@ where
 /// Panics
 /// ======
 /// Panics if `buffer_size_in_frames` is `0` or `> u32::MAX`.
+///
+/// Example
+/// =======
+/// ```
+/// use rsynth::backend::combined::dummy::{AudioDummy, MidiDummy};
+/// use rsynth::backend::combined::{run, DeltaEventSource, RunOptions};
+/// use rsynth::buffer::AudioBufferInOut;
+/// use rsynth::event::{DeltaEvent, EventHandler, RawMidiEvent, Timed};
+/// use rsynth::{AudioHandler, ContextualAudioRenderer};
+///
+/// struct MyPlugin;
+/// impl AudioHandler for MyPlugin {
+///     fn set_sample_rate(&mut self, _sample_rate: f64) {}
+/// }
+/// impl EventHandler<Timed<RawMidiEvent>> for MyPlugin {
+///     fn handle_event(&mut self, _event: Timed<RawMidiEvent>) {}
+/// }
+/// impl<C> ContextualAudioRenderer<f32, C> for MyPlugin {
+///     fn render_buffer(&mut self, _buffer: &mut AudioBufferInOut<f32>, _context: &mut C) {}
+/// }
+///
+/// let midi = vec![DeltaEvent {
+///     microseconds_since_previous_event: 1000,
+///     event: RawMidiEvent::new(&[0x90, 60, 100]),
+/// }];
+/// run(
+///     &mut MyPlugin,
+///     256,
+///     AudioDummy::<f32>::with_sample_rate_and_length(44100, 44100),
+///     AudioDummy::<f32>::with_sample_rate_and_length(44100, 0),
+///     MidiDummy::new(),
+///     RunOptions::new().midi_input(DeltaEventSource::new(midi.into_iter())),
+/// )
+/// .expect("Rendering failed.");
+/// ```
+///
+/// [`RunOptions`]: ./struct.RunOptions.html
+/// [`latency_in_frames`]: ../../trait.AudioHandler.html#method.latency_in_frames
 // TODO: support different number of input and output channels.
-pub fn run<S, AudioIn, AudioOut, MidiIn, MidiOut, R>(
+#[allow(clippy::type_complexity)]
+pub fn run<S, AudioIn, AudioOut, MidiOut, MidiIn, E, Automation, Delivery, R>(
     plugin: &mut R,
     buffer_size_in_frames: usize,
     mut audio_in: AudioIn,
     mut audio_out: AudioOut,
-    midi_in: MidiIn,
     midi_out: MidiOut,
+    options: RunOptions<MidiIn, Automation, Delivery>,
 ) -> Result<(), CombinedError<<AudioIn as AudioReader<S>>::Err, <AudioOut as AudioWriter<S>>::Err>>
 where
     AudioIn: AudioReader<S>,
     AudioOut: AudioWriter<S>,
-    MidiIn: Iterator<Item = DeltaEvent<RawMidiEvent>>,
     MidiOut: MidiWriter,
+    MidiIn: EventSource<Event = E>,
+    Automation: AutomationInput<R>,
+    Delivery: MidiDelivery<R, MidiWriterWrapper<MidiOut>, E>,
     S: Copy + Zero + 'static,
-    R: ContextualAudioRenderer<S, MidiWriterWrapper<MidiOut>> + EventHandler<Timed<RawMidiEvent>>,
+    R: ContextualAudioRenderer<S, MidiWriterWrapper<MidiOut>> + AudioHandler,
 {
+    let RunOptions {
+        midi_inputs,
+        mut automation,
+        transport,
+        mut delivery,
+    } = options;
+
     assert!(buffer_size_in_frames > 0);
     assert!(buffer_size_in_frames < u32::MAX as usize);
 
@@ -282,69 +1043,123 @@ where
         AudioChunk::zero(number_of_output_channels, buffer_size_in_frames).inner();
 
     let mut last_time_in_frames = 0;
-    let mut last_event_time_in_microseconds = 0;
 
-    let mut writer = MidiWriterWrapper::new(
-        midi_out,
-        MICROSECONDS_PER_SECOND as f64 / frames_per_second as f64,
-    );
+    let latency_in_frames = plugin.latency_in_frames() as usize;
+
+    let mut writer = MidiWriterWrapper::with_frames_per_second(midi_out, frames_per_second);
+    writer.set_transport(transport);
+    writer.set_latency_in_frames(latency_in_frames as u64);
 
-    let mut peekable_midi_reader = midi_in.peekable();
+    let mut midi_readers: Vec<_> = midi_inputs
+        .into_iter()
+        .map(EventSourceReader::new)
+        .collect();
 
     let mut conversion_storage: VecStorage<&'static [S]> =
         VecStorage::with_capacity(number_of_input_channels);
+    let mut trimmed_storage: VecStorage<&'static [S]> =
+        VecStorage::with_capacity(number_of_output_channels);
+
+    // The number of leading frames that still need to be trimmed from the output
+    // in order to compensate for the latency.
+    let mut frames_to_trim = latency_in_frames;
+    // The number of trailing frames of silence that still need to be rendered after the audio
+    // input is exhausted, so that the output is as long as the input.
+    let mut silent_frames_to_render = latency_in_frames;
+    let mut audio_in_is_exhausted = false;
 
     loop {
-        let mut slices = buffers_as_mut_slice(&mut input_buffers, buffer_size_in_frames);
-        let mut buffer = AudioBufferOut::new(&mut slices, buffer_size_in_frames);
-        // Read audio.
-        let frames_read = match audio_in.fill_buffer(&mut buffer) {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(CombinedError::AudioInError(e));
+        let frames_read = if audio_in_is_exhausted {
+            0
+        } else {
+            let mut slices = buffers_as_mut_slice(&mut input_buffers, buffer_size_in_frames);
+            let mut buffer = AudioBufferOut::new(&mut slices, buffer_size_in_frames);
+            // Read audio.
+            match audio_in.fill_buffer(&mut buffer) {
+                Ok(f) => f,
+                Err(e) => {
+                    return Err(CombinedError::AudioInError(e));
+                }
             }
         };
         assert!(frames_read <= buffer_size_in_frames);
-        if frames_read == 0 {
+        if frames_read < buffer_size_in_frames {
+            audio_in_is_exhausted = true;
+        }
+        // Pad the input with silence.
+        let silent_frames =
+            usize::min(buffer_size_in_frames - frames_read, silent_frames_to_render);
+        for channel in input_buffers.iter_mut() {
+            for sample in channel[frames_read..frames_read + silent_frames].iter_mut() {
+                *sample = S::zero();
+            }
+        }
+        silent_frames_to_render -= silent_frames;
+        let frames_to_render = frames_read + silent_frames;
+        if frames_to_render == 0 {
             break;
         }
 
         // Handle events
-        while let Some(event) = peekable_midi_reader.peek() {
-            let time_in_frames = (last_event_time_in_microseconds
-                + event.microseconds_since_previous_event)
-                * frames_per_second
-                / MICROSECONDS_PER_SECOND
-                - last_time_in_frames;
-            if time_in_frames < buffer_size_in_frames as u64 {
-                let event = peekable_midi_reader
-                    .next()
-                    .expect("to see event that I just peeked at");
-                plugin.handle_event(Timed {
-                    time_in_frames: time_in_frames as u32,
-                    event: event.event,
-                });
-                last_event_time_in_microseconds += event.microseconds_since_previous_event;
-            } else {
-                break;
+        loop {
+            let is_in_this_buffer =
+                |time_in_frames: &u64| *time_in_frames < buffer_size_in_frames as u64;
+            // The index of the midi input with the earliest event and the time of that event.
+            // On a tie, the midi input with the lowest index goes first.
+            let next_midi = midi_readers
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(index, midi_reader)| {
+                    midi_reader
+                        .peek_time_in_frames(frames_per_second, last_time_in_frames)
+                        .filter(is_in_this_buffer)
+                        .map(|time| (index, time))
+                })
+                .min_by_key(|&(index, time)| (time, index));
+            let next_automation_time = automation
+                .peek_time_in_frames(frames_per_second, last_time_in_frames)
+                .filter(is_in_this_buffer);
+            match (next_midi, next_automation_time) {
+                (_, Some(automation_time)) if !matches!(next_midi, Some((_, midi_time)) if midi_time < automation_time) =>
+                {
+                    automation.deliver_next(plugin, automation_time as u32);
+                }
+                (Some((index, midi_time)), _) => {
+                    let event = midi_readers[index]
+                        .next_event()
+                        .expect("to see event that I just peeked at");
+                    delivery.deliver(
+                        plugin,
+                        &mut writer,
+                        Indexed::new(index, Timed::new(midi_time as u32, event)),
+                    );
+                }
+                _ => break,
             }
         }
 
-        let inputs = buffers_as_slice(&input_buffers, frames_read);
-        let mut outputs = buffers_as_mut_slice(&mut output_buffers, frames_read);
-        let mut buffer = AudioBufferInOut::new(&inputs, &mut outputs, frames_read);
+        let inputs = buffers_as_slice(&input_buffers, frames_to_render);
+        let mut outputs = buffers_as_mut_slice(&mut output_buffers, frames_to_render);
+        let mut buffer = AudioBufferInOut::new(&inputs, &mut outputs, frames_to_render);
         plugin.render_buffer(&mut buffer, &mut writer);
 
         let mut guard = conversion_storage.vec_guard();
         let converted = buffer.outputs().as_audio_buffer_in(&mut guard);
 
-        if let Err(e) = audio_out.write_buffer(&converted) {
-            return Err(CombinedError::AudioOutError(e));
+        if frames_to_trim < frames_to_render {
+            let mut trimmed_guard = trimmed_storage.vec_guard();
+            let trimmed = converted.index_frames(frames_to_trim.., &mut trimmed_guard);
+            if let Err(e) = audio_out.write_buffer(&trimmed) {
+                return Err(CombinedError::AudioOutError(e));
+            }
+            frames_to_trim = 0;
+        } else {
+            frames_to_trim -= frames_to_render;
         }
 
-        writer.step_frames(frames_read as u64);
+        writer.step_frames(frames_to_render as u64);
 
-        if frames_read < buffer_size_in_frames {
+        if frames_to_render < buffer_size_in_frames {
             break;
         }
         if writer.must_stop {
@@ -356,6 +1171,69 @@ where
     Ok(())
 }
 
+/// Restore the state of the parameters of the plugin from data that has been created
+/// with the [`save`] function of the [`state`] module.
+///
+/// Call this before [`run`] in order to render with the restored state.
+///
+/// [`save`]: ../../state/fn.save.html
+/// [`state`]: ../../state/index.html
+/// [`run`]: ./fn.run.html
+pub fn restore_state<R>(plugin: &R, data: &[u8]) -> Result<(), StateError>
+where
+    R: ParameterHandler,
+    R::Parameters: PluginState,
+{
+    state::load(&*plugin.parameters(), data)
+}
+
+/// Reads the events from an event source, one event ahead.
+struct EventSourceReader<S>
+where
+    S: EventSource,
+{
+    source: S,
+    peeked: Option<(ExactTime, S::Event)>,
+}
+
+impl<S> EventSourceReader<S>
+where
+    S: EventSource,
+{
+    fn new(source: S) -> Self {
+        Self {
+            source,
+            peeked: None,
+        }
+    }
+
+    /// The time of the next event, in frames, relative to `buffer_start_in_frames`.
+    ///
+    /// The exact time of the event is rounded to the nearest frame, so that rounding errors
+    /// do not accumulate. An event that lies before `buffer_start_in_frames` is late and is
+    /// delivered at the start of the buffer.
+    fn peek_time_in_frames(
+        &mut self,
+        frames_per_second: u64,
+        buffer_start_in_frames: u64,
+    ) -> Option<u64> {
+        if self.peeked.is_none() {
+            self.peeked = self.source.next_event();
+        }
+        self.peeked.as_ref().map(|(time, _)| {
+            time.in_frames(frames_per_second)
+                .saturating_sub(buffer_start_in_frames)
+        })
+    }
+
+    fn next_event(&mut self) -> Option<S::Event> {
+        self.peeked
+            .take()
+            .or_else(|| self.source.next_event())
+            .map(|(_, event)| event)
+    }
+}
+
 /// An audio reader, useful for testing.
 pub struct TestAudioReader<'b, S>
 where
@@ -526,11 +1404,118 @@ impl MidiWriter for TestMidiWriter {
 
 #[cfg(test)]
 mod tests {
+    use super::{dummy::MidiDummy, MidiWriterWrapper};
+    use crate::backend::TransportInfo;
+    use crate::buffer::AudioBufferInOut;
+    use crate::event::{
+        ContextualEventHandler, EventHandler, Indexed, MetaEvent, ParameterChange, RawMidiEvent,
+        SysExEvent, Timed,
+    };
+    use crate::{AudioHandler, ContextualAudioRenderer};
+
+    /// Something the [`RecordingPlugin`] observed.
+    #[derive(Debug, PartialEq)]
+    enum Observed {
+        Midi(Timed<RawMidiEvent>),
+        IndexedMidi(Indexed<Timed<RawMidiEvent>>),
+        SysEx(Timed<Vec<u8>>),
+        IndexedSysEx(Indexed<Timed<Vec<u8>>>),
+        Meta(Timed<MetaEvent<'static>>),
+        IndexedMeta(Indexed<Timed<MetaEvent<'static>>>),
+        Automation(Timed<ParameterChange>),
+        /// A buffer with the given number of frames was rendered.
+        Render(usize),
+    }
+
+    /// A plugin that records the events it receives and the buffers it renders, in order.
+    struct RecordingPlugin {
+        observed: Vec<Observed>,
+        /// The position of the transport at the start of each rendered buffer.
+        positions_in_quarter_notes: Vec<Option<f64>>,
+    }
+
+    impl RecordingPlugin {
+        fn new() -> Self {
+            Self {
+                observed: Vec::new(),
+                positions_in_quarter_notes: Vec::new(),
+            }
+        }
+    }
+
+    impl AudioHandler for RecordingPlugin {
+        fn set_sample_rate(&mut self, _sample_rate: f64) {}
+    }
+
+    impl EventHandler<Timed<RawMidiEvent>> for RecordingPlugin {
+        fn handle_event(&mut self, event: Timed<RawMidiEvent>) {
+            self.observed.push(Observed::Midi(event));
+        }
+    }
+
+    impl<'a> EventHandler<Timed<SysExEvent<'a>>> for RecordingPlugin {
+        fn handle_event(&mut self, event: Timed<SysExEvent<'a>>) {
+            self.observed.push(Observed::SysEx(Timed::new(
+                event.time_in_frames,
+                event.event.data().to_vec(),
+            )));
+        }
+    }
+
+    impl EventHandler<Timed<MetaEvent<'static>>> for RecordingPlugin {
+        fn handle_event(&mut self, event: Timed<MetaEvent<'static>>) {
+            self.observed.push(Observed::Meta(event));
+        }
+    }
+
+    impl EventHandler<Timed<ParameterChange>> for RecordingPlugin {
+        fn handle_event(&mut self, event: Timed<ParameterChange>) {
+            self.observed.push(Observed::Automation(event));
+        }
+    }
+
+    impl<C> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, C> for RecordingPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, _context: &mut C) {
+            self.observed.push(Observed::IndexedMidi(event));
+        }
+    }
+
+    impl<'a, C> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, C> for RecordingPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, _context: &mut C) {
+            self.observed.push(Observed::IndexedSysEx(Indexed::new(
+                event.index,
+                Timed::new(
+                    event.event.time_in_frames,
+                    event.event.event.data().to_vec(),
+                ),
+            )));
+        }
+    }
+
+    impl<C> ContextualEventHandler<Indexed<Timed<MetaEvent<'static>>>, C> for RecordingPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<MetaEvent<'static>>>, _context: &mut C) {
+            self.observed.push(Observed::IndexedMeta(event));
+        }
+    }
+
+    impl ContextualAudioRenderer<f32, MidiWriterWrapper<MidiDummy>> for RecordingPlugin {
+        fn render_buffer(
+            &mut self,
+            buffer: &mut AudioBufferInOut<f32>,
+            context: &mut MidiWriterWrapper<MidiDummy>,
+        ) {
+            self.observed
+                .push(Observed::Render(buffer.number_of_frames()));
+            self.positions_in_quarter_notes
+                .push(context.transport().position_in_quarter_notes);
+        }
+    }
+
     mod run {
         use super::super::{
             dummy::MidiDummy,
             memory::{AudioBufferReader, AudioBufferWriter},
-            DeltaEvent, TestAudioReader, TestAudioWriter,
+            DeltaEvent, DeltaEventSource, RunOptions, TestAudioReader, TestAudioWriter,
         };
         use crate::backend::combined::{TestMidiReader, TestMidiWriter};
         use crate::buffer::AudioChunk;
@@ -609,8 +1594,10 @@ mod tests {
                     &mut AudioBufferWriter::new(&mut output_buffer),
                     output_data.clone().split(BUFFER_SIZE),
                 ),
-                TestMidiReader::new(vec![input_event]),
                 MidiDummy::new(),
+                RunOptions::new().midi_input(DeltaEventSource::new(TestMidiReader::new(vec![
+                    input_event,
+                ]))),
             )
             .expect("Unexpected error");
             test_plugin.check_last();
@@ -676,8 +1663,11 @@ mod tests {
                     &mut AudioBufferWriter::new(&mut output_buffer),
                     output_data.clone().split(BUFFER_SIZE),
                 ),
-                TestMidiReader::new(vec![input_event1, input_event2]),
                 MidiDummy::new(),
+                RunOptions::new().midi_input(DeltaEventSource::new(TestMidiReader::new(vec![
+                    input_event1,
+                    input_event2,
+                ]))),
             )
             .expect("Unexpected error");
             test_plugin.check_last();
@@ -712,7 +1702,7 @@ mod tests {
                     output_data.clone().split(buffer_size),
                 ),
                 MidiDummy::new(),
-                MidiDummy::new(),
+                RunOptions::new(),
             )
             .expect("Unexpected error.");
             assert_eq!(output_buffer, output_data);
@@ -771,8 +1761,8 @@ mod tests {
                     &mut AudioBufferWriter::new(&mut output_buffer),
                     output_data.clone().split(BUFFER_SIZE),
                 ),
-                MidiDummy::new(),
                 TestMidiWriter::new(vec![input_event]),
+                RunOptions::new(),
             )
             .expect("No errors are expected");
         }
@@ -833,8 +1823,8 @@ mod tests {
                     &mut AudioBufferWriter::new(&mut output_buffer),
                     output_data.clone().split(BUFFER_SIZE),
                 ),
-                MidiDummy::new(),
                 TestMidiWriter::new(vec![output_event1, output_event2]),
+                RunOptions::new(),
             )
             .expect("Unexpected error.");
         }
@@ -892,10 +1882,673 @@ mod tests {
                     &mut AudioBufferWriter::new(&mut output_buffer),
                     output_data.clone().split(BUFFER_SIZE),
                 ),
-                MidiDummy::new(),
                 TestMidiWriter::new(vec![output_event1, output_event2]),
+                RunOptions::new(),
+            )
+            .expect("Unexpected error.");
+        }
+    }
+
+    mod automation {
+        use super::super::{
+            dummy::{AudioDummy, MidiDummy},
+            run, DeltaEvent, DeltaEventSource, RunOptions, TestMidiReader,
+        };
+        use super::{Observed, RecordingPlugin};
+        use crate::event::{ParameterChange, RawMidiEvent, Timed};
+
+        #[test]
+        fn interleaves_automation_and_midi_at_the_right_time() {
+            const BUFFER_SIZE: usize = 3;
+            const SAMPLE_RATE: u32 = 8000;
+            // 1 frame is 1/8000 seconds = 125 microseconds.
+            let midi_event = RawMidiEvent::new(&[1, 2, 3]);
+            let change1 = ParameterChange::new(0, 0.5);
+            let change2 = ParameterChange::new(1, 0.25);
+            // Midi event at frame 4:
+            // 0 1 2 3 4 5 6 7 8
+            // . . .|. M .|. . .|
+            let midi_in = TestMidiReader::new(vec![DeltaEvent {
+                microseconds_since_previous_event: 500,
+                event: midi_event,
+            }]);
+            // Parameter changes at frame 4 and at frame 7:
+            // 0 1 2 3 4 5 6 7 8
+            // . . .|. A .|. A .|
+            let automation = vec![
+                DeltaEvent {
+                    microseconds_since_previous_event: 500,
+                    event: change1,
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 375,
+                    event: change2,
+                },
+            ];
+            let mut plugin = RecordingPlugin::new();
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 9),
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 0),
+                MidiDummy::new(),
+                RunOptions::new()
+                    .midi_input(DeltaEventSource::new(midi_in))
+                    .automation(automation.into_iter()),
+            )
+            .expect("Unexpected error.");
+            assert_eq!(
+                plugin.observed,
+                vec![
+                    Observed::Render(3),
+                    Observed::Automation(Timed::new(1, change1)),
+                    Observed::Midi(Timed::new(1, midi_event)),
+                    Observed::Render(3),
+                    Observed::Automation(Timed::new(1, change2)),
+                    Observed::Render(3),
+                ]
+            );
+        }
+    }
+
+    mod midi_inputs {
+        use super::super::{
+            dummy::{AudioDummy, MidiDummy},
+            run, DeltaEvent, DeltaEventSource, RunOptions, TestMidiReader,
+        };
+        use super::{Observed, RecordingPlugin};
+        use crate::event::{Indexed, RawMidiEvent, Timed};
+
+        #[test]
+        fn merges_the_midi_inputs_by_time() {
+            const BUFFER_SIZE: usize = 3;
+            const SAMPLE_RATE: u32 = 8000;
+            // 1 frame is 1/8000 seconds = 125 microseconds.
+            let event1 = RawMidiEvent::new(&[1, 2, 3]);
+            let event2 = RawMidiEvent::new(&[4, 5, 6]);
+            let event3 = RawMidiEvent::new(&[7, 8, 9]);
+            let event4 = RawMidiEvent::new(&[10, 11, 12]);
+            // Midi input 0 has events at frame 4 and at frame 7:
+            // 0 1 2 3 4 5 6 7 8
+            // . . .|. 1 .|. 3 .|
+            let midi_in0 = TestMidiReader::new(vec![
+                DeltaEvent {
+                    microseconds_since_previous_event: 500,
+                    event: event1,
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 375,
+                    event: event3,
+                },
+            ]);
+            // Midi input 1 has events at frame 4 and at frame 5:
+            // 0 1 2 3 4 5 6 7 8
+            // . . .|. 2 4|. . .|
+            let midi_in1 = TestMidiReader::new(vec![
+                DeltaEvent {
+                    microseconds_since_previous_event: 500,
+                    event: event2,
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 125,
+                    event: event4,
+                },
+            ]);
+            let mut plugin = RecordingPlugin::new();
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 9),
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 0),
+                MidiDummy::new(),
+                RunOptions::new()
+                    .midi_inputs(vec![
+                        DeltaEventSource::new(midi_in0),
+                        DeltaEventSource::new(midi_in1),
+                    ])
+                    .with_input_index(),
+            )
+            .expect("Unexpected error.");
+            assert_eq!(
+                plugin.observed,
+                vec![
+                    Observed::Render(3),
+                    Observed::IndexedMidi(Indexed::new(0, Timed::new(1, event1))),
+                    Observed::IndexedMidi(Indexed::new(1, Timed::new(1, event2))),
+                    Observed::IndexedMidi(Indexed::new(1, Timed::new(2, event4))),
+                    Observed::Render(3),
+                    Observed::IndexedMidi(Indexed::new(0, Timed::new(1, event3))),
+                    Observed::Render(3),
+                ]
+            );
+        }
+    }
+
+    mod midi_file_events {
+        use super::super::{
+            dummy::{AudioDummy, MidiDummy},
+            run, DeltaEvent, DeltaEventSource, MidiFileEvent, RunOptions,
+        };
+        use super::{Observed, RecordingPlugin};
+        use crate::event::{MetaEvent, RawMidiEvent, Timed};
+
+        #[test]
+        fn delivers_midi_sysex_and_meta_events_at_the_right_time() {
+            const BUFFER_SIZE: usize = 3;
+            const SAMPLE_RATE: u32 = 8000;
+            // 1 frame is 1/8000 seconds = 125 microseconds.
+            let midi_event = RawMidiEvent::new(&[0x90, 60, 100]);
+            // Meta event at frame 1, system exclusive message at frame 4 and midi event at frame 5:
+            // 0 1 2 3 4 5
+            // . M .|. S E|
+            let midi_in = vec![
+                DeltaEvent {
+                    microseconds_since_previous_event: 125,
+                    event: MidiFileEvent::Meta(MetaEvent::Lyric(b"la")),
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 375,
+                    event: MidiFileEvent::SysEx(&[0x7E, 0x7F, 0xF7]),
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 125,
+                    event: MidiFileEvent::Midi(midi_event),
+                },
+            ];
+            let mut plugin = RecordingPlugin::new();
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 6),
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 0),
+                MidiDummy::new(),
+                RunOptions::new().midi_input(DeltaEventSource::new(midi_in.into_iter())),
+            )
+            .expect("Unexpected error.");
+            assert_eq!(
+                plugin.observed,
+                vec![
+                    Observed::Meta(Timed::new(1, MetaEvent::Lyric(b"la"))),
+                    Observed::Render(3),
+                    Observed::SysEx(Timed::new(1, vec![0xF0, 0x7E, 0x7F, 0xF7])),
+                    Observed::Midi(Timed::new(2, midi_event)),
+                    Observed::Render(3),
+                ]
+            );
+        }
+    }
+
+    mod all_options {
+        use super::super::{
+            dummy::{AudioDummy, MidiDummy},
+            run,
+            transport::SyntheticTransport,
+            DeltaEvent, DeltaEventSource, MidiFileEvent, RunOptions,
+        };
+        use super::{Observed, RecordingPlugin};
+        use crate::backend::TimeSignature;
+        use crate::event::{Indexed, MetaEvent, ParameterChange, RawMidiEvent, Timed};
+
+        #[test]
+        fn midi_inputs_with_sysex_automation_and_transport_can_be_combined() {
+            const BUFFER_SIZE: usize = 4;
+            const SAMPLE_RATE: u32 = 8;
+            // 1 frame is 1/8 seconds = 125 000 microseconds.
+            // 60 beats per minute: one beat per second, so one beat per 8 frames.
+            let transport = SyntheticTransport::new(60.0, TimeSignature::new(4, 4));
+            let midi_event = RawMidiEvent::new(&[0x90, 60, 100]);
+            let change = ParameterChange::new(0, 0.5);
+            // Midi input 0: a midi event at frame 1 and a meta event at frame 5.
+            let midi_in0 = vec![
+                DeltaEvent {
+                    microseconds_since_previous_event: 125_000,
+                    event: MidiFileEvent::Midi(midi_event),
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 500_000,
+                    event: MidiFileEvent::Meta(MetaEvent::Marker(b"verse")),
+                },
+            ];
+            // Midi input 1: a system exclusive message at frame 2.
+            let midi_in1 = vec![DeltaEvent {
+                microseconds_since_previous_event: 250_000,
+                event: MidiFileEvent::SysEx(&[0x7E, 0x7F, 0xF7]),
+            }];
+            // A parameter change at frame 5.
+            let automation = vec![DeltaEvent {
+                microseconds_since_previous_event: 625_000,
+                event: change,
+            }];
+            let mut plugin = RecordingPlugin::new();
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 8),
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 0),
+                MidiDummy::new(),
+                RunOptions::new()
+                    .midi_inputs(vec![
+                        DeltaEventSource::new(midi_in0.into_iter()),
+                        DeltaEventSource::new(midi_in1.into_iter()),
+                    ])
+                    .with_input_index()
+                    .automation(automation.into_iter())
+                    .transport(transport),
+            )
+            .expect("Unexpected error.");
+            assert_eq!(
+                plugin.observed,
+                vec![
+                    Observed::IndexedMidi(Indexed::new(0, Timed::new(1, midi_event))),
+                    Observed::IndexedSysEx(Indexed::new(
+                        1,
+                        Timed::new(2, vec![0xF0, 0x7E, 0x7F, 0xF7])
+                    )),
+                    Observed::Render(4),
+                    Observed::Automation(Timed::new(1, change)),
+                    Observed::IndexedMeta(Indexed::new(
+                        0,
+                        Timed::new(1, MetaEvent::Marker(b"verse"))
+                    )),
+                    Observed::Render(4),
+                ]
+            );
+            assert_eq!(
+                plugin.positions_in_quarter_notes,
+                vec![Some(0.0), Some(0.5)]
+            );
+        }
+    }
+
+    mod transport {
+        use super::super::{
+            dummy::{AudioDummy, MidiDummy},
+            run,
+            transport::SyntheticTransport,
+            RunOptions,
+        };
+        use super::RecordingPlugin;
+        use crate::backend::TimeSignature;
+
+        #[test]
+        fn transport_follows_the_position_of_each_buffer() {
+            const BUFFER_SIZE: usize = 4;
+            const SAMPLE_RATE: u32 = 8;
+            // 60 beats per minute: one beat per second, so one beat per 8 frames.
+            let transport = SyntheticTransport::new(60.0, TimeSignature::new(4, 4));
+            let mut plugin = RecordingPlugin::new();
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 12),
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 0),
+                MidiDummy::new(),
+                RunOptions::new().transport(transport),
+            )
+            .expect("Unexpected error.");
+            assert_eq!(
+                plugin.positions_in_quarter_notes,
+                vec![Some(0.0), Some(0.5), Some(1.0)]
+            );
+        }
+    }
+
+    mod midi_output {
+        use super::super::{
+            dummy::AudioDummy, run, MidiWriter, MidiWriterWrapper, RunOptions, TestMidiWriter,
+        };
+        use crate::backend::{MidiOutput, MidiOutputError};
+        use crate::buffer::AudioBufferInOut;
+        use crate::event::{DeltaEvent, EventHandler, RawMidiEvent, SysExEvent, Timed};
+        use crate::{AudioHandler, ContextualAudioRenderer};
+
+        struct MidiGenerator;
+
+        impl AudioHandler for MidiGenerator {
+            fn set_sample_rate(&mut self, _sample_rate: f64) {}
+        }
+
+        impl EventHandler<Timed<RawMidiEvent>> for MidiGenerator {
+            fn handle_event(&mut self, _event: Timed<RawMidiEvent>) {}
+        }
+
+        impl<C> ContextualAudioRenderer<f32, C> for MidiGenerator
+        where
+            C: MidiOutput,
+        {
+            fn render_buffer(&mut self, _buffer: &mut AudioBufferInOut<f32>, context: &mut C) {
+                assert_eq!(context.number_of_midi_output_ports(), 1);
+                let event = RawMidiEvent::new(&[0x90, 60, 100]);
+                assert_eq!(context.write_midi(0, 1, event), Ok(()));
+                assert_eq!(
+                    context.write_midi(1, 1, event),
+                    Err(MidiOutputError::PortOutOfBounds {
+                        port_index: 1,
+                        number_of_ports: 1
+                    })
+                );
+                assert_eq!(
+                    context.write_sysex(0, 1, &[0xF0, 0x01, 0xF7]),
+                    Err(MidiOutputError::Unsupported)
+                );
+            }
+        }
+
+        #[test]
+        fn midi_written_by_the_plugin_is_written_to_the_midi_writer() {
+            const BUFFER_SIZE: usize = 4;
+            const SAMPLE_RATE: u32 = 1_000_000;
+            let event = RawMidiEvent::new(&[0x90, 60, 100]);
+            let mut plugin = MidiGenerator;
+            let mut midi_writer = TestMidiWriter::new(vec![
+                DeltaEvent {
+                    microseconds_since_previous_event: 1,
+                    event,
+                },
+                DeltaEvent {
+                    microseconds_since_previous_event: 4,
+                    event,
+                },
+            ]);
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 8),
+                AudioDummy::<f32>::with_sample_rate_and_length(SAMPLE_RATE, 0),
+                &mut midi_writer,
+                RunOptions::new(),
+            )
+            .expect("Unexpected error.");
+            midi_writer.check_last();
+        }
+
+        #[test]
+        fn write_midi_reports_a_full_buffer() {
+            let mut wrapper = MidiWriterWrapper::new(TestMidiWriter::new(Vec::new()), 1.0);
+            let event = RawMidiEvent::new(&[0x90, 60, 100]);
+            let mut result = Ok(());
+            for _ in 0..10_000 {
+                result = wrapper.write_midi(0, 0, event);
+                if result.is_err() {
+                    break;
+                }
+            }
+            assert_eq!(result, Err(MidiOutputError::BufferFull));
+        }
+
+        #[derive(Default)]
+        struct RecordingMidiWriter {
+            events: Vec<(u64, Vec<u8>)>,
+        }
+
+        impl MidiWriter for RecordingMidiWriter {
+            fn write_event(&mut self, event: DeltaEvent<RawMidiEvent>) {
+                self.events.push((
+                    event.microseconds_since_previous_event,
+                    event.event.bytes().to_vec(),
+                ));
+            }
+
+            fn supports_sysex(&self) -> bool {
+                true
+            }
+
+            fn write_sysex(&mut self, event: DeltaEvent<SysExEvent>) {
+                self.events.push((
+                    event.microseconds_since_previous_event,
+                    event.event.data().to_vec(),
+                ));
+            }
+        }
+
+        #[test]
+        fn sysex_is_written_in_time_order_with_the_midi_events() {
+            let mut wrapper =
+                MidiWriterWrapper::with_frames_per_second(RecordingMidiWriter::default(), 1_000);
+            let sysex = [0xF0, 0x01, 0x02, 0xF7];
+            let note_on = RawMidiEvent::new(&[0x90, 60, 100]);
+            assert_eq!(
+                wrapper.write_sysex(1, 0, &sysex),
+                Err(MidiOutputError::PortOutOfBounds {
+                    port_index: 1,
+                    number_of_ports: 1
+                })
+            );
+            assert_eq!(wrapper.write_midi(0, 3, note_on), Ok(()));
+            assert_eq!(wrapper.write_sysex(0, 1, &sysex), Ok(()));
+            wrapper.step_frames(4);
+            assert_eq!(wrapper.write_sysex(0, 0, &sysex[..3]), Ok(()));
+            wrapper.step_frames(4);
+            assert_eq!(
+                wrapper.inner.events,
+                vec![
+                    (1_000, sysex.to_vec()),
+                    (2_000, note_on.bytes().to_vec()),
+                    (1_000, sysex[..3].to_vec()),
+                ]
+            );
+        }
+    }
+
+    mod timing {
+        use super::super::{
+            frames_to_microseconds, microseconds_to_frames, DeltaEventSource, EventSourceReader,
+            MidiWriter, MidiWriterWrapper,
+        };
+        use crate::backend::MidiOutput;
+        use crate::event::{DeltaEvent, RawMidiEvent};
+
+        const SAMPLE_RATES: [u64; 6] = [8000, 22050, 44100, 48000, 96000, 192000];
+        const ONE_HOUR_IN_SECONDS: u64 = 3600;
+
+        #[test]
+        fn frames_survive_a_round_trip_through_microseconds() {
+            for &sample_rate in SAMPLE_RATES.iter() {
+                let one_hour_in_frames = ONE_HOUR_IN_SECONDS * sample_rate;
+                for time_in_frames in
+                    (0..1000).chain(one_hour_in_frames - 1000..one_hour_in_frames + 1000)
+                {
+                    let time_in_microseconds = frames_to_microseconds(time_in_frames, sample_rate);
+                    assert_eq!(
+                        microseconds_to_frames(time_in_microseconds, sample_rate),
+                        time_in_frames,
+                        "Round trip fails for frame {} at {} frames per second.",
+                        time_in_frames,
+                        sample_rate
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn late_events_are_at_the_start_of_the_buffer() {
+            // 8000 frames per second: the event at 500 microseconds is at frame 4.
+            let events = vec![DeltaEvent {
+                microseconds_since_previous_event: 500,
+                event: RawMidiEvent::new(&[1, 2, 3]),
+            }];
+            let mut reader = EventSourceReader::new(DeltaEventSource::new(events.into_iter()));
+            assert_eq!(reader.peek_time_in_frames(8000, 3), Some(1));
+            assert_eq!(reader.peek_time_in_frames(8000, 6), Some(0));
+        }
+
+        struct AbsoluteTimeRecorder {
+            time_in_microseconds: u64,
+            event_times_in_microseconds: Vec<u64>,
+        }
+
+        impl MidiWriter for AbsoluteTimeRecorder {
+            fn write_event(&mut self, event: DeltaEvent<RawMidiEvent>) {
+                self.time_in_microseconds += event.microseconds_since_previous_event;
+                self.event_times_in_microseconds
+                    .push(self.time_in_microseconds);
+            }
+        }
+
+        #[test]
+        fn midi_writer_wrapper_writes_events_at_the_exact_time_for_one_hour() {
+            const BUFFER_SIZE: u64 = 4096;
+            const OFFSET_IN_FRAMES: u64 = 7;
+            let event = RawMidiEvent::new(&[0x90, 60, 100]);
+            for &sample_rate in SAMPLE_RATES.iter() {
+                let mut wrapper = MidiWriterWrapper::with_frames_per_second(
+                    AbsoluteTimeRecorder {
+                        time_in_microseconds: 0,
+                        event_times_in_microseconds: Vec::new(),
+                    },
+                    sample_rate,
+                );
+                let number_of_buffers = ONE_HOUR_IN_SECONDS * sample_rate / BUFFER_SIZE;
+                for _ in 0..number_of_buffers {
+                    assert_eq!(
+                        wrapper.write_midi(0, OFFSET_IN_FRAMES as u32, event),
+                        Ok(())
+                    );
+                    wrapper.step_frames(BUFFER_SIZE);
+                }
+                let times_in_frames: Vec<u64> = wrapper
+                    .inner
+                    .event_times_in_microseconds
+                    .iter()
+                    .map(|&time_in_microseconds| {
+                        microseconds_to_frames(time_in_microseconds, sample_rate)
+                    })
+                    .collect();
+                let expected_times_in_frames: Vec<u64> = (0..number_of_buffers)
+                    .map(|buffer_index| buffer_index * BUFFER_SIZE + OFFSET_IN_FRAMES)
+                    .collect();
+                assert_eq!(
+                    times_in_frames, expected_times_in_frames,
+                    "Events are not on the expected frame at {} frames per second.",
+                    sample_rate
+                );
+            }
+        }
+    }
+
+    mod latency {
+        use super::super::{
+            dummy::MidiDummy, memory::AudioBufferReader, memory::AudioBufferWriter, run,
+            MidiWriter, MidiWriterWrapper, RunOptions, TestMidiWriter,
+        };
+        use crate::backend::MidiOutput;
+        use crate::buffer::{AudioBufferInOut, AudioChunk};
+        use crate::event::{DeltaEvent, EventHandler, RawMidiEvent, Timed};
+        use crate::{AudioHandler, ContextualAudioRenderer};
+        use std::collections::VecDeque;
+
+        const LATENCY: usize = 2;
+
+        /// Delays the input by `LATENCY` frames and writes a midi event at the given frames.
+        struct DelayPlugin {
+            delay_line: VecDeque<f32>,
+            frames_rendered: usize,
+            midi_output_times_in_frames: Vec<usize>,
+        }
+
+        impl DelayPlugin {
+            fn new(midi_output_times_in_frames: Vec<usize>) -> Self {
+                DelayPlugin {
+                    delay_line: vec![0.0; LATENCY].into_iter().collect(),
+                    frames_rendered: 0,
+                    midi_output_times_in_frames,
+                }
+            }
+        }
+
+        impl AudioHandler for DelayPlugin {
+            fn set_sample_rate(&mut self, _sample_rate: f64) {}
+
+            fn latency_in_frames(&self) -> u32 {
+                LATENCY as u32
+            }
+        }
+
+        impl EventHandler<Timed<RawMidiEvent>> for DelayPlugin {
+            fn handle_event(&mut self, _event: Timed<RawMidiEvent>) {}
+        }
+
+        impl<W: MidiWriter> ContextualAudioRenderer<f32, MidiWriterWrapper<W>> for DelayPlugin {
+            fn render_buffer(
+                &mut self,
+                buffer: &mut AudioBufferInOut<f32>,
+                context: &mut MidiWriterWrapper<W>,
+            ) {
+                let input = buffer.inputs().index_channel(0).to_vec();
+                for (sample, output) in input
+                    .into_iter()
+                    .zip(buffer.outputs().index_channel(0).iter_mut())
+                {
+                    self.delay_line.push_back(sample);
+                    *output = self.delay_line.pop_front().unwrap();
+                }
+                let number_of_frames = buffer.number_of_frames();
+                for &time in self.midi_output_times_in_frames.iter() {
+                    if self.frames_rendered <= time
+                        && time < self.frames_rendered + number_of_frames
+                    {
+                        context
+                            .write_midi(
+                                0,
+                                (time - self.frames_rendered) as u32,
+                                RawMidiEvent::new(&[0x90, 60, 100]),
+                            )
+                            .expect("Unexpected error.");
+                    }
+                }
+                self.frames_rendered += number_of_frames;
+            }
+        }
+
+        #[test]
+        fn leading_frames_are_trimmed_from_the_output_and_trailing_frames_are_rendered() {
+            for &buffer_size in &[1, 2, 3, 4, 8, 9] {
+                let input_data =
+                    AudioChunk::from_channels(vec![vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]]);
+                let mut output_data = AudioChunk::new(1);
+                let mut plugin = DelayPlugin::new(Vec::new());
+                run(
+                    &mut plugin,
+                    buffer_size,
+                    AudioBufferReader::new(&input_data, 8000),
+                    AudioBufferWriter::new(&mut output_data),
+                    MidiDummy::new(),
+                    RunOptions::new(),
+                )
+                .expect("Unexpected error.");
+                assert_eq!(
+                    output_data.channels(),
+                    input_data.channels(),
+                    "Unexpected output for buffer size {}.",
+                    buffer_size
+                );
+                assert_eq!(plugin.frames_rendered, 8 + LATENCY);
+            }
+        }
+
+        #[test]
+        fn midi_output_is_shifted_by_the_latency() {
+            const BUFFER_SIZE: usize = 3;
+            // 8000 frames per second: one frame lasts 125 microseconds.
+            let input_data = AudioChunk::from_channels(vec![vec![0.0; 8]]);
+            let mut output_data = AudioChunk::new(1);
+            let mut plugin = DelayPlugin::new(vec![1, 4, 9]);
+            let note_on = |microseconds_since_previous_event| DeltaEvent {
+                microseconds_since_previous_event,
+                event: RawMidiEvent::new(&[0x90, 60, 100]),
+            };
+            let mut midi_writer =
+                TestMidiWriter::new(vec![note_on(0), note_on(2 * 125), note_on(5 * 125)]);
+            run(
+                &mut plugin,
+                BUFFER_SIZE,
+                AudioBufferReader::new(&input_data, 8000),
+                AudioBufferWriter::new(&mut output_data),
+                &mut midi_writer,
+                RunOptions::new(),
             )
             .expect("Unexpected error.");
+            midi_writer.check_last();
         }
     }
 }
@@ -0,0 +1,206 @@
+//! A synthetic transport for offline rendering.
+use super::MICROSECONDS_PER_SECOND;
+use crate::backend::{TimeSignature, Transport};
+
+const MICROSECONDS_PER_MINUTE: f64 = 60.0 * MICROSECONDS_PER_SECOND as f64;
+
+struct TempoChange {
+    time_in_microseconds: u64,
+    position_in_quarter_notes: f64,
+    microseconds_per_quarter_note: f64,
+}
+
+struct TimeSignatureChange {
+    position_in_quarter_notes: f64,
+    time_signature: TimeSignature,
+}
+
+/// A transport that is always playing, with tempo changes and time signature changes
+/// at predefined times.
+///
+/// This is used to provide transport information when rendering offline, see the
+/// [`transport`] method of [`RunOptions`].
+/// When rendering a midi file, you can use the [`synthetic_transport`] function to create
+/// a `SyntheticTransport` from the tempo and time signature meta-events in the midi file.
+///
+/// Time signature changes are assumed to happen at the start of a bar.
+///
+/// # Example
+/// ```
+/// use rsynth::backend::TimeSignature;
+/// use rsynth::backend::combined::transport::SyntheticTransport;
+/// // Start with 120 beats per minute in 4/4.
+/// let mut transport = SyntheticTransport::new(120.0, TimeSignature::new(4, 4));
+/// // After one second (two beats), change the tempo to 60 beats per minute.
+/// transport.add_tempo_change(1_000_000, 60.0);
+/// // After three seconds, the position is 2 + 2 = 4 quarter notes.
+/// let position = transport.transport_at(3_000_000, 0).position_in_quarter_notes;
+/// assert_eq!(position, Some(4.0));
+/// ```
+///
+/// [`transport`]: ../struct.RunOptions.html#method.transport
+/// [`RunOptions`]: ../struct.RunOptions.html
+/// [`synthetic_transport`]: ../midly/fn.synthetic_transport.html
+pub struct SyntheticTransport {
+    tempo_changes: Vec<TempoChange>,
+    time_signature_changes: Vec<TimeSignatureChange>,
+}
+
+impl SyntheticTransport {
+    /// Create a new `SyntheticTransport` with the given initial tempo and time signature.
+    pub fn new(tempo_in_beats_per_minute: f64, time_signature: TimeSignature) -> Self {
+        Self {
+            tempo_changes: vec![TempoChange {
+                time_in_microseconds: 0,
+                position_in_quarter_notes: 0.0,
+                microseconds_per_quarter_note: MICROSECONDS_PER_MINUTE / tempo_in_beats_per_minute,
+            }],
+            time_signature_changes: vec![TimeSignatureChange {
+                position_in_quarter_notes: 0.0,
+                time_signature,
+            }],
+        }
+    }
+
+    fn last_tempo_change_before(&self, time_in_microseconds: u64) -> &TempoChange {
+        self.tempo_changes
+            .iter()
+            .rev()
+            .find(|change| change.time_in_microseconds <= time_in_microseconds)
+            .expect("the first tempo change to be at time 0")
+    }
+
+    fn position_in_quarter_notes(&self, time_in_microseconds: u64) -> f64 {
+        let change = self.last_tempo_change_before(time_in_microseconds);
+        change.position_in_quarter_notes
+            + (time_in_microseconds - change.time_in_microseconds) as f64
+                / change.microseconds_per_quarter_note
+    }
+
+    /// Change the tempo at the given time.
+    ///
+    /// # Panics
+    /// Panics if `time_in_microseconds` is before the time of the previous tempo change.
+    pub fn add_tempo_change(&mut self, time_in_microseconds: u64, tempo_in_beats_per_minute: f64) {
+        let previous = self
+            .tempo_changes
+            .last()
+            .expect("the first tempo change to be at time 0");
+        assert!(
+            time_in_microseconds >= previous.time_in_microseconds,
+            "tempo changes should be added in chronological order"
+        );
+        let position_in_quarter_notes = self.position_in_quarter_notes(time_in_microseconds);
+        let change = TempoChange {
+            time_in_microseconds,
+            position_in_quarter_notes,
+            microseconds_per_quarter_note: MICROSECONDS_PER_MINUTE / tempo_in_beats_per_minute,
+        };
+        if previous.time_in_microseconds == time_in_microseconds {
+            *self.tempo_changes.last_mut().unwrap() = change;
+        } else {
+            self.tempo_changes.push(change);
+        }
+    }
+
+    /// Change the time signature at the given time.
+    ///
+    /// # Panics
+    /// Panics if `time_in_microseconds` is before the time of the previous time signature change.
+    pub fn add_time_signature_change(
+        &mut self,
+        time_in_microseconds: u64,
+        time_signature: TimeSignature,
+    ) {
+        let position_in_quarter_notes = self.position_in_quarter_notes(time_in_microseconds);
+        let previous = self
+            .time_signature_changes
+            .last()
+            .expect("the first time signature change to be at time 0");
+        assert!(
+            position_in_quarter_notes >= previous.position_in_quarter_notes,
+            "time signature changes should be added in chronological order"
+        );
+        let change = TimeSignatureChange {
+            position_in_quarter_notes,
+            time_signature,
+        };
+        if previous.position_in_quarter_notes == position_in_quarter_notes {
+            *self.time_signature_changes.last_mut().unwrap() = change;
+        } else {
+            self.time_signature_changes.push(change);
+        }
+    }
+
+    /// Get the state of the transport at the given time.
+    /// `position_in_frames` is only used to fill in the corresponding field of the result.
+    pub fn transport_at(&self, time_in_microseconds: u64, position_in_frames: u64) -> Transport {
+        let tempo_change = self.last_tempo_change_before(time_in_microseconds);
+        let position_in_quarter_notes = self.position_in_quarter_notes(time_in_microseconds);
+        let time_signature_change = self
+            .time_signature_changes
+            .iter()
+            .rev()
+            .find(|change| change.position_in_quarter_notes <= position_in_quarter_notes)
+            .expect("the first time signature change to be at time 0");
+        let bar_length = time_signature_change
+            .time_signature
+            .bar_length_in_quarter_notes();
+        let bars_since_change = ((position_in_quarter_notes
+            - time_signature_change.position_in_quarter_notes)
+            / bar_length)
+            .floor();
+        Transport {
+            is_playing: true,
+            tempo_in_beats_per_minute: Some(
+                MICROSECONDS_PER_MINUTE / tempo_change.microseconds_per_quarter_note,
+            ),
+            time_signature: Some(time_signature_change.time_signature),
+            position_in_frames: Some(position_in_frames),
+            position_in_quarter_notes: Some(position_in_quarter_notes),
+            bar_start_in_quarter_notes: Some(
+                time_signature_change.position_in_quarter_notes + bars_since_change * bar_length,
+            ),
+        }
+    }
+}
+
+/// The default `SyntheticTransport` has a constant tempo of 120 beats per minute in 4/4.
+impl Default for SyntheticTransport {
+    fn default() -> Self {
+        Self::new(120.0, TimeSignature::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyntheticTransport;
+    use crate::backend::TimeSignature;
+
+    #[test]
+    fn position_takes_tempo_changes_into_account() {
+        // 120 beats per minute: 500 000 microseconds per beat.
+        let mut transport = SyntheticTransport::default();
+        transport.add_tempo_change(1_000_000, 240.0);
+        let at_one_second = transport.transport_at(1_000_000, 44100);
+        assert_eq!(at_one_second.position_in_quarter_notes, Some(2.0));
+        assert_eq!(at_one_second.tempo_in_beats_per_minute, Some(240.0));
+        assert_eq!(at_one_second.position_in_frames, Some(44100));
+        // 240 beats per minute: 250 000 microseconds per beat.
+        let later = transport.transport_at(1_500_000, 0);
+        assert_eq!(later.position_in_quarter_notes, Some(4.0));
+    }
+
+    #[test]
+    fn bar_start_takes_time_signature_changes_into_account() {
+        let mut transport = SyntheticTransport::new(60.0, TimeSignature::new(4, 4));
+        // One bar of 4/4, then 6/8 bars of 3 quarter notes.
+        transport.add_time_signature_change(4_000_000, TimeSignature::new(6, 8));
+        let in_first_bar = transport.transport_at(3_000_000, 0);
+        assert_eq!(in_first_bar.bar_start_in_quarter_notes, Some(0.0));
+        assert_eq!(in_first_bar.time_signature, Some(TimeSignature::new(4, 4)));
+        let in_third_bar = transport.transport_at(8_000_000, 0);
+        assert_eq!(in_third_bar.bar_start_in_quarter_notes, Some(7.0));
+        assert_eq!(in_third_bar.time_signature, Some(TimeSignature::new(6, 8)));
+    }
+}
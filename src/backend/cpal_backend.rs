@@ -0,0 +1,837 @@
+//! Wrapper for the [cpal] backend (behind the `backend-cpal` feature).
+//!
+//! Support is only enabled if you compile with the "backend-cpal" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//!
+//! [cpal] is a cross-platform audio library, so this backend can be used to create standalone
+//! applications that do not need a Jack server (e.g. on Windows or MacOs).
+//! The plugin renders audio to the default output device.
+//!
+//! For an example, see `cpal_synth.rs` in the `examples` folder.
+//! `examples/example_synth` contains the code that is shared for all backends and
+//! `examples/cpal_synth.rs` contains the cpal-specific code.
+//!
+//! # Usage
+//! See the documentation of the [`run`] function.
+//! The [`run`] function blocks until the user presses a key or until the plugin calls
+//! [`stop`] on the [`CpalHost`]. If you want to keep control, e.g. in a GUI application,
+//! use the [`start`] function instead, which returns a [`CpalHandle`] that can be used to stop
+//! the plugin.
+//!
+//! # Audio
+//! cpal uses interleaved buffers of varying sizes. The backend de-interleaves them into buffers
+//! that are allocated when the stream is started, so no memory is allocated in the
+//! audio callback. Buffers that are larger than the configured buffer size are rendered in
+//! chunks.
+//! The outputs of the plugin are written to the channels of the device; surplus channels of
+//! the device are filled with silence. The plugin gets silence on its audio inputs.
+//!
+//! # Midi
+//! cpal does not support midi. Instead, the backend polls a user-supplied [`MidiSource`] at
+//! the start of every chunk and passes the events to the plugin as
+//! `Indexed<Timed<RawMidiEvent>>` at the start of the chunk.
+//! Use [`midi_queue`] to send events from another thread, e.g. the thread of a midi input
+//! library: the [`MidiQueueReceiver`] is a [`MidiSource`]. The queue is bounded and its memory is
+//! allocated when it is created, so that the audio thread does not allocate or free memory.
+//!
+//! [`MidiSource`]: ./trait.MidiSource.html
+//! [`midi_queue`]: ./fn.midi_queue.html
+//! [`MidiQueueReceiver`]: ./struct.MidiQueueReceiver.html
+//! [`stop`]: ../trait.HostInterface.html#method.stop
+//! [`start`]: ./fn.start.html
+//! [`CpalHandle`]: ./struct.CpalHandle.html
+//! [`CpalHost`]: ./struct.CpalHost.html
+//! [cpal]: https://crates.io/crates/cpal
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+//! [`run`]: ./fn.run.html
+use crate::backend::{HostInterface, Stop};
+use crate::buffer::AudioBufferInOut;
+use crate::event::{ContextualEventHandler, Indexed, RawMidiEvent, Timed};
+use crate::{AudioHandler, AudioHandlerMeta, ContextualAudioRenderer};
+use std::cell::UnsafeCell;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use vecstorage::VecStorage;
+
+/// Re-exports of the [`cpal`](https://crates.io/crates/cpal) crate.
+/// Use this so that your code doesn't break when `rsynth` upgrades its dependency on `cpal`.
+pub mod cpal {
+    pub use cpal::*;
+}
+
+use self::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use self::cpal::{
+    BufferSize, BuildStreamError, DefaultStreamConfigError, Device, PlayStreamError, SampleFormat,
+    SampleRate, Stream, StreamConfig, SupportedStreamConfigsError,
+};
+
+/// The number of frames that is rendered at a time when no buffer size is configured.
+const DEFAULT_BUFFER_SIZE_IN_FRAMES: usize = 512;
+
+/// A source of midi events for the cpal backend.
+///
+/// The source is polled from the audio thread, so implementations must not block or allocate,
+/// e.g. by using a lock-free queue.
+pub trait MidiSource: Send + 'static {
+    /// Return the next event that is available, or `None` if there is no event available.
+    fn try_next(&mut self) -> Option<Indexed<RawMidiEvent>>;
+}
+
+/// Create a bounded queue for sending midi events from one thread to the audio thread.
+///
+/// Room for `capacity` events is allocated when the queue is created; sending and receiving
+/// events does not block, allocate or free memory.
+///
+/// # Panics
+/// Panics if `capacity` is `0`.
+pub fn midi_queue(capacity: usize) -> (MidiQueueSender, MidiQueueReceiver) {
+    assert!(capacity > 0);
+    let queue = Arc::new(MidiQueue {
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new((0, RawMidiEvent::new(&[0, 0, 0]))))
+            .collect(),
+        read_count: AtomicUsize::new(0),
+        write_count: AtomicUsize::new(0),
+    });
+    (
+        MidiQueueSender {
+            queue: queue.clone(),
+        },
+        MidiQueueReceiver { queue },
+    )
+}
+
+/// A single-producer single-consumer ring buffer.
+struct MidiQueue {
+    slots: Box<[UnsafeCell<(usize, RawMidiEvent)>]>,
+    // The number of events that have been read, only written by the receiver.
+    read_count: AtomicUsize,
+    // The number of events that have been written, only written by the sender.
+    write_count: AtomicUsize,
+}
+
+// A slot is only accessed by the sender before it is published by incrementing `write_count`
+// and only by the receiver after that, until the receiver releases it by incrementing
+// `read_count`.
+unsafe impl Sync for MidiQueue {}
+
+/// The sending end of a [`midi_queue`].
+///
+/// [`midi_queue`]: ./fn.midi_queue.html
+pub struct MidiQueueSender {
+    queue: Arc<MidiQueue>,
+}
+
+impl MidiQueueSender {
+    /// Send an event to the receiver.
+    /// Return the event as an error if the queue is full.
+    pub fn try_send(&mut self, event: Indexed<RawMidiEvent>) -> Result<(), Indexed<RawMidiEvent>> {
+        let write_count = self.queue.write_count.load(Ordering::Relaxed);
+        let read_count = self.queue.read_count.load(Ordering::Acquire);
+        if write_count.wrapping_sub(read_count) == self.queue.slots.len() {
+            return Err(event);
+        }
+        let slot = &self.queue.slots[write_count % self.queue.slots.len()];
+        unsafe {
+            *slot.get() = (event.index, event.event);
+        }
+        self.queue
+            .write_count
+            .store(write_count.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+/// The receiving end of a [`midi_queue`], to be used as the [`MidiSource`].
+///
+/// [`midi_queue`]: ./fn.midi_queue.html
+/// [`MidiSource`]: ./trait.MidiSource.html
+pub struct MidiQueueReceiver {
+    queue: Arc<MidiQueue>,
+}
+
+impl MidiSource for MidiQueueReceiver {
+    fn try_next(&mut self) -> Option<Indexed<RawMidiEvent>> {
+        let read_count = self.queue.read_count.load(Ordering::Relaxed);
+        let write_count = self.queue.write_count.load(Ordering::Acquire);
+        if read_count == write_count {
+            return None;
+        }
+        let slot = &self.queue.slots[read_count % self.queue.slots.len()];
+        let (index, event) = unsafe { *slot.get() };
+        self.queue
+            .read_count
+            .store(read_count.wrapping_add(1), Ordering::Release);
+        Some(Indexed { index, event })
+    }
+}
+
+/// A [`MidiSource`] without events, for applications that do not need midi input.
+///
+/// [`MidiSource`]: ./trait.MidiSource.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMidiSource;
+
+impl MidiSource for NoMidiSource {
+    fn try_next(&mut self) -> Option<Indexed<RawMidiEvent>> {
+        None
+    }
+}
+
+/// Used to communicate with the cpal backend.
+///
+/// You don't need to instantiate this yourself: it is passed as the `context`
+/// parameter to the [`render_audio`] method when using the [`run`] function.
+///
+/// [`render_audio`]: ../../trait.ContextualAudioRenderer.html#tymethod.render_buffer
+/// [`run`]: ./fn.run.html
+pub struct CpalHost {
+    sample_rate: u32,
+    stop_requested: bool,
+}
+
+impl CpalHost {
+    /// The sample rate of the output stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+impl HostInterface for CpalHost {
+    fn output_initialized(&self) -> bool {
+        false
+    }
+
+    fn stop(&mut self) {
+        self.stop_requested = true;
+    }
+}
+
+impl Stop for CpalHost {}
+
+/// Error that can occur when starting the cpal backend.
+#[derive(Debug)]
+pub enum CpalError {
+    /// There is no default output device.
+    NoOutputDevice,
+    /// The device does not support 32-bit floating point samples with the requested
+    /// sample rate.
+    UnsupportedConfig,
+    /// Failed to query the default configuration of the device.
+    DefaultStreamConfig(DefaultStreamConfigError),
+    /// Failed to query the supported configurations of the device.
+    SupportedStreamConfigs(SupportedStreamConfigsError),
+    /// Failed to create the output stream.
+    BuildStream(BuildStreamError),
+    /// Failed to start the output stream.
+    PlayStream(PlayStreamError),
+}
+
+impl Display for CpalError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            CpalError::NoOutputDevice => write!(f, "no default output device is available"),
+            CpalError::UnsupportedConfig => write!(
+                f,
+                "the output device does not support 32-bit floating point samples \
+                 with the requested sample rate"
+            ),
+            CpalError::DefaultStreamConfig(e) => e.fmt(f),
+            CpalError::SupportedStreamConfigs(e) => e.fmt(f),
+            CpalError::BuildStream(e) => e.fmt(f),
+            CpalError::PlayStream(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for CpalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CpalError::NoOutputDevice | CpalError::UnsupportedConfig => None,
+            CpalError::DefaultStreamConfig(e) => Some(e),
+            CpalError::SupportedStreamConfigs(e) => Some(e),
+            CpalError::BuildStream(e) => Some(e),
+            CpalError::PlayStream(e) => Some(e),
+        }
+    }
+}
+
+impl From<DefaultStreamConfigError> for CpalError {
+    fn from(e: DefaultStreamConfigError) -> Self {
+        CpalError::DefaultStreamConfig(e)
+    }
+}
+
+impl From<SupportedStreamConfigsError> for CpalError {
+    fn from(e: SupportedStreamConfigsError) -> Self {
+        CpalError::SupportedStreamConfigs(e)
+    }
+}
+
+impl From<BuildStreamError> for CpalError {
+    fn from(e: BuildStreamError) -> Self {
+        CpalError::BuildStream(e)
+    }
+}
+
+impl From<PlayStreamError> for CpalError {
+    fn from(e: PlayStreamError) -> Self {
+        CpalError::PlayStream(e)
+    }
+}
+
+struct CpalProcessHandler<P, M> {
+    plugin: P,
+    midi_source: M,
+    sample_rate: u32,
+    number_of_channels: usize,
+    buffer_size_in_frames: usize,
+    input_buffers: Vec<Vec<f32>>,
+    output_buffers: Vec<Vec<f32>>,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static mut [f32]>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl<P, M> CpalProcessHandler<P, M>
+where
+    P: AudioHandlerMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, CpalHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, CpalHost>,
+    M: MidiSource,
+{
+    fn new(
+        mut plugin: P,
+        midi_source: M,
+        sample_rate: u32,
+        number_of_channels: usize,
+        buffer_size_in_frames: usize,
+    ) -> Self {
+        trace!("CpalProcessHandler::new()");
+        plugin.set_sample_rate(sample_rate as f64);
+        let number_of_inputs = plugin.max_number_of_audio_inputs();
+        let number_of_outputs = plugin.max_number_of_audio_outputs();
+        CpalProcessHandler {
+            plugin,
+            midi_source,
+            sample_rate,
+            number_of_channels,
+            buffer_size_in_frames,
+            input_buffers: vec![vec![0.0; buffer_size_in_frames]; number_of_inputs],
+            output_buffers: vec![vec![0.0; buffer_size_in_frames]; number_of_outputs],
+            inputs: VecStorage::with_capacity(number_of_inputs),
+            outputs: VecStorage::with_capacity(number_of_outputs),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Fill the interleaved buffer `data` that is passed by cpal.
+    fn process(&mut self, data: &mut [f32]) {
+        if self.number_of_channels == 0 {
+            return;
+        }
+        let chunk_size = self.buffer_size_in_frames * self.number_of_channels;
+        for chunk in data.chunks_mut(chunk_size) {
+            if self.stopped.load(Ordering::SeqCst) {
+                for sample in chunk.iter_mut() {
+                    *sample = 0.0;
+                }
+                continue;
+            }
+            let number_of_frames = chunk.len() / self.number_of_channels;
+            if self.render(number_of_frames) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
+            self.interleave(chunk, number_of_frames);
+        }
+    }
+
+    /// Render `number_of_frames` frames. Returns `true` when the plugin has requested to stop.
+    fn render(&mut self, number_of_frames: usize) -> bool {
+        let mut host = CpalHost {
+            sample_rate: self.sample_rate,
+            stop_requested: false,
+        };
+        while let Some(Indexed { index, event }) = self.midi_source.try_next() {
+            let event = Indexed {
+                index,
+                event: Timed {
+                    time_in_frames: 0,
+                    event,
+                },
+            };
+            self.plugin.handle_event(event, &mut host);
+        }
+
+        let mut inputs = self.inputs.vec_guard();
+        for input_buffer in self.input_buffers.iter() {
+            inputs.push(&input_buffer[..number_of_frames]);
+        }
+        let mut outputs = self.outputs.vec_guard();
+        for output_buffer in self.output_buffers.iter_mut() {
+            outputs.push(&mut output_buffer[..number_of_frames]);
+        }
+        let mut buffer =
+            AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
+        self.plugin.render_buffer(&mut buffer, &mut host);
+        host.stop_requested
+    }
+
+    fn interleave(&self, chunk: &mut [f32], number_of_frames: usize) {
+        for (frame_index, frame) in chunk
+            .chunks_mut(self.number_of_channels)
+            .take(number_of_frames)
+            .enumerate()
+        {
+            for (channel_index, sample) in frame.iter_mut().enumerate() {
+                *sample = self
+                    .output_buffers
+                    .get(channel_index)
+                    .map(|channel| channel[frame_index])
+                    .unwrap_or(0.0);
+            }
+        }
+    }
+}
+
+/// Configuration for the cpal backend, to be used with the [`run_with_config`] and
+/// [`start_with_config`] functions.
+///
+/// # Example
+/// ```
+/// use rsynth::backend::cpal_backend::CpalConfig;
+/// let config = CpalConfig::new().sample_rate(48000).buffer_size_in_frames(256);
+/// ```
+///
+/// [`run_with_config`]: ./fn.run_with_config.html
+/// [`start_with_config`]: ./fn.start_with_config.html
+#[derive(Clone, Debug, Default)]
+pub struct CpalConfig {
+    sample_rate: Option<u32>,
+    buffer_size_in_frames: Option<u32>,
+}
+
+impl CpalConfig {
+    /// Create a new `CpalConfig` with the default configuration:
+    /// the default sample rate and buffer size of the device are used.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given sample rate instead of the default sample rate of the device.
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Request buffers of the given number of frames from the device.
+    pub fn buffer_size_in_frames(mut self, buffer_size_in_frames: u32) -> Self {
+        self.buffer_size_in_frames = Some(buffer_size_in_frames);
+        self
+    }
+
+    fn stream_config(&self, device: &Device) -> Result<StreamConfig, CpalError> {
+        let default_config = device.default_output_config()?;
+        let sample_rate = self
+            .sample_rate
+            .map(SampleRate)
+            .unwrap_or_else(|| default_config.sample_rate());
+        let channels = default_config.channels();
+        let supported_config = device
+            .supported_output_configs()?
+            .filter(|range| {
+                range.sample_format() == SampleFormat::F32 && range.channels() == channels
+            })
+            .find_map(|range| range.try_with_sample_rate(sample_rate))
+            .ok_or(CpalError::UnsupportedConfig)?;
+        let mut stream_config = supported_config.config();
+        if let Some(buffer_size_in_frames) = self.buffer_size_in_frames {
+            stream_config.buffer_size = BufferSize::Fixed(buffer_size_in_frames);
+        }
+        Ok(stream_config)
+    }
+}
+
+/// The interval with which [`CpalHandle::join`] checks if processing has stopped.
+///
+/// [`CpalHandle::join`]: ./struct.CpalHandle.html#method.join
+const STOP_POLLING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A handle to a plugin that is running with cpal, as returned by the [`start`] function.
+///
+/// The plugin is running as long as the handle is alive.
+/// Call [`stop`] to stop processing immediately or [`join`] to wait until processing stops.
+/// Both return the plugin.
+/// When the handle is dropped, the stream is closed and the plugin is dropped.
+///
+/// [`start`]: ./fn.start.html
+/// [`stop`]: #method.stop
+/// [`join`]: #method.join
+pub struct CpalHandle<P, M> {
+    stream: Stream,
+    process_handler: Arc<Mutex<CpalProcessHandler<P, M>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl<P, M> CpalHandle<P, M> {
+    /// Return `true` when the plugin has called [`stop`] on the [`CpalHost`].
+    ///
+    /// [`stop`]: ../trait.HostInterface.html#method.stop
+    /// [`CpalHost`]: ./struct.CpalHost.html
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Stop processing, close the stream and return the plugin.
+    pub fn stop(self) -> P {
+        info!("Closing the stream...");
+        // Dropping the stream waits until the audio callback has returned and drops the
+        // callback, so that we hold the only reference to the process handler.
+        drop(self.stream);
+        let process_handler = match Arc::try_unwrap(self.process_handler) {
+            Ok(process_handler) => process_handler,
+            Err(_) => unreachable!("the audio callback has been dropped"),
+        };
+        let process_handler = match process_handler.into_inner() {
+            Ok(process_handler) => process_handler,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        process_handler.plugin
+    }
+
+    /// Block until processing has stopped (see [`is_stopped`]), then close the stream
+    /// and return the plugin.
+    ///
+    /// [`is_stopped`]: #method.is_stopped
+    pub fn join(self) -> P {
+        while !self.is_stopped() {
+            thread::sleep(STOP_POLLING_INTERVAL);
+        }
+        self.stop()
+    }
+}
+
+/// Start running the plugin on the default output device without blocking, using the default
+/// [`CpalConfig`].
+///
+/// The midi events of `midi_source` are passed to the plugin; use [`NoMidiSource`] if the
+/// plugin does not need midi input.
+/// The plugin keeps running until [`stop`] is called on the returned [`CpalHandle`].
+/// Use [`join`] to wait until the plugin calls [`stop`] on the [`CpalHost`].
+///
+/// # Example
+/// ```no_run
+/// # use rsynth::backend::cpal_backend::{midi_queue, CpalHandle};
+/// # use rsynth::event::{Indexed, RawMidiEvent};
+/// # fn start<P, M>(plugin: P, midi_source: M) -> Result<CpalHandle<P, M>, ()> { unimplemented!() }
+/// # fn example<P>(plugin: P) -> Result<(), ()> {
+/// let (mut sender, receiver) = midi_queue(1024);
+/// let handle = start(plugin, receiver)?;
+/// // Send midi events from another thread, e.g. the callback of a midi input library.
+/// sender
+///     .try_send(Indexed { index: 0, event: RawMidiEvent::new(&[0x90, 60, 100]) })
+///     .ok();
+/// let plugin = handle.stop();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`CpalConfig`]: ./struct.CpalConfig.html
+/// [`NoMidiSource`]: ./struct.NoMidiSource.html
+/// [`stop`]: ./struct.CpalHandle.html#method.stop
+/// [`join`]: ./struct.CpalHandle.html#method.join
+/// [`CpalHandle`]: ./struct.CpalHandle.html
+/// [`CpalHost`]: ./struct.CpalHost.html
+pub fn start<P, M>(plugin: P, midi_source: M) -> Result<CpalHandle<P, M>, CpalError>
+where
+    P: AudioHandlerMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, CpalHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, CpalHost>
+        + Send
+        + 'static,
+    M: MidiSource,
+{
+    start_with_config(plugin, midi_source, CpalConfig::default())
+}
+
+/// Start running the plugin on the default output device without blocking, using the
+/// given configuration.
+///
+/// See the [`start`] function for more information.
+///
+/// [`start`]: ./fn.start.html
+pub fn start_with_config<P, M>(
+    plugin: P,
+    midi_source: M,
+    config: CpalConfig,
+) -> Result<CpalHandle<P, M>, CpalError>
+where
+    P: AudioHandlerMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, CpalHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, CpalHost>
+        + Send
+        + 'static,
+    M: MidiSource,
+{
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or(CpalError::NoOutputDevice)?;
+    let stream_config = config.stream_config(&device)?;
+    let buffer_size_in_frames = match stream_config.buffer_size {
+        BufferSize::Fixed(buffer_size_in_frames) => buffer_size_in_frames as usize,
+        BufferSize::Default => DEFAULT_BUFFER_SIZE_IN_FRAMES,
+    };
+    info!(
+        "Opening an output stream with {} channels and a sample rate of {} Hz.",
+        stream_config.channels, stream_config.sample_rate.0
+    );
+
+    let process_handler = CpalProcessHandler::new(
+        plugin,
+        midi_source,
+        stream_config.sample_rate.0,
+        stream_config.channels as usize,
+        buffer_size_in_frames,
+    );
+    let stopped = process_handler.stopped.clone();
+    let process_handler = Arc::new(Mutex::new(process_handler));
+    let callback_process_handler = process_handler.clone();
+    let stream = device.build_output_stream(
+        &stream_config,
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            // The lock is only contended while the stream is being closed.
+            match callback_process_handler.try_lock() {
+                Ok(mut process_handler) => process_handler.process(data),
+                Err(_) => {
+                    for sample in data.iter_mut() {
+                        *sample = 0.0;
+                    }
+                }
+            }
+        },
+        |e| error!("Error in the output stream: {}", e),
+        None,
+    )?;
+    stream.play()?;
+
+    Ok(CpalHandle {
+        stream,
+        process_handler,
+        stopped,
+    })
+}
+
+/// Run the plugin on the default output device until the user presses a key on the computer
+/// keyboard or the plugin requests the `CpalHost` to stop.
+///
+/// This is a thin wrapper around the [`start`] function.
+///
+/// [`start`]: ./fn.start.html
+pub fn run<P, M>(plugin: P, midi_source: M) -> Result<P, CpalError>
+where
+    P: AudioHandlerMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, CpalHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, CpalHost>
+        + Send
+        + 'static,
+    M: MidiSource,
+{
+    run_with_config(plugin, midi_source, CpalConfig::default())
+}
+
+/// Same as the [`run`] function, but with the given configuration.
+///
+/// # Example
+/// ```no_run
+/// # use rsynth::backend::cpal_backend::{CpalConfig, NoMidiSource};
+/// # fn run_with_config<P>(plugin: P, midi_source: NoMidiSource, config: CpalConfig) -> Result<P, ()> { Ok(plugin) }
+/// # fn example<P>(plugin: P) -> Result<(), ()> {
+/// let config = CpalConfig::new().buffer_size_in_frames(256);
+/// let plugin = run_with_config(plugin, NoMidiSource, config)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`run`]: ./fn.run.html
+pub fn run_with_config<P, M>(plugin: P, midi_source: M, config: CpalConfig) -> Result<P, CpalError>
+where
+    P: AudioHandlerMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, CpalHost>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, CpalHost>
+        + Send
+        + 'static,
+    M: MidiSource,
+{
+    let handle = start_with_config(plugin, midi_source, config)?;
+
+    // Wait for a key press on a separate thread, so that we also return when the plugin stops.
+    let (key_sender, key_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        println!("Press any key to quit");
+        let mut user_input = String::new();
+        io::stdin().read_line(&mut user_input).ok();
+        key_sender.send(()).ok();
+    });
+    while !handle.is_stopped() {
+        if key_receiver.recv_timeout(STOP_POLLING_INTERVAL).is_ok() {
+            break;
+        }
+    }
+
+    Ok(handle.stop())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::{InOut, Meta, MetaData};
+
+    struct TestPlugin {
+        meta: MetaData<&'static str, &'static str, &'static str>,
+        sample_rate: f64,
+        number_of_buffers: usize,
+        events: Vec<Indexed<Timed<RawMidiEvent>>>,
+        stop_after: usize,
+    }
+
+    impl TestPlugin {
+        fn new(stop_after: usize) -> Self {
+            TestPlugin {
+                meta: MetaData {
+                    general_meta: "test plugin",
+                    audio_port_meta: InOut {
+                        inputs: vec!["in"],
+                        outputs: vec!["out left", "out right"],
+                    },
+                    midi_port_meta: InOut {
+                        inputs: vec!["midi in"],
+                        outputs: vec![],
+                    },
+                },
+                sample_rate: 0.0,
+                number_of_buffers: 0,
+                events: Vec::new(),
+                stop_after,
+            }
+        }
+    }
+
+    impl Meta for TestPlugin {
+        type MetaData = MetaData<&'static str, &'static str, &'static str>;
+        fn meta(&self) -> &Self::MetaData {
+            &self.meta
+        }
+    }
+
+    impl AudioHandler for TestPlugin {
+        fn set_sample_rate(&mut self, sample_rate: f64) {
+            self.sample_rate = sample_rate;
+        }
+    }
+
+    // Left: the index of the frame in the buffer, right: minus the number of the buffer.
+    impl ContextualAudioRenderer<f32, CpalHost> for TestPlugin {
+        fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut CpalHost) {
+            self.number_of_buffers += 1;
+            let (inputs, mut outputs) = buffer.separate();
+            assert!(inputs.channels()[0].iter().all(|sample| *sample == 0.0));
+            for (index, sample) in outputs.index_channel(0).iter_mut().enumerate() {
+                *sample = index as f32;
+            }
+            for sample in outputs.index_channel(1).iter_mut() {
+                *sample = -(self.number_of_buffers as f32);
+            }
+            if self.number_of_buffers == self.stop_after {
+                context.stop();
+            }
+        }
+    }
+
+    impl ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, CpalHost> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, _context: &mut CpalHost) {
+            self.events.push(event);
+        }
+    }
+
+    #[test]
+    fn process_deinterleaves_in_chunks_and_fills_surplus_channels_with_silence() {
+        let mut process_handler =
+            CpalProcessHandler::new(TestPlugin::new(0), NoMidiSource, 44100, 3, 4);
+        assert_eq!(process_handler.plugin.sample_rate, 44100.0);
+        let mut data = vec![1.0; 3 * 6];
+        process_handler.process(&mut data);
+        assert_eq!(process_handler.plugin.number_of_buffers, 2);
+        assert_eq!(
+            data,
+            vec![
+                0.0, -1.0, 0.0, 1.0, -1.0, 0.0, 2.0, -1.0, 0.0, 3.0, -1.0, 0.0, //
+                0.0, -2.0, 0.0, 1.0, -2.0, 0.0,
+            ]
+        );
+    }
+
+    #[test]
+    fn process_passes_events_from_the_midi_source() {
+        let (mut sender, receiver) = midi_queue(4);
+        let mut process_handler =
+            CpalProcessHandler::new(TestPlugin::new(0), receiver, 44100, 2, 4);
+        let event = RawMidiEvent::new(&[0x90, 60, 100]);
+        sender.try_send(Indexed { index: 0, event }).unwrap();
+        let mut data = vec![0.0; 2 * 4];
+        process_handler.process(&mut data);
+        assert_eq!(
+            process_handler.plugin.events,
+            vec![Indexed {
+                index: 0,
+                event: Timed {
+                    time_in_frames: 0,
+                    event
+                }
+            }]
+        );
+        process_handler.process(&mut data);
+        assert_eq!(process_handler.plugin.events.len(), 1);
+    }
+
+    #[test]
+    fn midi_queue_is_bounded_and_keeps_the_order_of_the_events() {
+        let (mut sender, mut receiver) = midi_queue(2);
+        let event = |index| Indexed {
+            index,
+            event: RawMidiEvent::new(&[0x90, 60, 100]),
+        };
+        for round in 0..3 {
+            assert_eq!(receiver.try_next(), None);
+            assert!(sender.try_send(event(round)).is_ok());
+            assert!(sender.try_send(event(round + 1)).is_ok());
+            assert_eq!(sender.try_send(event(round + 2)), Err(event(round + 2)));
+            assert_eq!(receiver.try_next(), Some(event(round)));
+            assert_eq!(receiver.try_next(), Some(event(round + 1)));
+        }
+        assert_eq!(receiver.try_next(), None);
+    }
+
+    #[test]
+    fn process_outputs_silence_after_the_plugin_stops() {
+        let mut process_handler =
+            CpalProcessHandler::new(TestPlugin::new(1), NoMidiSource, 44100, 2, 2);
+        let mut data = vec![1.0; 2 * 4];
+        process_handler.process(&mut data);
+        assert!(process_handler.stopped.load(Ordering::SeqCst));
+        assert_eq!(process_handler.plugin.number_of_buffers, 1);
+        assert_eq!(data, vec![0.0, -1.0, 1.0, -1.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -9,22 +9,121 @@
 //!
 //! # Usage
 //! See the documentation of the [`run`] function.
+//! The [`run`] function blocks until the user presses a key or the plugin stops.
+//! If you want to keep control, e.g. in a GUI application or a daemon, use the [`start`]
+//! function instead, which returns a [`JackHandle`] that can be used to stop the plugin.
 //!
+//! # Configuration
+//! Use a [`JackConfig`] with the [`run_with_config`] or [`start_with_config`] functions to
+//! choose the client name and the server, and to connect the ports automatically.
+//!
+//! # Parameters
+//! Jack has no notion of parameters, but the application has to implement the
+//! [`ParameterHandler`] trait, so that the parameters can be changed while Jack is running
+//! (use [`NoParameters`] if the application has no parameters).
+//! The [`JackHandle`] returned by [`start`] gives access to the parameters with its
+//! [`parameters`] method. Changing a parameter with its [`set_parameter`] method delivers
+//! the change to the application as a `Timed<`[`ParameterChange`]`>` event at the start of
+//! the next buffer:
+//! ```no_run
+//! # use rsynth::ParameterHandler;
+//! # use rsynth::backend::jack_backend::{JackCallbacks, JackHandle};
+//! # fn start<P: ParameterHandler>(plugin: P, callbacks: JackCallbacks) -> Result<JackHandle<P>, ()> { unimplemented!() }
+//! # fn example<P: ParameterHandler>(plugin: P) -> Result<(), ()> {
+//! let handle = start(plugin, JackCallbacks::new())?;
+//! // E.g. when the user turns a knob in the GUI:
+//! handle.set_parameter(0, 0.5);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Latency
+//! The latency reported by the [`latency_in_frames`] method of the [`AudioHandler`] trait
+//! is added to the latency ranges of the audio ports.
+//! The latency of the ports that are connected to the plugin is available with the
+//! [`latency_range`] method of the [`JackHost`].
+//!
+//! # Midi output
+//! The [`JackHost`] implements the [`MidiOutput`] trait, so writing a midi event returns an
+//! error when it cannot be written, e.g. because there is no room for it or because it is
+//! earlier than an event that has already been written to the same port.
+//! The number of events that could not be written is counted per port and can be monitored
+//! with the [`midi_output_counts`] method of the [`JackHandle`].
+//!
+//! # Freewheel mode and transport
+//! The [`JackHost`] also tells whether Jack is in "freewheel" mode (see its
+//! [`is_freewheeling`] method) and implements the [`TransportInfo`] trait.
+//! Use [`JackCallbacks`] to be notified when the freewheel mode or the latency changes.
+//!
+//! # Saving and restoring the state
+//! If the parameters of the application implement the [`PluginState`] trait,
+//! the state can be loaded from a file before calling [`run`] and saved to a file
+//! after [`run`] returns, with the [`load_state`] and [`save_state`] functions:
+//! ```no_run
+//! # use rsynth::ParameterHandler;
+//! # use rsynth::backend::jack_backend::{load_state, save_state};
+//! # use rsynth::parameters::ParameterStore;
+//! # use rsynth::state::StateError;
+//! # fn run<P>(plugin: P) -> P { plugin }
+//! # fn example<P: ParameterHandler<Parameters = ParameterStore<&'static str>>>(plugin: P)
+//! #     -> Result<(), StateError> {
+//! load_state(&plugin, "my_synth.state")?;
+//! let plugin = run(plugin);
+//! save_state(&plugin, "my_synth.state")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ParameterHandler`]: ../../trait.ParameterHandler.html
+//! [`NoParameters`]: ../../parameters/struct.NoParameters.html
+//! [`ParameterChange`]: ../../event/struct.ParameterChange.html
+//! [`parameters`]: ./struct.JackHandle.html#method.parameters
+//! [`set_parameter`]: ./struct.JackHandle.html#method.set_parameter
+//! [`start`]: ./fn.start.html
+//! [`JackHandle`]: ./struct.JackHandle.html
+//! [`JackHost`]: ./struct.JackHost.html
+//! [`MidiOutput`]: ../trait.MidiOutput.html
+//! [`midi_output_counts`]: ./struct.JackHandle.html#method.midi_output_counts
+//! [`JackCallbacks`]: ./struct.JackCallbacks.html
+//! [`latency_range`]: ./struct.JackHost.html#method.latency_range
+//! [`is_freewheeling`]: ./struct.JackHost.html#method.is_freewheeling
+//! [`TransportInfo`]: ../trait.TransportInfo.html
+//! [`JackConfig`]: ./struct.JackConfig.html
+//! [`run_with_config`]: ./fn.run_with_config.html
+//! [`start_with_config`]: ./fn.start_with_config.html
+//! [`AudioHandler`]: ../../trait.AudioHandler.html
+//! [`latency_in_frames`]: ../../trait.AudioHandler.html#method.latency_in_frames
+//! [`PluginState`]: ../../state/trait.PluginState.html
+//! [`load_state`]: ./fn.load_state.html
+//! [`save_state`]: ./fn.save_state.html
 //! [JACK]: http://www.jackaudio.org/
 //! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
 //! [`run`]: ./fn.run.html
-use crate::backend::{HostInterface, Stop};
+use crate::backend::{
+    HostInterface, MidiOutput, MidiOutputError, Stop, TimeSignature, Transport, TransportInfo,
+};
 use crate::buffer::AudioBufferInOut;
 use crate::event::{
-    ContextualEventHandler, EventHandler, Indexed, RawMidiEvent, SysExEvent, Timed,
+    ContextualEventHandler, EventHandler, Indexed, ParameterChange, RawMidiEvent, SysExEvent, Timed,
 };
+use crate::parameters::{ChangedParameters, Parameters};
+use crate::state::{self, PluginState, StateError};
 use crate::{
     AudioHandler, CommonAudioPortMeta, CommonMidiPortMeta, CommonPluginMeta,
-    ContextualAudioRenderer,
+    ContextualAudioRenderer, ParameterHandler,
 };
 use core::cmp;
+use std::env;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::io;
+use std::mem;
+use std::path::Path;
 use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
 use vecstorage::VecStorage;
 
 /// Re-exports of the [`jack`](https://crates.io/crates/jack) crate.
@@ -33,8 +132,12 @@ pub mod jack {
     pub use jack::*;
 }
 
+use self::jack::{
+    AsyncClient, Client, ClientOptions, ClientStatus, Control, Frames, LatencyType,
+    NotificationHandler, PortFlags, PortSpec, ProcessHandler, Unowned,
+};
 use self::jack::{AudioIn, AudioOut, MidiIn, MidiOut, Port, ProcessScope, RawMidi};
-use self::jack::{Client, ClientOptions, Control, ProcessHandler};
+use self::jack::{TransportBBT, TransportState, TransportStatePosition};
 
 /// Used to communicate with `Jack`.
 ///
@@ -45,8 +148,10 @@ use self::jack::{Client, ClientOptions, Control, ProcessHandler};
 /// [`run`]: ./fn.run.html
 pub struct JackHost<'c, 'mp, 'mw> {
     client: &'c Client,
-    midi_out_ports: &'mp mut [jack::MidiWriter<'mw>],
+    midi_out_ports: &'mp mut [MidiOutPort<'mw>],
     control: jack::Control,
+    transport: Transport,
+    shared_state: &'c SharedState,
 }
 
 impl<'c, 'mp, 'mw> JackHost<'c, 'mp, 'mw> {
@@ -56,6 +161,26 @@ impl<'c, 'mp, 'mw> JackHost<'c, 'mp, 'mw> {
     pub fn client(&self) -> &'c Client {
         self.client
     }
+
+    /// Return `true` when Jack is in "freewheel" mode, i.e. when Jack is processing as fast as
+    /// possible instead of in real-time, e.g. when exporting.
+    /// Plugins can use this to switch to higher quality algorithms.
+    pub fn is_freewheeling(&self) -> bool {
+        self.shared_state.is_freewheeling.load(Ordering::SeqCst)
+    }
+
+    /// Get the latency range (minimum and maximum, in frames) as it was computed during the last
+    /// latency callback of Jack:
+    /// * for [`LatencyType::Capture`]: the latency between the moment that the signal was
+    ///   captured and the moment that it arrives at the audio inputs of the plugin
+    /// * for [`LatencyType::Playback`]: the latency between the moment that the signal leaves the
+    ///   audio outputs of the plugin and the moment that it is played back.
+    ///
+    /// [`LatencyType::Capture`]: ./jack/enum.LatencyType.html#variant.Capture
+    /// [`LatencyType::Playback`]: ./jack/enum.LatencyType.html#variant.Playback
+    pub fn latency_range(&self, mode: LatencyType) -> (Frames, Frames) {
+        self.shared_state.latency_range(mode)
+    }
 }
 
 impl<'c, 'mp, 'mw> HostInterface for JackHost<'c, 'mp, 'mw> {
@@ -70,41 +195,143 @@ impl<'c, 'mp, 'mw> HostInterface for JackHost<'c, 'mp, 'mw> {
 
 impl<'c, 'mp, 'mw> Stop for JackHost<'c, 'mp, 'mw> {}
 
+/// The transport is queried once at the start of each buffer.
+impl<'c, 'mp, 'mw> TransportInfo for JackHost<'c, 'mp, 'mw> {
+    fn transport(&self) -> Transport {
+        self.transport
+    }
+}
+
+/// The parts of the Jack [`Client`] that are used by the process handler and the notification
+/// handler, so that they can be tested without a Jack server.
+///
+/// [`Client`]: ./jack/struct.Client.html
+trait JackClient {
+    fn query_transport(&self) -> Result<TransportStatePosition, jack::Error>;
+
+    fn port_latency_range(&self, port_name: &str, mode: LatencyType) -> Option<(Frames, Frames)>;
+
+    fn set_port_latency_range(&self, port_name: &str, mode: LatencyType, range: (Frames, Frames));
+}
+
+impl JackClient for Client {
+    fn query_transport(&self) -> Result<TransportStatePosition, jack::Error> {
+        self.transport().query()
+    }
+
+    fn port_latency_range(&self, port_name: &str, mode: LatencyType) -> Option<(Frames, Frames)> {
+        self.port_by_name(port_name)
+            .map(|port| port.get_latency_range(mode))
+    }
+
+    fn set_port_latency_range(&self, port_name: &str, mode: LatencyType, range: (Frames, Frames)) {
+        if let Some(port) = self.port_by_name(port_name) {
+            port.set_latency_range(mode, range);
+        }
+    }
+}
+
+/// Query the Jack transport and convert it to a [`Transport`].
+fn query_transport<C: JackClient>(client: &C) -> Transport {
+    let TransportStatePosition { pos, state } = match client.query_transport() {
+        Ok(state_position) => state_position,
+        Err(e) => {
+            error!("Failed to query the Jack transport: {}", e);
+            return Transport::default();
+        }
+    };
+    let mut transport = Transport {
+        is_playing: state == TransportState::Rolling,
+        position_in_frames: Some(pos.frame() as u64),
+        ..Transport::default()
+    };
+    if let Some(bbt) = pos.bbt() {
+        let TransportBBT {
+            bar,
+            beat,
+            tick,
+            sig_num,
+            sig_denom,
+            ticks_per_beat,
+            bpm,
+            ..
+        } = bbt;
+        // The `jack` crate lets a denominator that is not a number or smaller than one through,
+        // so check the time signature as an integer.
+        let numerator = sig_num as u32;
+        let denominator = sig_denom as u32;
+        if numerator > 0 && denominator > 0 && ticks_per_beat > 0.0 {
+            // Jack counts beats in units of the denominator of the time signature.
+            let quarter_notes_per_beat = 4.0 / sig_denom as f64;
+            let beats_before_bar = bar.saturating_sub(1) as f64 * sig_num as f64;
+            let beats_in_bar = beat.saturating_sub(1) as f64 + tick as f64 / ticks_per_beat;
+            transport.tempo_in_beats_per_minute = Some(bpm);
+            transport.time_signature = Some(TimeSignature::new(numerator, denominator));
+            transport.bar_start_in_quarter_notes = Some(beats_before_bar * quarter_notes_per_beat);
+            transport.position_in_quarter_notes =
+                Some((beats_before_bar + beats_in_bar) * quarter_notes_per_beat);
+        }
+    }
+    transport
+}
+
+impl<'c, 'mp, 'mw> JackHost<'c, 'mp, 'mw> {
+    fn write_raw_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        bytes: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        let number_of_ports = self.midi_out_ports.len();
+        let midi_out_port =
+            self.midi_out_ports
+                .get_mut(port_index)
+                .ok_or(MidiOutputError::PortOutOfBounds {
+                    port_index,
+                    number_of_ports,
+                })?;
+        midi_out_port.write(time_in_frames, bytes)
+    }
+}
+
+impl<'c, 'mp, 'mw> MidiOutput for JackHost<'c, 'mp, 'mw> {
+    fn number_of_midi_output_ports(&self) -> usize {
+        self.midi_out_ports.len()
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        self.write_raw_midi(port_index, time_in_frames, event.bytes())
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        self.write_raw_midi(port_index, time_in_frames, data)
+    }
+}
+
+// These are called in the process callback, so failures are not logged here: they are counted
+// per port instead (see `JackHandle::midi_output_counts`).
+// Use the `MidiOutput` trait to handle the errors yourself.
 impl<'c, 'mp, 'mw> EventHandler<Indexed<Timed<RawMidiEvent>>> for JackHost<'c, 'mp, 'mw> {
     fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>) {
         let Indexed { index, event } = event;
-        if let Some(ref mut midi_out_port) = self.midi_out_ports.get_mut(index).as_mut() {
-            let raw_midi = RawMidi {
-                time: event.time_in_frames,
-                bytes: event.event.bytes(),
-            };
-            midi_out_port.write(&raw_midi); // TODO: error handling.
-        } else {
-            error!(
-                "midi port out of bounds: port index is {}, but only {} ports are available",
-                index,
-                self.midi_out_ports.len()
-            );
-        }
+        let _ = self.write_midi(index, event.time_in_frames, event.event);
     }
 }
 
 impl<'c, 'mp, 'mw, 'e> EventHandler<Indexed<Timed<SysExEvent<'e>>>> for JackHost<'c, 'mp, 'mw> {
     fn handle_event(&mut self, event: Indexed<Timed<SysExEvent>>) {
         let Indexed { index, event } = event;
-        if let Some(ref mut midi_out_port) = self.midi_out_ports.get_mut(index).as_mut() {
-            let raw_midi = RawMidi {
-                time: event.time_in_frames,
-                bytes: event.event.data(),
-            };
-            midi_out_port.write(&raw_midi); // TODO: error handling.
-        } else {
-            error!(
-                "midi port out of bounds: port index is {}, but only {} ports are available",
-                index,
-                self.midi_out_ports.len()
-            );
-        }
+        let _ = self.write_sysex(index, event.time_in_frames, event.event.data());
     }
 }
 
@@ -229,61 +456,655 @@ where
     out_ports
 }
 
+/// State that is shared between the [`JackHandle`], the notification handler and the
+/// process handler.
+struct SharedState {
+    stopped: AtomicBool,
+    sample_rate: AtomicU32,
+    is_freewheeling: AtomicBool,
+    capture_latency: [AtomicU32; 2],
+    playback_latency: [AtomicU32; 2],
+    port_names: Mutex<PortNames>,
+    port_exchange: Mutex<PortExchange>,
+}
+
+impl SharedState {
+    fn new(sample_rate: Frames, port_names: PortNames) -> Self {
+        Self {
+            stopped: AtomicBool::new(false),
+            sample_rate: AtomicU32::new(sample_rate),
+            is_freewheeling: AtomicBool::new(false),
+            capture_latency: [AtomicU32::new(0), AtomicU32::new(0)],
+            playback_latency: [AtomicU32::new(0), AtomicU32::new(0)],
+            port_names: Mutex::new(port_names),
+            port_exchange: Mutex::new(PortExchange::default()),
+        }
+    }
+
+    fn latency(&self, mode: LatencyType) -> &[AtomicU32; 2] {
+        match mode {
+            LatencyType::Capture => &self.capture_latency,
+            LatencyType::Playback => &self.playback_latency,
+        }
+    }
+
+    fn latency_range(&self, mode: LatencyType) -> (Frames, Frames) {
+        let [min, max] = self.latency(mode);
+        (min.load(Ordering::SeqCst), max.load(Ordering::SeqCst))
+    }
+
+    fn set_latency_range(&self, mode: LatencyType, (min, max): (Frames, Frames)) {
+        let latency = self.latency(mode);
+        latency[0].store(min, Ordering::SeqCst);
+        latency[1].store(max, Ordering::SeqCst);
+    }
+
+    fn port_names(&self) -> MutexGuard<'_, PortNames> {
+        self.port_names
+            .lock()
+            .expect("no thread to panic while holding the lock")
+    }
+
+    fn port_exchange(&self) -> MutexGuard<'_, PortExchange> {
+        self.port_exchange
+            .lock()
+            .expect("no thread to panic while holding the lock")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PortKind {
+    AudioIn,
+    AudioOut,
+    MidiIn,
+    MidiOut,
+}
+
+/// The full names of the ports of the plugin.
+///
+/// When the name of a port cannot be retrieved, an empty string is used instead, so that the
+/// index of a name is always the index of the port.
+#[derive(Default)]
+struct PortNames {
+    audio_in: Vec<String>,
+    audio_out: Vec<String>,
+    midi_in: Vec<String>,
+    midi_out: Vec<String>,
+}
+
+impl PortNames {
+    fn new(ports: &Ports) -> Self {
+        fn names<S>(ports: &[Port<S>]) -> Vec<String> {
+            ports
+                .iter()
+                .map(|port| {
+                    port.name().unwrap_or_else(|e| {
+                        error!("Failed to get the name of a port: {:?}.", e);
+                        String::new()
+                    })
+                })
+                .collect()
+        }
+        Self {
+            audio_in: names(&ports.audio_in),
+            audio_out: names(&ports.audio_out),
+            midi_in: names(&ports.midi_in),
+            midi_out: names(&ports.midi_out),
+        }
+    }
+
+    fn get_mut(&mut self, kind: PortKind) -> &mut Vec<String> {
+        match kind {
+            PortKind::AudioIn => &mut self.audio_in,
+            PortKind::AudioOut => &mut self.audio_out,
+            PortKind::MidiIn => &mut self.midi_in,
+            PortKind::MidiOut => &mut self.midi_out,
+        }
+    }
+}
+
+/// The ports, together with the storage that is needed to use them in the process callback.
+struct Ports {
+    audio_in: Vec<Port<AudioIn>>,
+    audio_out: Vec<Port<AudioOut>>,
+    midi_in: Vec<Port<MidiIn>>,
+    midi_out: Vec<Port<MidiOut>>,
+    midi_out_counters: Vec<Arc<MidiOutputCounters>>,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static [f32]>,
+    midi_writer: VecStorage<MidiOutPortWrapper>,
+}
+
+impl Ports {
+    fn with_capacity(audio_in: usize, audio_out: usize, midi_in: usize, midi_out: usize) -> Self {
+        Self {
+            audio_in: Vec::with_capacity(audio_in),
+            audio_out: Vec::with_capacity(audio_out),
+            midi_in: Vec::with_capacity(midi_in),
+            midi_out: Vec::with_capacity(midi_out),
+            midi_out_counters: Vec::with_capacity(midi_out),
+            inputs: VecStorage::with_capacity(audio_in),
+            outputs: VecStorage::with_capacity(audio_out),
+            midi_writer: VecStorage::with_capacity(midi_out),
+        }
+    }
+
+    /// Create empty `Ports` with enough capacity for the given ports, with one extra port of the
+    /// given kind.
+    fn with_capacity_for(port_names: &PortNames, extra: Option<PortKind>) -> Self {
+        let capacity = |names: &[String], kind| names.len() + (extra == Some(kind)) as usize;
+        Self::with_capacity(
+            capacity(&port_names.audio_in, PortKind::AudioIn),
+            capacity(&port_names.audio_out, PortKind::AudioOut),
+            capacity(&port_names.midi_in, PortKind::MidiIn),
+            capacity(&port_names.midi_out, PortKind::MidiOut),
+        )
+    }
+
+    /// Move the ports to `update.ports`, apply the change and swap `self` with `update.ports`.
+    ///
+    /// This is called in the process callback; it does not allocate because `update.ports` has
+    /// enough capacity.
+    fn apply(&mut self, update: &mut PortUpdate) {
+        let new = &mut update.ports;
+        new.audio_in.append(&mut self.audio_in);
+        new.audio_out.append(&mut self.audio_out);
+        new.midi_in.append(&mut self.midi_in);
+        new.midi_out.append(&mut self.midi_out);
+        new.midi_out_counters.append(&mut self.midi_out_counters);
+        fn remove<S>(ports: &mut Vec<Port<S>>, index: usize) -> PortChange {
+            if index < ports.len() {
+                PortChange::Removed(ports.remove(index).clone_unowned())
+            } else {
+                PortChange::Applied
+            }
+        }
+        update.change = match mem::replace(&mut update.change, PortChange::Applied) {
+            PortChange::AddAudioIn(port) => {
+                new.audio_in.push(port);
+                PortChange::Applied
+            }
+            PortChange::AddAudioOut(port) => {
+                new.audio_out.push(port);
+                PortChange::Applied
+            }
+            PortChange::AddMidiIn(port) => {
+                new.midi_in.push(port);
+                PortChange::Applied
+            }
+            PortChange::AddMidiOut(port, counters) => {
+                new.midi_out.push(port);
+                new.midi_out_counters.push(counters);
+                PortChange::Applied
+            }
+            PortChange::Remove(PortKind::AudioIn, index) => remove(&mut new.audio_in, index),
+            PortChange::Remove(PortKind::AudioOut, index) => remove(&mut new.audio_out, index),
+            PortChange::Remove(PortKind::MidiIn, index) => remove(&mut new.midi_in, index),
+            PortChange::Remove(PortKind::MidiOut, index) => {
+                if index < new.midi_out_counters.len() {
+                    // This does not de-allocate: the `JackHandle` still has a reference.
+                    new.midi_out_counters.remove(index);
+                }
+                remove(&mut new.midi_out, index)
+            }
+            other => other,
+        };
+        mem::swap(self, new);
+    }
+}
+
+enum PortChange {
+    AddAudioIn(Port<AudioIn>),
+    AddAudioOut(Port<AudioOut>),
+    AddMidiIn(Port<MidiIn>),
+    AddMidiOut(Port<MidiOut>, Arc<MidiOutputCounters>),
+    Remove(PortKind, usize),
+    /// The change has been applied and this port has been removed; it can now be unregistered.
+    Removed(Port<Unowned>),
+    /// The change has been applied.
+    Applied,
+}
+
+/// A change to the set of ports, together with pre-allocated storage for the new set of ports.
+struct PortUpdate {
+    change: PortChange,
+    ports: Ports,
+}
+
+/// Used to hand over a `PortUpdate` to the process callback and to hand it back after it has
+/// been applied, so that the old storage is de-allocated outside of the process callback.
+#[derive(Default)]
+struct PortExchange {
+    pending: Option<Box<PortUpdate>>,
+    applied: Option<Box<PortUpdate>>,
+}
+
+/// An error that can occur when registering or unregistering ports with the [`JackHandle`].
+///
+/// [`JackHandle`]: ./struct.JackHandle.html
+#[derive(Debug)]
+pub enum PortError {
+    /// An error from Jack.
+    Jack(jack::Error),
+    /// There is no port with the given index.
+    IndexOutOfBounds {
+        /// The index of the port.
+        index: usize,
+        /// The number of ports of this kind.
+        number_of_ports: usize,
+    },
+    /// Processing has stopped, so the change could not be applied.
+    Stopped,
+}
+
+impl Display for PortError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PortError::Jack(e) => write!(f, "Jack error: {}", e),
+            PortError::IndexOutOfBounds {
+                index,
+                number_of_ports,
+            } => write!(
+                f,
+                "port index out of bounds: index is {}, but there are only {} ports",
+                index, number_of_ports
+            ),
+            PortError::Stopped => write!(f, "processing has stopped"),
+        }
+    }
+}
+
+impl Error for PortError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PortError::Jack(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<jack::Error> for PortError {
+    fn from(e: jack::Error) -> Self {
+        PortError::Jack(e)
+    }
+}
+
+/// Callbacks for notifications from the Jack server.
+///
+/// The callbacks are called from a thread that is managed by Jack, but not from the
+/// real-time thread.
+///
+/// # Example
+/// ```no_run
+/// use rsynth::backend::jack_backend::JackCallbacks;
+/// let callbacks = JackCallbacks::new()
+///     .on_xrun(|| eprintln!("xrun"))
+///     .on_shutdown(|reason| eprintln!("Jack has shut down: {}", reason));
+/// ```
+#[derive(Default)]
+pub struct JackCallbacks {
+    sample_rate_changed: Option<Box<dyn FnMut(u32) + Send + Sync>>,
+    buffer_size_changed: Option<Box<dyn FnMut(u32) + Send + Sync>>,
+    xrun: Option<Box<dyn FnMut() + Send + Sync>>,
+    shutdown: Option<ShutdownCallback>,
+    freewheel: Option<Box<dyn FnMut(bool) + Send + Sync>>,
+    latency_changed: Option<LatencyCallback>,
+}
+
+type ShutdownCallback = Box<dyn FnMut(&str) + Send + Sync>;
+type LatencyCallback = Box<dyn FnMut(LatencyType, (Frames, Frames)) + Send + Sync>;
+
+impl JackCallbacks {
+    /// Create a new `JackCallbacks` without any callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call the given function with the new sample rate when the sample rate changes.
+    ///
+    /// _Note_: you don't need this to update the sample rate of the plugin:
+    /// [`set_sample_rate`] is called before the next buffer is rendered.
+    ///
+    /// [`set_sample_rate`]: ../../trait.AudioHandler.html#tymethod.set_sample_rate
+    pub fn on_sample_rate_change<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(u32) + Send + Sync + 'static,
+    {
+        self.sample_rate_changed = Some(Box::new(callback));
+        self
+    }
+
+    /// Call the given function with the new buffer size (in frames) when the buffer size changes.
+    pub fn on_buffer_size_change<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(u32) + Send + Sync + 'static,
+    {
+        self.buffer_size_changed = Some(Box::new(callback));
+        self
+    }
+
+    /// Call the given function when an xrun (a buffer under- or overrun) occurs.
+    pub fn on_xrun<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut() + Send + Sync + 'static,
+    {
+        self.xrun = Some(Box::new(callback));
+        self
+    }
+
+    /// Call the given function with the reason when the Jack server shuts down the client.
+    ///
+    /// _Note_: this function should only use async-safe functions, see the documentation of
+    /// [`NotificationHandler::shutdown`].
+    ///
+    /// [`NotificationHandler::shutdown`]: ./jack/trait.NotificationHandler.html#method.shutdown
+    pub fn on_shutdown<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str) + Send + Sync + 'static,
+    {
+        self.shutdown = Some(Box::new(callback));
+        self
+    }
+
+    /// Call the given function when Jack enters (`true`) or leaves (`false`) "freewheel" mode.
+    ///
+    /// See also the [`is_freewheeling`] method of [`JackHost`].
+    ///
+    /// [`is_freewheeling`]: ./struct.JackHost.html#method.is_freewheeling
+    /// [`JackHost`]: ./struct.JackHost.html
+    pub fn on_freewheel<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(bool) + Send + Sync + 'static,
+    {
+        self.freewheel = Some(Box::new(callback));
+        self
+    }
+
+    /// Call the given function when Jack has recomputed the latencies.
+    /// The function is called with the same arguments as what would be returned by the
+    /// [`latency_range`] method of [`JackHost`].
+    ///
+    /// [`latency_range`]: ./struct.JackHost.html#method.latency_range
+    /// [`JackHost`]: ./struct.JackHost.html
+    pub fn on_latency_change<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(LatencyType, (Frames, Frames)) + Send + Sync + 'static,
+    {
+        self.latency_changed = Some(Box::new(callback));
+        self
+    }
+}
+
+/// Handles the notifications from Jack: calls the [`JackCallbacks`] and reports the latency of
+/// the plugin to Jack by adding it to the latency ranges of the audio ports.
+struct JackNotificationHandler {
+    latency_in_frames: u32,
+    callbacks: JackCallbacks,
+    shared_state: Arc<SharedState>,
+}
+
+impl JackNotificationHandler {
+    fn new<P>(
+        process_handler: &JackProcessHandler<P>,
+        latency_in_frames: u32,
+        callbacks: JackCallbacks,
+    ) -> Self {
+        Self {
+            latency_in_frames,
+            callbacks,
+            shared_state: process_handler.shared_state.clone(),
+        }
+    }
+}
+
+impl NotificationHandler for JackNotificationHandler {
+    fn shutdown(&mut self, _status: ClientStatus, reason: &str) {
+        self.shared_state.stopped.store(true, Ordering::SeqCst);
+        if let Some(ref mut callback) = self.callbacks.shutdown {
+            callback(reason);
+        }
+    }
+
+    fn buffer_size(&mut self, _client: &Client, size: Frames) -> Control {
+        if let Some(ref mut callback) = self.callbacks.buffer_size_changed {
+            callback(size);
+        }
+        Control::Continue
+    }
+
+    fn sample_rate(&mut self, _client: &Client, sample_rate: Frames) -> Control {
+        self.shared_state
+            .sample_rate
+            .store(sample_rate, Ordering::SeqCst);
+        if let Some(ref mut callback) = self.callbacks.sample_rate_changed {
+            callback(sample_rate);
+        }
+        Control::Continue
+    }
+
+    fn xrun(&mut self, _client: &Client) -> Control {
+        if let Some(ref mut callback) = self.callbacks.xrun {
+            callback();
+        }
+        Control::Continue
+    }
+
+    fn freewheel(&mut self, _client: &Client, is_freewheel_enabled: bool) {
+        self.set_freewheel(is_freewheel_enabled);
+    }
+
+    fn latency(&mut self, client: &Client, mode: LatencyType) {
+        self.update_latency(client, mode);
+    }
+}
+
+impl JackNotificationHandler {
+    fn set_freewheel(&mut self, is_freewheel_enabled: bool) {
+        self.shared_state
+            .is_freewheeling
+            .store(is_freewheel_enabled, Ordering::SeqCst);
+        if let Some(ref mut callback) = self.callbacks.freewheel {
+            callback(is_freewheel_enabled);
+        }
+    }
+
+    fn update_latency<C: JackClient>(&mut self, client: &C, mode: LatencyType) {
+        // For the capture latency, the latency flows from the inputs to the outputs,
+        // for the playback latency, it flows from the outputs to the inputs.
+        let port_names = self.shared_state.port_names();
+        let (from, to) = match mode {
+            LatencyType::Capture => (&port_names.audio_in, &port_names.audio_out),
+            LatencyType::Playback => (&port_names.audio_out, &port_names.audio_in),
+        };
+        let (mut min, mut max) = (None, 0);
+        for name in from {
+            if let Some((port_min, port_max)) = client.port_latency_range(name, mode) {
+                min = Some(min.map_or(port_min, |m: Frames| m.min(port_min)));
+                max = max.max(port_max);
+            }
+        }
+        let upstream_range = (min.unwrap_or(0), max);
+        let range = (
+            upstream_range.0 + self.latency_in_frames,
+            upstream_range.1 + self.latency_in_frames,
+        );
+        for name in to {
+            client.set_port_latency_range(name, mode, range);
+        }
+        drop(port_names);
+        self.shared_state.set_latency_range(mode, upstream_range);
+        if let Some(ref mut callback) = self.callbacks.latency_changed {
+            callback(mode, upstream_range);
+        }
+    }
+}
+
+/// The number of midi events that could not be written to a midi output port.
+///
+/// This is returned by the [`midi_output_counts`] method of the [`JackHandle`].
+/// The counts are the totals since the port has been registered.
+///
+/// [`midi_output_counts`]: ./struct.JackHandle.html#method.midi_output_counts
+/// [`JackHandle`]: ./struct.JackHandle.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MidiOutputCounts {
+    /// The number of events that were dropped because there was no room for them.
+    pub dropped: u64,
+    /// The number of events that were dropped because they were written out of order, i.e.
+    /// their time was before the time of an event that was written earlier during the same buffer.
+    pub out_of_order: u64,
+}
+
+/// The counters behind `MidiOutputCounts`, shared between the `JackHandle` and the process
+/// callback. Only atomic operations are used, so that this is real-time safe.
+#[derive(Default)]
+struct MidiOutputCounters {
+    dropped: AtomicU64,
+    out_of_order: AtomicU64,
+}
+
+impl MidiOutputCounters {
+    fn counts(&self) -> MidiOutputCounts {
+        MidiOutputCounts {
+            dropped: self.dropped.load(Ordering::Relaxed),
+            out_of_order: self.out_of_order.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Write an event at the given time with the `write` function, unless it is earlier than
+    /// `last_time`, and count the events that could not be written.
+    fn write<F>(
+        &self,
+        last_time: &mut Option<u32>,
+        time_in_frames: u32,
+        write: F,
+    ) -> Result<(), MidiOutputError>
+    where
+        F: FnOnce() -> Result<(), jack::Error>,
+    {
+        if let Some(last_time) = *last_time {
+            if time_in_frames < last_time {
+                self.out_of_order.fetch_add(1, Ordering::Relaxed);
+                return Err(MidiOutputError::OutOfOrder);
+            }
+        }
+        match write() {
+            Ok(()) => {
+                *last_time = Some(time_in_frames);
+                Ok(())
+            }
+            Err(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(MidiOutputError::BufferFull)
+            }
+        }
+    }
+}
+
+/// A midi output port during one call of the process callback.
+struct MidiOutPort<'a> {
+    writer: jack::MidiWriter<'a>,
+    counters: &'a MidiOutputCounters,
+    last_time: Option<u32>,
+}
+
+impl<'a> MidiOutPort<'a> {
+    fn write(&mut self, time_in_frames: u32, bytes: &[u8]) -> Result<(), MidiOutputError> {
+        let writer = &mut self.writer;
+        self.counters
+            .write(&mut self.last_time, time_in_frames, || {
+                writer.write(&RawMidi {
+                    time: time_in_frames,
+                    bytes,
+                })
+            })
+    }
+}
+
 // `MidiWriter` does not implement `Send`, but we do want `JackProcessHandler` to implement `Send`.
-// `JackProcessHandler` contains only `VecStorage` of `MidiWriter`s, not a real `MidiWriter`.
+// `JackProcessHandler` contains only `VecStorage` of `MidiOutPort`s, not a real `MidiOutPort`.
 // So we solve this by creating a data type that is guaranteed to have the same alignment and
-// size as a `MidiWriter`.
-struct MidiWriterWrapper {
-    _inner: jack::MidiWriter<'static>,
+// size as a `MidiOutPort`.
+struct MidiOutPortWrapper {
+    _inner: MidiOutPort<'static>,
 }
 
-unsafe impl Send for MidiWriterWrapper {}
-unsafe impl Sync for MidiWriterWrapper {}
+unsafe impl Send for MidiOutPortWrapper {}
+unsafe impl Sync for MidiOutPortWrapper {}
 
 struct JackProcessHandler<P> {
-    audio_in_ports: Vec<Port<AudioIn>>,
-    audio_out_ports: Vec<Port<AudioOut>>,
-    midi_in_ports: Vec<Port<MidiIn>>,
-    midi_out_ports: Vec<Port<MidiOut>>,
+    ports: Ports,
     plugin: P,
-    inputs: VecStorage<&'static [f32]>,
-    outputs: VecStorage<&'static [f32]>,
-    midi_writer: VecStorage<MidiWriterWrapper>,
+    sample_rate: Frames,
+    shared_state: Arc<SharedState>,
+    changed_parameters: Arc<ChangedParameters>,
 }
 
 impl<P> JackProcessHandler<P>
 where
-    P: CommonAudioPortMeta + CommonMidiPortMeta + CommonPluginMeta + Send,
+    P: CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + CommonPluginMeta
+        + AudioHandler
+        + ParameterHandler
+        + Send,
     for<'c, 'mp, 'mw> P: ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>
-        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>,
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>
+        + ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>,
     for<'c, 'mp, 'mw, 'a> P:
         ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, JackHost<'c, 'mp, 'mw>>,
 {
-    fn new(client: &Client, plugin: P) -> Self {
+    fn new(client: &Client, mut plugin: P) -> Self {
         trace!("JackProcessHandler::new()");
-        let audio_in_ports = audio_in_ports::<P>(&client, &plugin);
-        let audio_out_ports = audio_out_ports::<P>(&client, &plugin);
-
-        let midi_in_ports = midi_in_ports::<P>(&client, &plugin);
-        let midi_out_ports = midi_out_ports::<P>(&client, &plugin);
-
-        let inputs = VecStorage::with_capacity(plugin.max_number_of_audio_inputs());
-        let outputs = VecStorage::with_capacity(plugin.max_number_of_audio_outputs());
+        let midi_out = midi_out_ports::<P>(&client, &plugin);
+        let ports = Ports {
+            audio_in: audio_in_ports::<P>(&client, &plugin),
+            audio_out: audio_out_ports::<P>(&client, &plugin),
+            midi_in: midi_in_ports::<P>(&client, &plugin),
+            midi_out_counters: midi_out.iter().map(|_| Arc::default()).collect(),
+            midi_out,
+            inputs: VecStorage::with_capacity(plugin.max_number_of_audio_inputs()),
+            outputs: VecStorage::with_capacity(plugin.max_number_of_audio_outputs()),
+            midi_writer: VecStorage::with_capacity(plugin.max_number_of_midi_outputs()),
+        };
 
-        let midi_writer = VecStorage::with_capacity(plugin.max_number_of_midi_outputs());
+        let sample_rate = client.sample_rate() as Frames;
+        plugin.set_sample_rate(sample_rate as f64);
 
+        let port_names = PortNames::new(&ports);
+        let changed_parameters = Arc::new(ChangedParameters::new(
+            plugin.parameters().number_of_parameters(),
+        ));
         JackProcessHandler {
-            audio_in_ports,
-            audio_out_ports,
-            midi_in_ports,
-            midi_out_ports,
+            ports,
             plugin,
-            inputs,
-            outputs,
-            midi_writer,
+            sample_rate,
+            shared_state: Arc::new(SharedState::new(sample_rate, port_names)),
+            changed_parameters,
         }
     }
 
+    /// Deliver the parameter changes that have been made with the [`JackHandle`] since the
+    /// previous call to the plugin.
+    /// Jack has no notion of parameters, so the parameter changes are delivered with
+    /// a timestamp of `0`.
+    ///
+    /// [`JackHandle`]: ./struct.JackHandle.html
+    fn handle_parameter_changes<'c, 'mp, 'mw>(
+        plugin: &mut P,
+        changed_parameters: &ChangedParameters,
+        jack_host: &mut JackHost<'c, 'mp, 'mw>,
+    ) {
+        let parameters = plugin.parameters();
+        changed_parameters.drain(|index| {
+            let event = Timed {
+                time_in_frames: 0,
+                event: ParameterChange::new(index, parameters.normalized_value(index)),
+            };
+            plugin.handle_event(event, jack_host);
+        });
+    }
+
     fn handle_events<'c, 'mp, 'mw>(
         midi_in_ports: &[Port<MidiIn>],
         plugin: &mut P,
@@ -329,36 +1150,64 @@ where
 
 impl<P> ProcessHandler for JackProcessHandler<P>
 where
-    P: CommonAudioPortMeta + CommonMidiPortMeta + CommonPluginMeta + Send,
+    P: CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + CommonPluginMeta
+        + AudioHandler
+        + ParameterHandler
+        + Send,
     for<'c, 'mp, 'mw> P: ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>
-        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>,
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>
+        + ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>,
     for<'c, 'mp, 'mw, 'a> P:
         ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, JackHost<'c, 'mp, 'mw>>,
 {
     fn process(&mut self, client: &Client, process_scope: &ProcessScope) -> Control {
-        let mut midi_writer_guard = self.midi_writer.vec_guard();
-        for midi_output in self.midi_out_ports.iter_mut() {
-            midi_writer_guard.push(midi_output.writer(process_scope));
+        let sample_rate = self.shared_state.sample_rate.load(Ordering::SeqCst);
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.plugin.set_sample_rate(sample_rate as f64);
+        }
+
+        // Use `try_lock` in order not to block the process callback.
+        if let Ok(mut port_exchange) = self.shared_state.port_exchange.try_lock() {
+            if let Some(mut update) = port_exchange.pending.take() {
+                self.ports.apply(&mut update);
+                port_exchange.applied = Some(update);
+            }
+        }
+
+        let ports = &mut self.ports;
+        let mut midi_writer_guard = ports.midi_writer.vec_guard();
+        for (midi_output, counters) in ports.midi_out.iter_mut().zip(&ports.midi_out_counters) {
+            midi_writer_guard.push(MidiOutPort {
+                writer: midi_output.writer(process_scope),
+                counters,
+                last_time: None,
+            });
         }
         let mut jack_host: JackHost = JackHost {
             client,
             midi_out_ports: midi_writer_guard.as_mut_slice(),
             control: jack::Control::Continue,
+            transport: query_transport(client),
+            shared_state: &self.shared_state,
         };
+        Self::handle_parameter_changes(&mut self.plugin, &self.changed_parameters, &mut jack_host);
         Self::handle_events(
-            &self.midi_in_ports,
+            &ports.midi_in,
             &mut self.plugin,
             process_scope,
             &mut jack_host,
         );
 
-        let mut inputs = self.inputs.vec_guard();
-        for port in self.audio_in_ports.iter().take(inputs.capacity()) {
+        let mut inputs = ports.inputs.vec_guard();
+        for port in ports.audio_in.iter().take(inputs.capacity()) {
             inputs.push(port.as_slice(process_scope));
         }
 
-        let mut outputs = self.outputs.vec_guard();
-        for port in self.audio_out_ports.iter_mut().take(outputs.capacity()) {
+        let mut outputs = ports.outputs.vec_guard();
+        for port in ports.audio_out.iter_mut().take(outputs.capacity()) {
             outputs.push(port.as_mut_slice(process_scope));
         }
 
@@ -368,41 +1217,922 @@ where
             client.buffer_size() as usize,
         );
         self.plugin.render_buffer(&mut buffer, &mut jack_host);
+        if jack_host.control == Control::Quit {
+            self.shared_state.stopped.store(true, Ordering::SeqCst);
+        }
         jack_host.control
     }
 }
 
+/// The environment variable that Jack uses to choose the server.
+const JACK_DEFAULT_SERVER: &str = "JACK_DEFAULT_SERVER";
+
+/// The interval with which [`JackHandle::join`] checks if processing has stopped.
+///
+/// [`JackHandle::join`]: ./struct.JackHandle.html#method.join
+const STOP_POLLING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A handle to a plugin that is running with Jack, as returned by the [`start`] function.
+///
+/// The plugin is running as long as the handle is alive.
+/// Call [`stop`] to stop processing immediately or [`join`] to wait until processing stops.
+/// Both return the plugin.
+/// When the handle is dropped, the client is deactivated and the plugin is dropped.
+///
+/// Ports can be registered and unregistered while the plugin is running, e.g. with
+/// [`register_audio_input`] and [`unregister_audio_input`].
+/// The new set of ports is handed over to the process callback without allocating memory in the
+/// process callback; the plugin sees the new ports from the next buffer on.
+/// These methods block until the change has been picked up by the process callback.
+///
+/// The parameters of the plugin can be changed while the plugin is running with
+/// [`set_parameter`].
+///
+/// [`start`]: ./fn.start.html
+/// [`stop`]: #method.stop
+/// [`join`]: #method.join
+/// [`register_audio_input`]: #method.register_audio_input
+/// [`unregister_audio_input`]: #method.unregister_audio_input
+/// [`set_parameter`]: #method.set_parameter
+pub struct JackHandle<P>
+where
+    P: ParameterHandler,
+{
+    active_client: AsyncClient<JackNotificationHandler, JackProcessHandler<P>>,
+    shared_state: Arc<SharedState>,
+    midi_out_counters: Vec<Arc<MidiOutputCounters>>,
+    parameters: Arc<P::Parameters>,
+    changed_parameters: Arc<ChangedParameters>,
+}
+
+impl<P> JackHandle<P>
+where
+    P: ParameterHandler,
+{
+    /// Get access to the underlying [`Client`] so that you can use Jack-specific features.
+    ///
+    /// [`Client`]: ./jack/struct.Client.html
+    pub fn client(&self) -> &Client {
+        self.active_client.as_client()
+    }
+
+    /// Return `true` when processing has stopped, either because the plugin called
+    /// [`stop`] on the [`JackHost`], or because the Jack server has shut down the client.
+    ///
+    /// [`stop`]: ../trait.HostInterface.html#method.stop
+    /// [`JackHost`]: ./struct.JackHost.html
+    pub fn is_stopped(&self) -> bool {
+        self.shared_state.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Stop processing, deactivate the client and return the plugin.
+    pub fn stop(self) -> Result<P, jack::Error> {
+        info!("Deactivating client...");
+        let (_, _, process_handler) = self.active_client.deactivate()?;
+        Ok(process_handler.plugin)
+    }
+
+    /// Block until processing has stopped (see [`is_stopped`]), then deactivate the client
+    /// and return the plugin.
+    ///
+    /// [`is_stopped`]: #method.is_stopped
+    pub fn join(self) -> Result<P, jack::Error> {
+        while !self.is_stopped() {
+            thread::sleep(STOP_POLLING_INTERVAL);
+        }
+        self.stop()
+    }
+
+    /// Get the number of midi events that could not be written to the midi output port with
+    /// the given index, or `None` if there is no such port.
+    ///
+    /// This can be used for monitoring, e.g. to warn the user when events are being dropped.
+    pub fn midi_output_counts(&self, port_index: usize) -> Option<MidiOutputCounts> {
+        self.midi_out_counters
+            .get(port_index)
+            .map(|counters| counters.counts())
+    }
+
+    /// Get a handle to the parameters of the plugin.
+    ///
+    /// Changing a parameter directly with this handle does not notify the plugin;
+    /// use [`set_parameter`] for that.
+    ///
+    /// [`set_parameter`]: #method.set_parameter
+    pub fn parameters(&self) -> Arc<P::Parameters> {
+        self.parameters.clone()
+    }
+
+    /// Set the normalized value of the parameter with the given index and deliver the change
+    /// to the plugin as a `Timed<ParameterChange>` event at the start of the next buffer.
+    ///
+    /// When a parameter is changed several times before the next buffer, the plugin
+    /// only receives the last value.
+    ///
+    /// # Panics
+    /// Panics if `index` is not smaller than the number of parameters.
+    pub fn set_parameter(&self, index: usize, normalized_value: f32) {
+        let number_of_parameters = self.parameters.number_of_parameters();
+        assert!(
+            index < number_of_parameters,
+            "parameter index out of bounds: index is {}, but there are only {} parameters",
+            index,
+            number_of_parameters
+        );
+        self.parameters
+            .set_normalized_value(index, normalized_value.clamp(0.0, 1.0));
+        self.changed_parameters.mark_changed(index);
+    }
+
+    /// Register a new audio input port with the given (short) name and return its index.
+    pub fn register_audio_input(&mut self, name: &str) -> Result<usize, PortError> {
+        self.register_port(name, AudioIn, PortKind::AudioIn, PortChange::AddAudioIn)
+    }
+
+    /// Register a new audio output port with the given (short) name and return its index.
+    pub fn register_audio_output(&mut self, name: &str) -> Result<usize, PortError> {
+        self.register_port(name, AudioOut, PortKind::AudioOut, PortChange::AddAudioOut)
+    }
+
+    /// Register a new midi input port with the given (short) name and return its index.
+    pub fn register_midi_input(&mut self, name: &str) -> Result<usize, PortError> {
+        self.register_port(name, MidiIn, PortKind::MidiIn, PortChange::AddMidiIn)
+    }
+
+    /// Register a new midi output port with the given (short) name and return its index.
+    pub fn register_midi_output(&mut self, name: &str) -> Result<usize, PortError> {
+        let counters = Arc::new(MidiOutputCounters::default());
+        let port_counters = counters.clone();
+        let index = self.register_port(name, MidiOut, PortKind::MidiOut, move |port| {
+            PortChange::AddMidiOut(port, port_counters)
+        })?;
+        self.midi_out_counters.push(counters);
+        Ok(index)
+    }
+
+    /// Unregister the audio input port with the given index.
+    /// The indices of the audio input ports after this port decrease by one.
+    pub fn unregister_audio_input(&mut self, index: usize) -> Result<(), PortError> {
+        self.unregister_port(index, PortKind::AudioIn)
+    }
+
+    /// Unregister the audio output port with the given index.
+    /// The indices of the audio output ports after this port decrease by one.
+    pub fn unregister_audio_output(&mut self, index: usize) -> Result<(), PortError> {
+        self.unregister_port(index, PortKind::AudioOut)
+    }
+
+    /// Unregister the midi input port with the given index.
+    /// The indices of the midi input ports after this port decrease by one.
+    pub fn unregister_midi_input(&mut self, index: usize) -> Result<(), PortError> {
+        self.unregister_port(index, PortKind::MidiIn)
+    }
+
+    /// Unregister the midi output port with the given index.
+    /// The indices of the midi output ports after this port decrease by one.
+    pub fn unregister_midi_output(&mut self, index: usize) -> Result<(), PortError> {
+        self.unregister_port(index, PortKind::MidiOut)
+    }
+
+    fn register_port<PS, F>(
+        &mut self,
+        name: &str,
+        spec: PS,
+        kind: PortKind,
+        change: F,
+    ) -> Result<usize, PortError>
+    where
+        PS: PortSpec,
+        F: FnOnce(Port<PS>) -> PortChange,
+    {
+        let port = self.client().register_port(name, spec)?;
+        let full_name = match port.name() {
+            Ok(full_name) => full_name,
+            Err(e) => {
+                self.client().unregister_port(port)?;
+                return Err(e.into());
+            }
+        };
+        self.update_ports(change(port), Some(kind))?;
+        let mut port_names = self.shared_state.port_names();
+        let names = port_names.get_mut(kind);
+        names.push(full_name);
+        Ok(names.len() - 1)
+    }
+
+    fn unregister_port(&mut self, index: usize, kind: PortKind) -> Result<(), PortError> {
+        let number_of_ports = self.shared_state.port_names().get_mut(kind).len();
+        if index >= number_of_ports {
+            return Err(PortError::IndexOutOfBounds {
+                index,
+                number_of_ports,
+            });
+        }
+        let removed_port = self.update_ports(PortChange::Remove(kind, index), None)?;
+        self.shared_state.port_names().get_mut(kind).remove(index);
+        if kind == PortKind::MidiOut {
+            self.midi_out_counters.remove(index);
+        }
+        if let Some(port) = removed_port {
+            self.client().unregister_port(port)?;
+        }
+        Ok(())
+    }
+
+    /// Hand over the change to the process callback and wait until it has been applied.
+    /// Return the port that has been removed, if any.
+    fn update_ports(
+        &mut self,
+        change: PortChange,
+        extra: Option<PortKind>,
+    ) -> Result<Option<Port<Unowned>>, PortError> {
+        let ports = Ports::with_capacity_for(&self.shared_state.port_names(), extra);
+        self.shared_state.port_exchange().pending = Some(Box::new(PortUpdate { change, ports }));
+        loop {
+            {
+                let mut port_exchange = self.shared_state.port_exchange();
+                if let Some(update) = port_exchange.applied.take() {
+                    return Ok(match update.change {
+                        PortChange::Removed(port) => Some(port),
+                        _ => None,
+                    });
+                }
+                if self.is_stopped() {
+                    if let Some(update) = port_exchange.pending.take() {
+                        drop(port_exchange);
+                        self.unregister_unused_port(update.change)?;
+                    }
+                    return Err(PortError::Stopped);
+                }
+            }
+            thread::sleep(STOP_POLLING_INTERVAL);
+        }
+    }
+
+    fn unregister_unused_port(&self, change: PortChange) -> Result<(), jack::Error> {
+        let client = self.client();
+        match change {
+            PortChange::AddAudioIn(port) => client.unregister_port(port),
+            PortChange::AddAudioOut(port) => client.unregister_port(port),
+            PortChange::AddMidiIn(port) => client.unregister_port(port),
+            PortChange::AddMidiOut(port, _) => client.unregister_port(port),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Configuration of the Jack client: the client name, the server and which ports to connect to.
+///
+/// The ports are connected after the client has been activated.
+/// Ports are selected with a regular expression on the full port name
+/// (e.g. `"system:playback_.*"`), as in the `jack_get_ports` function of Jack.
+/// When a pattern matches several ports, the ports of the plugin are connected to the
+/// matching ports in order: the first port of the plugin to the first matching port, the second
+/// port of the plugin to the second matching port etc.
+///
+/// # Example
+/// ```no_run
+/// use rsynth::backend::jack_backend::JackConfig;
+/// let config = JackConfig::new()
+///     .client_name("my_synth")
+///     .start_server(true)
+///     .connect_audio_outputs_to("system:playback_.*")
+///     .connect_midi_inputs_to(".*Keystation.*");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct JackConfig {
+    client_name: Option<String>,
+    server_name: Option<String>,
+    start_server: bool,
+    audio_inputs_from: Option<String>,
+    audio_outputs_to: Option<String>,
+    midi_inputs_from: Option<String>,
+    midi_outputs_to: Option<String>,
+}
+
+impl JackConfig {
+    /// Create a new `JackConfig` with the default configuration:
+    /// the name of the plugin is used as client name, the default server is used,
+    /// the server is not started if it is not running and no ports are connected.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given client name instead of the name of the plugin.
+    pub fn client_name<S: Into<String>>(mut self, client_name: S) -> Self {
+        self.client_name = Some(client_name.into());
+        self
+    }
+
+    /// Connect to the Jack server with the given name instead of to the default server.
+    ///
+    /// _Note_: this is done by setting the `JACK_DEFAULT_SERVER` environment variable
+    /// while the client is created; the previous value is restored afterwards.
+    /// Avoid reading or changing the environment from other threads while the client is
+    /// being created.
+    pub fn server_name<S: Into<String>>(mut self, server_name: S) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Start the Jack server if it is not running yet (`false` by default).
+    pub fn start_server(mut self, start_server: bool) -> Self {
+        self.start_server = start_server;
+        self
+    }
+
+    /// Connect the ports matching the given pattern to the audio inputs of the plugin,
+    /// e.g. `"system:capture_.*"`.
+    pub fn connect_audio_inputs_to<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.audio_inputs_from = Some(pattern.into());
+        self
+    }
+
+    /// Connect the audio outputs of the plugin to the ports matching the given pattern,
+    /// e.g. `"system:playback_.*"`.
+    pub fn connect_audio_outputs_to<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.audio_outputs_to = Some(pattern.into());
+        self
+    }
+
+    /// Connect the ports matching the given pattern to the midi inputs of the plugin.
+    pub fn connect_midi_inputs_to<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.midi_inputs_from = Some(pattern.into());
+        self
+    }
+
+    /// Connect the midi outputs of the plugin to the ports matching the given pattern.
+    pub fn connect_midi_outputs_to<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.midi_outputs_to = Some(pattern.into());
+        self
+    }
+
+    fn client_options(&self) -> ClientOptions {
+        if self.start_server {
+            ClientOptions::empty()
+        } else {
+            ClientOptions::NO_START_SERVER
+        }
+    }
+}
+
+/// Connect the ports of the plugin to the ports of other clients, as configured in `config`.
+fn connect_ports(client: &Client, config: &JackConfig, port_names: &PortNames) {
+    let audio_spec = AudioIn;
+    let audio_type = audio_spec.jack_port_type();
+    let midi_spec = MidiIn;
+    let midi_type = midi_spec.jack_port_type();
+    let connect = |pattern: &Option<String>, port_type, ours: &[String], is_output: bool| {
+        let pattern = match pattern {
+            Some(pattern) => pattern,
+            None => return,
+        };
+        // Our outputs are connected to inputs of other clients and vice versa.
+        let flags = if is_output {
+            PortFlags::IS_INPUT
+        } else {
+            PortFlags::IS_OUTPUT
+        };
+        let theirs = client.ports(Some(pattern), Some(port_type), flags);
+        if theirs.is_empty() {
+            warn!("No ports match {:?}, not connecting.", pattern);
+        }
+        for (our_port, their_port) in ours.iter().zip(theirs.iter()) {
+            let (source, destination) = if is_output {
+                (our_port, their_port)
+            } else {
+                (their_port, our_port)
+            };
+            info!("Connecting {} to {}", source, destination);
+            if let Err(e) = client.connect_ports_by_name(source, destination) {
+                error!("Failed to connect {} to {}: {:?}.", source, destination, e);
+            }
+        }
+    };
+    connect(
+        &config.audio_inputs_from,
+        audio_type,
+        &port_names.audio_in,
+        false,
+    );
+    connect(
+        &config.audio_outputs_to,
+        audio_type,
+        &port_names.audio_out,
+        true,
+    );
+    connect(
+        &config.midi_inputs_from,
+        midi_type,
+        &port_names.midi_in,
+        false,
+    );
+    connect(
+        &config.midi_outputs_to,
+        midi_type,
+        &port_names.midi_out,
+        true,
+    );
+}
+
+/// Start running the plugin with Jack without blocking, using the default [`JackConfig`].
+///
+/// The plugin keeps running until [`stop`] is called on the returned [`JackHandle`].
+/// Use [`join`] to wait until the plugin calls [`stop`] on the [`JackHost`] or until the Jack
+/// server shuts down the client.
+///
+/// # Example
+/// ```no_run
+/// # use rsynth::ParameterHandler;
+/// # use rsynth::backend::jack_backend::{JackCallbacks, JackHandle};
+/// # fn start<P: ParameterHandler>(plugin: P, callbacks: JackCallbacks) -> Result<JackHandle<P>, ()> { unimplemented!() }
+/// # fn example<P: ParameterHandler>(plugin: P) -> Result<(), ()> {
+/// let handle = start(plugin, JackCallbacks::new().on_xrun(|| eprintln!("xrun")))?;
+/// // Do something else, e.g. run a GUI.
+/// let plugin = handle.stop();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`JackConfig`]: ./struct.JackConfig.html
+/// [`stop`]: ./struct.JackHandle.html#method.stop
+/// [`join`]: ./struct.JackHandle.html#method.join
+/// [`JackHandle`]: ./struct.JackHandle.html
+/// [`JackHost`]: ./struct.JackHost.html
+pub fn start<P>(plugin: P, callbacks: JackCallbacks) -> Result<JackHandle<P>, jack::Error>
+where
+    P: CommonPluginMeta
+        + AudioHandler
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + ParameterHandler
+        + Send
+        + Sync
+        + 'static,
+    for<'c, 'mp, 'mw> P: ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P:
+        ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P: ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw, 'a> P:
+        ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, JackHost<'c, 'mp, 'mw>>,
+{
+    start_with_config(plugin, JackConfig::default(), callbacks)
+}
+
+/// Start running the plugin with Jack without blocking, using the given configuration.
+///
+/// See the [`start`] function for more information.
+///
+/// [`start`]: ./fn.start.html
+pub fn start_with_config<P>(
+    plugin: P,
+    config: JackConfig,
+    callbacks: JackCallbacks,
+) -> Result<JackHandle<P>, jack::Error>
+where
+    P: CommonPluginMeta
+        + AudioHandler
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + ParameterHandler
+        + Send
+        + Sync
+        + 'static,
+    for<'c, 'mp, 'mw> P: ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P:
+        ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P: ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw, 'a> P:
+        ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, JackHost<'c, 'mp, 'mw>>,
+{
+    let client_name = match config.client_name {
+        Some(ref client_name) => client_name.clone(),
+        None => plugin.name().to_string(),
+    };
+    let (client, _status) = match config.server_name {
+        Some(ref server_name) => {
+            let previous_server_name = env::var_os(JACK_DEFAULT_SERVER);
+            env::set_var(JACK_DEFAULT_SERVER, server_name);
+            let result = Client::new(&client_name, config.client_options());
+            match previous_server_name {
+                Some(previous_server_name) => {
+                    env::set_var(JACK_DEFAULT_SERVER, previous_server_name)
+                }
+                None => env::remove_var(JACK_DEFAULT_SERVER),
+            }
+            result
+        }
+        None => Client::new(&client_name, config.client_options()),
+    }?;
+
+    let latency_in_frames = plugin.latency_in_frames();
+    let parameters = plugin.parameters();
+    let jack_process_handler = JackProcessHandler::new(&client, plugin);
+    let shared_state = jack_process_handler.shared_state.clone();
+    let changed_parameters = jack_process_handler.changed_parameters.clone();
+    let midi_out_counters = jack_process_handler.ports.midi_out_counters.clone();
+    let jack_notification_handler =
+        JackNotificationHandler::new(&jack_process_handler, latency_in_frames, callbacks);
+    let active_client = client.activate_async(jack_notification_handler, jack_process_handler)?;
+
+    connect_ports(
+        active_client.as_client(),
+        &config,
+        &shared_state.port_names(),
+    );
+
+    Ok(JackHandle {
+        active_client,
+        shared_state,
+        midi_out_counters,
+        parameters,
+        changed_parameters,
+    })
+}
+
 /// Run the plugin until the user presses a key on the computer keyboard or the plugin
 /// requests the `JackHost` to stop.
-pub fn run<P>(mut plugin: P) -> Result<P, jack::Error>
+///
+/// This is a thin wrapper around the [`start`] function.
+///
+/// [`start`]: ./fn.start.html
+pub fn run<P>(plugin: P) -> Result<P, jack::Error>
+where
+    P: CommonPluginMeta
+        + AudioHandler
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + ParameterHandler
+        + Send
+        + Sync
+        + 'static,
+    for<'c, 'mp, 'mw> P: ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P:
+        ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P: ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw, 'a> P:
+        ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, JackHost<'c, 'mp, 'mw>>,
+{
+    run_with_config(plugin, JackConfig::default())
+}
+
+/// Same as the [`run`] function, but with the given configuration.
+///
+/// # Example
+/// ```no_run
+/// # use rsynth::backend::jack_backend::JackConfig;
+/// # fn run_with_config<P>(plugin: P, config: JackConfig) -> Result<P, ()> { Ok(plugin) }
+/// # fn example<P>(plugin: P) -> Result<(), ()> {
+/// let config = JackConfig::new().connect_audio_outputs_to("system:playback_.*");
+/// let plugin = run_with_config(plugin, config)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`run`]: ./fn.run.html
+pub fn run_with_config<P>(plugin: P, config: JackConfig) -> Result<P, jack::Error>
 where
     P: CommonPluginMeta
         + AudioHandler
         + CommonAudioPortMeta
         + CommonMidiPortMeta
+        + ParameterHandler
         + Send
         + Sync
         + 'static,
     for<'c, 'mp, 'mw> P: ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>,
     for<'c, 'mp, 'mw> P:
         ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>,
+    for<'c, 'mp, 'mw> P: ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>,
     for<'c, 'mp, 'mw, 'a> P:
         ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, JackHost<'c, 'mp, 'mw>>,
 {
-    let (client, _status) = Client::new(plugin.name(), ClientOptions::NO_START_SERVER)?;
+    let handle = start_with_config(plugin, config, JackCallbacks::new())?;
+
+    // Wait for a key press on a separate thread, so that we also return when the plugin stops.
+    let (key_sender, key_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        println!("Press any key to quit");
+        let mut user_input = String::new();
+        io::stdin().read_line(&mut user_input).ok();
+        key_sender.send(()).ok();
+    });
+    while !handle.is_stopped() {
+        if key_receiver.recv_timeout(STOP_POLLING_INTERVAL).is_ok() {
+            break;
+        }
+    }
 
-    let sample_rate = client.sample_rate();
-    plugin.set_sample_rate(sample_rate as f64);
+    handle.stop()
+}
 
-    let jack_process_handler = JackProcessHandler::new(&client, plugin);
-    let active_client = client.activate_async((), jack_process_handler)?;
+/// Save the state of the parameters of the application to the file with the given path.
+/// If the file already exists, it is overwritten.
+pub fn save_state<P, F>(plugin: &P, path: F) -> Result<(), StateError>
+where
+    P: ParameterHandler,
+    P::Parameters: PluginState,
+    F: AsRef<Path>,
+{
+    state::save_to_file(&*plugin.parameters(), path)
+}
+
+/// Load the state of the parameters of the application from the file with the given path.
+/// The file should have been created with the [`save_state`] function.
+///
+/// [`save_state`]: ./fn.save_state.html
+pub fn load_state<P, F>(plugin: &P, path: F) -> Result<(), StateError>
+where
+    P: ParameterHandler,
+    P::Parameters: PluginState,
+    F: AsRef<Path>,
+{
+    state::load_from_file(&*plugin.parameters(), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::jack::{TransportPosition, TransportState, TransportStatePosition};
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    struct MockClient {
+        // `TransportPosition` does not implement `Clone`, so it can be queried only once.
+        transport: RefCell<Option<(TransportState, TransportPosition)>>,
+        latency_ranges: RefCell<HashMap<(String, bool), (Frames, Frames)>>,
+    }
+
+    fn is_capture(mode: LatencyType) -> bool {
+        match mode {
+            LatencyType::Capture => true,
+            LatencyType::Playback => false,
+        }
+    }
+
+    impl MockClient {
+        fn new() -> Self {
+            Self {
+                transport: RefCell::new(None),
+                latency_ranges: RefCell::new(HashMap::new()),
+            }
+        }
+
+        fn with_latency_range(
+            self,
+            port_name: &str,
+            mode: LatencyType,
+            range: (Frames, Frames),
+        ) -> Self {
+            self.set_port_latency_range(port_name, mode, range);
+            self
+        }
 
-    println!("Press any key to quit");
-    let mut user_input = String::new();
-    io::stdin().read_line(&mut user_input).ok();
+        fn latency_range(&self, port_name: &str, mode: LatencyType) -> Option<(Frames, Frames)> {
+            self.port_latency_range(port_name, mode)
+        }
+    }
+
+    impl JackClient for MockClient {
+        fn query_transport(&self) -> Result<TransportStatePosition, jack::Error> {
+            match self.transport.borrow_mut().take() {
+                Some((state, pos)) => Ok(TransportStatePosition { pos, state }),
+                None => Err(jack::Error::ClientIsNoLongerAlive),
+            }
+        }
+
+        fn port_latency_range(
+            &self,
+            port_name: &str,
+            mode: LatencyType,
+        ) -> Option<(Frames, Frames)> {
+            self.latency_ranges
+                .borrow()
+                .get(&(port_name.to_string(), is_capture(mode)))
+                .cloned()
+        }
+
+        fn set_port_latency_range(
+            &self,
+            port_name: &str,
+            mode: LatencyType,
+            range: (Frames, Frames),
+        ) {
+            self.latency_ranges
+                .borrow_mut()
+                .insert((port_name.to_string(), is_capture(mode)), range);
+        }
+    }
+
+    fn notification_handler(
+        latency_in_frames: u32,
+        callbacks: JackCallbacks,
+    ) -> JackNotificationHandler {
+        let port_names = PortNames {
+            audio_in: vec!["in_1".to_string(), "in_2".to_string()],
+            audio_out: vec!["out_1".to_string()],
+            midi_in: Vec::new(),
+            midi_out: Vec::new(),
+        };
+        JackNotificationHandler {
+            latency_in_frames,
+            callbacks,
+            shared_state: Arc::new(SharedState::new(44100, port_names)),
+        }
+    }
+
+    #[test]
+    fn query_transport_converts_bar_beat_tick_to_quarter_notes() {
+        let mut pos = TransportPosition::default();
+        pos.set_frame(48000);
+        let bbt = *TransportBBT::default()
+            .with_bbt(3, 2, 240)
+            .with_timesig(6.0, 8.0)
+            .with_ticks_per_beat(480.0)
+            .with_bpm(90.0);
+        pos.set_bbt(Some(bbt)).expect("BBT should be valid");
+        let client = MockClient {
+            transport: RefCell::new(Some((TransportState::Rolling, pos))),
+            ..MockClient::new()
+        };
+
+        let transport = query_transport(&client);
+
+        assert!(transport.is_playing);
+        assert_eq!(transport.position_in_frames, Some(48000));
+        assert_eq!(transport.tempo_in_beats_per_minute, Some(90.0));
+        assert_eq!(transport.time_signature, Some(TimeSignature::new(6, 8)));
+        // Two bars of six eighth notes: 6 quarter notes.
+        assert_eq!(transport.bar_start_in_quarter_notes, Some(6.0));
+        // One and a half eighth notes later.
+        assert_eq!(transport.position_in_quarter_notes, Some(6.75));
+    }
+
+    #[test]
+    fn query_transport_without_bbt_only_has_frames() {
+        let mut pos = TransportPosition::default();
+        pos.set_frame(1234);
+        let client = MockClient {
+            transport: RefCell::new(Some((TransportState::Stopped, pos))),
+            ..MockClient::new()
+        };
+
+        let transport = query_transport(&client);
+
+        assert_eq!(
+            transport,
+            Transport {
+                is_playing: false,
+                position_in_frames: Some(1234),
+                ..Transport::default()
+            }
+        );
+    }
+
+    #[test]
+    fn query_transport_ignores_bbt_with_an_invalid_time_signature() {
+        let mut pos = TransportPosition::default();
+        pos.set_frame(1234);
+        let bbt = *TransportBBT::default()
+            .with_bbt(1, 1, 0)
+            .with_timesig(4.0, f32::NAN)
+            .with_ticks_per_beat(480.0)
+            .with_bpm(90.0);
+        pos.set_bbt(Some(bbt)).expect("BBT should be valid");
+        let client = MockClient {
+            transport: RefCell::new(Some((TransportState::Rolling, pos))),
+            ..MockClient::new()
+        };
+
+        let transport = query_transport(&client);
+
+        assert_eq!(
+            transport,
+            Transport {
+                is_playing: true,
+                position_in_frames: Some(1234),
+                ..Transport::default()
+            }
+        );
+    }
+
+    #[test]
+    fn query_transport_returns_default_on_error() {
+        assert_eq!(query_transport(&MockClient::new()), Transport::default());
+    }
+
+    #[test]
+    fn capture_latency_is_propagated_from_inputs_to_outputs() {
+        let reported = Arc::new(Mutex::new(None));
+        let reported_clone = reported.clone();
+        let callbacks = JackCallbacks::new().on_latency_change(move |mode, range| {
+            *reported_clone.lock().unwrap() = Some((is_capture(mode), range));
+        });
+        let mut handler = notification_handler(10, callbacks);
+        let client = MockClient::new()
+            .with_latency_range("in_1", LatencyType::Capture, (5, 20))
+            .with_latency_range("in_2", LatencyType::Capture, (3, 15));
+
+        handler.update_latency(&client, LatencyType::Capture);
+
+        assert_eq!(
+            client.latency_range("out_1", LatencyType::Capture),
+            Some((13, 30))
+        );
+        assert_eq!(
+            handler.shared_state.latency_range(LatencyType::Capture),
+            (3, 20)
+        );
+        assert_eq!(
+            handler.shared_state.latency_range(LatencyType::Playback),
+            (0, 0)
+        );
+        assert_eq!(*reported.lock().unwrap(), Some((true, (3, 20))));
+    }
+
+    #[test]
+    fn playback_latency_is_propagated_from_outputs_to_inputs() {
+        let mut handler = notification_handler(10, JackCallbacks::new());
+        let client = MockClient::new().with_latency_range("out_1", LatencyType::Playback, (7, 8));
 
-    info!("Deactivating client...");
+        handler.update_latency(&client, LatencyType::Playback);
 
-    let (_, _, plugin) = active_client.deactivate()?;
-    return Ok(plugin.plugin);
+        for name in &["in_1", "in_2"] {
+            assert_eq!(
+                client.latency_range(name, LatencyType::Playback),
+                Some((17, 18))
+            );
+        }
+        assert_eq!(
+            handler.shared_state.latency_range(LatencyType::Playback),
+            (7, 8)
+        );
+    }
+
+    #[test]
+    fn midi_output_counters_count_dropped_and_out_of_order_events() {
+        let counters = MidiOutputCounters::default();
+        let mut last_time = None;
+        let mut written = Vec::new();
+
+        assert_eq!(
+            counters.write(&mut last_time, 5, || {
+                written.push(5);
+                Ok(())
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            counters.write(&mut last_time, 5, || {
+                written.push(5);
+                Ok(())
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            counters.write(&mut last_time, 3, || {
+                written.push(3);
+                Ok(())
+            }),
+            Err(MidiOutputError::OutOfOrder)
+        );
+        assert_eq!(
+            counters.write(&mut last_time, 7, || Err(jack::Error::NotEnoughSpace)),
+            Err(MidiOutputError::BufferFull)
+        );
+        // A dropped event does not change the time of the last event.
+        assert_eq!(
+            counters.write(&mut last_time, 6, || {
+                written.push(6);
+                Ok(())
+            }),
+            Ok(())
+        );
+
+        assert_eq!(written, vec![5, 5, 6]);
+        assert_eq!(
+            counters.counts(),
+            MidiOutputCounts {
+                dropped: 1,
+                out_of_order: 1
+            }
+        );
+    }
+
+    #[test]
+    fn freewheel_mode_is_stored_and_reported() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let callbacks = JackCallbacks::new()
+            .on_freewheel(move |enabled| reported_clone.lock().unwrap().push(enabled));
+        let mut handler = notification_handler(0, callbacks);
+        assert!(!handler.shared_state.is_freewheeling.load(Ordering::SeqCst));
+
+        handler.set_freewheel(true);
+        assert!(handler.shared_state.is_freewheeling.load(Ordering::SeqCst));
+
+        handler.set_freewheel(false);
+        assert!(!handler.shared_state.is_freewheeling.load(Ordering::SeqCst));
+        assert_eq!(*reported.lock().unwrap(), vec![true, false]);
+    }
 }
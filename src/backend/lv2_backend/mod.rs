@@ -0,0 +1,1032 @@
+//! Wrapper for the [LV2] plugin backend (behind the `backend-lv2` feature).
+//!
+//! Support is only enabled if you compile with the "backend-lv2" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//!
+//! For an example, see `lv2_synth.rs` in the `examples` folder.
+//! `examples/example_synth` contains the code that is shared for all backends and
+//! `examples/lv2_synth.rs` contains the LV2-specific code.
+//!
+//! # Usage
+//! See the documentation of the [`lv2_init`] macro.
+//!
+//! # Creating the bundle
+//! LV2 plugins are installed as a "bundle": a directory that contains the plugin library and
+//! Turtle files that describe the plugin to the host.
+//! The functions in the [`ttl`] module generate these Turtle files from the meta-data of the
+//! plugin; [`ttl::create_bundle`] creates the complete bundle.
+//! See `examples/lv2_bundle.rs` for an example.
+//!
+//! # Ports
+//! The LV2 ports are numbered in the following order:
+//! * the audio inputs (as defined by the [`CommonAudioPortMeta`] trait),
+//! * the audio outputs,
+//! * the midi inputs (as defined by the [`CommonMidiPortMeta`] trait), as atom ports that
+//!   support midi events,
+//! * the midi outputs, also as atom ports that support midi events,
+//! * one control output port that reports the latency of the plugin.
+//!
+//! # Events
+//! Events are delivered to the plugin before the audio of the buffer is rendered, with the
+//! timestamp that was given by the host.
+//! The index of the midi input port is used as the index of the [`Indexed`] event.
+//!
+//! # Limitations
+//! Parameters, saving and restoring the state, transport information and GUI's are not
+//! supported (yet).
+//!
+//! [LV2]: https://lv2plug.in/
+//! [`lv2_init`]: ../../macro.lv2_init.html
+//! [`ttl`]: ./ttl/index.html
+//! [`ttl::create_bundle`]: ./ttl/fn.create_bundle.html
+//! [`CommonAudioPortMeta`]: ../../trait.CommonAudioPortMeta.html
+//! [`CommonMidiPortMeta`]: ../../trait.CommonMidiPortMeta.html
+//! [`Indexed`]: ../../event/struct.Indexed.html
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+use crate::backend::{HostInterface, MidiOutput, MidiOutputError};
+use crate::buffer::AudioBufferInOut;
+use crate::event::{
+    ContextualEventHandler, EventHandler, Indexed, RawMidiEvent, SysExEvent, Timed,
+};
+use crate::{
+    AudioHandler, AudioHandlerMeta, CommonAudioPortMeta, CommonMidiPortMeta, CommonPluginMeta,
+    ContextualAudioRenderer, MidiHandlerMeta,
+};
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::OnceLock;
+use vecstorage::VecStorage;
+
+pub mod sys;
+pub mod ttl;
+
+use self::sys::{
+    lv2_atom_pad_size, LV2_ATOM__Sequence, LV2_Atom, LV2_Atom_Event, LV2_Atom_Sequence,
+    LV2_Atom_Sequence_Body, LV2_Descriptor, LV2_Feature, LV2_Handle, LV2_MIDI__MidiEvent,
+    LV2_URID_Map, LV2_URID__map, LV2_URID,
+};
+
+/// Define some LV2-specific meta-data for an LV2 plugin.
+pub trait Lv2PluginMeta: CommonPluginMeta {
+    /// The URI that uniquely identifies the plugin, e.g. `"https://example.com/plugins/my-synth"`.
+    fn uri(&self) -> &str;
+
+    /// The LV2 plugin class, in addition to `lv2:Plugin`, as the local name in the
+    /// `http://lv2plug.in/ns/lv2core#` namespace, e.g. `"InstrumentPlugin"`.
+    ///
+    /// The default implementation returns `None`.
+    fn plugin_class(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// The kind of an LV2 port, together with the index of the port among the ports of this kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PortKind {
+    AudioIn(usize),
+    AudioOut(usize),
+    MidiIn(usize),
+    MidiOut(usize),
+    Latency,
+}
+
+/// How the ports of the plugin are mapped to LV2 port indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PortLayout {
+    audio_inputs: usize,
+    audio_outputs: usize,
+    midi_inputs: usize,
+    midi_outputs: usize,
+}
+
+impl PortLayout {
+    fn new<P: AudioHandlerMeta + MidiHandlerMeta>(plugin: &P) -> Self {
+        Self {
+            audio_inputs: plugin.max_number_of_audio_inputs(),
+            audio_outputs: plugin.max_number_of_audio_outputs(),
+            midi_inputs: plugin.max_number_of_midi_inputs(),
+            midi_outputs: plugin.max_number_of_midi_outputs(),
+        }
+    }
+
+    fn number_of_ports(&self) -> usize {
+        self.audio_inputs + self.audio_outputs + self.midi_inputs + self.midi_outputs + 1
+    }
+
+    fn audio_out_offset(&self) -> usize {
+        self.audio_inputs
+    }
+
+    fn midi_in_offset(&self) -> usize {
+        self.audio_out_offset() + self.audio_outputs
+    }
+
+    fn midi_out_offset(&self) -> usize {
+        self.midi_in_offset() + self.midi_inputs
+    }
+
+    fn latency_port(&self) -> usize {
+        self.midi_out_offset() + self.midi_outputs
+    }
+
+    fn port_kind(&self, port: usize) -> Option<PortKind> {
+        if port < self.audio_out_offset() {
+            Some(PortKind::AudioIn(port))
+        } else if port < self.midi_in_offset() {
+            Some(PortKind::AudioOut(port - self.audio_out_offset()))
+        } else if port < self.midi_out_offset() {
+            Some(PortKind::MidiIn(port - self.midi_in_offset()))
+        } else if port < self.latency_port() {
+            Some(PortKind::MidiOut(port - self.midi_out_offset()))
+        } else if port == self.latency_port() {
+            Some(PortKind::Latency)
+        } else {
+            None
+        }
+    }
+}
+
+/// The URIDs that are used by the backend.
+#[derive(Clone, Copy, Debug)]
+struct Urids {
+    atom_sequence: LV2_URID,
+    midi_event: LV2_URID,
+}
+
+impl Urids {
+    unsafe fn new(map: &LV2_URID_Map) -> Option<Self> {
+        let map_function = map.map?;
+        let map_uri = |uri: &[u8]| map_function(map.handle, uri.as_ptr() as *const c_char);
+        Some(Self {
+            atom_sequence: map_uri(LV2_ATOM__Sequence),
+            midi_event: map_uri(LV2_MIDI__MidiEvent),
+        })
+    }
+}
+
+/// A midi output port, as connected by the host for the current buffer.
+struct MidiOutSequence {
+    sequence: *mut LV2_Atom_Sequence,
+    capacity: u32,
+    last_time: Option<u32>,
+}
+
+/// Used to communicate with the LV2 host.
+///
+/// This is the "context" that is passed to the plugin when rendering audio and handling events.
+/// It can be used to send midi events and SysEx events to the midi output ports of the plugin,
+/// see the [`MidiOutput`] trait.
+/// LV2 requires the events of one port to be in chronological order,
+/// so writing an event that is earlier than a previously written event returns an error.
+///
+/// [`MidiOutput`]: ../trait.MidiOutput.html
+pub struct Lv2Host {
+    urids: Urids,
+    midi_outputs: Vec<MidiOutSequence>,
+}
+
+impl Lv2Host {
+    fn new(urids: Urids, number_of_midi_outputs: usize) -> Self {
+        Self {
+            urids,
+            midi_outputs: Vec::with_capacity(number_of_midi_outputs),
+        }
+    }
+
+    /// Prepare the midi output ports for writing.
+    /// The host sets the size of the atom to the available space.
+    unsafe fn prepare_midi_outputs(&mut self, sequences: &[*mut c_void]) {
+        self.midi_outputs.clear();
+        for sequence in sequences {
+            let sequence = *sequence as *mut LV2_Atom_Sequence;
+            let mut capacity = 0;
+            if let Some(sequence) = sequence.as_mut() {
+                capacity = sequence.atom.size;
+                sequence.atom = LV2_Atom {
+                    size: mem::size_of::<LV2_Atom_Sequence_Body>() as u32,
+                    type_: self.urids.atom_sequence,
+                };
+                sequence.body = LV2_Atom_Sequence_Body { unit: 0, pad: 0 };
+            }
+            self.midi_outputs.push(MidiOutSequence {
+                sequence,
+                capacity,
+                last_time: None,
+            });
+        }
+    }
+
+    fn write_event(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        let number_of_ports = self.midi_outputs.len();
+        let midi_event_type = self.urids.midi_event;
+        let output =
+            self.midi_outputs
+                .get_mut(port_index)
+                .ok_or(MidiOutputError::PortOutOfBounds {
+                    port_index,
+                    number_of_ports,
+                })?;
+        let sequence = match unsafe { output.sequence.as_mut() } {
+            Some(sequence) => sequence,
+            None => return Err(MidiOutputError::BufferFull),
+        };
+        if let Some(last_time) = output.last_time {
+            if time_in_frames < last_time {
+                return Err(MidiOutputError::OutOfOrder);
+            }
+        }
+        let event_size = mem::size_of::<LV2_Atom_Event>() as u32;
+        let required_size = event_size + lv2_atom_pad_size(data.len() as u32);
+        if sequence.atom.size + required_size > output.capacity {
+            return Err(MidiOutputError::BufferFull);
+        }
+        unsafe {
+            let event = (sequence as *mut LV2_Atom_Sequence as *mut u8)
+                .add(mem::size_of::<LV2_Atom>() + sequence.atom.size as usize);
+            ptr::write_unaligned(
+                event as *mut LV2_Atom_Event,
+                LV2_Atom_Event {
+                    time_frames: time_in_frames as i64,
+                    body: LV2_Atom {
+                        size: data.len() as u32,
+                        type_: midi_event_type,
+                    },
+                },
+            );
+            ptr::copy_nonoverlapping(data.as_ptr(), event.add(event_size as usize), data.len());
+        }
+        sequence.atom.size += required_size;
+        output.last_time = Some(time_in_frames);
+        Ok(())
+    }
+}
+
+impl HostInterface for Lv2Host {
+    fn output_initialized(&self) -> bool {
+        false
+    }
+}
+
+impl MidiOutput for Lv2Host {
+    fn number_of_midi_output_ports(&self) -> usize {
+        self.midi_outputs.len()
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        self.write_event(port_index, time_in_frames, event.bytes())
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        self.write_event(port_index, time_in_frames, data)
+    }
+}
+
+impl EventHandler<Indexed<Timed<RawMidiEvent>>> for Lv2Host {
+    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_midi(index, event.time_in_frames, event.event) {
+            error!("Failed to send midi event: {}", e);
+        }
+    }
+}
+
+impl<'a> EventHandler<Indexed<Timed<SysExEvent<'a>>>> for Lv2Host {
+    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_sysex(index, event.time_in_frames, event.event.data()) {
+            error!("Failed to send SysEx event: {}", e);
+        }
+    }
+}
+
+/// Used internally by the [`lv2_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`lv2_init`]: ../../macro.lv2_init.html
+pub trait Lv2Factory: 'static {
+    type Plugin: Lv2PluginMeta
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, Lv2Host>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, Lv2Host>
+        + for<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Lv2Host>
+        + Send
+        + 'static;
+
+    /// Create a new instance of the plugin.
+    fn create_plugin() -> Self::Plugin;
+
+    /// The statics for this plugin.
+    fn statics() -> &'static Lv2Statics;
+}
+
+/// The plugin descriptor, together with the URI it points to.
+struct Lv2Descriptor {
+    _uri: CString,
+    raw: LV2_Descriptor,
+}
+
+// The raw pointer only points to the (immutable) URI that is owned by the descriptor.
+unsafe impl Send for Lv2Descriptor {}
+unsafe impl Sync for Lv2Descriptor {}
+
+/// The data that the host accesses via static pointers.
+///
+/// Used internally by the [`lv2_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`lv2_init`]: ../../macro.lv2_init.html
+pub struct Lv2Statics {
+    descriptor: OnceLock<Lv2Descriptor>,
+}
+
+impl Lv2Statics {
+    /// Create new, empty, statics.
+    pub const fn new() -> Self {
+        Self {
+            descriptor: OnceLock::new(),
+        }
+    }
+}
+
+impl Default for Lv2Statics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Return the descriptor with the given index for the plugin created by the given factory.
+///
+/// Used internally by the [`lv2_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`lv2_init`]: ../../macro.lv2_init.html
+pub fn descriptor<F: Lv2Factory>(index: u32) -> *const LV2_Descriptor {
+    if index != 0 {
+        return ptr::null();
+    }
+    let descriptor = F::statics().descriptor.get_or_init(|| {
+        let plugin = F::create_plugin();
+        let uri = CString::new(plugin.uri().replace('\0', ""))
+            .expect("all NUL characters have been removed");
+        let raw = LV2_Descriptor {
+            URI: uri.as_ptr(),
+            instantiate: Some(instantiate::<F>),
+            connect_port: Some(connect_port::<F>),
+            activate: Some(activate),
+            run: Some(run::<F>),
+            deactivate: Some(deactivate),
+            cleanup: Some(cleanup::<F>),
+            extension_data: Some(extension_data),
+        };
+        Lv2Descriptor { _uri: uri, raw }
+    });
+    &descriptor.raw
+}
+
+/// A plugin instance, as seen by the host.
+struct Lv2Instance<P> {
+    plugin: P,
+    host: Lv2Host,
+    layout: PortLayout,
+    ports: Vec<*mut c_void>,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static [f32]>,
+}
+
+impl<P> Lv2Instance<P>
+where
+    P: CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, Lv2Host>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, Lv2Host>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Lv2Host>,
+{
+    fn new(plugin: P, urids: Urids) -> Self {
+        let layout = PortLayout::new(&plugin);
+        Self {
+            host: Lv2Host::new(urids, layout.midi_outputs),
+            ports: vec![ptr::null_mut(); layout.number_of_ports()],
+            inputs: VecStorage::with_capacity(layout.audio_inputs),
+            outputs: VecStorage::with_capacity(layout.audio_outputs),
+            layout,
+            plugin,
+        }
+    }
+
+    unsafe fn handle_events(&mut self) {
+        let midi_in_ports =
+            &self.ports[self.layout.midi_in_offset()..self.layout.midi_out_offset()];
+        for (index, sequence) in midi_in_ports.iter().enumerate() {
+            let sequence = *sequence as *const LV2_Atom_Sequence;
+            if sequence.is_null() {
+                continue;
+            }
+            let sequence_size = (*sequence).atom.size as usize;
+            let body = (sequence as *const u8).add(mem::size_of::<LV2_Atom>());
+            let event_size = mem::size_of::<LV2_Atom_Event>();
+            let mut offset = mem::size_of::<LV2_Atom_Sequence_Body>();
+            while offset + event_size <= sequence_size {
+                let event = ptr::read_unaligned(body.add(offset) as *const LV2_Atom_Event);
+                let data =
+                    slice::from_raw_parts(body.add(offset + event_size), event.body.size as usize);
+                offset += event_size + lv2_atom_pad_size(event.body.size) as usize;
+                if event.body.type_ != self.host.urids.midi_event {
+                    continue;
+                }
+                let time_in_frames = event.time_frames.max(0) as u32;
+                if data.len() <= 3 {
+                    if let Some(raw_event) = RawMidiEvent::try_new(data) {
+                        let event = Indexed {
+                            index,
+                            event: Timed {
+                                time_in_frames,
+                                event: raw_event,
+                            },
+                        };
+                        self.plugin.handle_event(event, &mut self.host);
+                    } else {
+                        warn!(
+                            "Strange event of length {}; ignoring this event.",
+                            data.len()
+                        );
+                    }
+                } else {
+                    let event = Indexed {
+                        index,
+                        event: Timed {
+                            time_in_frames,
+                            event: SysExEvent::new(data),
+                        },
+                    };
+                    self.plugin.handle_event(event, &mut self.host);
+                }
+            }
+        }
+    }
+
+    unsafe fn run(&mut self, number_of_frames: usize) {
+        let layout = self.layout;
+        self.host
+            .prepare_midi_outputs(&self.ports[layout.midi_out_offset()..layout.latency_port()]);
+        self.handle_events();
+
+        let mut inputs = self.inputs.vec_guard();
+        for port in &self.ports[..layout.audio_out_offset()] {
+            if port.is_null() {
+                return;
+            }
+            inputs.push(slice::from_raw_parts(*port as *const f32, number_of_frames));
+        }
+        let mut outputs = self.outputs.vec_guard();
+        for port in &self.ports[layout.audio_out_offset()..layout.midi_in_offset()] {
+            if port.is_null() {
+                return;
+            }
+            outputs.push(slice::from_raw_parts_mut(
+                *port as *mut f32,
+                number_of_frames,
+            ));
+        }
+
+        let mut audio_buffer =
+            AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
+        self.plugin.render_buffer(&mut audio_buffer, &mut self.host);
+
+        if let Some(latency) = (self.ports[layout.latency_port()] as *mut f32).as_mut() {
+            *latency = self.plugin.latency_in_frames() as f32;
+        }
+    }
+}
+
+unsafe extern "C" fn instantiate<F: Lv2Factory>(
+    _descriptor: *const LV2_Descriptor,
+    sample_rate: f64,
+    _bundle_path: *const c_char,
+    features: *const *const LV2_Feature,
+) -> LV2_Handle {
+    trace!("instantiate(sample_rate: {})", sample_rate);
+    let mut urids = None;
+    if !features.is_null() {
+        let mut feature = features;
+        while let Some(current) = (*feature).as_ref() {
+            if !current.URI.is_null()
+                && CStr::from_ptr(current.URI).to_bytes_with_nul() == LV2_URID__map
+            {
+                if let Some(map) = (current.data as *const LV2_URID_Map).as_ref() {
+                    urids = Urids::new(map);
+                }
+            }
+            feature = feature.add(1);
+        }
+    }
+    let urids = match urids {
+        Some(urids) => urids,
+        None => {
+            error!("The host does not support the required feature urid:map.");
+            return ptr::null_mut();
+        }
+    };
+    let mut plugin = F::create_plugin();
+    plugin.set_sample_rate(sample_rate);
+    Box::into_raw(Box::new(Lv2Instance::new(plugin, urids))) as LV2_Handle
+}
+
+unsafe extern "C" fn connect_port<F: Lv2Factory>(
+    instance: LV2_Handle,
+    port: u32,
+    data_location: *mut c_void,
+) {
+    let instance = &mut *(instance as *mut Lv2Instance<F::Plugin>);
+    match instance.ports.get_mut(port as usize) {
+        Some(location) => *location = data_location,
+        None => error!(
+            "The host tries to connect port {}, which does not exist.",
+            port
+        ),
+    }
+}
+
+unsafe extern "C" fn activate(_instance: LV2_Handle) {}
+
+unsafe extern "C" fn run<F: Lv2Factory>(instance: LV2_Handle, sample_count: u32) {
+    let instance = &mut *(instance as *mut Lv2Instance<F::Plugin>);
+    instance.run(sample_count as usize);
+}
+
+unsafe extern "C" fn deactivate(_instance: LV2_Handle) {}
+
+unsafe extern "C" fn cleanup<F: Lv2Factory>(instance: LV2_Handle) {
+    drop(Box::from_raw(instance as *mut Lv2Instance<F::Plugin>));
+}
+
+unsafe extern "C" fn extension_data(_uri: *const c_char) -> *const c_void {
+    ptr::null()
+}
+
+/// Export a plugin as an LV2 plugin.
+/// You call this with one parameter, which is the function declaration of a function
+/// that creates your plugin.
+/// This function may also do some setup (e.g. initialize logging).
+/// _Note_: this function is also called once to read the meta-data, see
+/// [Creating the plugin for the meta-data].
+///
+/// The plugin is typically a custom data type and should implement
+/// the following traits:
+///
+/// **Traits for meta-data** (Note: you can use the [`Meta`] trait for this.
+/// * [`CommonPluginMeta`] (name of the plugin etc),
+/// * [`AudioHandlerMeta`] (number of audio ports),
+/// * [`CommonAudioPortMeta`] (names of the audio in and out ports),
+/// * [`MidiHandlerMeta`] (number of midi ports),
+/// * [`CommonMidiPortMeta`] (names of the midi in and out ports) and
+/// * [`Lv2PluginMeta`], (LV2-specific meta-data)
+///
+/// **Traits for rendering audio**
+/// * [`AudioHandler`] and
+/// * [`ContextualAudioRenderer`]`<f32,`[`Lv2Host`]`>`
+///
+/// **Traits for handling midi events**
+/// * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`Lv2Host`]`>` and
+/// * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`Lv2Host`]`>`.
+///
+/// **Generating midi events**
+///
+/// [`Lv2Host`] implements [`MidiOutput`], so the plugin can send midi events
+/// and SysEx events to its midi output ports.
+///
+/// The host also needs the Turtle files that describe the plugin;
+/// these can be generated with the functions in the [`ttl`] module.
+///
+/// # Example
+/// ```
+/// # #[macro_use] extern crate rsynth;
+/// use rsynth::{
+///     meta::{Meta, MetaData, InOut},
+///     event::{ContextualEventHandler, Indexed, Timed, RawMidiEvent, SysExEvent},
+///     backend::{HostInterface, lv2_backend::Lv2PluginMeta},
+///     buffer::AudioBufferInOut,
+///     ContextualAudioRenderer,
+///     AudioHandler,
+/// };
+///
+/// struct MyPlugin {
+///   meta: MetaData<&'static str, &'static str, &'static str>
+///   // Define other fields here
+/// }
+///
+/// impl Meta for MyPlugin {
+///    type MetaData = MetaData<&'static str, &'static str, &'static str>;
+///     fn meta(&self) -> &Self::MetaData {
+///         &self.meta
+///     }
+/// }
+///
+/// impl Lv2PluginMeta for MyPlugin {
+///     fn uri(&self) -> &str { "https://example.com/plugins/my-plugin" }
+///     fn plugin_class(&self) -> Option<&str> { Some("InstrumentPlugin") }
+/// }
+///
+/// impl AudioHandler for MyPlugin {
+///     // Implementation omitted for brevity.
+/// #     fn set_sample_rate(&mut self, new_sample_rate: f64) {}
+/// }
+///
+/// impl<H> ContextualAudioRenderer<f32, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+///     // Implementation omitted for brevity.
+/// #    fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut H) {}
+/// }
+///
+/// impl<H> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
+/// impl<'a, H> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
+/// lv2_init!(
+///    fn init() -> MyPlugin {
+///        MyPlugin {
+///             meta: MetaData {
+///                 general_meta: "my_plugin",
+///                 audio_port_meta: InOut {
+///                     inputs: vec![],
+///                     outputs: vec!["left", "right"],
+///                 },
+///                 midi_port_meta: InOut {
+///                     inputs: vec!["midi in"],
+///                     outputs: vec![],
+///                 },
+///             }
+///        }
+///    }
+/// );
+/// # fn main() {}
+/// ```
+/// [`RawMidiEvent`]: ./event/struct.RawMidiEvent.html
+/// [`SysExEvent`]: ./event/struct.SysExEvent.html
+/// [`Timed`]: ./event/struct.Timed.html
+/// [`Indexed`]: ./event/struct.Indexed.html
+/// [`CommonPluginMeta`]: ./trait.CommonPluginMeta.html
+/// [`AudioHandlerMeta`]: ./trait.AudioHandlerMeta.html
+/// [`MidiHandlerMeta`]: ./trait.MidiHandlerMeta.html
+/// [`CommonAudioPortMeta`]: ./trait.CommonAudioPortMeta.html
+/// [`CommonMidiPortMeta`]: ./trait.CommonMidiPortMeta.html
+/// [`Meta`]: ./meta/trait.Meta.html
+/// [`ContextualAudioRenderer`]: trait.ContextualAudioRenderer.html
+/// [`ContextualEventHandler`]: ./event/trait.ContextualEventHandler.html
+/// [`Lv2Host`]: ./backend/lv2_backend/struct.Lv2Host.html
+/// [`Lv2PluginMeta`]: ./backend/lv2_backend/trait.Lv2PluginMeta.html
+/// [`MidiOutput`]: ./backend/trait.MidiOutput.html
+/// [`AudioHandler`]: ./trait.AudioHandler.html
+/// [`ttl`]: ./backend/lv2_backend/ttl/index.html
+/// [Creating the plugin for the meta-data]: ./backend/index.html#creating-the-plugin-for-the-meta-data
+//
+// As with `vst_init`, we use a macro so that plugins do not have to implement the `Default`
+// trait: the host creates the plugin via a C function to which we cannot supply an extra
+// parameter.
+#[macro_export]
+macro_rules! lv2_init {
+    (fn $function_name:ident() -> $return_type:ty
+        $body:block
+    ) => {
+        fn $function_name () -> $return_type
+        $body
+
+        struct Lv2PluginFactory;
+
+        static LV2_STATICS: $crate::backend::lv2_backend::Lv2Statics =
+            $crate::backend::lv2_backend::Lv2Statics::new();
+
+        impl $crate::backend::lv2_backend::Lv2Factory for Lv2PluginFactory {
+            type Plugin = $return_type;
+
+            fn create_plugin() -> $return_type {
+                $function_name()
+            }
+
+            fn statics() -> &'static $crate::backend::lv2_backend::Lv2Statics {
+                &LV2_STATICS
+            }
+        }
+
+        #[no_mangle]
+        pub extern "C" fn lv2_descriptor(
+            index: u32,
+        ) -> *const $crate::backend::lv2_backend::sys::LV2_Descriptor {
+            $crate::backend::lv2_backend::descriptor::<Lv2PluginFactory>(index)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::{InOut, Meta, MetaData};
+
+    pub(super) struct TestPlugin {
+        meta: MetaData<&'static str, &'static str, &'static str>,
+    }
+
+    impl TestPlugin {
+        pub(super) fn new() -> Self {
+            TestPlugin {
+                meta: MetaData {
+                    general_meta: "test \"plugin\"",
+                    audio_port_meta: InOut {
+                        inputs: vec!["in"],
+                        outputs: vec!["out"],
+                    },
+                    midi_port_meta: InOut {
+                        inputs: vec!["midi in"],
+                        outputs: vec!["midi out"],
+                    },
+                },
+            }
+        }
+    }
+
+    impl Meta for TestPlugin {
+        type MetaData = MetaData<&'static str, &'static str, &'static str>;
+        fn meta(&self) -> &Self::MetaData {
+            &self.meta
+        }
+    }
+
+    impl Lv2PluginMeta for TestPlugin {
+        fn uri(&self) -> &str {
+            "urn:rsynth:test"
+        }
+        fn plugin_class(&self) -> Option<&str> {
+            Some("InstrumentPlugin")
+        }
+    }
+
+    impl AudioHandler for TestPlugin {
+        fn set_sample_rate(&mut self, _sample_rate: f64) {}
+
+        fn latency_in_frames(&self) -> u32 {
+            3
+        }
+    }
+
+    impl ContextualAudioRenderer<f32, Lv2Host> for TestPlugin {
+        fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, _context: &mut Lv2Host) {
+            let (inputs, mut outputs) = buffer.separate();
+            for (input, output) in inputs.channels().iter().zip(outputs.channel_iter_mut()) {
+                for (i, o) in input.iter().zip(output.iter_mut()) {
+                    *o = *i * 2.0;
+                }
+            }
+        }
+    }
+
+    // Echo the midi events to the midi output.
+    impl ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, Lv2Host> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut Lv2Host) {
+            context.handle_event(event);
+        }
+    }
+
+    impl<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Lv2Host> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut Lv2Host) {
+            context.handle_event(event);
+        }
+    }
+
+    crate::lv2_init!(
+        fn init() -> TestPlugin {
+            TestPlugin::new()
+        }
+    );
+
+    #[test]
+    fn port_layout_numbers_the_ports_in_order() {
+        let layout = PortLayout {
+            audio_inputs: 1,
+            audio_outputs: 2,
+            midi_inputs: 1,
+            midi_outputs: 1,
+        };
+        assert_eq!(layout.number_of_ports(), 6);
+        assert_eq!(layout.port_kind(0), Some(PortKind::AudioIn(0)));
+        assert_eq!(layout.port_kind(1), Some(PortKind::AudioOut(0)));
+        assert_eq!(layout.port_kind(2), Some(PortKind::AudioOut(1)));
+        assert_eq!(layout.port_kind(3), Some(PortKind::MidiIn(0)));
+        assert_eq!(layout.port_kind(4), Some(PortKind::MidiOut(0)));
+        assert_eq!(layout.port_kind(5), Some(PortKind::Latency));
+        assert_eq!(layout.port_kind(6), None);
+    }
+
+    // A minimal host that only uses the C interface.
+
+    const SEQUENCE: LV2_URID = 1;
+    const MIDI_EVENT: LV2_URID = 2;
+    const OTHER: LV2_URID = 3;
+
+    unsafe extern "C" fn map(_handle: *mut c_void, uri: *const c_char) -> LV2_URID {
+        let uri = CStr::from_ptr(uri).to_bytes_with_nul();
+        if uri == LV2_ATOM__Sequence {
+            SEQUENCE
+        } else if uri == LV2_MIDI__MidiEvent {
+            MIDI_EVENT
+        } else {
+            OTHER
+        }
+    }
+
+    /// An atom sequence with room for `size` bytes of events, aligned to 64 bits.
+    struct SequenceBuffer {
+        data: Vec<u64>,
+    }
+
+    impl SequenceBuffer {
+        fn new(size: usize) -> Self {
+            let mut buffer = Self {
+                data: vec![0; (mem::size_of::<LV2_Atom_Sequence>() + size) / 8],
+            };
+            buffer.sequence().atom = LV2_Atom {
+                size: mem::size_of::<LV2_Atom_Sequence_Body>() as u32,
+                type_: SEQUENCE,
+            };
+            buffer
+        }
+
+        fn sequence(&mut self) -> &mut LV2_Atom_Sequence {
+            unsafe { &mut *(self.data.as_mut_ptr() as *mut LV2_Atom_Sequence) }
+        }
+
+        fn push(&mut self, time: i64, type_: LV2_URID, data: &[u8]) {
+            let size = self.sequence().atom.size as usize;
+            let bytes = self.data.as_mut_ptr() as *mut u8;
+            unsafe {
+                let event = bytes.add(mem::size_of::<LV2_Atom>() + size);
+                *(event as *mut LV2_Atom_Event) = LV2_Atom_Event {
+                    time_frames: time,
+                    body: LV2_Atom {
+                        size: data.len() as u32,
+                        type_,
+                    },
+                };
+                ptr::copy_nonoverlapping(
+                    data.as_ptr(),
+                    event.add(mem::size_of::<LV2_Atom_Event>()),
+                    data.len(),
+                );
+            }
+            self.sequence().atom.size +=
+                mem::size_of::<LV2_Atom_Event>() as u32 + lv2_atom_pad_size(data.len() as u32);
+        }
+
+        fn events(&mut self) -> Vec<(i64, LV2_URID, Vec<u8>)> {
+            let size = self.sequence().atom.size as usize;
+            let body = unsafe { (self.data.as_ptr() as *const u8).add(mem::size_of::<LV2_Atom>()) };
+            let mut offset = mem::size_of::<LV2_Atom_Sequence_Body>();
+            let mut events = Vec::new();
+            while offset < size {
+                unsafe {
+                    let event = &*(body.add(offset) as *const LV2_Atom_Event);
+                    let data = slice::from_raw_parts(
+                        body.add(offset + mem::size_of::<LV2_Atom_Event>()),
+                        event.body.size as usize,
+                    );
+                    events.push((event.time_frames, event.body.type_, data.to_vec()));
+                    offset += mem::size_of::<LV2_Atom_Event>()
+                        + lv2_atom_pad_size(event.body.size) as usize;
+                }
+            }
+            events
+        }
+    }
+
+    #[test]
+    fn a_host_can_instantiate_and_run_the_plugin() {
+        unsafe {
+            assert!(lv2_descriptor(1).is_null());
+            let descriptor = &*lv2_descriptor(0);
+            assert_eq!(
+                CStr::from_ptr(descriptor.URI).to_str(),
+                Ok("urn:rsynth:test")
+            );
+
+            // Instantiating fails without the urid:map feature.
+            let no_features: [*const LV2_Feature; 1] = [ptr::null()];
+            let instantiate = descriptor.instantiate.unwrap();
+            let bundle_path = b"/tmp/\0".as_ptr() as *const c_char;
+            assert!(instantiate(descriptor, 44100.0, bundle_path, no_features.as_ptr()).is_null());
+
+            let mut urid_map = LV2_URID_Map {
+                handle: ptr::null_mut(),
+                map: Some(map),
+            };
+            let map_feature = LV2_Feature {
+                URI: LV2_URID__map.as_ptr() as *const c_char,
+                data: &mut urid_map as *mut LV2_URID_Map as *mut c_void,
+            };
+            let features: [*const LV2_Feature; 2] = [&map_feature, ptr::null()];
+            let instance = instantiate(descriptor, 44100.0, bundle_path, features.as_ptr());
+            assert!(!instance.is_null());
+
+            let mut audio_in = [1.0f32, 2.0, 3.0, 4.0];
+            let mut audio_out = [0.0f32; 4];
+            let mut midi_in = SequenceBuffer::new(128);
+            midi_in.push(1, MIDI_EVENT, &[0x90, 60, 100]);
+            midi_in.push(2, OTHER, &[1, 2, 3, 4]);
+            midi_in.push(3, MIDI_EVENT, &[0xF0, 0x01, 0x02, 0xF7]);
+            let mut midi_out = SequenceBuffer::new(128);
+            // The host sets the size of the output atom to the available space.
+            midi_out.sequence().atom.size = 128;
+            let mut latency = 0.0f32;
+
+            let connect_port = descriptor.connect_port.unwrap();
+            connect_port(instance, 0, audio_in.as_mut_ptr() as *mut c_void);
+            connect_port(instance, 1, audio_out.as_mut_ptr() as *mut c_void);
+            connect_port(instance, 2, midi_in.sequence() as *mut _ as *mut c_void);
+            connect_port(instance, 3, midi_out.sequence() as *mut _ as *mut c_void);
+            connect_port(instance, 4, &mut latency as *mut f32 as *mut c_void);
+
+            descriptor.activate.unwrap()(instance);
+            descriptor.run.unwrap()(instance, 4);
+
+            assert_eq!(audio_out, [2.0, 4.0, 6.0, 8.0]);
+            assert_eq!(latency, 3.0);
+            assert_eq!(midi_out.sequence().atom.type_, SEQUENCE);
+            assert_eq!(
+                midi_out.events(),
+                vec![
+                    (1, MIDI_EVENT, vec![0x90, 60, 100]),
+                    (3, MIDI_EVENT, vec![0xF0, 0x01, 0x02, 0xF7]),
+                ]
+            );
+
+            descriptor.deactivate.unwrap()(instance);
+            descriptor.cleanup.unwrap()(instance);
+        }
+    }
+
+    #[test]
+    fn writing_midi_fails_when_the_output_is_full_or_out_of_order() {
+        let mut midi_out = SequenceBuffer::new(32);
+        midi_out.sequence().atom.size = 32;
+        let mut host = Lv2Host::new(
+            Urids {
+                atom_sequence: SEQUENCE,
+                midi_event: MIDI_EVENT,
+            },
+            1,
+        );
+        unsafe {
+            host.prepare_midi_outputs(&[midi_out.sequence() as *mut _ as *mut c_void]);
+        }
+        let event = RawMidiEvent::new(&[0x90, 60, 100]);
+        assert_eq!(host.write_midi(0, 5, event), Ok(()));
+        assert_eq!(
+            host.write_midi(0, 4, event),
+            Err(MidiOutputError::OutOfOrder)
+        );
+        assert_eq!(
+            host.write_midi(0, 6, event),
+            Err(MidiOutputError::BufferFull)
+        );
+        assert_eq!(
+            host.write_midi(1, 6, event),
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index: 1,
+                number_of_ports: 1
+            })
+        );
+        assert_eq!(
+            midi_out.events(),
+            vec![(5, MIDI_EVENT, vec![0x90, 60, 100])]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! The parts of the LV2 C API that are used by the LV2 backend.
+//!
+//! These definitions correspond to the headers `lv2/core/lv2.h`, `lv2/urid/urid.h`,
+//! `lv2/atom/atom.h` and `lv2/midi/midi.h` of the [LV2 specification].
+//!
+//! [LV2 specification]: https://lv2plug.in/
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+use std::os::raw::{c_char, c_void};
+
+/// Opaque handle to a plugin instance.
+pub type LV2_Handle = *mut c_void;
+
+/// A feature that is provided by the host.
+#[repr(C)]
+pub struct LV2_Feature {
+    /// The URI of the feature.
+    pub URI: *const c_char,
+    /// Pointer to the feature-specific data.
+    pub data: *mut c_void,
+}
+
+/// The descriptor of a plugin, as returned by `lv2_descriptor`.
+#[repr(C)]
+pub struct LV2_Descriptor {
+    /// The URI of the plugin.
+    pub URI: *const c_char,
+    /// Create a new plugin instance.
+    pub instantiate: Option<
+        unsafe extern "C" fn(
+            descriptor: *const LV2_Descriptor,
+            sample_rate: f64,
+            bundle_path: *const c_char,
+            features: *const *const LV2_Feature,
+        ) -> LV2_Handle,
+    >,
+    /// Connect a port to a buffer.
+    pub connect_port:
+        Option<unsafe extern "C" fn(instance: LV2_Handle, port: u32, data_location: *mut c_void)>,
+    /// Prepare the instance for running.
+    pub activate: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    /// Process a block of `sample_count` frames.
+    pub run: Option<unsafe extern "C" fn(instance: LV2_Handle, sample_count: u32)>,
+    /// Counterpart of `activate`.
+    pub deactivate: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    /// Destroy the instance.
+    pub cleanup: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    /// Return the extension data for the given URI.
+    pub extension_data: Option<unsafe extern "C" fn(uri: *const c_char) -> *const c_void>,
+}
+
+/// The type of the `lv2_descriptor` function that is exported by the plugin library.
+pub type LV2_Descriptor_Function = unsafe extern "C" fn(index: u32) -> *const LV2_Descriptor;
+
+/// An integer that is mapped to a URI.
+pub type LV2_URID = u32;
+
+/// The data of the `urid:map` feature.
+#[repr(C)]
+pub struct LV2_URID_Map {
+    /// Opaque data that is passed to `map`.
+    pub handle: *mut c_void,
+    /// Map a URI to a `LV2_URID`.
+    pub map: Option<unsafe extern "C" fn(handle: *mut c_void, uri: *const c_char) -> LV2_URID>,
+}
+
+/// The header of an atom.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LV2_Atom {
+    /// The size of the body of the atom, in bytes.
+    pub size: u32,
+    /// The type of the atom, as a `LV2_URID`.
+    pub type_: u32,
+}
+
+/// The header of an event in an atom sequence; the body of the event follows the header.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LV2_Atom_Event {
+    /// The time stamp of the event, in frames.
+    pub time_frames: i64,
+    /// The header of the body of the event.
+    pub body: LV2_Atom,
+}
+
+/// The body of an atom sequence, excluding the events.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LV2_Atom_Sequence_Body {
+    /// The unit of the time stamps, `0` means frames.
+    pub unit: u32,
+    /// Padding, currently unused.
+    pub pad: u32,
+}
+
+/// An atom sequence; the events follow the header.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LV2_Atom_Sequence {
+    /// The header of the atom.
+    pub atom: LV2_Atom,
+    /// The body of the sequence, excluding the events.
+    pub body: LV2_Atom_Sequence_Body,
+}
+
+/// The URI of the `urid:map` feature.
+pub const LV2_URID__map: &[u8] = b"http://lv2plug.in/ns/ext/urid#map\0";
+/// The URI of the type of an atom sequence.
+pub const LV2_ATOM__Sequence: &[u8] = b"http://lv2plug.in/ns/ext/atom#Sequence\0";
+/// The URI of the type of a midi event.
+pub const LV2_MIDI__MidiEvent: &[u8] = b"http://lv2plug.in/ns/ext/midi#MidiEvent\0";
+
+/// Return the size of an atom, padded to a multiple of 64 bits.
+pub fn lv2_atom_pad_size(size: u32) -> u32 {
+    (size + 7) & !7
+}
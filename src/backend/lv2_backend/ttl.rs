@@ -0,0 +1,323 @@
+//! Generate the Turtle files that describe an LV2 plugin to the host.
+//!
+//! An LV2 bundle is a directory (typically with a name ending in `.lv2`) that contains
+//! * `manifest.ttl`, which lists the plugins in the bundle and the library that implements them,
+//! * a Turtle file that describes the plugin and its ports, and
+//! * the plugin library itself.
+//!
+//! The port indices in the generated files correspond to the port indices that are used
+//! by the [`lv2_init`] macro, so the generated files must be regenerated when the ports of the
+//! plugin change.
+//!
+//! # Example
+//! ```no_run
+//! use rsynth::backend::lv2_backend::{ttl::create_bundle, Lv2PluginMeta};
+//! use rsynth::{CommonAudioPortMeta, CommonMidiPortMeta};
+//! use std::path::Path;
+//!
+//! fn make_bundle<P>(plugin: &P)
+//! where
+//!     P: Lv2PluginMeta + CommonAudioPortMeta + CommonMidiPortMeta,
+//! {
+//!     create_bundle(
+//!         plugin,
+//!         Path::new("target/release/libmy_plugin.so"),
+//!         Path::new("my_plugin.lv2"),
+//!     )
+//!     .expect("Failed to create the bundle.");
+//! }
+//! ```
+//!
+//! [`lv2_init`]: ../../../macro.lv2_init.html
+use super::{Lv2PluginMeta, PortKind, PortLayout};
+use crate::{CommonAudioPortMeta, CommonMidiPortMeta};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Write a string literal in Turtle syntax.
+fn write_literal<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
+    write!(writer, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            '\n' => write!(writer, "\\n")?,
+            '\r' => write!(writer, "\\r")?,
+            '\t' => write!(writer, "\\t")?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    write!(writer, "\"")
+}
+
+/// Write an IRI (a URI or a relative path) in Turtle syntax.
+fn write_iri<W: Write>(writer: &mut W, iri: &str) -> io::Result<()> {
+    write!(writer, "<")?;
+    for c in iri.chars() {
+        match c {
+            '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\' => {
+                write!(writer, "\\u{:04X}", c as u32)?
+            }
+            c if (c as u32) <= 0x20 => write!(writer, "\\u{:04X}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    write!(writer, ">")
+}
+
+fn to_io_error(_: std::fmt::Error) -> io::Error {
+    io::Error::other("failed to get the name of a port")
+}
+
+/// Write `manifest.ttl` for a bundle that contains the given plugin.
+///
+/// `binary` is the file name of the plugin library and `plugin_ttl` is the file name of the
+/// Turtle file that describes the plugin (see [`write_plugin_ttl`]), both relative to the bundle.
+///
+/// [`write_plugin_ttl`]: ./fn.write_plugin_ttl.html
+pub fn write_manifest<P, W>(
+    plugin: &P,
+    binary: &str,
+    plugin_ttl: &str,
+    writer: &mut W,
+) -> io::Result<()>
+where
+    P: Lv2PluginMeta,
+    W: Write,
+{
+    writeln!(writer, "@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .")?;
+    writeln!(
+        writer,
+        "@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> ."
+    )?;
+    writeln!(writer)?;
+    write_iri(writer, plugin.uri())?;
+    writeln!(writer)?;
+    writeln!(writer, "    a lv2:Plugin ;")?;
+    write!(writer, "    lv2:binary ")?;
+    write_iri(writer, binary)?;
+    writeln!(writer, " ;")?;
+    write!(writer, "    rdfs:seeAlso ")?;
+    write_iri(writer, plugin_ttl)?;
+    writeln!(writer, " .")
+}
+
+/// Write the Turtle file that describes the plugin and its ports.
+pub fn write_plugin_ttl<P, W>(plugin: &P, writer: &mut W) -> io::Result<()>
+where
+    P: Lv2PluginMeta + CommonAudioPortMeta + CommonMidiPortMeta,
+    W: Write,
+{
+    writeln!(writer, "@prefix atom: <http://lv2plug.in/ns/ext/atom#> .")?;
+    writeln!(writer, "@prefix doap: <http://usefulinc.com/ns/doap#> .")?;
+    writeln!(writer, "@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .")?;
+    writeln!(writer, "@prefix midi: <http://lv2plug.in/ns/ext/midi#> .")?;
+    writeln!(writer, "@prefix urid: <http://lv2plug.in/ns/ext/urid#> .")?;
+    writeln!(writer)?;
+    write_iri(writer, plugin.uri())?;
+    writeln!(writer)?;
+    write!(writer, "    a lv2:Plugin")?;
+    if let Some(class) = plugin.plugin_class() {
+        write!(writer, ", lv2:{}", class)?;
+    }
+    writeln!(writer, " ;")?;
+    write!(writer, "    doap:name ")?;
+    write_literal(writer, plugin.name())?;
+    writeln!(writer, " ;")?;
+    writeln!(writer, "    lv2:requiredFeature urid:map ;")?;
+    writeln!(writer, "    lv2:optionalFeature lv2:hardRTCapable ;")?;
+
+    let layout = PortLayout::new(plugin);
+    for port in 0..layout.number_of_ports() {
+        if port == 0 {
+            writeln!(writer, "    lv2:port [")?;
+        } else {
+            writeln!(writer, "    ] , [")?;
+        }
+        let mut name = String::new();
+        let (types, symbol) = match layout.port_kind(port) {
+            Some(PortKind::AudioIn(index)) => {
+                CommonAudioPortMeta::input_name(plugin, &mut name, index).map_err(to_io_error)?;
+                (
+                    "lv2:AudioPort, lv2:InputPort",
+                    format!("audio_in_{}", index),
+                )
+            }
+            Some(PortKind::AudioOut(index)) => {
+                CommonAudioPortMeta::output_name(plugin, &mut name, index).map_err(to_io_error)?;
+                (
+                    "lv2:AudioPort, lv2:OutputPort",
+                    format!("audio_out_{}", index),
+                )
+            }
+            Some(PortKind::MidiIn(index)) => {
+                CommonMidiPortMeta::input_name(plugin, &mut name, index).map_err(to_io_error)?;
+                ("atom:AtomPort, lv2:InputPort", format!("midi_in_{}", index))
+            }
+            Some(PortKind::MidiOut(index)) => {
+                CommonMidiPortMeta::output_name(plugin, &mut name, index).map_err(to_io_error)?;
+                (
+                    "atom:AtomPort, lv2:OutputPort",
+                    format!("midi_out_{}", index),
+                )
+            }
+            Some(PortKind::Latency) | None => {
+                name.push_str("Latency");
+                ("lv2:ControlPort, lv2:OutputPort", "latency".to_string())
+            }
+        };
+        writeln!(writer, "        a {} ;", types)?;
+        writeln!(writer, "        lv2:index {} ;", port)?;
+        writeln!(writer, "        lv2:symbol \"{}\" ;", symbol)?;
+        write!(writer, "        lv2:name ")?;
+        write_literal(writer, &name)?;
+        writeln!(writer, " ;")?;
+        match layout.port_kind(port) {
+            Some(PortKind::MidiIn(_)) | Some(PortKind::MidiOut(_)) => {
+                writeln!(writer, "        atom:bufferType atom:Sequence ;")?;
+                writeln!(writer, "        atom:supports midi:MidiEvent ;")?;
+            }
+            Some(PortKind::Latency) => {
+                writeln!(writer, "        lv2:designation lv2:latency ;")?;
+                writeln!(
+                    writer,
+                    "        lv2:portProperty lv2:reportsLatency, lv2:integer ;"
+                )?;
+            }
+            _ => {}
+        }
+    }
+    writeln!(writer, "    ] .")
+}
+
+/// Create an LV2 bundle for the plugin in the directory `bundle`.
+///
+/// The directory is created if it does not exist yet.
+/// The plugin library `binary` is copied into the bundle, next to `manifest.ttl` and a Turtle file
+/// describing the plugin.
+/// The Turtle file has the same name as the library, but with the extension `.ttl`.
+pub fn create_bundle<P>(plugin: &P, binary: &Path, bundle: &Path) -> io::Result<()>
+where
+    P: Lv2PluginMeta + CommonAudioPortMeta + CommonMidiPortMeta,
+{
+    let binary_name = binary
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file name"))?;
+    let plugin_ttl = Path::new(binary_name).with_extension("ttl");
+    let plugin_ttl_name = plugin_ttl
+        .to_str()
+        .expect("The file name is valid unicode.");
+
+    fs::create_dir_all(bundle)?;
+    fs::copy(binary, bundle.join(binary_name))?;
+
+    let mut manifest = BufWriter::new(File::create(bundle.join("manifest.ttl"))?);
+    write_manifest(plugin, binary_name, plugin_ttl_name, &mut manifest)?;
+    manifest.flush()?;
+
+    let mut plugin_description = BufWriter::new(File::create(bundle.join(plugin_ttl_name))?);
+    write_plugin_ttl(plugin, &mut plugin_description)?;
+    plugin_description.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::lv2_backend::tests::TestPlugin;
+
+    #[test]
+    fn write_manifest_works() {
+        let mut manifest = Vec::new();
+        write_manifest(
+            &TestPlugin::new(),
+            "libtest.so",
+            "libtest.ttl",
+            &mut manifest,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(manifest).unwrap(),
+            "@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<urn:rsynth:test>
+    a lv2:Plugin ;
+    lv2:binary <libtest.so> ;
+    rdfs:seeAlso <libtest.ttl> .
+"
+        );
+    }
+
+    #[test]
+    fn write_plugin_ttl_works() {
+        let mut plugin_ttl = Vec::new();
+        write_plugin_ttl(&TestPlugin::new(), &mut plugin_ttl).unwrap();
+        assert_eq!(
+            String::from_utf8(plugin_ttl).unwrap(),
+            r#"@prefix atom: <http://lv2plug.in/ns/ext/atom#> .
+@prefix doap: <http://usefulinc.com/ns/doap#> .
+@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .
+@prefix midi: <http://lv2plug.in/ns/ext/midi#> .
+@prefix urid: <http://lv2plug.in/ns/ext/urid#> .
+
+<urn:rsynth:test>
+    a lv2:Plugin, lv2:InstrumentPlugin ;
+    doap:name "test \"plugin\"" ;
+    lv2:requiredFeature urid:map ;
+    lv2:optionalFeature lv2:hardRTCapable ;
+    lv2:port [
+        a lv2:AudioPort, lv2:InputPort ;
+        lv2:index 0 ;
+        lv2:symbol "audio_in_0" ;
+        lv2:name "in" ;
+    ] , [
+        a lv2:AudioPort, lv2:OutputPort ;
+        lv2:index 1 ;
+        lv2:symbol "audio_out_0" ;
+        lv2:name "out" ;
+    ] , [
+        a atom:AtomPort, lv2:InputPort ;
+        lv2:index 2 ;
+        lv2:symbol "midi_in_0" ;
+        lv2:name "midi in" ;
+        atom:bufferType atom:Sequence ;
+        atom:supports midi:MidiEvent ;
+    ] , [
+        a atom:AtomPort, lv2:OutputPort ;
+        lv2:index 3 ;
+        lv2:symbol "midi_out_0" ;
+        lv2:name "midi out" ;
+        atom:bufferType atom:Sequence ;
+        atom:supports midi:MidiEvent ;
+    ] , [
+        a lv2:ControlPort, lv2:OutputPort ;
+        lv2:index 4 ;
+        lv2:symbol "latency" ;
+        lv2:name "Latency" ;
+        lv2:designation lv2:latency ;
+        lv2:portProperty lv2:reportsLatency, lv2:integer ;
+    ] .
+"#
+        );
+    }
+
+    #[test]
+    fn create_bundle_creates_the_bundle() {
+        let directory = std::env::temp_dir().join(format!("rsynth-lv2-{}", std::process::id()));
+        let binary = directory.join("libtest.so");
+        let bundle = directory.join("test.lv2");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(&binary, b"binary").unwrap();
+
+        create_bundle(&TestPlugin::new(), &binary, &bundle).unwrap();
+
+        assert_eq!(fs::read(bundle.join("libtest.so")).unwrap(), b"binary");
+        let manifest = fs::read_to_string(bundle.join("manifest.ttl")).unwrap();
+        assert!(manifest.contains("rdfs:seeAlso <libtest.ttl>"));
+        let plugin_ttl = fs::read_to_string(bundle.join("libtest.ttl")).unwrap();
+        assert!(plugin_ttl.contains("<urn:rsynth:test>"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -5,12 +5,26 @@
 //! `rsynth` currently supports the following back-ends:
 //! * [`combined`] combine different back-ends for audio input, audio output, midi input and
 //!     midi output, mostly for offline rendering and testing (behind various features)
+//! * [`alsa`] (behind the `backend-alsa` feature)
+//! * [`clap`] (behind the `backend-clap` feature)
+//! * [`cpal`] (behind the `backend-cpal` feature)
 //! * [`jack`] (behind the `backend-jack` feature)
+//! * [`lv2`] (behind the `backend-lv2` feature)
 //! * [`vst`] (behind the `backend-vst` feature)
+//! * [`vst3`] (behind the `backend-vst3` feature)
 //!
 //! These backends are currently in the `rsynth` crate, but we may eventually move them to
 //! separate crates.
 //!
+//! Creating the plugin for the meta-data
+//! -------------------------------------
+//! The [`clap_init!`], [`lv2_init!`] and [`vst3_init!`] macros call the function that creates
+//! the plugin once more to read the meta-data of the plugin (for the descriptor or the class
+//! information), before the host creates an instance of the plugin.
+//! The plugin that is created for this is dropped immediately, so the setup in this function
+//! (and any other side effect of it) happens one more time than the number of instances
+//! that the host creates.
+//!
 //! Custom backends
 //! ===============
 //! You can write a backend in a separate crate. If you encounter problems that prevent you
@@ -23,13 +37,37 @@
 //! When you publish a backend crate, let us know by opening an issue or pull request
 //! so that we can link to it in the documentation of rsynth.
 //!
+//! [`alsa`]: ./alsa_backend/index.html
+//! [`clap`]: ./clap_backend/index.html
+//! [`cpal`]: ./cpal_backend/index.html
 //! [`jack`]: ./jack_backend/index.html
+//! [`lv2`]: ./lv2_backend/index.html
 //! [`vst`]: ./vst_backend/index.html
+//! [`vst3`]: ./vst3_backend/index.html
 //! [`combined`]: ./combined/index.html
+//! [`clap_init!`]: ../macro.clap_init.html
+//! [`lv2_init!`]: ../macro.lv2_init.html
+//! [`vst3_init!`]: ../macro.vst3_init.html
+use crate::event::RawMidiEvent;
+#[cfg(any(feature = "backend-clap", feature = "backend-vst3"))]
+use crate::{CommonAudioPortMeta, CommonMidiPortMeta};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "backend-alsa")]
+pub mod alsa_backend;
+#[cfg(feature = "backend-clap")]
+pub mod clap_backend;
 #[cfg(feature = "backend-combined")]
 pub mod combined;
+#[cfg(feature = "backend-cpal")]
+pub mod cpal_backend;
 #[cfg(feature = "backend-jack")]
 pub mod jack_backend;
+#[cfg(feature = "backend-lv2")]
+pub mod lv2_backend;
+#[cfg(feature = "backend-vst3")]
+pub mod vst3_backend;
 #[cfg(feature = "backend-vst")]
 pub mod vst_backend;
 
@@ -105,3 +143,264 @@ pub trait HostInterface {
 /// }
 /// ```
 pub trait Stop: HostInterface {}
+
+/// The time signature, e.g. 3/4 or 6/8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    /// The number of beats per bar, e.g. `3` for 3/4.
+    pub numerator: u32,
+    /// The note value of one beat, e.g. `4` for 3/4.
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    /// Create a new `TimeSignature`.
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// The length of one bar, expressed in quarter notes.
+    pub fn bar_length_in_quarter_notes(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+/// The state of the transport of the host at the start of the current buffer.
+///
+/// Fields that are `None` are not known, e.g. because the host does not provide them.
+/// Musical positions are expressed in quarter notes, regardless of the time signature.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Transport {
+    /// Whether the transport is playing (`true`) or stopped (`false`).
+    pub is_playing: bool,
+    /// The tempo in beats (quarter notes) per minute.
+    pub tempo_in_beats_per_minute: Option<f64>,
+    /// The time signature.
+    pub time_signature: Option<TimeSignature>,
+    /// The position of the start of the current buffer, in frames.
+    pub position_in_frames: Option<u64>,
+    /// The position of the start of the current buffer, in quarter notes.
+    pub position_in_quarter_notes: Option<f64>,
+    /// The position of the start of the current bar, in quarter notes.
+    pub bar_start_in_quarter_notes: Option<f64>,
+}
+
+/// Provides information about the transport of the host, such as the tempo, the time signature,
+/// whether the transport is playing and the song position.
+///
+/// This is typically implemented by the "context" that is passed to the
+/// [`ContextualAudioRenderer`] and [`ContextualEventHandler`] traits.
+///
+/// # Example
+/// The following illustrates a plugin that computes the phase of a tempo-synced LFO.
+/// ```
+/// use rsynth::ContextualAudioRenderer;
+/// use rsynth::backend::TransportInfo;
+/// use rsynth::buffer::AudioBufferInOut;
+/// struct MyPlugin { lfo_phase: f64 }
+/// impl<H> ContextualAudioRenderer<f32, H> for MyPlugin
+/// where H: TransportInfo
+/// {
+///     fn render_buffer(
+///         &mut self,
+///         buffer: &mut AudioBufferInOut<f32>,
+///         context: &mut H)
+///     {
+///         if let Some(position) = context.transport().position_in_quarter_notes {
+///             // One LFO cycle per bar of four quarter notes.
+///             self.lfo_phase = (position / 4.0).fract();
+///         }
+///         // The rest of the audio rendering.
+///     }
+/// }
+/// ```
+///
+/// [`ContextualAudioRenderer`]: ../trait.ContextualAudioRenderer.html
+/// [`ContextualEventHandler`]: ../event/trait.ContextualEventHandler.html
+pub trait TransportInfo {
+    /// Get the state of the transport at the start of the current buffer.
+    fn transport(&self) -> Transport;
+}
+
+/// An error that can occur when writing midi events with the [`MidiOutput`] trait.
+///
+/// [`MidiOutput`]: ./trait.MidiOutput.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiOutputError {
+    /// There is no midi output port with the given index.
+    PortOutOfBounds {
+        /// The index of the port.
+        port_index: usize,
+        /// The number of midi output ports.
+        number_of_ports: usize,
+    },
+    /// There is no room for the event, e.g. because too many events have been written
+    /// during the current buffer.
+    BufferFull,
+    /// The event is earlier than an event that has already been written to the same port
+    /// during the current buffer and the backend cannot reorder events.
+    OutOfOrder,
+    /// The backend does not support this kind of event.
+    Unsupported,
+    /// The backend can only write midi events while the plugin is processing audio,
+    /// e.g. during the call to `render_buffer`.
+    NotProcessing,
+}
+
+impl Display for MidiOutputError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports,
+            } => write!(
+                f,
+                "midi port out of bounds: port index is {}, but only {} ports are available",
+                port_index, number_of_ports
+            ),
+            MidiOutputError::BufferFull => write!(f, "no room for the midi event"),
+            MidiOutputError::OutOfOrder => write!(
+                f,
+                "the midi event is earlier than a previously written midi event"
+            ),
+            MidiOutputError::Unsupported => {
+                write!(f, "this kind of midi event is not supported by the backend")
+            }
+            MidiOutputError::NotProcessing => write!(
+                f,
+                "midi events can only be written while the plugin is processing audio"
+            ),
+        }
+    }
+}
+
+impl Error for MidiOutputError {}
+
+/// Write midi events to the midi output ports of the backend.
+///
+/// This is implemented by the "context" that is passed to the [`ContextualAudioRenderer`] and
+/// [`ContextualEventHandler`] traits, so that a plugin can generate midi in a
+/// backend-independent way.
+/// Ports are identified by their index, which is strictly smaller than
+/// `self.number_of_midi_output_ports()`;
+/// `time_in_frames` is the offset in frames, relative to the start of the current buffer.
+///
+/// # Example
+/// The following illustrates a plugin that plays a note at the start of each buffer.
+/// ```
+/// use rsynth::ContextualAudioRenderer;
+/// use rsynth::backend::MidiOutput;
+/// use rsynth::buffer::AudioBufferInOut;
+/// use rsynth::event::RawMidiEvent;
+/// struct MyPlugin { /* ... */ }
+/// impl<H> ContextualAudioRenderer<f32, H> for MyPlugin
+/// where H: MidiOutput
+/// {
+///     fn render_buffer(
+///         &mut self,
+///         buffer: &mut AudioBufferInOut<f32>,
+///         context: &mut H)
+///     {
+///         let note_on = RawMidiEvent::new(&[0x90, 60, 100]);
+///         if let Err(e) = context.write_midi(0, 0, note_on) {
+///             // The event could not be written, e.g. because there is no midi output.
+///         }
+///         // The rest of the audio rendering.
+///     }
+/// }
+/// ```
+///
+/// [`ContextualAudioRenderer`]: ../trait.ContextualAudioRenderer.html
+/// [`ContextualEventHandler`]: ../event/trait.ContextualEventHandler.html
+pub trait MidiOutput {
+    /// The number of midi output ports.
+    fn number_of_midi_output_ports(&self) -> usize;
+
+    /// Write a midi event to the midi output port with the given index.
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError>;
+
+    /// Write a SysEx event to the midi output port with the given index.
+    /// `data` is the complete SysEx message, including the leading `0xF0` and the
+    /// trailing `0xF7`.
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError>;
+}
+
+/// The names of the ports of a plugin.
+///
+/// Plugin backends retrieve these when the plugin is created, so that the host can query them
+/// while the plugin is processing audio.
+#[cfg(any(feature = "backend-clap", feature = "backend-vst3"))]
+pub(crate) struct PortNames {
+    pub(crate) audio_in: Vec<String>,
+    pub(crate) audio_out: Vec<String>,
+    pub(crate) midi_in: Vec<String>,
+    pub(crate) midi_out: Vec<String>,
+}
+
+#[cfg(any(feature = "backend-clap", feature = "backend-vst3"))]
+impl PortNames {
+    pub(crate) fn new<P>(plugin: &P) -> Self
+    where
+        P: CommonAudioPortMeta + CommonMidiPortMeta,
+    {
+        fn names<F>(number_of_ports: usize, kind: &str, get_name: F) -> Vec<String>
+        where
+            F: Fn(&mut String, usize) -> Result<(), std::fmt::Error>,
+        {
+            (0..number_of_ports)
+                .map(|index| {
+                    let mut name = String::new();
+                    if let Err(e) = get_name(&mut name, index) {
+                        error!(
+                            "Failed to get the name of {} port with index {}: {}.",
+                            kind, index, e
+                        );
+                    }
+                    name
+                })
+                .collect()
+        }
+        Self {
+            audio_in: names(
+                plugin.max_number_of_audio_inputs(),
+                "audio input",
+                |n, i| CommonAudioPortMeta::input_name(plugin, n, i),
+            ),
+            audio_out: names(
+                plugin.max_number_of_audio_outputs(),
+                "audio output",
+                |n, i| CommonAudioPortMeta::output_name(plugin, n, i),
+            ),
+            midi_in: names(plugin.max_number_of_midi_inputs(), "midi input", |n, i| {
+                CommonMidiPortMeta::input_name(plugin, n, i)
+            }),
+            midi_out: names(
+                plugin.max_number_of_midi_outputs(),
+                "midi output",
+                |n, i| CommonMidiPortMeta::output_name(plugin, n, i),
+            ),
+        }
+    }
+}
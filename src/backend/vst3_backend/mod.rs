@@ -0,0 +1,1952 @@
+//! Wrapper for the [VST3] plugin backend (behind the `backend-vst3` feature).
+//!
+//! Support is only enabled if you compile with the "backend-vst3" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//!
+//! For an example, see `vst3_synth.rs` in the `examples` folder.
+//! `examples/example_synth` contains the code that is shared for all backends and
+//! `examples/vst3_synth.rs` contains the VST3-specific code.
+//!
+//! # Usage
+//! See the documentation of the [`vst3_init`] macro.
+//!
+//! # Buses
+//! The plugin is exposed as one class that implements both the `IComponent` and the
+//! `IAudioProcessor` interfaces.
+//! * The audio inputs and the audio outputs of the plugin (as defined by the
+//!   [`CommonAudioPortMeta`] trait) are grouped into audio buses. The first two inputs form a
+//!   stereo main bus and each other input is exposed as a mono auxiliary bus with the same name;
+//!   a plugin with only one input has a mono main bus. The same holds for the outputs.
+//!   Only these speaker arrangements are accepted.
+//! * Each midi input and each midi output of the plugin (as defined by the
+//!   [`CommonMidiPortMeta`] trait) is exposed as an event bus with 16 channels.
+//!
+//! Only 32-bit audio is supported.
+//!
+//! # Events
+//! Events are delivered to the plugin before the audio of the buffer is rendered, with the
+//! sample offset that was given by the host.
+//! The index of the event bus is used as the index of the [`Indexed`] event.
+//! VST3 does not have raw midi events: note on, note off and polyphonic pressure events are
+//! converted to midi events and SysEx events are passed as such.
+//! When generating midi, other channel messages (control change, program change,
+//! channel pressure and pitch bend) are sent as "legacy midi cc out" events.
+//!
+//! # Limitations
+//! * Only Linux is supported for now.
+//! * Parameters, saving and restoring the state and GUI's are not supported (yet); the plugin
+//!   has no edit controller (`IComponent::getControllerClassId` returns `kResultFalse`) and
+//!   `IComponent::setState` and `IComponent::getState` return `kNotImplemented`, so the host
+//!   does not store a state for the plugin.
+//!
+//! [VST3]: https://steinbergmedia.github.io/vst3_doc/
+//! [`vst3_init`]: ../../macro.vst3_init.html
+//! [`CommonAudioPortMeta`]: ../../trait.CommonAudioPortMeta.html
+//! [`CommonMidiPortMeta`]: ../../trait.CommonMidiPortMeta.html
+//! [`Indexed`]: ../../event/struct.Indexed.html
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+use crate::backend::{
+    HostInterface, MidiOutput, MidiOutputError, PortNames, TimeSignature, Transport, TransportInfo,
+};
+use crate::buffer::AudioBufferInOut;
+use crate::event::{
+    ContextualEventHandler, EventHandler, Indexed, RawMidiEvent, SysExEvent, Timed,
+};
+use crate::{
+    AudioHandler, CommonAudioPortMeta, CommonMidiPortMeta, CommonPluginMeta,
+    ContextualAudioRenderer,
+};
+use core::cmp;
+use midi_consts::channel_event::{
+    CHANNEL_KEY_PRESSURE, CONTROL_CHANGE, EVENT_TYPE_MASK, NOTE_OFF, NOTE_ON, PITCH_BEND_CHANGE,
+    POLYPHONIC_KEY_PRESSURE, PROGRAM_CHANGE,
+};
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use vecstorage::VecStorage;
+
+pub mod sys;
+
+use self::sys::*;
+
+/// The maximum total size (in bytes) of the SysEx events that can be sent to the host per buffer.
+const MAX_SYSEX_DATA_SIZE: usize = 64 * 1024;
+
+/// Define some VST3-specific meta-data for a VST3 plugin.
+pub trait Vst3PluginMeta: CommonPluginMeta {
+    /// The unique identifier of the plugin class.
+    fn class_id(&self) -> [u8; 16];
+
+    /// The name of the vendor of the plugin.
+    fn vendor(&self) -> &str {
+        ""
+    }
+
+    /// The version of the plugin, e.g. `"1.0.0"`.
+    fn version(&self) -> &str {
+        ""
+    }
+
+    /// The sub-categories of the plugin, separated by `|`, e.g. `"Instrument|Synth"` or `"Fx"`.
+    fn sub_categories(&self) -> &str {
+        "Fx"
+    }
+}
+
+/// Used to communicate with the VST3 host.
+///
+/// This is the "context" that is passed to the plugin when rendering audio and handling events.
+/// It can be used to send midi events and SysEx events to the event output buses of the plugin,
+/// see the [`MidiOutput`] trait.
+///
+/// [`MidiOutput`]: ../trait.MidiOutput.html
+pub struct Vst3Host {
+    output_events: *mut c_void,
+    transport: Transport,
+    number_of_midi_outputs: usize,
+    sysex_data: Vec<u8>,
+}
+
+impl Vst3Host {
+    fn new(number_of_midi_outputs: usize) -> Self {
+        Self {
+            output_events: ptr::null_mut(),
+            transport: Transport::default(),
+            number_of_midi_outputs,
+            sysex_data: Vec::with_capacity(MAX_SYSEX_DATA_SIZE),
+        }
+    }
+
+    fn check_port_index(&self, port_index: usize) -> Result<(), MidiOutputError> {
+        if port_index < self.number_of_midi_outputs {
+            Ok(())
+        } else {
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports: self.number_of_midi_outputs,
+            })
+        }
+    }
+
+    // `output_events` is only set during the `process` call of the host.
+    fn check_processing(&self) -> Result<(), MidiOutputError> {
+        if self.output_events.is_null() {
+            Err(MidiOutputError::NotProcessing)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_event(&mut self, mut event: Event) -> Result<(), MidiOutputError> {
+        self.check_processing()?;
+        let result = unsafe {
+            (vtable::<IEventListVtbl>(self.output_events).add_event)(self.output_events, &mut event)
+        };
+        if result == kResultOk {
+            Ok(())
+        } else {
+            Err(MidiOutputError::BufferFull)
+        }
+    }
+}
+
+fn event(port_index: usize, time_in_frames: u32, type_: u16, data: EventData) -> Event {
+    Event {
+        bus_index: port_index as i32,
+        sample_offset: time_in_frames as i32,
+        ppq_position: 0.0,
+        flags: 0,
+        type_,
+        data,
+    }
+}
+
+/// Convert a midi event to a VST3 event.
+///
+/// Returns `None` for midi events that cannot be represented as a VST3 event.
+fn midi_to_event(
+    port_index: usize,
+    time_in_frames: u32,
+    midi_event: RawMidiEvent,
+) -> Option<Event> {
+    let [status, data1, data2] = *midi_event.data();
+    let channel = (status & 0x0F) as i16;
+    let to_float = |value: u8| value as f32 / 127.0;
+    let cc_out = |control_number: u8, value: u8, value2: u8| {
+        event(
+            port_index,
+            time_in_frames,
+            kLegacyMIDICCOutEvent,
+            EventData {
+                midi_cc_out: LegacyMIDICCOutEvent {
+                    control_number,
+                    channel: channel as i8,
+                    value: value as i8,
+                    value2: value2 as i8,
+                },
+            },
+        )
+    };
+    let event = match status & EVENT_TYPE_MASK {
+        NOTE_ON if data2 > 0 => event(
+            port_index,
+            time_in_frames,
+            kNoteOnEvent,
+            EventData {
+                note_on: NoteOnEvent {
+                    channel,
+                    pitch: data1 as i16,
+                    tuning: 0.0,
+                    velocity: to_float(data2),
+                    length: 0,
+                    note_id: -1,
+                },
+            },
+        ),
+        NOTE_ON | NOTE_OFF => event(
+            port_index,
+            time_in_frames,
+            kNoteOffEvent,
+            EventData {
+                note_off: NoteOffEvent {
+                    channel,
+                    pitch: data1 as i16,
+                    velocity: to_float(data2),
+                    note_id: -1,
+                    tuning: 0.0,
+                },
+            },
+        ),
+        POLYPHONIC_KEY_PRESSURE => event(
+            port_index,
+            time_in_frames,
+            kPolyPressureEvent,
+            EventData {
+                poly_pressure: PolyPressureEvent {
+                    channel,
+                    pitch: data1 as i16,
+                    pressure: to_float(data2),
+                    note_id: -1,
+                },
+            },
+        ),
+        CONTROL_CHANGE => cc_out(data1, data2, 0),
+        PROGRAM_CHANGE => cc_out(kCtrlProgramChange, data1, 0),
+        CHANNEL_KEY_PRESSURE => cc_out(kAfterTouch, data1, 0),
+        PITCH_BEND_CHANGE => cc_out(kPitchBend, data1, data2),
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Convert a note on, note off or polyphonic pressure event to a midi event.
+///
+/// Returns `None` for other events and for events that do not correspond to a midi event.
+fn event_to_midi(event: &Event) -> Option<Indexed<Timed<RawMidiEvent>>> {
+    let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+    let (status, channel, pitch, value) = unsafe {
+        if event.type_ == kNoteOnEvent {
+            let e = event.data.note_on;
+            // A velocity of `0` would be interpreted as a note off event.
+            (
+                NOTE_ON,
+                e.channel,
+                e.pitch,
+                cmp::max(to_byte(e.velocity), 1),
+            )
+        } else if event.type_ == kNoteOffEvent {
+            let e = event.data.note_off;
+            (NOTE_OFF, e.channel, e.pitch, to_byte(e.velocity))
+        } else if event.type_ == kPolyPressureEvent {
+            let e = event.data.poly_pressure;
+            (
+                POLYPHONIC_KEY_PRESSURE,
+                e.channel,
+                e.pitch,
+                to_byte(e.pressure),
+            )
+        } else {
+            return None;
+        }
+    };
+    if event.bus_index < 0 || !(0..16).contains(&channel) || !(0..128).contains(&pitch) {
+        return None;
+    }
+    Some(Indexed {
+        index: event.bus_index as usize,
+        event: Timed {
+            time_in_frames: cmp::max(event.sample_offset, 0) as u32,
+            event: RawMidiEvent::new(&[status | channel as u8, pitch as u8, value]),
+        },
+    })
+}
+
+impl HostInterface for Vst3Host {
+    fn output_initialized(&self) -> bool {
+        false
+    }
+}
+
+/// The transport is the transport that the host passed for the current buffer.
+impl TransportInfo for Vst3Host {
+    fn transport(&self) -> Transport {
+        self.transport
+    }
+}
+
+impl MidiOutput for Vst3Host {
+    fn number_of_midi_output_ports(&self) -> usize {
+        self.number_of_midi_outputs
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        self.check_port_index(port_index)?;
+        let event =
+            midi_to_event(port_index, time_in_frames, event).ok_or(MidiOutputError::Unsupported)?;
+        self.add_event(event)
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        self.check_port_index(port_index)?;
+        self.check_processing()?;
+        let start = self.sysex_data.len();
+        let end = start + data.len();
+        if end > self.sysex_data.capacity() {
+            // Do not allocate in the real-time thread.
+            return Err(MidiOutputError::BufferFull);
+        }
+        // The data must remain valid until the end of the `process` call, so it is copied.
+        self.sysex_data.extend_from_slice(data);
+        let event = event(
+            port_index,
+            time_in_frames,
+            kDataEvent,
+            EventData {
+                data: DataEvent {
+                    size: data.len() as u32,
+                    type_: kMidiSysEx,
+                    bytes: self.sysex_data[start..end].as_ptr(),
+                },
+            },
+        );
+        self.add_event(event)
+    }
+}
+
+impl EventHandler<Indexed<Timed<RawMidiEvent>>> for Vst3Host {
+    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_midi(index, event.time_in_frames, event.event) {
+            error!("Failed to send midi event: {}", e);
+        }
+    }
+}
+
+impl<'a> EventHandler<Indexed<Timed<SysExEvent<'a>>>> for Vst3Host {
+    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>) {
+        let Indexed { index, event } = event;
+        if let Err(e) = self.write_sysex(index, event.time_in_frames, event.event.data()) {
+            error!("Failed to send SysEx event: {}", e);
+        }
+    }
+}
+
+fn convert_transport(context: &ProcessContext) -> Transport {
+    let has = |flag| context.state & flag != 0;
+    Transport {
+        is_playing: has(kPlaying),
+        tempo_in_beats_per_minute: if has(kTempoValid) {
+            Some(context.tempo)
+        } else {
+            None
+        },
+        time_signature: if has(kTimeSigValid)
+            && context.time_sig_numerator > 0
+            && context.time_sig_denominator > 0
+        {
+            Some(TimeSignature::new(
+                context.time_sig_numerator as u32,
+                context.time_sig_denominator as u32,
+            ))
+        } else {
+            None
+        },
+        position_in_frames: if context.project_time_samples >= 0 {
+            Some(context.project_time_samples as u64)
+        } else {
+            None
+        },
+        position_in_quarter_notes: if has(kProjectTimeMusicValid) {
+            Some(context.project_time_music)
+        } else {
+            None
+        },
+        bar_start_in_quarter_notes: if has(kBarPositionValid) {
+            Some(context.bar_position_music)
+        } else {
+            None
+        },
+    }
+}
+
+/// Copy `s` to `buffer`, truncating it if needed, and add a terminating `0`.
+fn copy_c_string(buffer: &mut [c_char], s: &str) {
+    let length = cmp::min(s.len(), buffer.len() - 1);
+    for (target, source) in buffer.iter_mut().zip(&s.as_bytes()[..length]) {
+        *target = *source as c_char;
+    }
+    buffer[length] = 0;
+}
+
+/// Copy `s` to `buffer` as UTF-16, truncating it if needed, and add a terminating `0`.
+fn copy_string128(buffer: &mut String128, s: &str) {
+    let mut length = 0;
+    for (target, source) in buffer[..127].iter_mut().zip(s.encode_utf16()) {
+        *target = source;
+        length += 1;
+    }
+    buffer[length] = 0;
+}
+
+/// An audio bus, consisting of one or two consecutive audio ports of the plugin.
+struct AudioBus {
+    name: String,
+    arrangement: SpeakerArrangement,
+}
+
+impl AudioBus {
+    /// The first two ports form a stereo main bus, the other ports are mono auxiliary buses.
+    /// A plugin with only one port has a mono main bus.
+    fn from_port_names(port_names: Vec<String>) -> Vec<Self> {
+        let mut port_names = port_names.into_iter();
+        let mut buses = Vec::new();
+        if port_names.len() >= 2 {
+            let left = port_names.next().unwrap();
+            let right = port_names.next().unwrap();
+            buses.push(AudioBus {
+                name: format!("{} / {}", left, right),
+                arrangement: kStereo,
+            });
+        }
+        buses.extend(port_names.map(|name| AudioBus {
+            name,
+            arrangement: kSpeakerM,
+        }));
+        buses
+    }
+
+    fn channel_count(&self) -> i32 {
+        self.arrangement.count_ones() as i32
+    }
+}
+
+/// The buses of the plugin, derived from the [`PortNames`] of the plugin.
+struct Buses {
+    audio_in: Vec<AudioBus>,
+    audio_out: Vec<AudioBus>,
+    midi_in: Vec<String>,
+    midi_out: Vec<String>,
+}
+
+impl Buses {
+    fn new<P>(plugin: &P) -> Self
+    where
+        P: CommonAudioPortMeta + CommonMidiPortMeta,
+    {
+        let PortNames {
+            audio_in,
+            audio_out,
+            midi_in,
+            midi_out,
+        } = PortNames::new(plugin);
+        Self {
+            audio_in: AudioBus::from_port_names(audio_in),
+            audio_out: AudioBus::from_port_names(audio_out),
+            midi_in,
+            midi_out,
+        }
+    }
+
+    fn audio(&self, direction: i32) -> Option<&[AudioBus]> {
+        if direction == kInput {
+            Some(&self.audio_in)
+        } else if direction == kOutput {
+            Some(&self.audio_out)
+        } else {
+            None
+        }
+    }
+
+    fn midi(&self, direction: i32) -> Option<&[String]> {
+        if direction == kInput {
+            Some(&self.midi_in)
+        } else if direction == kOutput {
+            Some(&self.midi_out)
+        } else {
+            None
+        }
+    }
+
+    /// The number of buses with the given media type and direction.
+    fn count(&self, media_type: i32, direction: i32) -> usize {
+        if media_type == kAudio {
+            self.audio(direction).map_or(0, <[_]>::len)
+        } else if media_type == kEvent {
+            self.midi(direction).map_or(0, <[_]>::len)
+        } else {
+            0
+        }
+    }
+
+    /// The name and the number of channels of a bus.
+    fn info(&self, media_type: i32, direction: i32, index: i32) -> Option<(&str, i32)> {
+        if index < 0 {
+            return None;
+        }
+        if media_type == kAudio {
+            let bus = self.audio(direction)?.get(index as usize)?;
+            Some((&bus.name, bus.channel_count()))
+        } else if media_type == kEvent {
+            let name = self.midi(direction)?.get(index as usize)?;
+            Some((name, 16))
+        } else {
+            None
+        }
+    }
+}
+
+/// Used internally by the [`vst3_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`vst3_init`]: ../../macro.vst3_init.html
+pub trait Vst3Factory: 'static {
+    type Plugin: Vst3PluginMeta
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + AudioHandler
+        + ContextualAudioRenderer<f32, Vst3Host>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, Vst3Host>
+        + for<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Vst3Host>
+        + Send
+        + 'static;
+
+    /// Create a new instance of the plugin.
+    fn create_plugin() -> Self::Plugin;
+
+    /// The statics for this plugin.
+    fn statics() -> &'static Vst3Statics;
+}
+
+/// The information about the plugin class that the factory gives to the host.
+struct ClassInfo {
+    cid: TUID,
+    name: String,
+    vendor: String,
+    version: String,
+    sub_categories: String,
+}
+
+/// The plugin factory object, as seen by the host.
+#[repr(C)]
+struct PluginFactory {
+    vtable: *const IPluginFactory2Vtbl,
+}
+
+/// The data that the host accesses via static pointers: the plugin factory and the information
+/// about the plugin class.
+///
+/// Used internally by the [`vst3_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`vst3_init`]: ../../macro.vst3_init.html
+pub struct Vst3Statics {
+    factory: PluginFactory,
+    class_info: OnceLock<ClassInfo>,
+}
+
+// The factory only points to the (immutable) virtual table.
+unsafe impl Sync for Vst3Statics {}
+
+impl Vst3Statics {
+    /// Create the statics for the plugin created by the given factory.
+    pub const fn new<F: Vst3Factory>() -> Self {
+        Self {
+            factory: PluginFactory {
+                vtable: &FactoryImpl::<F>::VTABLE,
+            },
+            class_info: OnceLock::new(),
+        }
+    }
+}
+
+/// Return the plugin factory for the plugin created by the given factory.
+///
+/// Used internally by the [`vst3_init`] macro. Normally, plugins do not need to use this.
+///
+/// [`vst3_init`]: ../../macro.vst3_init.html
+pub fn get_plugin_factory<F: Vst3Factory>() -> *mut c_void {
+    &F::statics().factory as *const PluginFactory as *mut c_void
+}
+
+struct FactoryImpl<F>(PhantomData<F>);
+
+impl<F: Vst3Factory> FactoryImpl<F> {
+    const VTABLE: IPluginFactory2Vtbl = IPluginFactory2Vtbl {
+        unknown: FUnknownVtbl {
+            query_interface: Self::query_interface,
+            add_ref: Self::add_ref,
+            release: Self::release,
+        },
+        get_factory_info: Self::get_factory_info,
+        count_classes: Self::count_classes,
+        get_class_info: Self::get_class_info,
+        create_instance: Self::create_instance,
+        get_class_info2: Self::get_class_info2,
+    };
+
+    fn class_info() -> &'static ClassInfo {
+        F::statics().class_info.get_or_init(|| {
+            let plugin = F::create_plugin();
+            ClassInfo {
+                cid: plugin.class_id(),
+                name: plugin.name().to_string(),
+                vendor: plugin.vendor().to_string(),
+                version: plugin.version().to_string(),
+                sub_categories: plugin.sub_categories().to_string(),
+            }
+        })
+    }
+
+    unsafe extern "C" fn query_interface(
+        this: *mut c_void,
+        iid: *const TUID,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        if obj.is_null() {
+            return kInvalidArgument;
+        }
+        match iid.as_ref() {
+            Some(iid)
+                if *iid == FUnknown_iid
+                    || *iid == IPluginFactory_iid
+                    || *iid == IPluginFactory2_iid =>
+            {
+                *obj = this;
+                kResultOk
+            }
+            _ => {
+                *obj = ptr::null_mut();
+                kNoInterface
+            }
+        }
+    }
+
+    // The factory is a static object, so it does not need reference counting.
+    unsafe extern "C" fn add_ref(_this: *mut c_void) -> u32 {
+        1
+    }
+
+    unsafe extern "C" fn release(_this: *mut c_void) -> u32 {
+        1
+    }
+
+    unsafe extern "C" fn get_factory_info(_this: *mut c_void, info: *mut PFactoryInfo) -> tresult {
+        let info = match info.as_mut() {
+            Some(info) => info,
+            None => return kInvalidArgument,
+        };
+        copy_c_string(&mut info.vendor, &Self::class_info().vendor);
+        copy_c_string(&mut info.url, "");
+        copy_c_string(&mut info.email, "");
+        info.flags = kUnicode;
+        kResultOk
+    }
+
+    unsafe extern "C" fn count_classes(_this: *mut c_void) -> i32 {
+        1
+    }
+
+    unsafe extern "C" fn get_class_info(
+        _this: *mut c_void,
+        index: i32,
+        info: *mut PClassInfo,
+    ) -> tresult {
+        let info = match info.as_mut() {
+            Some(info) if index == 0 => info,
+            _ => return kInvalidArgument,
+        };
+        let class_info = Self::class_info();
+        info.cid = class_info.cid;
+        info.cardinality = kManyInstances;
+        copy_c_string(&mut info.category, kVstAudioEffectClass);
+        copy_c_string(&mut info.name, &class_info.name);
+        kResultOk
+    }
+
+    unsafe extern "C" fn get_class_info2(
+        _this: *mut c_void,
+        index: i32,
+        info: *mut PClassInfo2,
+    ) -> tresult {
+        let info = match info.as_mut() {
+            Some(info) if index == 0 => info,
+            _ => return kInvalidArgument,
+        };
+        let class_info = Self::class_info();
+        info.cid = class_info.cid;
+        info.cardinality = kManyInstances;
+        copy_c_string(&mut info.category, kVstAudioEffectClass);
+        copy_c_string(&mut info.name, &class_info.name);
+        info.class_flags = 0;
+        copy_c_string(&mut info.sub_categories, &class_info.sub_categories);
+        copy_c_string(&mut info.vendor, &class_info.vendor);
+        copy_c_string(&mut info.version, &class_info.version);
+        copy_c_string(&mut info.sdk_version, kVstVersionString);
+        kResultOk
+    }
+
+    unsafe extern "C" fn create_instance(
+        _this: *mut c_void,
+        cid: FIDString,
+        iid: FIDString,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        if obj.is_null() {
+            return kInvalidArgument;
+        }
+        *obj = ptr::null_mut();
+        if cid.is_null() || iid.is_null() || *(cid as *const TUID) != Self::class_info().cid {
+            return kNoInterface;
+        }
+        let component = Box::into_raw(Box::new(Component::<F>::new(F::create_plugin())));
+        let result = Component::<F>::query_interface(component as *mut c_void, iid as _, obj);
+        // Undo the reference of `Box::into_raw`; the instance is dropped if the query failed.
+        Component::<F>::release(component as *mut c_void);
+        result
+    }
+}
+
+/// Does the audio processing.
+struct Vst3PluginWrapper<P> {
+    plugin: P,
+    host: Vst3Host,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static [f32]>,
+}
+
+impl<P> Vst3PluginWrapper<P>
+where
+    P: CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + ContextualAudioRenderer<f32, Vst3Host>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, Vst3Host>,
+    for<'a> P: ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Vst3Host>,
+{
+    fn new(plugin: P) -> Self {
+        Self {
+            inputs: VecStorage::with_capacity(plugin.max_number_of_audio_inputs()),
+            outputs: VecStorage::with_capacity(plugin.max_number_of_audio_outputs()),
+            host: Vst3Host::new(plugin.max_number_of_midi_outputs()),
+            plugin,
+        }
+    }
+
+    unsafe fn handle_events(&mut self, input_events: *mut c_void) {
+        let vtable = vtable::<IEventListVtbl>(input_events);
+        for index in 0..(vtable.get_event_count)(input_events) {
+            let mut event: Event = mem::zeroed();
+            if (vtable.get_event)(input_events, index, &mut event) != kResultOk {
+                continue;
+            }
+            if event.type_ == kDataEvent {
+                let data = event.data.data;
+                if data.type_ != kMidiSysEx || data.bytes.is_null() || event.bus_index < 0 {
+                    continue;
+                }
+                let event = Indexed {
+                    index: event.bus_index as usize,
+                    event: Timed {
+                        time_in_frames: cmp::max(event.sample_offset, 0) as u32,
+                        event: SysExEvent::new(slice::from_raw_parts(
+                            data.bytes,
+                            data.size as usize,
+                        )),
+                    },
+                };
+                self.plugin.handle_event(event, &mut self.host);
+            } else if let Some(event) = event_to_midi(&event) {
+                self.plugin.handle_event(event, &mut self.host);
+            }
+        }
+    }
+
+    unsafe fn process(&mut self, data: &ProcessData) -> tresult {
+        self.host.transport = match data.process_context.as_ref() {
+            Some(context) => convert_transport(context),
+            None => Transport::default(),
+        };
+        self.host.sysex_data.clear();
+        self.host.output_events = data.output_events;
+
+        if !data.input_events.is_null() {
+            self.handle_events(data.input_events);
+        }
+
+        // The host may call `process` without audio, e.g. to flush parameter changes.
+        if data.num_samples > 0 {
+            if data.symbolic_sample_size != kSample32 {
+                self.host.output_events = ptr::null_mut();
+                return kResultFalse;
+            }
+            let number_of_frames = data.num_samples as usize;
+            let buses = |buffers: *mut AudioBusBuffers, number_of_buses: i32| {
+                if buffers.is_null() || number_of_buses <= 0 {
+                    &[][..]
+                } else {
+                    slice::from_raw_parts(buffers, number_of_buses as usize)
+                }
+            };
+
+            let mut inputs = self.inputs.vec_guard();
+            for bus in buses(data.inputs, data.num_inputs) {
+                for channel in 0..cmp::max(bus.num_channels, 0) as usize {
+                    let buffer = *bus.channel_buffers.add(channel) as *const f32;
+                    if !buffer.is_null() && inputs.len() < inputs.capacity() {
+                        inputs.push(slice::from_raw_parts(buffer, number_of_frames));
+                    }
+                }
+            }
+
+            let mut outputs = self.outputs.vec_guard();
+            for bus in buses(data.outputs, data.num_outputs) {
+                for channel in 0..cmp::max(bus.num_channels, 0) as usize {
+                    let buffer = *bus.channel_buffers.add(channel) as *mut f32;
+                    if !buffer.is_null() && outputs.len() < outputs.capacity() {
+                        outputs.push(slice::from_raw_parts_mut(buffer, number_of_frames));
+                    }
+                }
+            }
+
+            let mut audio_buffer =
+                AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
+            self.plugin.render_buffer(&mut audio_buffer, &mut self.host);
+        }
+        self.host.output_events = ptr::null_mut();
+        kResultOk
+    }
+}
+
+/// The plugin instance as seen by the host.
+///
+/// The object implements two interfaces, so it starts with two pointers to virtual tables.
+/// The host may query the buses and the latency on the main thread while the plugin is
+/// processing audio on the audio thread. For this reason, the information that these functions
+/// need is stored separately from the plugin itself.
+#[repr(C)]
+struct Component<F: Vst3Factory> {
+    component_vtable: *const IComponentVtbl,
+    processor_vtable: *const IAudioProcessorVtbl,
+    reference_count: AtomicU32,
+    buses: Buses,
+    latency_in_frames: AtomicU32,
+    wrapper: UnsafeCell<Vst3PluginWrapper<F::Plugin>>,
+}
+
+impl<F: Vst3Factory> Component<F> {
+    const COMPONENT_VTABLE: IComponentVtbl = IComponentVtbl {
+        unknown: FUnknownVtbl {
+            query_interface: Self::query_interface,
+            add_ref: Self::add_ref,
+            release: Self::release,
+        },
+        initialize: Self::initialize,
+        terminate: Self::terminate,
+        get_controller_class_id: Self::get_controller_class_id,
+        set_io_mode: Self::set_io_mode,
+        get_bus_count: Self::get_bus_count,
+        get_bus_info: Self::get_bus_info,
+        get_routing_info: Self::get_routing_info,
+        activate_bus: Self::activate_bus,
+        set_active: Self::set_active,
+        set_state: Self::set_state,
+        get_state: Self::get_state,
+    };
+
+    const PROCESSOR_VTABLE: IAudioProcessorVtbl = IAudioProcessorVtbl {
+        unknown: FUnknownVtbl {
+            query_interface: Self::processor_query_interface,
+            add_ref: Self::processor_add_ref,
+            release: Self::processor_release,
+        },
+        set_bus_arrangements: Self::set_bus_arrangements,
+        get_bus_arrangement: Self::get_bus_arrangement,
+        can_process_sample_size: Self::can_process_sample_size,
+        get_latency_samples: Self::get_latency_samples,
+        setup_processing: Self::setup_processing,
+        set_processing: Self::set_processing,
+        process: Self::process,
+        get_tail_samples: Self::get_tail_samples,
+    };
+
+    fn new(plugin: F::Plugin) -> Self {
+        Self {
+            component_vtable: &Self::COMPONENT_VTABLE,
+            processor_vtable: &Self::PROCESSOR_VTABLE,
+            reference_count: AtomicU32::new(1),
+            buses: Buses::new(&plugin),
+            latency_in_frames: AtomicU32::new(plugin.latency_in_frames()),
+            wrapper: UnsafeCell::new(Vst3PluginWrapper::new(plugin)),
+        }
+    }
+
+    unsafe fn from_component<'a>(this: *mut c_void) -> &'a Self {
+        &*(this as *const Self)
+    }
+
+    unsafe fn from_processor<'a>(this: *mut c_void) -> &'a Self {
+        &*((this as *const u8).sub(mem::offset_of!(Self, processor_vtable)) as *const Self)
+    }
+
+    /// The caller must ensure that the host does not access the plugin concurrently.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn wrapper(&self) -> &mut Vst3PluginWrapper<F::Plugin> {
+        &mut *self.wrapper.get()
+    }
+
+    unsafe extern "C" fn query_interface(
+        this: *mut c_void,
+        iid: *const TUID,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        if obj.is_null() {
+            return kInvalidArgument;
+        }
+        let component = Self::from_component(this);
+        let interface = match iid.as_ref() {
+            Some(iid)
+                if *iid == FUnknown_iid || *iid == IPluginBase_iid || *iid == IComponent_iid =>
+            {
+                &component.component_vtable as *const _ as *mut c_void
+            }
+            Some(iid) if *iid == IAudioProcessor_iid => {
+                &component.processor_vtable as *const _ as *mut c_void
+            }
+            _ => {
+                *obj = ptr::null_mut();
+                return kNoInterface;
+            }
+        };
+        component.reference_count.fetch_add(1, Ordering::SeqCst);
+        *obj = interface;
+        kResultOk
+    }
+
+    unsafe extern "C" fn add_ref(this: *mut c_void) -> u32 {
+        Self::from_component(this)
+            .reference_count
+            .fetch_add(1, Ordering::SeqCst)
+            + 1
+    }
+
+    unsafe extern "C" fn release(this: *mut c_void) -> u32 {
+        let remaining = Self::from_component(this)
+            .reference_count
+            .fetch_sub(1, Ordering::SeqCst)
+            - 1;
+        if remaining == 0 {
+            drop(Box::from_raw(this as *mut Self));
+        }
+        remaining
+    }
+
+    unsafe extern "C" fn processor_query_interface(
+        this: *mut c_void,
+        iid: *const TUID,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        let component = Self::from_processor(this) as *const Self as *mut c_void;
+        Self::query_interface(component, iid, obj)
+    }
+
+    unsafe extern "C" fn processor_add_ref(this: *mut c_void) -> u32 {
+        Self::add_ref(Self::from_processor(this) as *const Self as *mut c_void)
+    }
+
+    unsafe extern "C" fn processor_release(this: *mut c_void) -> u32 {
+        Self::release(Self::from_processor(this) as *const Self as *mut c_void)
+    }
+
+    unsafe extern "C" fn initialize(_this: *mut c_void, _context: *mut c_void) -> tresult {
+        kResultOk
+    }
+
+    unsafe extern "C" fn terminate(_this: *mut c_void) -> tresult {
+        kResultOk
+    }
+
+    unsafe extern "C" fn get_controller_class_id(
+        _this: *mut c_void,
+        _class_id: *mut TUID,
+    ) -> tresult {
+        // The plugin has no edit controller.
+        kResultFalse
+    }
+
+    unsafe extern "C" fn set_io_mode(_this: *mut c_void, _mode: i32) -> tresult {
+        kResultOk
+    }
+
+    unsafe extern "C" fn get_bus_count(this: *mut c_void, type_: i32, dir: i32) -> i32 {
+        Self::from_component(this).buses.count(type_, dir) as i32
+    }
+
+    unsafe extern "C" fn get_bus_info(
+        this: *mut c_void,
+        type_: i32,
+        dir: i32,
+        index: i32,
+        bus: *mut BusInfo,
+    ) -> tresult {
+        let info = Self::from_component(this).buses.info(type_, dir, index);
+        match (info, bus.as_mut()) {
+            (Some((name, channel_count)), Some(bus)) => {
+                bus.media_type = type_;
+                bus.direction = dir;
+                bus.channel_count = channel_count;
+                copy_string128(&mut bus.name, name);
+                bus.bus_type = if index == 0 { kMain } else { kAux };
+                bus.flags = kDefaultActive;
+                kResultOk
+            }
+            _ => kInvalidArgument,
+        }
+    }
+
+    unsafe extern "C" fn get_routing_info(
+        _this: *mut c_void,
+        _in_info: *mut RoutingInfo,
+        _out_info: *mut RoutingInfo,
+    ) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe extern "C" fn activate_bus(
+        this: *mut c_void,
+        type_: i32,
+        dir: i32,
+        index: i32,
+        _state: TBool,
+    ) -> tresult {
+        match Self::from_component(this).buses.info(type_, dir, index) {
+            Some(_) => kResultOk,
+            None => kInvalidArgument,
+        }
+    }
+
+    unsafe extern "C" fn set_active(_this: *mut c_void, _state: TBool) -> tresult {
+        kResultOk
+    }
+
+    // Saving and restoring the state is not supported yet.
+    unsafe extern "C" fn set_state(_this: *mut c_void, _state: *mut c_void) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe extern "C" fn get_state(_this: *mut c_void, _state: *mut c_void) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe extern "C" fn set_bus_arrangements(
+        this: *mut c_void,
+        inputs: *mut SpeakerArrangement,
+        num_ins: i32,
+        outputs: *mut SpeakerArrangement,
+        num_outs: i32,
+    ) -> tresult {
+        let buses = &Self::from_processor(this).buses;
+        // Only the arrangements of the buses of the plugin are supported.
+        let matches = |arrangements: *mut SpeakerArrangement, count: i32, buses: &[AudioBus]| {
+            count >= 0
+                && count as usize == buses.len()
+                && (count == 0
+                    || slice::from_raw_parts(arrangements, count as usize)
+                        .iter()
+                        .zip(buses.iter())
+                        .all(|(arrangement, bus)| *arrangement == bus.arrangement))
+        };
+        if matches(inputs, num_ins, &buses.audio_in) && matches(outputs, num_outs, &buses.audio_out)
+        {
+            kResultTrue
+        } else {
+            kResultFalse
+        }
+    }
+
+    unsafe extern "C" fn get_bus_arrangement(
+        this: *mut c_void,
+        dir: i32,
+        index: i32,
+        arr: *mut SpeakerArrangement,
+    ) -> tresult {
+        let buses = Self::from_processor(this).buses.audio(dir);
+        let bus = buses.and_then(|buses| buses.get(index as usize));
+        match (bus, arr.as_mut()) {
+            (Some(bus), Some(arr)) if index >= 0 => {
+                *arr = bus.arrangement;
+                kResultOk
+            }
+            _ => kInvalidArgument,
+        }
+    }
+
+    unsafe extern "C" fn can_process_sample_size(
+        _this: *mut c_void,
+        symbolic_sample_size: i32,
+    ) -> tresult {
+        if symbolic_sample_size == kSample32 {
+            kResultTrue
+        } else {
+            kResultFalse
+        }
+    }
+
+    unsafe extern "C" fn get_latency_samples(this: *mut c_void) -> u32 {
+        Self::from_processor(this)
+            .latency_in_frames
+            .load(Ordering::SeqCst)
+    }
+
+    unsafe extern "C" fn setup_processing(this: *mut c_void, setup: *mut ProcessSetup) -> tresult {
+        let setup = match setup.as_ref() {
+            Some(setup) => setup,
+            None => return kInvalidArgument,
+        };
+        if setup.symbolic_sample_size != kSample32 {
+            return kResultFalse;
+        }
+        trace!("setup_processing(sample_rate: {})", setup.sample_rate);
+        let component = Self::from_processor(this);
+        let wrapper = component.wrapper();
+        wrapper.plugin.set_sample_rate(setup.sample_rate);
+        component
+            .latency_in_frames
+            .store(wrapper.plugin.latency_in_frames(), Ordering::SeqCst);
+        kResultOk
+    }
+
+    unsafe extern "C" fn set_processing(_this: *mut c_void, _state: TBool) -> tresult {
+        kResultOk
+    }
+
+    unsafe extern "C" fn process(this: *mut c_void, data: *mut ProcessData) -> tresult {
+        match data.as_ref() {
+            Some(data) => Self::from_processor(this).wrapper().process(data),
+            None => kInvalidArgument,
+        }
+    }
+
+    unsafe extern "C" fn get_tail_samples(_this: *mut c_void) -> u32 {
+        kNoTail
+    }
+}
+
+/// Export a plugin as a VST3 plugin.
+/// You call this with one parameter, which is the function declaration of a function
+/// that creates your plugin.
+/// This function may also do some setup (e.g. initialize logging).
+/// _Note_: this function is also called once to read the meta-data, see
+/// [Creating the plugin for the meta-data].
+///
+/// The plugin is typically a custom data type and should implement
+/// the following traits:
+///
+/// **Traits for meta-data** (Note: you can use the [`Meta`] trait for this.
+/// * [`CommonPluginMeta`] (name of the plugin etc),
+/// * [`AudioHandlerMeta`] (number of audio ports),
+/// * [`CommonAudioPortMeta`] (names of the audio in and out ports, these are used for the buses),
+/// * [`MidiHandlerMeta`] (number of midi ports),
+/// * [`CommonMidiPortMeta`] (names of the midi in and out ports, these are used for the event buses) and
+/// * [`Vst3PluginMeta`], (VST3-specific meta-data)
+///
+/// **Traits for rendering audio**
+/// * [`AudioHandler`] and
+/// * [`ContextualAudioRenderer`]`<f32,`[`Vst3Host`]`>`
+///
+/// **Traits for handling midi events**
+/// * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`Vst3Host`]`>` and
+/// * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`Vst3Host`]`>`.
+///
+/// **Generating midi events**
+///
+/// [`Vst3Host`] implements [`MidiOutput`], so the plugin can send midi events
+/// and SysEx events to its event output buses.
+///
+/// # Example
+/// ```
+/// # #[macro_use] extern crate rsynth;
+/// use rsynth::{
+///     meta::{Meta, MetaData, InOut},
+///     event::{ContextualEventHandler, Indexed, Timed, RawMidiEvent, SysExEvent},
+///     backend::{HostInterface, vst3_backend::Vst3PluginMeta},
+///     buffer::AudioBufferInOut,
+///     ContextualAudioRenderer,
+///     AudioHandler,
+/// };
+///
+/// struct MyPlugin {
+///   meta: MetaData<&'static str, &'static str, &'static str>
+///   // Define other fields here
+/// }
+///
+/// impl Meta for MyPlugin {
+///    type MetaData = MetaData<&'static str, &'static str, &'static str>;
+///     fn meta(&self) -> &Self::MetaData {
+///         &self.meta
+///     }
+/// }
+///
+/// impl Vst3PluginMeta for MyPlugin {
+///     fn class_id(&self) -> [u8; 16] { *b"my-plugin-class!" }
+///     fn sub_categories(&self) -> &str { "Instrument|Synth" }
+/// }
+///
+/// impl AudioHandler for MyPlugin {
+///     // Implementation omitted for brevity.
+/// #     fn set_sample_rate(&mut self, new_sample_rate: f64) {}
+/// }
+///
+/// impl<H> ContextualAudioRenderer<f32, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+///     // Implementation omitted for brevity.
+/// #    fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut H) {}
+/// }
+///
+/// impl<H> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
+/// impl<'a, H> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
+/// vst3_init!(
+///    fn init() -> MyPlugin {
+///        MyPlugin {
+///             meta: MetaData {
+///                 general_meta: "my_plugin",
+///                 audio_port_meta: InOut {
+///                     inputs: vec![],
+///                     outputs: vec!["left", "right"],
+///                 },
+///                 midi_port_meta: InOut {
+///                     inputs: vec!["midi in"],
+///                     outputs: vec![],
+///                 },
+///             }
+///        }
+///    }
+/// );
+/// # fn main() {}
+/// ```
+/// [`RawMidiEvent`]: ./event/struct.RawMidiEvent.html
+/// [`SysExEvent`]: ./event/struct.SysExEvent.html
+/// [`Timed`]: ./event/struct.Timed.html
+/// [`Indexed`]: ./event/struct.Indexed.html
+/// [`CommonPluginMeta`]: ./trait.CommonPluginMeta.html
+/// [`AudioHandlerMeta`]: ./trait.AudioHandlerMeta.html
+/// [`MidiHandlerMeta`]: ./trait.MidiHandlerMeta.html
+/// [`CommonAudioPortMeta`]: ./trait.CommonAudioPortMeta.html
+/// [`CommonMidiPortMeta`]: ./trait.CommonMidiPortMeta.html
+/// [`Meta`]: ./meta/trait.Meta.html
+/// [`ContextualAudioRenderer`]: trait.ContextualAudioRenderer.html
+/// [`ContextualEventHandler`]: ./event/trait.ContextualEventHandler.html
+/// [`Vst3Host`]: ./backend/vst3_backend/struct.Vst3Host.html
+/// [`Vst3PluginMeta`]: ./backend/vst3_backend/trait.Vst3PluginMeta.html
+/// [`MidiOutput`]: ./backend/trait.MidiOutput.html
+/// [`AudioHandler`]: ./trait.AudioHandler.html
+/// [Creating the plugin for the meta-data]: ./backend/index.html#creating-the-plugin-for-the-meta-data
+//
+// As with `vst_init`, we use a macro so that plugins do not have to implement the `Default`
+// trait: the host creates the plugin via a C function to which we cannot supply an extra
+// parameter.
+#[macro_export]
+macro_rules! vst3_init {
+    (fn $function_name:ident() -> $return_type:ty
+        $body:block
+    ) => {
+        fn $function_name () -> $return_type
+        $body
+
+        struct Vst3PluginFactory;
+
+        static VST3_STATICS: $crate::backend::vst3_backend::Vst3Statics =
+            $crate::backend::vst3_backend::Vst3Statics::new::<Vst3PluginFactory>();
+
+        impl $crate::backend::vst3_backend::Vst3Factory for Vst3PluginFactory {
+            type Plugin = $return_type;
+
+            fn create_plugin() -> $return_type {
+                $function_name()
+            }
+
+            fn statics() -> &'static $crate::backend::vst3_backend::Vst3Statics {
+                &VST3_STATICS
+            }
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn GetPluginFactory() -> *mut ::std::os::raw::c_void {
+            $crate::backend::vst3_backend::get_plugin_factory::<Vst3PluginFactory>()
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn ModuleEntry(_library_handle: *mut ::std::os::raw::c_void) -> bool {
+            true
+        }
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        pub extern "C" fn ModuleExit() -> bool {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::{InOut, Meta, MetaData};
+    use std::ffi::CStr;
+
+    struct TestPlugin {
+        meta: MetaData<&'static str, &'static str, &'static str>,
+        sample_rate: f64,
+    }
+
+    impl Meta for TestPlugin {
+        type MetaData = MetaData<&'static str, &'static str, &'static str>;
+        fn meta(&self) -> &Self::MetaData {
+            &self.meta
+        }
+    }
+
+    impl Vst3PluginMeta for TestPlugin {
+        fn class_id(&self) -> [u8; 16] {
+            *b"rsynth-test-vst3"
+        }
+        fn vendor(&self) -> &str {
+            "rsynth"
+        }
+        fn sub_categories(&self) -> &str {
+            "Instrument|Synth"
+        }
+    }
+
+    impl AudioHandler for TestPlugin {
+        fn set_sample_rate(&mut self, sample_rate: f64) {
+            self.sample_rate = sample_rate;
+        }
+
+        fn latency_in_frames(&self) -> u32 {
+            if self.sample_rate > 0.0 {
+                (self.sample_rate / 1000.0) as u32
+            } else {
+                0
+            }
+        }
+    }
+
+    impl ContextualAudioRenderer<f32, Vst3Host> for TestPlugin {
+        fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, _context: &mut Vst3Host) {
+            let (inputs, mut outputs) = buffer.separate();
+            for (input, output) in inputs.channels().iter().zip(outputs.channel_iter_mut()) {
+                for (i, o) in input.iter().zip(output.iter_mut()) {
+                    *o = *i * 2.0;
+                }
+            }
+        }
+    }
+
+    // Echo the midi events to the midi output.
+    impl ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, Vst3Host> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut Vst3Host) {
+            context.handle_event(event);
+        }
+    }
+
+    impl<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, Vst3Host> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut Vst3Host) {
+            context.handle_event(event);
+        }
+    }
+
+    crate::vst3_init!(
+        fn init() -> TestPlugin {
+            TestPlugin {
+                meta: MetaData {
+                    general_meta: "test plugin",
+                    audio_port_meta: InOut {
+                        inputs: vec!["in"],
+                        outputs: vec!["left", "right"],
+                    },
+                    midi_port_meta: InOut {
+                        inputs: vec!["midi in"],
+                        outputs: vec!["midi out"],
+                    },
+                },
+                sample_rate: 0.0,
+            }
+        }
+    );
+
+    /// An `IEventList` as the host implements it.
+    #[repr(C)]
+    struct TestEventList {
+        vtable: *const IEventListVtbl,
+        events: Vec<Event>,
+    }
+
+    impl TestEventList {
+        const VTABLE: IEventListVtbl = IEventListVtbl {
+            unknown: FUnknownVtbl {
+                query_interface: Self::query_interface,
+                add_ref: Self::add_ref,
+                release: Self::add_ref,
+            },
+            get_event_count: Self::get_event_count,
+            get_event: Self::get_event,
+            add_event: Self::add_event,
+        };
+
+        fn new(events: Vec<Event>) -> Self {
+            Self {
+                vtable: &Self::VTABLE,
+                events,
+            }
+        }
+
+        fn as_ptr(&mut self) -> *mut c_void {
+            self as *mut Self as *mut c_void
+        }
+
+        unsafe fn events<'a>(this: *mut c_void) -> &'a mut Vec<Event> {
+            &mut (*(this as *mut Self)).events
+        }
+
+        unsafe extern "C" fn query_interface(
+            _this: *mut c_void,
+            _iid: *const TUID,
+            _obj: *mut *mut c_void,
+        ) -> tresult {
+            kNoInterface
+        }
+
+        unsafe extern "C" fn add_ref(_this: *mut c_void) -> u32 {
+            1
+        }
+
+        unsafe extern "C" fn get_event_count(this: *mut c_void) -> i32 {
+            Self::events(this).len() as i32
+        }
+
+        unsafe extern "C" fn get_event(this: *mut c_void, index: i32, e: *mut Event) -> tresult {
+            match Self::events(this).get(index as usize) {
+                Some(event) => {
+                    *e = *event;
+                    kResultOk
+                }
+                None => kInvalidArgument,
+            }
+        }
+
+        unsafe extern "C" fn add_event(this: *mut c_void, e: *mut Event) -> tresult {
+            let events = Self::events(this);
+            if events.len() >= 2 {
+                return kResultFalse;
+            }
+            events.push(*e);
+            kResultOk
+        }
+    }
+
+    fn c_str(buffer: &[c_char]) -> &str {
+        unsafe { CStr::from_ptr(buffer.as_ptr()).to_str().unwrap() }
+    }
+
+    fn midi(event: &Event) -> Option<[u8; 3]> {
+        event_to_midi(event).map(|e| *e.event.event.data())
+    }
+
+    #[test]
+    fn the_layout_of_the_structs_matches_the_vst3_sdk() {
+        assert_eq!(mem::size_of::<Event>(), 48);
+        assert_eq!(mem::size_of::<BusInfo>(), 276);
+        assert_eq!(mem::size_of::<PClassInfo2>(), 440);
+        assert_eq!(mem::size_of::<AudioBusBuffers>(), 24);
+        assert_eq!(mem::size_of::<ProcessData>(), 80);
+        assert_eq!(mem::size_of::<ProcessContext>(), 112);
+    }
+
+    #[test]
+    fn channel_messages_are_converted_to_events_and_back() {
+        for data in [[0x91, 60, 100], [0x82, 61, 0], [0xA3, 62, 33]] {
+            let event = midi_to_event(1, 5, RawMidiEvent::new(&data)).unwrap();
+            assert_eq!(event.bus_index, 1);
+            assert_eq!(event.sample_offset, 5);
+            assert_eq!(midi(&event), Some(data));
+        }
+    }
+
+    #[test]
+    fn note_on_with_velocity_zero_is_converted_to_note_off() {
+        let event = midi_to_event(0, 0, RawMidiEvent::new(&[0x90, 60, 0])).unwrap();
+        assert_eq!(event.type_, kNoteOffEvent);
+        assert_eq!(midi(&event), Some([0x80, 60, 0]));
+    }
+
+    #[test]
+    fn other_channel_messages_are_converted_to_legacy_midi_cc_out_events() {
+        let expected = [
+            ([0xB1, 7, 100], (7, 100, 0)),
+            ([0xC1, 5, 0], (kCtrlProgramChange, 5, 0)),
+            ([0xD1, 64, 0], (kAfterTouch, 64, 0)),
+            ([0xE1, 0x12, 0x34], (kPitchBend, 0x12, 0x34)),
+        ];
+        for (data, (control_number, value, value2)) in expected {
+            let event = midi_to_event(0, 0, RawMidiEvent::new(&data)).unwrap();
+            assert_eq!(event.type_, kLegacyMIDICCOutEvent);
+            let cc_out = unsafe { event.data.midi_cc_out };
+            assert_eq!(cc_out.control_number, control_number);
+            assert_eq!(cc_out.channel, 1);
+            assert_eq!(cc_out.value, value as i8);
+            assert_eq!(cc_out.value2, value2 as i8);
+            // These events are only used for output.
+            assert_eq!(midi(&event), None);
+        }
+    }
+
+    #[test]
+    fn system_messages_are_not_converted() {
+        assert!(midi_to_event(0, 0, RawMidiEvent::new(&[0xF8])).is_none());
+    }
+
+    #[test]
+    fn note_on_events_with_a_low_velocity_are_not_converted_to_note_off() {
+        let mut event = midi_to_event(0, 0, RawMidiEvent::new(&[0x90, 60, 100])).unwrap();
+        event.data.note_on.velocity = 0.001;
+        assert_eq!(midi(&event), Some([0x90, 60, 1]));
+    }
+
+    #[test]
+    fn writing_midi_fails_outside_of_process() {
+        let mut host = Vst3Host::new(1);
+        assert_eq!(
+            host.write_midi(0, 0, RawMidiEvent::new(&[0x90, 60, 100])),
+            Err(MidiOutputError::NotProcessing)
+        );
+        assert_eq!(
+            host.write_sysex(0, 0, &[0xF0, 0x01, 0xF7]),
+            Err(MidiOutputError::NotProcessing)
+        );
+        assert!(host.sysex_data.is_empty());
+        assert_eq!(
+            host.write_midi(1, 0, RawMidiEvent::new(&[0x90, 60, 100])),
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index: 1,
+                number_of_ports: 1
+            })
+        );
+    }
+
+    #[test]
+    fn a_host_can_instantiate_the_plugin_and_process_a_block() {
+        unsafe {
+            assert!(ModuleEntry(ptr::null_mut()));
+            let factory = GetPluginFactory();
+            let factory_vtable = vtable::<IPluginFactory2Vtbl>(factory);
+
+            let mut factory2 = ptr::null_mut();
+            assert_eq!(
+                (factory_vtable.unknown.query_interface)(
+                    factory,
+                    &IPluginFactory2_iid,
+                    &mut factory2
+                ),
+                kResultOk
+            );
+            assert_eq!(factory2, factory);
+
+            let mut factory_info: PFactoryInfo = mem::zeroed();
+            assert_eq!(
+                (factory_vtable.get_factory_info)(factory, &mut factory_info),
+                kResultOk
+            );
+            assert_eq!(c_str(&factory_info.vendor), "rsynth");
+            assert_eq!(factory_info.flags, kUnicode);
+
+            assert_eq!((factory_vtable.count_classes)(factory), 1);
+            let mut class_info: PClassInfo2 = mem::zeroed();
+            assert_eq!(
+                (factory_vtable.get_class_info2)(factory, 0, &mut class_info),
+                kResultOk
+            );
+            assert_eq!(&class_info.cid, b"rsynth-test-vst3");
+            assert_eq!(c_str(&class_info.name), "test plugin");
+            assert_eq!(c_str(&class_info.category), kVstAudioEffectClass);
+            assert_eq!(c_str(&class_info.sub_categories), "Instrument|Synth");
+
+            // Unknown classes and interfaces are refused.
+            let mut component = ptr::null_mut();
+            let unknown_class = [0u8; 16];
+            assert_eq!(
+                (factory_vtable.create_instance)(
+                    factory,
+                    unknown_class.as_ptr() as FIDString,
+                    IComponent_iid.as_ptr() as FIDString,
+                    &mut component
+                ),
+                kNoInterface
+            );
+            assert_eq!(
+                (factory_vtable.create_instance)(
+                    factory,
+                    class_info.cid.as_ptr() as FIDString,
+                    IEventList_iid.as_ptr() as FIDString,
+                    &mut component
+                ),
+                kNoInterface
+            );
+            assert!(component.is_null());
+
+            assert_eq!(
+                (factory_vtable.create_instance)(
+                    factory,
+                    class_info.cid.as_ptr() as FIDString,
+                    IComponent_iid.as_ptr() as FIDString,
+                    &mut component
+                ),
+                kResultOk
+            );
+            let component_vtable = vtable::<IComponentVtbl>(component);
+            assert_eq!(
+                (component_vtable.initialize)(component, ptr::null_mut()),
+                kResultOk
+            );
+
+            // Saving and restoring the state is not supported.
+            assert_eq!(
+                (component_vtable.get_state)(component, ptr::null_mut()),
+                kNotImplemented
+            );
+            assert_eq!(
+                (component_vtable.set_state)(component, ptr::null_mut()),
+                kNotImplemented
+            );
+
+            // Buses.
+            assert_eq!(
+                (component_vtable.get_bus_count)(component, kAudio, kInput),
+                1
+            );
+            assert_eq!(
+                (component_vtable.get_bus_count)(component, kAudio, kOutput),
+                1
+            );
+            assert_eq!(
+                (component_vtable.get_bus_count)(component, kEvent, kInput),
+                1
+            );
+            assert_eq!(
+                (component_vtable.get_bus_count)(component, kEvent, kOutput),
+                1
+            );
+            let mut bus_info: BusInfo = mem::zeroed();
+            assert_eq!(
+                (component_vtable.get_bus_info)(component, kAudio, kInput, 0, &mut bus_info),
+                kResultOk
+            );
+            assert_eq!(String::from_utf16_lossy(&bus_info.name[..3]), "in\0");
+            assert_eq!(bus_info.channel_count, 1);
+            assert_eq!(bus_info.bus_type, kMain);
+            assert_eq!(
+                (component_vtable.get_bus_info)(component, kAudio, kOutput, 0, &mut bus_info),
+                kResultOk
+            );
+            assert_eq!(
+                String::from_utf16_lossy(&bus_info.name[..12]),
+                "left / right"
+            );
+            assert_eq!(bus_info.name[12], 0);
+            assert_eq!(bus_info.channel_count, 2);
+            assert_eq!(bus_info.bus_type, kMain);
+            assert_eq!(
+                (component_vtable.get_bus_info)(component, kAudio, kOutput, 1, &mut bus_info),
+                kInvalidArgument
+            );
+            assert_eq!(
+                (component_vtable.get_bus_info)(component, kEvent, kInput, 0, &mut bus_info),
+                kResultOk
+            );
+            assert_eq!(String::from_utf16_lossy(&bus_info.name[..7]), "midi in");
+            assert_eq!(bus_info.channel_count, 16);
+            assert_eq!(bus_info.bus_type, kMain);
+            assert_eq!(
+                (component_vtable.get_bus_info)(component, kEvent, kInput, 1, &mut bus_info),
+                kInvalidArgument
+            );
+
+            let mut processor = ptr::null_mut();
+            assert_eq!(
+                (component_vtable.unknown.query_interface)(
+                    component,
+                    &IAudioProcessor_iid,
+                    &mut processor
+                ),
+                kResultOk
+            );
+            let processor_vtable = vtable::<IAudioProcessorVtbl>(processor);
+
+            let mut mono = [kSpeakerM; 2];
+            let mut stereo = [kStereo];
+            assert_eq!(
+                (processor_vtable.set_bus_arrangements)(
+                    processor,
+                    mono.as_mut_ptr(),
+                    1,
+                    stereo.as_mut_ptr(),
+                    1
+                ),
+                kResultTrue
+            );
+            assert_eq!(
+                (processor_vtable.set_bus_arrangements)(
+                    processor,
+                    mono.as_mut_ptr(),
+                    1,
+                    mono.as_mut_ptr(),
+                    2
+                ),
+                kResultFalse
+            );
+            let mut arrangement = 0;
+            assert_eq!(
+                (processor_vtable.get_bus_arrangement)(processor, kOutput, 0, &mut arrangement),
+                kResultOk
+            );
+            assert_eq!(arrangement, kStereo);
+            assert_eq!(
+                (processor_vtable.can_process_sample_size)(processor, kSample64),
+                kResultFalse
+            );
+
+            let mut setup = ProcessSetup {
+                process_mode: 0,
+                symbolic_sample_size: kSample32,
+                max_samples_per_block: 8,
+                sample_rate: 48000.0,
+            };
+            assert_eq!(
+                (processor_vtable.setup_processing)(processor, &mut setup),
+                kResultOk
+            );
+            assert_eq!((processor_vtable.get_latency_samples)(processor), 48);
+            assert_eq!((component_vtable.set_active)(component, 1), kResultOk);
+            assert_eq!((processor_vtable.set_processing)(processor, 1), kResultOk);
+
+            // Process a block.
+            let mut input = [1.0f32, 2.0, 3.0, 4.0];
+            let mut left = [0.0f32; 4];
+            let mut right = [0.0f32; 4];
+            let mut input_channels = [input.as_mut_ptr() as *mut c_void];
+            let mut output_channels = [
+                left.as_mut_ptr() as *mut c_void,
+                right.as_mut_ptr() as *mut c_void,
+            ];
+            let mut inputs = [AudioBusBuffers {
+                num_channels: 1,
+                silence_flags: 0,
+                channel_buffers: input_channels.as_mut_ptr(),
+            }];
+            let mut outputs = [AudioBusBuffers {
+                num_channels: 2,
+                silence_flags: 0,
+                channel_buffers: output_channels.as_mut_ptr(),
+            }];
+            let sysex = [0xF0, 0x01, 0x02, 0xF7];
+            let mut sysex_event = midi_to_event(0, 3, RawMidiEvent::new(&[0x90, 0, 1])).unwrap();
+            sysex_event.type_ = kDataEvent;
+            sysex_event.data = EventData {
+                data: DataEvent {
+                    size: sysex.len() as u32,
+                    type_: kMidiSysEx,
+                    bytes: sysex.as_ptr(),
+                },
+            };
+            let mut input_events = TestEventList::new(vec![
+                midi_to_event(0, 1, RawMidiEvent::new(&[0x90, 60, 127])).unwrap(),
+                sysex_event,
+                // This event does not fit in the output list; this is logged.
+                midi_to_event(0, 3, RawMidiEvent::new(&[0x80, 60, 0])).unwrap(),
+            ]);
+            let mut output_events = TestEventList::new(Vec::new());
+            let mut context: ProcessContext = mem::zeroed();
+            context.state = kPlaying | kTempoValid;
+            context.tempo = 120.0;
+            let mut data = ProcessData {
+                process_mode: 0,
+                symbolic_sample_size: kSample32,
+                num_samples: 4,
+                num_inputs: 1,
+                num_outputs: 1,
+                inputs: inputs.as_mut_ptr(),
+                outputs: outputs.as_mut_ptr(),
+                input_parameter_changes: ptr::null_mut(),
+                output_parameter_changes: ptr::null_mut(),
+                input_events: input_events.as_ptr(),
+                output_events: output_events.as_ptr(),
+                process_context: &mut context,
+            };
+            assert_eq!((processor_vtable.process)(processor, &mut data), kResultOk);
+
+            // Only the first output is used.
+            assert_eq!(left, [2.0, 4.0, 6.0, 8.0]);
+            assert_eq!(right, [0.0; 4]);
+
+            assert_eq!(output_events.events.len(), 2);
+            assert_eq!(midi(&output_events.events[0]), Some([0x90, 60, 127]));
+            assert_eq!(output_events.events[0].sample_offset, 1);
+            let echoed_sysex = output_events.events[1];
+            assert_eq!(echoed_sysex.type_, kDataEvent);
+            assert_eq!(echoed_sysex.sample_offset, 3);
+            let echoed_data = echoed_sysex.data.data;
+            assert_eq!(
+                slice::from_raw_parts(echoed_data.bytes, echoed_data.size as usize),
+                &sysex
+            );
+
+            assert_eq!((processor_vtable.set_processing)(processor, 0), kResultOk);
+            assert_eq!((component_vtable.set_active)(component, 0), kResultOk);
+            assert_eq!((component_vtable.terminate)(component), kResultOk);
+            assert_eq!((processor_vtable.unknown.release)(processor), 1);
+            assert_eq!((component_vtable.unknown.release)(component), 0);
+            assert!(ModuleExit());
+        }
+    }
+
+    // This loads the `vst3_synth` example in the same way as a host would do.
+    // Build the examples first, so that the library is not missing or outdated.
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "loads the example plugin; run `cargo build --examples --features backend-vst3` and then `cargo test --features backend-vst3 -- --ignored`"]
+    fn a_host_can_load_the_example_plugin() {
+        let executable = std::env::current_exe().unwrap();
+        let target_dir = executable.parent().unwrap().parent().unwrap();
+        let library = target_dir.join("examples").join("libvst3_synth.so");
+        assert!(
+            library.exists(),
+            "{} does not exist; run `cargo build --examples` first.",
+            library.display()
+        );
+
+        unsafe {
+            let library = libloading::Library::new(&library).unwrap();
+            let module_entry: libloading::Symbol<unsafe extern "C" fn(*mut c_void) -> bool> =
+                library.get(b"ModuleEntry\0").unwrap();
+            assert!(module_entry(ptr::null_mut()));
+            let get_plugin_factory: libloading::Symbol<unsafe extern "C" fn() -> *mut c_void> =
+                library.get(b"GetPluginFactory\0").unwrap();
+            let factory = get_plugin_factory();
+            let factory_vtable = vtable::<IPluginFactory2Vtbl>(factory);
+            let mut class_info: PClassInfo = mem::zeroed();
+            assert_eq!(
+                (factory_vtable.get_class_info)(factory, 0, &mut class_info),
+                kResultOk
+            );
+            assert_eq!(&class_info.cid, b"rsynth.noise.vst");
+
+            let mut processor = ptr::null_mut();
+            assert_eq!(
+                (factory_vtable.create_instance)(
+                    factory,
+                    class_info.cid.as_ptr() as FIDString,
+                    IAudioProcessor_iid.as_ptr() as FIDString,
+                    &mut processor
+                ),
+                kResultOk
+            );
+            let processor_vtable = vtable::<IAudioProcessorVtbl>(processor);
+            let mut setup = ProcessSetup {
+                process_mode: 0,
+                symbolic_sample_size: kSample32,
+                max_samples_per_block: 16,
+                sample_rate: 44100.0,
+            };
+            assert_eq!(
+                (processor_vtable.setup_processing)(processor, &mut setup),
+                kResultOk
+            );
+
+            let mut input_events = TestEventList::new(vec![midi_to_event(
+                0,
+                4,
+                RawMidiEvent::new(&[0x90, 60, 100]),
+            )
+            .unwrap()]);
+            let mut left = [0.0f32; 16];
+            let mut right = [0.0f32; 16];
+            let mut output_channels = [
+                left.as_mut_ptr() as *mut c_void,
+                right.as_mut_ptr() as *mut c_void,
+            ];
+            let mut outputs = [AudioBusBuffers {
+                num_channels: 2,
+                silence_flags: 0,
+                channel_buffers: output_channels.as_mut_ptr(),
+            }];
+            let mut data = ProcessData {
+                process_mode: 0,
+                symbolic_sample_size: kSample32,
+                num_samples: 16,
+                num_inputs: 0,
+                num_outputs: 1,
+                inputs: ptr::null_mut(),
+                outputs: outputs.as_mut_ptr(),
+                input_parameter_changes: ptr::null_mut(),
+                output_parameter_changes: ptr::null_mut(),
+                input_events: input_events.as_ptr(),
+                output_events: ptr::null_mut(),
+                process_context: ptr::null_mut(),
+            };
+            assert_eq!((processor_vtable.process)(processor, &mut data), kResultOk);
+            assert!(left.iter().any(|sample| *sample != 0.0));
+
+            assert_eq!((processor_vtable.unknown.release)(processor), 0);
+        }
+    }
+}
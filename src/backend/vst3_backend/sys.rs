@@ -0,0 +1,390 @@
+//! The parts of the VST3 API that are used by the VST3 backend.
+//!
+//! VST3 interfaces are C++ classes with only pure virtual methods. Here, each interface is
+//! represented by a `#[repr(C)]` struct with the function pointers of its virtual table, in the
+//! order in which the methods are declared in the VST3 SDK (including the methods of the base
+//! interfaces). A pointer to an interface is a pointer to an object that starts with a pointer
+//! to such a table.
+//!
+//! The interface identifiers and the result codes are those of the non-Windows platforms.
+//! The names of the types and constants follow the VST3 SDK, the names of the fields are
+//! converted to snake case.
+#![allow(non_camel_case_types, non_upper_case_globals)]
+use std::os::raw::{c_char, c_void};
+
+/// The result of a method call.
+pub type tresult = i32;
+/// A boolean: `0` is false, everything else is true.
+pub type TBool = u8;
+/// A unique identifier of an interface or a class.
+pub type TUID = [u8; 16];
+/// A pointer to a `TUID`.
+pub type FIDString = *const c_char;
+/// A 16-bit character, as used by the VST3 SDK.
+pub type TChar = u16;
+/// A string of 128 16-bit characters.
+pub type String128 = [TChar; 128];
+/// A bit set of speakers.
+pub type SpeakerArrangement = u64;
+
+pub const kResultOk: tresult = 0;
+pub const kResultTrue: tresult = kResultOk;
+pub const kResultFalse: tresult = 1;
+pub const kInvalidArgument: tresult = 2;
+pub const kNotImplemented: tresult = 3;
+pub const kNoInterface: tresult = -1;
+
+/// Create a `TUID` from four 32-bit integers, as the `INLINE_UID` macro of the SDK does.
+pub const fn inline_uid(l1: u32, l2: u32, l3: u32, l4: u32) -> TUID {
+    let (a, b, c, d) = (
+        l1.to_be_bytes(),
+        l2.to_be_bytes(),
+        l3.to_be_bytes(),
+        l4.to_be_bytes(),
+    );
+    [
+        a[0], a[1], a[2], a[3], b[0], b[1], b[2], b[3], c[0], c[1], c[2], c[3], d[0], d[1], d[2],
+        d[3],
+    ]
+}
+
+pub const FUnknown_iid: TUID = inline_uid(0x00000000, 0x00000000, 0xC0000000, 0x00000046);
+pub const IPluginBase_iid: TUID = inline_uid(0x22888DDB, 0x156E45AE, 0x8358B348, 0x08190625);
+pub const IPluginFactory_iid: TUID = inline_uid(0x7A4D811C, 0x52114A1F, 0xAED9D2EE, 0x0B43BF9F);
+pub const IPluginFactory2_iid: TUID = inline_uid(0x0007B650, 0xF24B4C0B, 0xA464EDB9, 0xF00B2ABB);
+pub const IComponent_iid: TUID = inline_uid(0xE831FF31, 0xF2D54301, 0x928EBBEE, 0x25697802);
+pub const IAudioProcessor_iid: TUID = inline_uid(0x42043F99, 0xB7DA453C, 0xA569E79D, 0x9AAEC33D);
+pub const IEventList_iid: TUID = inline_uid(0x3A2C4214, 0x346349FE, 0xB2C4F397, 0xB9695A44);
+
+/// The virtual table of `FUnknown`, the base of all interfaces.
+#[repr(C)]
+pub struct FUnknownVtbl {
+    pub query_interface:
+        unsafe extern "C" fn(this: *mut c_void, iid: *const TUID, obj: *mut *mut c_void) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this: *mut c_void) -> u32,
+    pub release: unsafe extern "C" fn(this: *mut c_void) -> u32,
+}
+
+// Factory
+
+pub const kUnicode: i32 = 1 << 4;
+pub const kManyInstances: i32 = 0x7FFF_FFFF;
+pub const kVstAudioEffectClass: &str = "Audio Module Class";
+pub const kVstVersionString: &str = "VST 3.7.0";
+
+#[repr(C)]
+pub struct PFactoryInfo {
+    pub vendor: [c_char; 64],
+    pub url: [c_char; 256],
+    pub email: [c_char; 128],
+    pub flags: i32,
+}
+
+#[repr(C)]
+pub struct PClassInfo {
+    pub cid: TUID,
+    pub cardinality: i32,
+    pub category: [c_char; 32],
+    pub name: [c_char; 64],
+}
+
+#[repr(C)]
+pub struct PClassInfo2 {
+    pub cid: TUID,
+    pub cardinality: i32,
+    pub category: [c_char; 32],
+    pub name: [c_char; 64],
+    pub class_flags: u32,
+    pub sub_categories: [c_char; 128],
+    pub vendor: [c_char; 64],
+    pub version: [c_char; 64],
+    pub sdk_version: [c_char; 64],
+}
+
+#[repr(C)]
+pub struct IPluginFactory2Vtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_factory_info:
+        unsafe extern "C" fn(this: *mut c_void, info: *mut PFactoryInfo) -> tresult,
+    pub count_classes: unsafe extern "C" fn(this: *mut c_void) -> i32,
+    pub get_class_info:
+        unsafe extern "C" fn(this: *mut c_void, index: i32, info: *mut PClassInfo) -> tresult,
+    pub create_instance: unsafe extern "C" fn(
+        this: *mut c_void,
+        cid: FIDString,
+        iid: FIDString,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub get_class_info2:
+        unsafe extern "C" fn(this: *mut c_void, index: i32, info: *mut PClassInfo2) -> tresult,
+}
+
+// Component
+
+pub const kAudio: i32 = 0;
+pub const kEvent: i32 = 1;
+pub const kInput: i32 = 0;
+pub const kOutput: i32 = 1;
+pub const kMain: i32 = 0;
+pub const kAux: i32 = 1;
+pub const kDefaultActive: u32 = 1;
+
+#[repr(C)]
+pub struct BusInfo {
+    pub media_type: i32,
+    pub direction: i32,
+    pub channel_count: i32,
+    pub name: String128,
+    pub bus_type: i32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+pub struct RoutingInfo {
+    pub media_type: i32,
+    pub bus_index: i32,
+    pub channel: i32,
+}
+
+#[repr(C)]
+pub struct IComponentVtbl {
+    pub unknown: FUnknownVtbl,
+    // IPluginBase
+    pub initialize: unsafe extern "C" fn(this: *mut c_void, context: *mut c_void) -> tresult,
+    pub terminate: unsafe extern "C" fn(this: *mut c_void) -> tresult,
+    // IComponent
+    pub get_controller_class_id:
+        unsafe extern "C" fn(this: *mut c_void, class_id: *mut TUID) -> tresult,
+    pub set_io_mode: unsafe extern "C" fn(this: *mut c_void, mode: i32) -> tresult,
+    pub get_bus_count: unsafe extern "C" fn(this: *mut c_void, type_: i32, dir: i32) -> i32,
+    pub get_bus_info: unsafe extern "C" fn(
+        this: *mut c_void,
+        type_: i32,
+        dir: i32,
+        index: i32,
+        bus: *mut BusInfo,
+    ) -> tresult,
+    pub get_routing_info: unsafe extern "C" fn(
+        this: *mut c_void,
+        in_info: *mut RoutingInfo,
+        out_info: *mut RoutingInfo,
+    ) -> tresult,
+    pub activate_bus: unsafe extern "C" fn(
+        this: *mut c_void,
+        type_: i32,
+        dir: i32,
+        index: i32,
+        state: TBool,
+    ) -> tresult,
+    pub set_active: unsafe extern "C" fn(this: *mut c_void, state: TBool) -> tresult,
+    pub set_state: unsafe extern "C" fn(this: *mut c_void, state: *mut c_void) -> tresult,
+    pub get_state: unsafe extern "C" fn(this: *mut c_void, state: *mut c_void) -> tresult,
+}
+
+// Audio processor
+
+pub const kSample32: i32 = 0;
+pub const kSample64: i32 = 1;
+pub const kSpeakerL: SpeakerArrangement = 1 << 0;
+pub const kSpeakerR: SpeakerArrangement = 1 << 1;
+pub const kSpeakerM: SpeakerArrangement = 1 << 19;
+pub const kStereo: SpeakerArrangement = kSpeakerL | kSpeakerR;
+pub const kNoTail: u32 = 0;
+
+#[repr(C)]
+pub struct ProcessSetup {
+    pub process_mode: i32,
+    pub symbolic_sample_size: i32,
+    pub max_samples_per_block: i32,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct AudioBusBuffers {
+    pub num_channels: i32,
+    pub silence_flags: u64,
+    /// `channelBuffers32` or `channelBuffers64`, depending on the symbolic sample size.
+    pub channel_buffers: *mut *mut c_void,
+}
+
+pub const kPlaying: u32 = 1 << 1;
+pub const kProjectTimeMusicValid: u32 = 1 << 9;
+pub const kTempoValid: u32 = 1 << 10;
+pub const kBarPositionValid: u32 = 1 << 11;
+pub const kTimeSigValid: u32 = 1 << 13;
+
+#[repr(C)]
+pub struct Chord {
+    pub key_note: u8,
+    pub root_note: u8,
+    pub chord_mask: i16,
+}
+
+#[repr(C)]
+pub struct FrameRate {
+    pub frames_per_second: u32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+pub struct ProcessContext {
+    pub state: u32,
+    pub sample_rate: f64,
+    pub project_time_samples: i64,
+    pub system_time: i64,
+    pub continous_time_samples: i64,
+    pub project_time_music: f64,
+    pub bar_position_music: f64,
+    pub cycle_start_music: f64,
+    pub cycle_end_music: f64,
+    pub tempo: f64,
+    pub time_sig_numerator: i32,
+    pub time_sig_denominator: i32,
+    pub chord: Chord,
+    pub smpte_offset_subframes: i32,
+    pub frame_rate: FrameRate,
+    pub samples_to_next_clock: i32,
+}
+
+#[repr(C)]
+pub struct ProcessData {
+    pub process_mode: i32,
+    pub symbolic_sample_size: i32,
+    pub num_samples: i32,
+    pub num_inputs: i32,
+    pub num_outputs: i32,
+    pub inputs: *mut AudioBusBuffers,
+    pub outputs: *mut AudioBusBuffers,
+    pub input_parameter_changes: *mut c_void,
+    pub output_parameter_changes: *mut c_void,
+    /// Pointer to an `IEventList`.
+    pub input_events: *mut c_void,
+    /// Pointer to an `IEventList`.
+    pub output_events: *mut c_void,
+    pub process_context: *mut ProcessContext,
+}
+
+#[repr(C)]
+pub struct IAudioProcessorVtbl {
+    pub unknown: FUnknownVtbl,
+    pub set_bus_arrangements: unsafe extern "C" fn(
+        this: *mut c_void,
+        inputs: *mut SpeakerArrangement,
+        num_ins: i32,
+        outputs: *mut SpeakerArrangement,
+        num_outs: i32,
+    ) -> tresult,
+    pub get_bus_arrangement: unsafe extern "C" fn(
+        this: *mut c_void,
+        dir: i32,
+        index: i32,
+        arr: *mut SpeakerArrangement,
+    ) -> tresult,
+    pub can_process_sample_size:
+        unsafe extern "C" fn(this: *mut c_void, symbolic_sample_size: i32) -> tresult,
+    pub get_latency_samples: unsafe extern "C" fn(this: *mut c_void) -> u32,
+    pub setup_processing:
+        unsafe extern "C" fn(this: *mut c_void, setup: *mut ProcessSetup) -> tresult,
+    pub set_processing: unsafe extern "C" fn(this: *mut c_void, state: TBool) -> tresult,
+    pub process: unsafe extern "C" fn(this: *mut c_void, data: *mut ProcessData) -> tresult,
+    pub get_tail_samples: unsafe extern "C" fn(this: *mut c_void) -> u32,
+}
+
+// Events
+
+pub const kNoteOnEvent: u16 = 0;
+pub const kNoteOffEvent: u16 = 1;
+pub const kDataEvent: u16 = 2;
+pub const kPolyPressureEvent: u16 = 3;
+pub const kLegacyMIDICCOutEvent: u16 = 65535;
+pub const kMidiSysEx: u32 = 0;
+pub const kAfterTouch: u8 = 128;
+pub const kPitchBend: u8 = 129;
+pub const kCtrlProgramChange: u8 = 130;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoteOnEvent {
+    pub channel: i16,
+    pub pitch: i16,
+    pub tuning: f32,
+    pub velocity: f32,
+    pub length: i32,
+    pub note_id: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoteOffEvent {
+    pub channel: i16,
+    pub pitch: i16,
+    pub velocity: f32,
+    pub note_id: i32,
+    pub tuning: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DataEvent {
+    pub size: u32,
+    pub type_: u32,
+    pub bytes: *const u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PolyPressureEvent {
+    pub channel: i16,
+    pub pitch: i16,
+    pub pressure: f32,
+    pub note_id: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LegacyMIDICCOutEvent {
+    pub control_number: u8,
+    pub channel: i8,
+    pub value: i8,
+    pub value2: i8,
+}
+
+/// The event-specific data of an `Event`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union EventData {
+    pub note_on: NoteOnEvent,
+    pub note_off: NoteOffEvent,
+    pub data: DataEvent,
+    pub poly_pressure: PolyPressureEvent,
+    pub midi_cc_out: LegacyMIDICCOutEvent,
+    /// The size of the largest event type of the SDK.
+    pub _padding: [u64; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Event {
+    pub bus_index: i32,
+    pub sample_offset: i32,
+    pub ppq_position: f64,
+    pub flags: u16,
+    pub type_: u16,
+    pub data: EventData,
+}
+
+#[repr(C)]
+pub struct IEventListVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_event_count: unsafe extern "C" fn(this: *mut c_void) -> i32,
+    pub get_event: unsafe extern "C" fn(this: *mut c_void, index: i32, e: *mut Event) -> tresult,
+    pub add_event: unsafe extern "C" fn(this: *mut c_void, e: *mut Event) -> tresult,
+}
+
+/// Get the virtual table of an interface pointer.
+///
+/// # Safety
+/// `this` must point to an object that starts with a pointer to a `V`.
+pub unsafe fn vtable<'a, V>(this: *mut c_void) -> &'a V {
+    &**(this as *mut *const V)
+}
@@ -12,13 +12,22 @@
 //!
 //! [`vst_init`]: ../../macro.vst_init.html
 //! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
-use crate::backend::HostInterface;
+use crate::backend::{
+    HostInterface, MidiOutput, MidiOutputError, TimeSignature, Transport, TransportInfo,
+};
 use crate::buffer::AudioBufferInOut;
-use crate::event::{ContextualEventHandler, RawMidiEvent, SysExEvent, Timed};
+use crate::event::event_queue::{AlwaysInsertNewAfterOld, EventQueue};
+use crate::event::{
+    ContextualEventHandler, EventHandler, ParameterChange, RawMidiEvent, SysExEvent, Timed,
+};
+use crate::parameters::{ChangedParameters, Parameters};
+use crate::state::{self, PluginState};
 use crate::{
     AudioHandler, AudioHandlerMeta, CommonAudioPortMeta, CommonPluginMeta, ContextualAudioRenderer,
+    ParameterHandler,
 };
 use core::cmp;
+use std::sync::Arc;
 use vecstorage::VecStorage;
 
 /// Re-exports from the [`vst-rs`](https://github.com/RustAudio/vst-rs) crate.
@@ -29,13 +38,187 @@ pub mod vst {
 }
 
 use self::vst::{
-    api::Events,
-    buffer::AudioBuffer,
+    api::{Events, Supported, TimeInfoFlags},
+    buffer::{AudioBuffer, SendEventBuffer},
     channels::ChannelInfo,
     event::{Event as VstEvent, MidiEvent as VstMidiEvent, SysExEvent as VstSysExEvent},
-    plugin::{Category, HostCallback, Info},
+    host::Host,
+    plugin::{CanDo, Category, HostCallback, Info, PluginParameters},
 };
 
+/// The maximum number of midi and SysEx events that can be sent to the host per buffer.
+const MAX_NUMBER_OF_OUTGOING_EVENTS: usize = 1024;
+/// The maximum total size (in bytes) of the SysEx events that can be sent to the host per buffer.
+const MAX_SYSEX_DATA_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy)]
+enum OutgoingEvent {
+    Midi(RawMidiEvent),
+    /// A SysEx event; the data is stored in `VstHost::sysex_data` at the given range.
+    SysEx {
+        start: usize,
+        end: usize,
+    },
+}
+
+/// Used to communicate with the VST host.
+///
+/// This is the "context" that is passed to the plugin when rendering audio and handling events.
+/// It can be used to send midi events and SysEx events to the host: implement your plugin
+/// generically over the context and require `EventHandler<Timed<RawMidiEvent>>` or
+/// `EventHandler<Timed<SysExEvent>>` for it.
+/// The events are buffered and sent to the host at the end of the current buffer.
+///
+/// Use [`host_callback`] to access VST-specific features.
+///
+/// [`host_callback`]: ./struct.VstHost.html#method.host_callback
+pub struct VstHost {
+    host: HostCallback,
+    outgoing_events: EventQueue<OutgoingEvent>,
+    sysex_data: Vec<u8>,
+    send_buffer: SendEventBuffer,
+}
+
+impl VstHost {
+    fn new(host: HostCallback) -> Self {
+        Self {
+            host,
+            outgoing_events: EventQueue::new(MAX_NUMBER_OF_OUTGOING_EVENTS),
+            sysex_data: Vec::with_capacity(MAX_SYSEX_DATA_SIZE),
+            send_buffer: SendEventBuffer::new(MAX_NUMBER_OF_OUTGOING_EVENTS),
+        }
+    }
+
+    /// Get access to the underlying [`HostCallback`] so that you can use VST-specific features.
+    ///
+    /// [`HostCallback`]: ./vst/plugin/struct.HostCallback.html
+    pub fn host_callback(&self) -> &HostCallback {
+        &self.host
+    }
+
+    fn is_full(&self) -> bool {
+        self.outgoing_events.len() >= MAX_NUMBER_OF_OUTGOING_EVENTS
+    }
+
+    fn queue_event(&mut self, event: Timed<OutgoingEvent>) -> Result<(), MidiOutputError> {
+        if self.is_full() {
+            return Err(MidiOutputError::BufferFull);
+        }
+        match self
+            .outgoing_events
+            .queue_event(event, AlwaysInsertNewAfterOld)
+        {
+            Some(_) => Err(MidiOutputError::BufferFull),
+            None => Ok(()),
+        }
+    }
+
+    fn check_port_index(port_index: usize) -> Result<(), MidiOutputError> {
+        if port_index == 0 {
+            Ok(())
+        } else {
+            Err(MidiOutputError::PortOutOfBounds {
+                port_index,
+                number_of_ports: 1,
+            })
+        }
+    }
+
+    /// Send the buffered events to the host.
+    fn flush_events(&mut self) {
+        if self.outgoing_events.is_empty() {
+            return;
+        }
+        let sysex_data = &self.sysex_data;
+        let events = self.outgoing_events.iter().map(|event| {
+            let delta_frames = event.time_in_frames as i32;
+            match event.event {
+                OutgoingEvent::Midi(midi_event) => VstEvent::Midi(VstMidiEvent {
+                    data: *midi_event.data(),
+                    delta_frames,
+                    live: false,
+                    note_length: None,
+                    note_offset: None,
+                    detune: 0,
+                    note_off_velocity: 0,
+                }),
+                OutgoingEvent::SysEx { start, end } => VstEvent::SysEx(VstSysExEvent {
+                    payload: &sysex_data[start..end],
+                    delta_frames,
+                }),
+            }
+        });
+        self.send_buffer.send_events(events, &mut self.host);
+        self.outgoing_events.clear();
+        self.sysex_data.clear();
+    }
+}
+
+impl HostInterface for VstHost {
+    fn output_initialized(&self) -> bool {
+        self.host.output_initialized()
+    }
+}
+
+impl TransportInfo for VstHost {
+    fn transport(&self) -> Transport {
+        self.host.transport()
+    }
+}
+
+/// VST 2.4 has only one midi output port, with index `0`.
+impl MidiOutput for VstHost {
+    fn number_of_midi_output_ports(&self) -> usize {
+        1
+    }
+
+    fn write_midi(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        event: RawMidiEvent,
+    ) -> Result<(), MidiOutputError> {
+        Self::check_port_index(port_index)?;
+        self.queue_event(Timed::new(time_in_frames, OutgoingEvent::Midi(event)))
+    }
+
+    fn write_sysex(
+        &mut self,
+        port_index: usize,
+        time_in_frames: u32,
+        data: &[u8],
+    ) -> Result<(), MidiOutputError> {
+        Self::check_port_index(port_index)?;
+        let start = self.sysex_data.len();
+        let end = start + data.len();
+        if end > self.sysex_data.capacity() || self.is_full() {
+            // Do not allocate in the real-time thread.
+            return Err(MidiOutputError::BufferFull);
+        }
+        self.sysex_data.extend_from_slice(data);
+        self.queue_event(Timed::new(
+            time_in_frames,
+            OutgoingEvent::SysEx { start, end },
+        ))
+    }
+}
+
+impl EventHandler<Timed<RawMidiEvent>> for VstHost {
+    fn handle_event(&mut self, event: Timed<RawMidiEvent>) {
+        if let Err(e) = self.write_midi(0, event.time_in_frames, event.event) {
+            error!("Failed to send midi event: {}", e);
+        }
+    }
+}
+
+impl<'a> EventHandler<Timed<SysExEvent<'a>>> for VstHost {
+    fn handle_event(&mut self, event: Timed<SysExEvent<'a>>) {
+        if let Err(e) = self.write_sysex(0, event.time_in_frames, event.event.data()) {
+            error!("Failed to send SysEx event: {}", e);
+        }
+    }
+}
+
 /// Define some VST-specific meta-data for a VST plugin.
 pub trait VstPluginMeta: CommonPluginMeta + AudioHandlerMeta {
     fn plugin_id(&self) -> i32;
@@ -46,11 +229,12 @@ pub trait VstPluginMeta: CommonPluginMeta + AudioHandlerMeta {
 // //! [`vst_init`]: ../../macro.vst_init.html
 pub struct VstPluginWrapper<P> {
     plugin: P,
-    host: HostCallback,
+    host: VstHost,
     inputs_f32: VecStorage<&'static [f32]>,
     outputs_f32: VecStorage<&'static [f32]>,
     inputs_f64: VecStorage<&'static [f64]>,
     outputs_f64: VecStorage<&'static [f64]>,
+    changed_parameters: Arc<ChangedParameters>,
 }
 
 impl<P> VstPluginWrapper<P>
@@ -58,10 +242,13 @@ where
     P: CommonAudioPortMeta
         + VstPluginMeta
         + AudioHandler
-        + ContextualEventHandler<Timed<RawMidiEvent>, HostCallback>
-        + ContextualAudioRenderer<f32, HostCallback>
-        + ContextualAudioRenderer<f64, HostCallback>,
-    for<'a> P: ContextualEventHandler<Timed<SysExEvent<'a>>, HostCallback>,
+        + ParameterHandler
+        + ContextualEventHandler<Timed<RawMidiEvent>, VstHost>
+        + ContextualEventHandler<Timed<ParameterChange>, VstHost>
+        + ContextualAudioRenderer<f32, VstHost>
+        + ContextualAudioRenderer<f64, VstHost>,
+    for<'a> P: ContextualEventHandler<Timed<SysExEvent<'a>>, VstHost>,
+    P::Parameters: PluginState,
 {
     pub fn get_info(&self) -> Info {
         trace!("get_info");
@@ -71,6 +258,9 @@ where
             outputs: self.plugin.max_number_of_audio_outputs() as i32,
             unique_id: self.plugin.plugin_id(),
             category: self.plugin.category(),
+            parameters: self.plugin.parameters().number_of_parameters() as i32,
+            preset_chunks: true,
+            initial_delay: self.plugin.latency_in_frames() as i32,
             ..Info::default()
         }
     }
@@ -87,16 +277,50 @@ where
             outputs_f32: VecStorage::with_capacity(plugin.max_number_of_audio_outputs()),
             inputs_f64: VecStorage::with_capacity(plugin.max_number_of_audio_inputs()),
             outputs_f64: VecStorage::with_capacity(plugin.max_number_of_audio_outputs()),
+            changed_parameters: Arc::new(ChangedParameters::new(
+                plugin.parameters().number_of_parameters(),
+            )),
             plugin,
-            host,
+            host: VstHost::new(host),
         }
     }
 
+    /// Deliver the parameter changes that the host has made since the previous call
+    /// to the plugin.
+    /// VST 2.4 does not support sample-accurate automation, so the parameter changes
+    /// are delivered with a timestamp of `0`.
+    fn handle_parameter_changes(&mut self) {
+        let parameters = self.plugin.parameters();
+        let plugin = &mut self.plugin;
+        let host = &mut self.host;
+        self.changed_parameters.drain(|index| {
+            let event = Timed {
+                time_in_frames: 0,
+                event: ParameterChange::new(index, parameters.normalized_value(index)),
+            };
+            plugin.handle_event(event, host);
+        });
+    }
+
     pub fn host(&self) -> &HostCallback {
-        &self.host
+        self.host.host_callback()
+    }
+
+    pub fn can_do(&self, can_do: CanDo) -> Supported {
+        trace!("can_do({:?})", can_do);
+        match can_do {
+            CanDo::SendEvents
+            | CanDo::SendMidiEvent
+            | CanDo::ReceiveEvents
+            | CanDo::ReceiveMidiEvent
+            | CanDo::ReceiveSysExEvent
+            | CanDo::ReceiveTimeInfo => Supported::Yes,
+            _ => Supported::Maybe,
+        }
     }
 
     pub fn process<'b>(&mut self, buffer: &mut AudioBuffer<'b, f32>) {
+        self.handle_parameter_changes();
         let number_of_frames = buffer.samples();
         let (input_buffers, mut output_buffers) = buffer.split();
 
@@ -113,9 +337,11 @@ where
         let mut audio_buffer =
             AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
         self.plugin.render_buffer(&mut audio_buffer, &mut self.host);
+        self.host.flush_events();
     }
 
     pub fn process_f64<'b>(&mut self, buffer: &mut AudioBuffer<'b, f64>) {
+        self.handle_parameter_changes();
         let number_of_frames = buffer.samples();
         let (input_buffers, mut output_buffers) = buffer.split();
 
@@ -132,6 +358,7 @@ where
         let mut audio_buffer =
             AudioBufferInOut::new(inputs.as_slice(), outputs.as_mut_slice(), number_of_frames);
         self.plugin.render_buffer(&mut audio_buffer, &mut self.host);
+        self.host.flush_events();
     }
 
     pub fn get_input_info(&self, input_index: i32) -> ChannelInfo {
@@ -191,6 +418,132 @@ where
         trace!("sample_rate: {}", sample_rate);
         self.plugin.set_sample_rate(sample_rate);
     }
+
+    pub fn get_parameter_object(&mut self) -> Arc<dyn PluginParameters> {
+        trace!("get_parameter_object");
+        Arc::new(VstParameters {
+            parameters: self.plugin.parameters(),
+            changed_parameters: self.changed_parameters.clone(),
+        })
+    }
+}
+
+/// Makes the parameters and the state of a plugin available to the VST host.
+///
+/// The state is used for both the preset data and the bank data, since plugins
+/// created with `rsynth` have only one program.
+///
+/// You don't need to use this yourself: it is used internally by the [`vst_init`] macro.
+///
+/// [`vst_init`]: ../../macro.vst_init.html
+pub struct VstParameters<T> {
+    parameters: Arc<T>,
+    changed_parameters: Arc<ChangedParameters>,
+}
+
+impl<T> VstParameters<T>
+where
+    T: Parameters + PluginState,
+{
+    fn index(&self, index: i32) -> Option<usize> {
+        if index >= 0 && (index as usize) < self.parameters.number_of_parameters() {
+            Some(index as usize)
+        } else {
+            error!(
+                "parameter index out of bounds: index is {}, but there are only {} parameters",
+                index,
+                self.parameters.number_of_parameters()
+            );
+            None
+        }
+    }
+
+    fn load_data(&self, data: &[u8]) {
+        match state::load(&*self.parameters, data) {
+            Ok(()) => self.changed_parameters.mark_all_changed(),
+            Err(e) => error!("Failed to load the state: {}.", e),
+        }
+    }
+}
+
+impl<T> PluginParameters for VstParameters<T>
+where
+    T: Parameters + PluginState,
+{
+    fn get_parameter_label(&self, index: i32) -> String {
+        let mut label = String::new();
+        if let Some(index) = self.index(index) {
+            if let Err(e) = self.parameters.parameter_label(&mut label, index) {
+                error!(
+                    "Failed to get the label of parameter with index {}: {}.",
+                    index, e
+                );
+            }
+        }
+        label
+    }
+
+    fn get_parameter_text(&self, index: i32) -> String {
+        let mut text = String::new();
+        if let Some(index) = self.index(index) {
+            let value = self.parameters.normalized_value(index);
+            if let Err(e) = self.parameters.write_value(&mut text, index, value) {
+                error!(
+                    "Failed to get the text of parameter with index {}: {}.",
+                    index, e
+                );
+            }
+        }
+        text
+    }
+
+    fn get_parameter_name(&self, index: i32) -> String {
+        let mut name = String::new();
+        if let Some(index) = self.index(index) {
+            if let Err(e) = self.parameters.parameter_name(&mut name, index) {
+                error!(
+                    "Failed to get the name of parameter with index {}: {}.",
+                    index, e
+                );
+            }
+        }
+        name
+    }
+
+    fn get_parameter(&self, index: i32) -> f32 {
+        match self.index(index) {
+            Some(index) => self.parameters.normalized_value(index),
+            None => 0.0,
+        }
+    }
+
+    fn set_parameter(&self, index: i32, value: f32) {
+        if let Some(index) = self.index(index) {
+            self.parameters
+                .set_normalized_value(index, value.clamp(0.0, 1.0));
+            self.changed_parameters.mark_changed(index);
+        }
+    }
+
+    fn can_be_automated(&self, index: i32) -> bool {
+        self.index(index).is_some()
+    }
+
+    fn get_preset_data(&self) -> Vec<u8> {
+        state::save(&*self.parameters)
+    }
+
+    fn get_bank_data(&self) -> Vec<u8> {
+        state::save(&*self.parameters)
+    }
+
+    fn load_preset_data(&self, data: &[u8]) {
+        self.load_data(data);
+    }
+
+    fn load_bank_data(&self, data: &[u8]) {
+        self.load_data(data);
+    }
 }
 
 impl HostInterface for HostCallback {
@@ -201,6 +554,48 @@ impl HostInterface for HostCallback {
     }
 }
 
+impl TransportInfo for HostCallback {
+    fn transport(&self) -> Transport {
+        let request = TimeInfoFlags::TEMPO_VALID
+            | TimeInfoFlags::PPQ_POS_VALID
+            | TimeInfoFlags::BARS_VALID
+            | TimeInfoFlags::TIME_SIG_VALID;
+        let time_info = match self.get_time_info(request.bits()) {
+            Some(time_info) => time_info,
+            None => return Transport::default(),
+        };
+        let flags = TimeInfoFlags::from_bits_truncate(time_info.flags);
+        let if_valid = |flag, value| {
+            if flags.contains(flag) {
+                Some(value)
+            } else {
+                None
+            }
+        };
+        Transport {
+            is_playing: flags.contains(TimeInfoFlags::TRANSPORT_PLAYING),
+            tempo_in_beats_per_minute: if_valid(TimeInfoFlags::TEMPO_VALID, time_info.tempo),
+            time_signature: if flags.contains(TimeInfoFlags::TIME_SIG_VALID)
+                && time_info.time_sig_numerator > 0
+                && time_info.time_sig_denominator > 0
+            {
+                Some(TimeSignature::new(
+                    time_info.time_sig_numerator as u32,
+                    time_info.time_sig_denominator as u32,
+                ))
+            } else {
+                None
+            },
+            position_in_frames: Some(time_info.sample_pos as u64),
+            position_in_quarter_notes: if_valid(TimeInfoFlags::PPQ_POS_VALID, time_info.ppq_pos),
+            bar_start_in_quarter_notes: if_valid(
+                TimeInfoFlags::BARS_VALID,
+                time_info.bar_start_pos,
+            ),
+        }
+    }
+}
+
 /// A wrapper around the `plugin_main!` macro from the `vst` crate.
 /// You call this with one parameter, which is the function declaration of a function
 /// that creates your plugin.
@@ -214,16 +609,31 @@ impl HostInterface for HostCallback {
 /// * [`CommonAudioPortMeta`] (names of the audio in and out ports) and
 /// * [`VstPluginMeta`], (VST-specific meta-data)
 ///
+/// **Traits for parameters**
+/// * [`ParameterHandler`] (use [`NoParameters`] if the plugin has no parameters)
+/// * [`PluginState`] for the associated type [`ParameterHandler::Parameters`].
+///   This is used for the preset and bank data. [`ParameterStore`] and [`NoParameters`]
+///   already implement this trait.
+///
 /// **Traits for rendering audio**
 /// * [`AudioHandler`],
-/// * [`ContextualAudioRenderer`]`<f32,`[`HostCallback`]`>` and
-/// * [`ContextualAudioRenderer`]`<f64,`[`HostCallback`]`>`
+/// * [`ContextualAudioRenderer`]`<f32,`[`VstHost`]`>` and
+/// * [`ContextualAudioRenderer`]`<f64,`[`VstHost`]`>`
 ///
 /// **Traits for handling midi events**
-/// * [`ContextualEventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>, `[`HostCallback`]`>` and
-/// * [`ContextualEventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>, `[`HostCallback`]`>`.
+/// * [`ContextualEventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>, `[`VstHost`]`>` and
+/// * [`ContextualEventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>, `[`VstHost`]`>`.
 ///
+/// **Traits for handling automation**
+/// * [`ContextualEventHandler`]`<`[`Timed`]`<`[`ParameterChange`]`>, `[`VstHost`]`>`.
+///   This is called before rendering when the host has changed a parameter.
+///   The new value has already been stored in the parameters when this is called.
 ///
+/// **Generating midi events**
+///
+/// [`VstHost`] implements [`EventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>` and
+/// [`EventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>>`, so the plugin can send midi events
+/// and SysEx events to the host when rendering audio or when handling events.
 ///
 /// # Example using generic code
 /// ```
@@ -237,15 +647,19 @@ impl HostInterface for HostCallback {
 ///         ContextualEventHandler,
 ///         Timed,
 ///         RawMidiEvent,
-///         SysExEvent
+///         SysExEvent,
+///         ParameterChange
 ///     },
 ///     backend::{
 ///         HostInterface,
 ///         vst_backend::VstPluginMeta
 ///     },
+///     parameters::NoParameters,
 ///     ContextualAudioRenderer,
-///     AudioHandler
+///     AudioHandler,
+///     ParameterHandler
 /// };
+/// use std::sync::Arc;
 ///
 /// struct MyPlugin {
 ///   meta: MetaData<&'static str, &'static str, &'static str>
@@ -276,6 +690,13 @@ impl HostInterface for HostCallback {
 /// #     fn set_sample_rate(&mut self, new_sample_rate: f64) {}
 /// }
 ///
+/// impl ParameterHandler for MyPlugin {
+///     type Parameters = NoParameters;
+///     fn parameters(&self) -> Arc<NoParameters> {
+///         Arc::new(NoParameters)
+///     }
+/// }
+///
 ///
 /// impl<S, H> ContextualAudioRenderer<S, H> for MyPlugin
 /// where
@@ -305,6 +726,14 @@ impl HostInterface for HostCallback {
 ///     // Implementation omitted for brevity.
 /// }
 ///
+/// impl<H> ContextualEventHandler<Timed<ParameterChange>, H> for MyPlugin
+/// where
+///     H: HostInterface,
+/// {
+/// #    fn handle_event(&mut self, event: Timed<ParameterChange>, context: &mut H) {}
+///     // Implementation omitted for brevity.
+/// }
+///
 /// vst_init!(
 ///    fn init() -> MyPlugin {
 ///        MyPlugin {
@@ -333,17 +762,22 @@ impl HostInterface for HostCallback {
 ///     meta::{Meta, MetaData, Port, MidiPort, AudioPort, InOut},
 ///     event::{
 ///         ContextualEventHandler,
+///         EventHandler,
 ///         Timed,
 ///         RawMidiEvent,
-///         SysExEvent
+///         SysExEvent,
+///         ParameterChange
 ///     },
 ///     backend::{
 ///         HostInterface,
 ///         vst_backend::VstPluginMeta
 ///     },
+///     parameters::NoParameters,
 ///     ContextualAudioRenderer,
-///     AudioHandler
+///     AudioHandler,
+///     ParameterHandler
 /// };
+/// use std::sync::Arc;
 ///
 /// struct MyPlugin {
 ///   meta: MetaData<&'static str, &'static str, &'static str>
@@ -374,30 +808,44 @@ impl HostInterface for HostCallback {
 /// #     fn set_sample_rate(&mut self, new_sample_rate: f64) {}
 /// }
 ///
-/// // Use the re-exports from `rsynth` so that your code doesn't break when `rsynth` upgrades
-/// // its dependency on `vst-rs`
-/// use rsynth::backend::vst_backend::vst::plugin::HostCallback;
-/// impl<S> ContextualAudioRenderer<S, HostCallback> for MyPlugin
+/// impl ParameterHandler for MyPlugin {
+///     type Parameters = NoParameters;
+///     fn parameters(&self) -> Arc<NoParameters> {
+///         Arc::new(NoParameters)
+///     }
+/// }
+///
+/// use rsynth::backend::vst_backend::VstHost;
+/// impl<S> ContextualAudioRenderer<S, VstHost> for MyPlugin
 /// where
 ///     S: Float + AsPrim,
 /// {
-///     fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<S>, context: &mut HostCallback)
+///     fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<S>, context: &mut VstHost)
 ///     {
 ///          // Here you can call functions on the context if you want.
 /// #        unimplemented!()
 ///     }
 /// }
 ///
-/// impl ContextualEventHandler<Timed<RawMidiEvent>, HostCallback> for MyPlugin
+/// impl ContextualEventHandler<Timed<RawMidiEvent>, VstHost> for MyPlugin
 /// {
-///     fn handle_event(&mut self, event: Timed<RawMidiEvent>, context: &mut HostCallback) {
+///     fn handle_event(&mut self, event: Timed<RawMidiEvent>, context: &mut VstHost) {
 ///         // Here you can call functions on the context if you want.
+///         // E.g. send the event back to the host:
+///         context.handle_event(event);
 ///     }
 /// }
 ///
-/// impl<'a> ContextualEventHandler<Timed<SysExEvent<'a>>, HostCallback> for MyPlugin
+/// impl<'a> ContextualEventHandler<Timed<SysExEvent<'a>>, VstHost> for MyPlugin
 /// {
-///     fn handle_event(&mut self, event: Timed<SysExEvent<'a>>, context: &mut HostCallback) {
+///     fn handle_event(&mut self, event: Timed<SysExEvent<'a>>, context: &mut VstHost) {
+///         // Here you can call functions on the context if you want.
+///     }
+/// }
+///
+/// impl ContextualEventHandler<Timed<ParameterChange>, VstHost> for MyPlugin
+/// {
+///     fn handle_event(&mut self, event: Timed<ParameterChange>, context: &mut VstHost) {
 ///         // Here you can call functions on the context if you want.
 ///     }
 /// }
@@ -422,6 +870,7 @@ impl HostInterface for HostCallback {
 /// ```
 /// [`RawMidiEvent`]: ./event/struct.RawMidiEvent.html
 /// [`SysExEvent`]: ./event/struct.SysExEvent.html
+/// [`ParameterChange`]: ./event/struct.ParameterChange.html
 /// [`Timed<T>`]: ./event/struct.Timed.html
 /// [`Timed`]: ./event/struct.Timed.html
 /// [`Indexed<T>`]: ./event/struct.Indexed.html
@@ -433,11 +882,17 @@ impl HostInterface for HostCallback {
 /// [`Meta`]: ./meta/trait.Meta.html
 /// [`ContextualAudioRenderer`]: trait.ContextualAudioRenderer.html
 /// [`ContextualEventHandler`]: ./event/trait.ContextualEventHandler.html
-/// [`HostCallback`]: ./backend/vst_backend/vst/plugin/struct.HostCallback.html
+/// [`VstHost`]: ./backend/vst_backend/struct.VstHost.html
+/// [`EventHandler`]: ./event/trait.EventHandler.html
 /// [`HostInterface`]: ./backend/trait.HostInterface.html
 /// [`CommonMidiPortMeta`]: ./trait.CommonMidiPortMeta.html
 /// [`VstPluginMeta`]: ./backend/vst_backend/trait.VstPluginMeta.html
 /// [`AudioHandler`]: ./trait.AudioHandler.html
+/// [`ParameterHandler`]: ./trait.ParameterHandler.html
+/// [`NoParameters`]: ./parameters/struct.NoParameters.html
+/// [`ParameterStore`]: ./parameters/struct.ParameterStore.html
+/// [`PluginState`]: ./state/trait.PluginState.html
+/// [`ParameterHandler::Parameters`]: ./trait.ParameterHandler.html#associatedtype.Parameters
 //
 // We define this macro so that plugins do not have to implement th `Default` trait.
 //
@@ -528,6 +983,14 @@ macro_rules! vst_init {
             fn process_events(&mut self, events: &vst::api::Events) {
                 self.wrapper.process_events(events)
             }
+
+            fn get_parameter_object(&mut self) -> std::sync::Arc<dyn vst::plugin::PluginParameters> {
+                self.wrapper.get_parameter_object()
+            }
+
+            fn can_do(&self, can_do: vst::plugin::CanDo) -> vst::api::Supported {
+                self.wrapper.can_do(can_do)
+            }
         }
 
         plugin_main!(VstWrapperWrapper);
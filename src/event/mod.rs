@@ -1,7 +1,7 @@
 //! Event handling
 //!
 //! This module defines the `EventHandler` trait and some event types: `RawMidiEvent`,
-//! `SysExEvent`, ...
+//! `SysExEvent`, `MetaEvent`, ...
 //!
 //! Custom events
 //! =============
@@ -18,6 +18,7 @@ use crate::backend::combined::midly::midly::{
     num::{u4, u7},
     MidiMessage,
 };
+use crate::backend::TimeSignature;
 use std::convert::{AsMut, AsRef, TryFrom};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter, Write};
@@ -304,6 +305,64 @@ impl AsMut<Self> for RawMidiEvent {
     }
 }
 
+/// A meta event from a midi file, e.g. a marker, a lyric or a time signature.
+///
+/// Meta events only occur in midi files, so they are only delivered when rendering
+/// offline (see [`RunOptions`]).
+/// The text of the text-based events is delivered as raw bytes, because midi files
+/// do not specify the encoding of the text.
+///
+/// [`RunOptions`]: ../backend/combined/struct.RunOptions.html
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MetaEvent<'a> {
+    /// Arbitrary text.
+    Text(&'a [u8]),
+    /// A copyright notice.
+    Copyright(&'a [u8]),
+    /// The name of the track.
+    TrackName(&'a [u8]),
+    /// The name of the instrument of the track.
+    InstrumentName(&'a [u8]),
+    /// A lyric, usually one syllable.
+    Lyric(&'a [u8]),
+    /// A marker, e.g. the name of a section of the song.
+    Marker(&'a [u8]),
+    /// A cue point, e.g. a description of something happening on stage.
+    CuePoint(&'a [u8]),
+    /// A change of the key signature.
+    KeySignature {
+        /// The number of sharps (positive) or flats (negative).
+        sharps: i8,
+        /// `true` for a minor key, `false` for a major key.
+        minor: bool,
+    },
+    /// A change of the time signature.
+    TimeSignature(TimeSignature),
+}
+
+/// A change of the value of a parameter.
+///
+/// Backends that support automation of parameters (e.g. by the host) deliver these
+/// events as `Timed<ParameterChange>`, so that the parameter changes can be handled
+/// in a sample-accurate way, e.g. by queueing them in an [`EventQueue`] and using
+/// its `split` method to render the audio between two changes.
+///
+/// [`EventQueue`]: ./event_queue/struct.EventQueue.html
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ParameterChange {
+    /// The index of the parameter.
+    pub index: usize,
+    /// The new normalized value of the parameter, between `0.0` and `1.0`.
+    pub value: f32,
+}
+
+impl ParameterChange {
+    /// Create a new `ParameterChange`.
+    pub fn new(index: usize, value: f32) -> Self {
+        Self { index, value }
+    }
+}
+
 /// `Timed<E>` adds timing to an event.
 ///
 /// # Suggestion
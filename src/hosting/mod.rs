@@ -0,0 +1,14 @@
+//! Hosting plugins.
+//!
+//! The modules in this module load existing plugins (e.g. plugins that have not been written
+//! with `rsynth`) and wrap them in a type that implements the traits of `rsynth`, so that they
+//! can be used with the `rsynth` backends, e.g. for offline rendering with the [`combined`]
+//! backend.
+//!
+//! The following plugin formats are currently supported:
+//! * [`vst`] (VST 2.4, behind the `hosting-vst` feature)
+//!
+//! [`combined`]: ../backend/combined/index.html
+//! [`vst`]: ./vst/index.html
+#[cfg(feature = "hosting-vst")]
+pub mod vst;
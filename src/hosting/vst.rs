@@ -0,0 +1,405 @@
+//! Load VST 2.4 plugins (behind the `hosting-vst` feature).
+//!
+//! Support is only enabled if you compile with the "hosting-vst" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//!
+//! A plugin is loaded with [`VstPlugin::load`]. The resulting [`VstPlugin`] implements
+//! * [`AudioHandlerMeta`] (the number of audio inputs and outputs of the plugin),
+//! * [`AudioHandler`] (the latency is the "initial delay" of the plugin),
+//! * [`EventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>` and
+//! * [`ContextualAudioRenderer`]`<f32, H>` for every context `H` that implements [`MidiOutput`];
+//!   midi events and SysEx events that the plugin sends to the host are written to the first
+//!   midi output port of the context.
+//!
+//! so it can be passed directly to the [`run`] function of the [`combined`] backend.
+//!
+//! # Example
+//! ```no_run
+//! use rsynth::backend::combined::{dummy::{AudioDummy, MidiDummy}, memory::AudioBufferWriter, run, RunOptions};
+//! use rsynth::buffer::AudioChunk;
+//! use rsynth::hosting::vst::VstPlugin;
+//! use rsynth::AudioHandler;
+//! use std::path::Path;
+//!
+//! let mut plugin = VstPlugin::load(Path::new("/usr/lib/vst/some_plugin.so")).unwrap();
+//! plugin.set_sample_rate(44100.0);
+//! let mut output = AudioChunk::new(2);
+//! run(
+//!     &mut plugin,
+//!     256,
+//!     AudioDummy::with_sample_rate_and_length(44100, 44100),
+//!     AudioBufferWriter::new(&mut output),
+//!     MidiDummy::new(),
+//!     RunOptions::new(),
+//! ).unwrap();
+//! ```
+//!
+//! # Limitations
+//! * Only `f32` samples are supported.
+//! * The plugin does not get transport information from the host.
+//! * The plugin does not have an editor.
+//!
+//! [`VstPlugin::load`]: ./struct.VstPlugin.html#method.load
+//! [`VstPlugin`]: ./struct.VstPlugin.html
+//! [`AudioHandlerMeta`]: ../../trait.AudioHandlerMeta.html
+//! [`AudioHandler`]: ../../trait.AudioHandler.html
+//! [`EventHandler`]: ../../event/trait.EventHandler.html
+//! [`Timed`]: ../../event/struct.Timed.html
+//! [`RawMidiEvent`]: ../../event/struct.RawMidiEvent.html
+//! [`ContextualAudioRenderer`]: ../../trait.ContextualAudioRenderer.html
+//! [`MidiOutput`]: ../../backend/trait.MidiOutput.html
+//! [`run`]: ../../backend/combined/fn.run.html
+//! [`combined`]: ../../backend/combined/index.html
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+use crate::backend::MidiOutput;
+use crate::buffer::AudioBufferInOut;
+use crate::event::event_queue::{AlwaysInsertNewAfterOld, EventQueue};
+use crate::event::{EventHandler, RawMidiEvent, Timed};
+use crate::{AudioHandler, AudioHandlerMeta, CommonPluginMeta, ContextualAudioRenderer};
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use vecstorage::VecStorage;
+
+use ::vst::api::Events;
+use ::vst::buffer::SendEventBuffer;
+use ::vst::event::{Event as VstEvent, MidiEvent as VstMidiEvent};
+use ::vst::host::{Host, HostBuffer, PluginLoader};
+use ::vst::plugin::Plugin;
+
+pub use ::vst::host::{PluginInstance, PluginLoadError};
+pub use ::vst::plugin::Info;
+
+/// The maximum number of midi events that can be sent to the plugin per buffer.
+const MAX_NUMBER_OF_INCOMING_EVENTS: usize = 1024;
+
+/// An event that the plugin sent to the host.
+enum PluginEvent {
+    Midi(RawMidiEvent),
+    SysEx(Vec<u8>),
+}
+
+/// The host as seen by the plugin.
+///
+/// The plugin sends its events to the host while it is processing, so they are collected here
+/// and passed to the context after processing.
+struct VstPluginHost {
+    events_from_plugin: RefCell<Vec<Timed<PluginEvent>>>,
+}
+
+impl Host for VstPluginHost {
+    fn get_info(&self) -> (isize, String, String) {
+        (1, "rsynth".to_string(), "rsynth".to_string())
+    }
+
+    fn process_events(&self, events: &Events) {
+        let mut events_from_plugin = self.events_from_plugin.borrow_mut();
+        for event in events.events() {
+            let event = match event {
+                VstEvent::Midi(event) => Timed::new(
+                    event.delta_frames as u32,
+                    PluginEvent::Midi(RawMidiEvent::new(&event.data)),
+                ),
+                VstEvent::SysEx(event) => Timed::new(
+                    event.delta_frames as u32,
+                    PluginEvent::SysEx(event.payload.to_vec()),
+                ),
+                VstEvent::Deprecated(_) => {
+                    warn!("Ignoring deprecated event from VST plugin.");
+                    continue;
+                }
+            };
+            events_from_plugin.push(event);
+        }
+    }
+}
+
+/// A VST 2.4 plugin that is loaded from a shared library.
+///
+/// See the [module level documentation] for more information.
+///
+/// [module level documentation]: ./index.html
+pub struct VstPlugin {
+    instance: PluginInstance,
+    host: Arc<Mutex<VstPluginHost>>,
+    info: Info,
+    is_resumed: bool,
+    block_size: usize,
+    incoming_events: EventQueue<RawMidiEvent>,
+    send_buffer: SendEventBuffer,
+    host_buffer: HostBuffer<f32>,
+    inputs: VecStorage<&'static [f32]>,
+    outputs: VecStorage<&'static mut [f32]>,
+    // Used for the inputs and outputs of the plugin that are not present in the audio buffer.
+    silence: Vec<f32>,
+    discarded_outputs: Vec<Vec<f32>>,
+}
+
+impl VstPlugin {
+    /// Load the plugin from the shared library with the given path and initialize it.
+    ///
+    /// Call [`set_sample_rate`] before rendering audio.
+    ///
+    /// [`set_sample_rate`]: ../../trait.AudioHandler.html#tymethod.set_sample_rate
+    pub fn load(path: &Path) -> Result<Self, PluginLoadError> {
+        let host = Arc::new(Mutex::new(VstPluginHost {
+            events_from_plugin: RefCell::new(Vec::new()),
+        }));
+        let mut loader = PluginLoader::load(path, Arc::clone(&host))?;
+        let mut instance = loader.instance()?;
+        let info = instance.get_info();
+        instance.init();
+        Ok(Self {
+            instance,
+            host,
+            is_resumed: false,
+            block_size: 0,
+            incoming_events: EventQueue::new(MAX_NUMBER_OF_INCOMING_EVENTS),
+            send_buffer: SendEventBuffer::new(MAX_NUMBER_OF_INCOMING_EVENTS),
+            host_buffer: HostBuffer::from_info(&info),
+            inputs: VecStorage::with_capacity(info.inputs as usize),
+            outputs: VecStorage::with_capacity(info.outputs as usize),
+            silence: Vec::new(),
+            discarded_outputs: Vec::new(),
+            info,
+        })
+    }
+
+    /// Information about the plugin, such as its name, vendor and number of inputs and outputs.
+    pub fn info(&self) -> &Info {
+        &self.info
+    }
+
+    /// Get access to the underlying [`PluginInstance`] so that you can use VST-specific features,
+    /// e.g. the parameters of the plugin.
+    ///
+    /// [`PluginInstance`]: https://docs.rs/vst/0.2.1/vst/host/struct.PluginInstance.html
+    pub fn instance(&mut self) -> &mut PluginInstance {
+        &mut self.instance
+    }
+
+    fn suspend(&mut self) {
+        if self.is_resumed {
+            self.instance.suspend();
+            self.is_resumed = false;
+        }
+    }
+
+    /// Make sure that the plugin is ready to process a buffer of `number_of_frames` frames.
+    ///
+    /// _Note_: this allocates memory when the buffer is larger than the previous buffers.
+    fn prepare(&mut self, number_of_frames: usize) {
+        if number_of_frames > self.block_size {
+            self.suspend();
+            self.block_size = number_of_frames;
+            self.instance.set_block_size(number_of_frames as i64);
+            self.silence.resize(number_of_frames, 0.0);
+            self.discarded_outputs = vec![vec![0.0; number_of_frames]; self.info.outputs as usize];
+        }
+        if !self.is_resumed {
+            self.instance.resume();
+            self.is_resumed = true;
+        }
+    }
+
+    fn send_events_to_plugin(&mut self) {
+        if self.incoming_events.is_empty() {
+            return;
+        }
+        let events = self.incoming_events.iter().map(|event| {
+            VstEvent::Midi(VstMidiEvent {
+                data: *event.event.data(),
+                delta_frames: event.time_in_frames as i32,
+                live: false,
+                note_length: None,
+                note_offset: None,
+                detune: 0,
+                note_off_velocity: 0,
+            })
+        });
+        self.send_buffer
+            .send_events_to_plugin(events, &mut self.instance);
+        self.incoming_events.clear();
+    }
+
+    fn send_events_to_context<H: MidiOutput>(&mut self, context: &mut H) {
+        let host = match self.host.lock() {
+            Ok(host) => host,
+            Err(_) => {
+                error!("Failed to get the events of the VST plugin.");
+                return;
+            }
+        };
+        for event in host.events_from_plugin.borrow_mut().drain(..) {
+            let result = match event.event {
+                PluginEvent::Midi(midi_event) => {
+                    context.write_midi(0, event.time_in_frames, midi_event)
+                }
+                PluginEvent::SysEx(data) => context.write_sysex(0, event.time_in_frames, &data),
+            };
+            if let Err(e) = result {
+                error!("Failed to send midi event: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for VstPlugin {
+    fn drop(&mut self) {
+        self.suspend();
+    }
+}
+
+impl CommonPluginMeta for VstPlugin {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+}
+
+impl AudioHandlerMeta for VstPlugin {
+    fn max_number_of_audio_inputs(&self) -> usize {
+        self.info.inputs as usize
+    }
+
+    fn max_number_of_audio_outputs(&self) -> usize {
+        self.info.outputs as usize
+    }
+}
+
+impl AudioHandler for VstPlugin {
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.suspend();
+        self.instance.set_sample_rate(sample_rate as f32);
+    }
+
+    fn latency_in_frames(&self) -> u32 {
+        self.info.initial_delay.max(0) as u32
+    }
+}
+
+/// The events are sent to the plugin before the next call to `render_buffer`.
+/// The time of the event is relative to the start of the next buffer.
+impl EventHandler<Timed<RawMidiEvent>> for VstPlugin {
+    fn handle_event(&mut self, event: Timed<RawMidiEvent>) {
+        if self.incoming_events.len() >= MAX_NUMBER_OF_INCOMING_EVENTS {
+            error!("Too many midi events for one buffer; ignoring this event.");
+            return;
+        }
+        self.incoming_events
+            .queue_event(event, AlwaysInsertNewAfterOld);
+    }
+}
+
+/// Input channels that the plugin expects but that are not in the buffer are filled with
+/// silence; output channels of the plugin that are not in the buffer are discarded.
+impl<H> ContextualAudioRenderer<f32, H> for VstPlugin
+where
+    H: MidiOutput,
+{
+    fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut H) {
+        let number_of_frames = buffer.number_of_frames();
+        self.prepare(number_of_frames);
+        self.send_events_to_plugin();
+
+        let (inputs, mut outputs) = buffer.separate();
+        {
+            let number_of_inputs = self.info.inputs as usize;
+            let number_of_outputs = self.info.outputs as usize;
+            let mut plugin_inputs = self.inputs.vec_guard();
+            for input in inputs.channels().iter().take(number_of_inputs) {
+                plugin_inputs.push(*input);
+            }
+            while plugin_inputs.len() < number_of_inputs {
+                plugin_inputs.push(&self.silence[..number_of_frames]);
+            }
+
+            let mut plugin_outputs = self.outputs.vec_guard();
+            for output in outputs.channel_iter_mut().take(number_of_outputs) {
+                plugin_outputs.push(output);
+            }
+            let number_of_present_outputs = plugin_outputs.len();
+            for discarded in self.discarded_outputs[number_of_present_outputs..].iter_mut() {
+                plugin_outputs.push(&mut discarded[..number_of_frames]);
+            }
+
+            let mut audio_buffer = self
+                .host_buffer
+                .bind(plugin_inputs.as_slice(), plugin_outputs.as_mut_slice());
+            self.instance.process(&mut audio_buffer);
+        }
+
+        self.send_events_to_context(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_a_file_that_is_not_a_plugin_fails() {
+        let result = VstPlugin::load(Path::new("/this/plugin/does/not/exist.so"));
+        assert!(matches!(result, Err(PluginLoadError::InvalidPath)));
+    }
+
+    // This loads the `vst_synth` example (which is only a VST plugin when the `backend-vst`
+    // feature is enabled). Build the examples first, so that the library is not missing or outdated.
+    #[cfg(all(target_os = "linux", feature = "backend-vst"))]
+    #[test]
+    #[ignore = "loads the example plugin; run `cargo build --examples --features hosting-vst,backend-vst` and then `cargo test --features hosting-vst,backend-vst -- --ignored`"]
+    fn the_example_plugin_can_be_rendered_offline() {
+        use crate::backend::combined::dummy::AudioDummy;
+        use crate::backend::combined::memory::AudioBufferWriter;
+        use crate::backend::combined::{run, DeltaEventSource, MidiWriter, RunOptions};
+        use crate::buffer::AudioChunk;
+        use crate::event::DeltaEvent;
+
+        struct MidiCollector(Vec<DeltaEvent<RawMidiEvent>>);
+
+        impl MidiWriter for MidiCollector {
+            fn write_event(&mut self, event: DeltaEvent<RawMidiEvent>) {
+                self.0.push(event);
+            }
+        }
+
+        let executable = std::env::current_exe().unwrap();
+        let target_dir = executable.parent().unwrap().parent().unwrap();
+        let library = target_dir.join("examples").join("libvst_synth.so");
+        assert!(
+            library.exists(),
+            "{} does not exist; run `cargo build --examples` first.",
+            library.display()
+        );
+
+        // The example is built with the same features as this test, so it is a VST plugin.
+        let mut plugin = VstPlugin::load(&library).expect("Unexpected error.");
+        assert_eq!(plugin.info().inputs, 0);
+        assert_eq!(plugin.info().outputs, 2);
+        assert_eq!(plugin.max_number_of_audio_outputs(), 2);
+        plugin.set_sample_rate(44100.0);
+
+        let note_on = DeltaEvent {
+            microseconds_since_previous_event: 10_000,
+            event: RawMidiEvent::new(&[0x90, 60, 100]),
+        };
+        let mut output = AudioChunk::new(2);
+        let mut midi_out = MidiCollector(Vec::new());
+        run(
+            &mut plugin,
+            64,
+            AudioDummy::with_sample_rate_and_length(44100, 4410),
+            AudioBufferWriter::new(&mut output),
+            &mut midi_out,
+            RunOptions::new().midi_input(DeltaEventSource::new(vec![note_on].into_iter())),
+        )
+        .unwrap();
+
+        let channels = output.channels();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].len(), 4410);
+        // The note starts after 10 ms (441 frames); the plugin starts it at the start of a block.
+        assert!(channels[0][..384].iter().all(|sample| *sample == 0.0));
+        assert!(channels[0][448..].iter().any(|sample| *sample != 0.0));
+        assert!(midi_out.0.is_empty());
+    }
+}
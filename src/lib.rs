@@ -1,20 +1,34 @@
 //! # Rsynth
 //! An API abstraction for API's for audio plugins and applications.
 //! Use it to write real-time audio effects, software synthesizers, ... and target different platforms
-//! (vst, jack, offline audio rendering, ...).
+//! (vst, vst3, clap, lv2, jack, alsa, cpal, offline audio rendering, ...).
 //! It is currently most suitable for real-time or "streaming" audio processing.
 //! E.g. you cannot use it to reverse audio in time.
 //!
 //! ## Back-ends
 //! `rsynth` currently supports the following back-ends:
 //!
+//! * [`alsa`] (behind the `backend-alsa` feature)
+//! * [`clap`] (behind the `backend-clap` feature)
+//! * [`cpal`] (behind the `backend-cpal` feature)
 //! * [`jack`] (behind the `backend-jack` feature)
+//! * [`lv2`] (behind the `backend-lv2` feature)
 //! * [`vst`] (behind the `backend-vst` feature)
+//! * [`vst3`] (behind the `backend-vst3` feature)
 //! * [`combined`] combine different back-ends for audio input, audio output, midi input and
 //!     midi output, mostly for offline rendering and testing (behind various features)
 //!
 //! See the documentation of each back-end for more information.
 //!
+//! ## Hosting plugins
+//! Existing plugins can be loaded and used as if they were written with `rsynth`,
+//! e.g. for offline rendering. See the [`hosting`] module for more information.
+//!
+//! ## Standalone applications
+//! The [`standalone_main!`] macro turns a plugin into an application that runs either live
+//! with Jack or renders a midi file offline, depending on the command line arguments.
+//! See the [`standalone`] module for more information (behind the `standalone` feature).
+//!
 //! ## Features and how to use them
 //!
 //! `rsynth` puts common functionality of the different backends behind common traits.
@@ -24,9 +38,15 @@
 //!
 //! ### Starting the backend/entry point for the host
 //!
+//! * ALSA: [`run()`](./backend/alsa_backend/fn.run.html)
+//! * CLAP: [`clap_init!`]
+//! * cpal: [`run()`](./backend/cpal_backend/fn.run.html)
 //! * Jack: [`run()`](./backend/jack_backend/fn.run.html)
+//! * LV2: [`lv2_init!`] (see also the [`ttl`] module for generating the Turtle files)
 //! * Offline : [`run()`](backend/combined/fn.run.html)
+//! * Standalone application: [`standalone_main!`]
 //! * VST 2.4:  [`vst_init!`]
+//! * VST3: [`vst3_init!`]
 //!
 //! ### Meta-data
 //! There are a number of traits that an application or plugin needs to implement in order to define
@@ -34,6 +54,30 @@
 //! implement these traits in a more straightforward way by implementing the [`Meta`] trait.
 //! However, you can also implement these trait "by hand".
 //!
+//! **Meta-data for ALSA**
+//!
+//! Applications need to implement
+//! * [`CommonPluginMeta`] (name of the plugin etc, used as the name of the sequencer client)
+//! * [`AudioHandlerMeta`] (number of audio ports)
+//! * [`CommonAudioPortMeta`] (names of the audio in and out ports)
+//! * [`MidiHandlerMeta`] (number of midi ports)
+//! * [`CommonMidiPortMeta`] (names of the midi in and out ports, used for the sequencer ports)
+//!
+//! **Meta-data for CLAP**
+//!
+//! Plugins need to implement
+//! * [`CommonPluginMeta`] (name of the plugin etc)
+//! * [`AudioHandlerMeta`] (number of audio ports)
+//! * [`CommonAudioPortMeta`] (names of the audio in and out ports)
+//! * [`MidiHandlerMeta`] (number of midi ports)
+//! * [`CommonMidiPortMeta`] (names of the midi in and out ports, used for the note ports)
+//! * [`ClapPluginMeta`] (CLAP-specific meta-data)
+//!
+//! **Meta-data for cpal**
+//!
+//! Applications need to implement
+//! * [`AudioHandlerMeta`] (number of audio ports)
+//!
 //! **Meta-data for Jack**
 //!
 //! Applications need to implement
@@ -43,6 +87,16 @@
 //! * [`MidiHandlerMeta`] (number of midi ports)
 //! * [`CommonMidiPortMeta`] (names of the audio in and out ports)
 //!
+//! **Meta-data for LV2**
+//!
+//! Plugins need to implement
+//! * [`CommonPluginMeta`] (name of the plugin etc)
+//! * [`AudioHandlerMeta`] (number of audio ports)
+//! * [`CommonAudioPortMeta`] (names of the audio in and out ports)
+//! * [`MidiHandlerMeta`] (number of midi ports)
+//! * [`CommonMidiPortMeta`] (names of the midi in and out ports)
+//! * [`Lv2PluginMeta`] (LV2-specific meta-data)
+//!
 //! **No meta-data for offline rendering**
 //!
 //! Applications do not need to implement special traits describing meta-data.
@@ -54,6 +108,97 @@
 //! * [`AudioHandlerMeta`] (number of audio ports)
 //! * [`CommonAudioPortMeta`] (names of the audio in and out ports)
 //! * [`VstPluginMeta`] (vst-specific meta-data)
+//! * [`ParameterHandler`] (parameters, use [`NoParameters`] if the plugin has no parameters)
+//!
+//! **Meta-data for VST3**
+//!
+//! Plugins need to implement
+//! * [`CommonPluginMeta`] (name of the plugin etc)
+//! * [`AudioHandlerMeta`] (number of audio ports)
+//! * [`CommonAudioPortMeta`] (names of the audio in and out ports, used for the audio buses)
+//! * [`MidiHandlerMeta`] (number of midi ports)
+//! * [`CommonMidiPortMeta`] (names of the midi in and out ports, used for the event buses)
+//! * [`Vst3PluginMeta`] (VST3-specific meta-data)
+//!
+//! ### Parameters
+//! A plugin or application can define parameters by implementing the [`ParameterHandler`] trait.
+//! The parameters themselves are stored in a data structure that implements the [`Parameters`]
+//! trait, such as [`ParameterStore`].
+//! See the documentation of the [`parameters`] module for more information.
+//!
+//! **Parameters with ALSA**
+//!
+//! Applications do not need to implement [`ParameterHandler`].
+//!
+//! **Parameters with CLAP**
+//!
+//! Parameters are not yet supported by the CLAP back-end.
+//!
+//! **Parameters with cpal**
+//!
+//! Applications do not need to implement [`ParameterHandler`].
+//!
+//! **Parameters with Jack**
+//!
+//! Applications need to implement [`ParameterHandler`] (use [`NoParameters`] if the application
+//! has no parameters) and handle `Timed<ParameterChange>` events.
+//! The handle that is returned when starting Jack can be used to change the parameters from other
+//! threads, e.g. a GUI thread; the changes are delivered to the application as events.
+//! See the documentation of the [`jack`] back-end for more information.
+//!
+//! **Parameters with LV2**
+//!
+//! Parameters are not yet supported by the LV2 back-end.
+//!
+//! **Parameters for VST 2.4**
+//!
+//! Plugins need to implement [`ParameterHandler`] and handle `Timed<ParameterChange>` events.
+//! The host can then display and automate the parameters.
+//!
+//! **Parameters with VST3**
+//!
+//! Parameters are not yet supported by the VST3 back-end.
+//!
+//! ### Saving and restoring the state
+//! The state of a plugin or application can be saved and restored by implementing the
+//! [`PluginState`] trait for the data structure that stores the parameters
+//! ([`ParameterStore`] and [`NoParameters`] already implement it).
+//! See the documentation of the [`state`] module for more information.
+//!
+//! **State with ALSA**
+//!
+//! Saving and restoring the state is not yet supported by the ALSA back-end.
+//!
+//! **State with CLAP**
+//!
+//! Saving and restoring the state is not yet supported by the CLAP back-end.
+//!
+//! **State with cpal**
+//!
+//! Saving and restoring the state is not yet supported by the cpal back-end.
+//!
+//! **State with Jack**
+//!
+//! The state can be saved to and loaded from a file with the [`save_state`] and [`load_state`]
+//! functions of the [`jack`] back-end.
+//!
+//! **State with LV2**
+//!
+//! Saving and restoring the state is not yet supported by the LV2 back-end.
+//!
+//! **State for offline rendering**
+//!
+//! The state can be restored before rendering with the
+//! [`restore_state`](backend/combined/fn.restore_state.html) function.
+//!
+//! **State for VST 2.4**
+//!
+//! Plugins need to implement [`PluginState`] for their parameters. The state is used for
+//! the preset and bank data.
+//!
+//! **State with VST3**
+//!
+//! Saving and restoring the state is not yet supported by the VST3 back-end.
 //!
 //! ### Rendering audio
 //! All backends require the plugin/application to implement the [`ContextualAudioRenderer`] trait.
@@ -66,26 +211,67 @@
 //! The application or plugin can have either a generic implementation of the [`ContextualAudioRenderer`]
 //! or choose to use different, specialized implementations if different behaviour is needed.
 //!
+//! **Rendering audio with ALSA**
+//!
+//! Applications need to implement
+//! * [`AudioHandler`]
+//! * [`ContextualAudioRenderer`]`<f32,`[`AlsaHost`]`>`
+//!
+//! **Rendering audio with CLAP**
+//!
+//! Plugins need to implement
+//! * [`AudioHandler`]
+//! * [`ContextualAudioRenderer`]`<f32,`[`ClapHost`]`>`
+//!
+//! **Rendering audio with cpal**
+//!
+//! Applications need to implement
+//! * [`AudioHandler`]
+//! * [`ContextualAudioRenderer`]`<f32,`[`CpalHost`]`>`
+//!
 //! **Rendering audio with Jack**
 //!
 //! Applications need to implement
 //! * [`AudioHandler`]
 //! * [`ContextualAudioRenderer`]`<f32,`[`JackHost`]`>`
 //!
+//! **Rendering audio with LV2**
+//!
+//! Plugins need to implement
+//! * [`AudioHandler`]
+//! * [`ContextualAudioRenderer`]`<f32,`[`Lv2Host`]`>`
+//!
 //! **Rendering audio offline**
 //!
 //! Applications need to implement
+//! * [`AudioHandler`]
 //! * [`ContextualAudioRenderer`]`<S, `[`MidiWriterWrapper`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>>` Note: the type parameter `S`, which represents the sample data type, is free.
 //!
 //! **Rendering audio with VST 2.4**
 //!
 //! Plugins need to implement
 //! * [`AudioHandler`]
-//! * [`ContextualAudioRenderer`]`<f32,`[`HostCallback`]`>`
-//! * [`ContextualAudioRenderer`]`<f64,`[`HostCallback`]`>`
+//! * [`ContextualAudioRenderer`]`<f32,`[`VstHost`]`>`
+//! * [`ContextualAudioRenderer`]`<f64,`[`VstHost`]`>`
+//!
+//! **Rendering audio with VST3**
+//!
+//! Plugins need to implement
+//! * [`AudioHandler`]
+//! * [`ContextualAudioRenderer`]`<f32,`[`Vst3Host`]`>`
 //!
-//! _Note_: [`HostCallback`] is re-exported from the vst crate, but implements `rsynth`'s
-//! [`HostInterface`], which defines functionality shared by all backends.
+//! ### Latency
+//! Plugins and applications that delay their output (e.g. because of lookahead) can report
+//! this by overriding the [`latency_in_frames`] method of the [`AudioHandler`] trait.
+//!
+//! * ALSA: the latency is not reported.
+//! * CLAP: the latency is reported to the host with the latency extension.
+//! * cpal: the latency is not reported.
+//! * Jack: the latency is added to the latency ranges of the audio ports.
+//! * LV2: the latency is reported to the host with a control output port.
+//! * Offline rendering: the corresponding number of leading frames is trimmed from the output.
+//! * VST 2.4: the latency is reported to the host as the "initial delay".
+//! * VST3: the latency is reported to the host by the audio processor.
 //!
 //! ### Handling (midi) events
 //! A plugin or application can handle events (typically midi events) by implementing the
@@ -93,12 +279,42 @@
 //! a second type parameter, the context, which typically corresponds to the host, so that
 //! plugins or applications can have access to the host while handling events.
 //!
+//! **Handling events with ALSA**
+//!
+//! Applications need to implement
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`AlsaHost`]`>`,
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`AlsaHost`]`>`
+//!
+//! The events that the sequencer receives during one period are delivered at the start of the
+//! next period.
+//!
+//! **Handling events with CLAP**
+//!
+//! Plugins need to implement
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`ClapHost`]`>`,
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`ClapHost`]`>`
+//!
+//! Note events in the CLAP dialect are converted to midi events.
+//!
+//! **Handling events with cpal**
+//!
+//! Applications need to implement
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`CpalHost`]`>`
+//!
+//! The events come from a user-supplied midi source and are delivered at the start of the buffer.
+//!
 //! **Handling events with Jack**
 //!
 //! Applications need to implement
 //! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`JackHost`]`>`,
 //! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`JackHost`]`>`
 //!
+//! **Handling events with LV2**
+//!
+//! Plugins need to implement
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`Lv2Host`]`>`,
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`Lv2Host`]`>`
+//!
 //! **Handling events with the "offline" backend**
 //!
 //! Applications need to implement
@@ -108,22 +324,67 @@
 //! We would like to make this more uniform in a future version and also require
 //! [`ContextualEventHandler`] here.
 //!
+//! When rendering system exclusive messages and meta events from a midi file,
+//! applications additionally need to implement
+//! * [`EventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>>` and
+//! * [`EventHandler`]`<`[`Timed`]`<`[`MetaEvent`]`>>`
+//!
+//! When the events are delivered with the index of the midi input (see [`RunOptions`]),
+//! e.g. when rendering with more than one midi input, applications need to implement
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`MidiWriterWrapper`]`>`
+//!   (and the same for [`SysExEvent`] and [`MetaEvent`]) instead.
+//!
 //! **Handling events with VST 2.4**
 //! Plugins need to implement
 //!
-//! * [`ContextualEventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>, `[`HostCallback`]`>` and
-//! * [`ContextualEventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>, `[`HostCallback`]`>`.
+//! * [`ContextualEventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>, `[`VstHost`]`>` and
+//! * [`ContextualEventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>, `[`VstHost`]`>`.
 //!
 //! _Note_: VST 2.4 does not support sample-accurate events; a dummy timestamp of `0` is always added.
 //!
-//! _Note_: [`HostCallback`] is re-exported from the vst crate, but implements `rsynth`'s
-//! [`HostInterface`], which defines functionality shared by all backends.
+//! **Handling events with VST3**
+//!
+//! Plugins need to implement
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>, `[`Vst3Host`]`>`,
+//! * [`ContextualEventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>, `[`Vst3Host`]`>`
+//!
+//! Note on, note off and polyphonic pressure events in the VST3 dialect are converted to midi events.
 //!
 //! ### Generating midi events
 //! The "context" parameter passed in the methods from the [`ContextualAudioRenderer`] and
 //! [`ContextualEventHandler`] traits allows to access features from the host/backend, such as
 //! generating midi events.
 //!
+//! **Generating midi events in a backend-independent way**
+//!
+//! [`AlsaHost`], [`ClapHost`], [`JackHost`], [`Lv2Host`], [`MidiWriterWrapper`], [`VstHost`] and [`Vst3Host`] all implement the [`MidiOutput`] trait,
+//! so a plugin that only requires [`MidiOutput`] for the context can generate midi events
+//! with every backend.
+//! Writing an event returns an error when the event could not be written,
+//! e.g. because there is no midi output port with the given index.
+//!
+//! **Generating midi events with ALSA**
+//!
+//! [`AlsaHost`] implements the following traits:
+//!
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>>`
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>>`
+//!
+//! The events are scheduled on the queue of the sequencer client.
+//!
+//! **Generating midi events with CLAP**
+//!
+//! [`ClapHost`] implements the following traits:
+//!
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>>`
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>>`
+//!
+//! The events are passed to the host immediately, so they must be sent in chronological order.
+//!
+//! **Generating midi events with cpal**
+//!
+//! Generating midi events is not supported by the cpal back-end.
+//!
 //! **Generating midi events with Jack**
 //!
 //! [`JackHost`] implements the following traits:
@@ -131,12 +392,48 @@
 //! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>>`
 //! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>>`
 //!
+//! **Generating midi events with LV2**
+//!
+//! [`Lv2Host`] implements the following traits:
+//!
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>>`
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>>`
+//!
+//! The events of one port must be sent in chronological order.
+//!
 //! **Generating midi events with offline rendering**
 //!
 //! [`MidiWriterWrapper`] implements
 //! * [`EventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>`
 //!
-//! **Generating midi events with VST 2.4 is not possible**
+//! **Generating midi events with VST 2.4**
+//!
+//! [`VstHost`] implements
+//! * [`EventHandler`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>` and
+//! * [`EventHandler`]`<`[`Timed`]`<`[`SysExEvent`]`>>`.
+//!
+//! The events are sent to the host at the end of each block.
+//! _Note_: the timestamp of the event is used as the `delta_frames` of the VST event.
+//!
+//! **Generating midi events with VST3**
+//!
+//! [`Vst3Host`] implements the following traits:
+//!
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`RawMidiEvent`]`>>>`
+//! * [`EventHandler`]`<`[`Indexed`]`<`[`Timed`]`<`[`SysExEvent`]`>>>`
+//!
+//! The events are passed to the host immediately.
+//!
+//! ### Transport information
+//! The "context" parameter also gives access to the transport of the host (tempo,
+//! time signature, song position, ...) for backends that implement the [`TransportInfo`] trait.
+//!
+//! * CLAP: [`ClapHost`] implements [`TransportInfo`] with the transport passed by the host.
+//! * Jack: [`JackHost`] implements [`TransportInfo`] by querying the Jack transport.
+//! * Offline rendering: [`MidiWriterWrapper`] implements [`TransportInfo`] with a synthetic
+//!   transport, see [`RunOptions`](backend/combined/struct.RunOptions.html#method.transport).
+//! * VST 2.4: [`VstHost`] implements [`TransportInfo`] by asking the host.
+//! * VST3: [`Vst3Host`] implements [`TransportInfo`] with the process context passed by the host.
 //!
 //! ### Stopping the backend
 //! The "context" parameter passed in the methods from the [`ContextualAudioRenderer`] and
@@ -146,6 +443,14 @@
 //! The [`stop`] method only actually does something if the backend additionally implements
 //! the [`Stop`] trait.
 //!
+//! **Stopping ALSA**
+//!
+//! Stopping ALSA is possible: [`AlsaHost`] implements the [`Stop`] trait.
+//!
+//! **Stopping cpal**
+//!
+//! Stopping cpal is possible: [`CpalHost`] implements the [`Stop`] trait.
+//!
 //! **Stopping Jack**
 //!
 //! Stopping Jack is possible: [`JackHost`] implements the [`Stop`] trait.
@@ -158,6 +463,31 @@
 //!
 //! **Stopping VST 2.4 is not possible**
 //!
+//! **Stopping CLAP is not possible**
+//!
+//! **Stopping LV2 is not possible**
+//!
+//! **Stopping VST3 is not possible**
+//!
+//! [`vst3`]: ./backend/vst3_backend/index.html
+//! [`hosting`]: ./hosting/index.html
+//! [`standalone`]: ./standalone/index.html
+//! [`RunOptions`]: ./backend/combined/struct.RunOptions.html
+//! [`MetaEvent`]: ./event/enum.MetaEvent.html
+//! [`standalone_main!`]: ./macro.standalone_main.html
+//! [`vst3_init!`]: ./macro.vst3_init.html
+//! [`Vst3Host`]: ./backend/vst3_backend/struct.Vst3Host.html
+//! [`Vst3PluginMeta`]: ./backend/vst3_backend/trait.Vst3PluginMeta.html
+//! [`lv2`]: ./backend/lv2_backend/index.html
+//! [`lv2_init!`]: ./macro.lv2_init.html
+//! [`ttl`]: ./backend/lv2_backend/ttl/index.html
+//! [`Lv2Host`]: ./backend/lv2_backend/struct.Lv2Host.html
+//! [`Lv2PluginMeta`]: ./backend/lv2_backend/trait.Lv2PluginMeta.html
+//! [`alsa`]: ./backend/alsa_backend/index.html
+//! [`AlsaHost`]: ./backend/alsa_backend/struct.AlsaHost.html
+//! [`clap`]: ./backend/clap_backend/index.html
+//! [`cpal`]: ./backend/cpal_backend/index.html
+//! [`CpalHost`]: ./backend/cpal_backend/struct.CpalHost.html
 //! [`jack`]: ./backend/jack_backend/index.html
 //! [`vst`]: ./backend/vst_backend/index.html
 //! [`combined`]: ./backend/combined/index.html
@@ -178,34 +508,56 @@
 //! [`ContextualEventHandler`]: ./event/trait.ContextualEventHandler.html
 //! [`EventHandler`]: ./event/trait.EventHandler.html
 //! [`vst_init!`]: ./macro.vst_init.html
+//! [`clap_init!`]: ./macro.clap_init.html
+//! [`ClapHost`]: ./backend/clap_backend/struct.ClapHost.html
+//! [`ClapPluginMeta`]: ./backend/clap_backend/trait.ClapPluginMeta.html
 //! [`jack_backend::run()`]:  ./backend/jack_backend/fn.run.html
 //! [`combined::run()`]: backend/combined/fn.run.html
-//! [`HostCallback`]: ./backend/vst_backend/vst/plugin/struct.HostCallback.html
+//! [`VstHost`]: ./backend/vst_backend/struct.VstHost.html
 //! [`HostInterface`]: ./backend/trait.HostInterface.html
 //! [`JackHost`]: ./backend/jack_backend/struct.JackHost.html
 //! [`AudioHandler`]: ./trait.AudioHandler.html
+//! [`latency_in_frames`]: ./trait.AudioHandler.html#method.latency_in_frames
 //! [`MidiWriterWrapper`]: ./backend/combined/struct.MidiWriterWrapper.html
 //! [`CommonMidiPortMeta`]: ./trait.CommonMidiPortMeta.html
 //! [`VstPluginMeta`]: ./backend/vst_backend/trait.VstPluginMeta.html
 //! [`MidiWriterWrapper`]: ./backend/combined/struct.MidiWriterWrapper.html
 //! [`stop`]: ./backend/trait.HostInterface.html#method.stop
 //! [`Stop`]: ./backend/trait.Stop.html
+//! [`TransportInfo`]: ./backend/trait.TransportInfo.html
+//! [`MidiOutput`]: ./backend/trait.MidiOutput.html
 //! [`fill_buffer`]: ./backend/combined/trait.AudioReader.html#tymethod.fill_buffer
 //! [`AudioReader`]: ./backend/combined/trait.AudioReader.html
+//! [`ParameterHandler`]: ./trait.ParameterHandler.html
+//! [`Parameters`]: ./parameters/trait.Parameters.html
+//! [`ParameterStore`]: ./parameters/struct.ParameterStore.html
+//! [`NoParameters`]: ./parameters/struct.NoParameters.html
+//! [`parameters`]: ./parameters/index.html
+//! [`PluginState`]: ./state/trait.PluginState.html
+//! [`state`]: ./state/index.html
+//! [`save_state`]: ./backend/jack_backend/fn.save_state.html
+//! [`load_state`]: ./backend/jack_backend/fn.load_state.html
 
 #[macro_use]
 extern crate log;
 
 use crate::buffer::AudioBufferInOut;
 use crate::meta::{AudioPort, General, Meta, MidiPort, Name, Port};
+use crate::parameters::Parameters;
 use std::fmt::{Error, Write};
+use std::sync::Arc;
 
 #[macro_use]
 pub mod buffer;
 pub mod backend;
 pub mod envelope;
 pub mod event;
+pub mod hosting;
 pub mod meta;
+pub mod parameters;
+#[cfg(feature = "standalone")]
+pub mod standalone;
+pub mod state;
 pub mod test_utilities;
 pub mod utilities;
 
@@ -251,6 +603,19 @@ pub trait AudioHandler {
     // TODO: Looking at the WikiPedia list https://en.wikipedia.org/wiki/Sample_rate, it seems that
     // TODO: there are no fractional sample rates. Maybe change the data type into u32?
     fn set_sample_rate(&mut self, sample_rate: f64);
+
+    /// The latency introduced by the plugin or application, in frames.
+    /// E.g. a lookahead limiter that delays its output by 64 frames should return `64`.
+    ///
+    /// Backends typically query the latency only once, so this method should return the same
+    /// value every time it is called.
+    /// _Note_: the VST backend queries the latency when the plugin is loaded,
+    /// which may be before `set_sample_rate` is called.
+    ///
+    /// The default implementation returns `0`.
+    fn latency_in_frames(&self) -> u32 {
+        0
+    }
 }
 
 /// Define the maximum number of midi inputs and the maximum number of midi outputs.
@@ -267,6 +632,31 @@ pub trait MidiHandlerMeta {
     fn max_number_of_midi_outputs(&self) -> usize;
 }
 
+/// Define the parameters of the plugin or application.
+///
+/// The parameters are stored in a separate data structure that implements the [`Parameters`]
+/// trait and that can be shared between threads, so that the host (or e.g. a GUI) can change
+/// the parameters while the plugin is rendering audio.
+///
+/// See the documentation of the [`parameters`] module for more information and an example.
+///
+/// [`Parameters`]: ./parameters/trait.Parameters.html
+/// [`parameters`]: ./parameters/index.html
+pub trait ParameterHandler {
+    /// The data type that stores the parameters.
+    ///
+    /// Note
+    /// ----
+    /// Use [`NoParameters`] if the plugin or application has no parameters.
+    ///
+    /// [`NoParameters`]: ./parameters/struct.NoParameters.html
+    type Parameters: Parameters + 'static;
+
+    /// Get a handle to the parameters.
+    /// The returned handle should always refer to the same parameters.
+    fn parameters(&self) -> Arc<Self::Parameters>;
+}
+
 // TODO: Is this trait actually used?
 /// Defines how audio is rendered.
 ///
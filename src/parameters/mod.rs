@@ -0,0 +1,436 @@
+//! Mechanisms for defining the parameters of a plugin or application.
+//!
+//! Parameters are values that can be changed by the host or the user while the plugin
+//! or application is running, e.g. a "gain" or a "cutoff frequency".
+//! Parameters are typically changed from another thread than the thread that is rendering
+//! the audio (e.g. the GUI thread of the host).
+//! For this reason, the parameters are not stored in the plugin itself, but in a separate
+//! data structure that implements the [`Parameters`] trait, which can be shared between threads.
+//!
+//! A plugin makes its parameters available by implementing the [`ParameterHandler`] trait.
+//!
+//! Values of parameters are "normalized": they are in the range from `0.0` to `1.0`.
+//! The [`ParameterRange`] of a parameter defines how normalized values map to "plain" values
+//! (the values that are displayed to the user and that are used in the audio rendering).
+//!
+//! Example
+//! -------
+//! The [`ParameterStore`] struct can be used for storing the values of the parameters,
+//! so that you don't need to implement the [`Parameters`] trait yourself.
+//! ```
+//! use std::sync::Arc;
+//! use rsynth::ParameterHandler;
+//! use rsynth::parameters::{Parameters, ParameterStore, ParameterInfo, ParameterRange};
+//!
+//! struct MyPlugin {
+//!     parameters: Arc<ParameterStore<&'static str>>,
+//!     // ...
+//! }
+//!
+//! impl MyPlugin {
+//!     pub fn new() -> Self {
+//!         Self {
+//!             parameters: Arc::new(ParameterStore::new(vec![
+//!                 ParameterInfo {
+//!                     name: "gain",
+//!                     label: "dB",
+//!                     range: ParameterRange::new(-60.0, 6.0),
+//!                     default_value: 0.0,
+//!                 }
+//!             ]))
+//!         }
+//!     }
+//! }
+//!
+//! impl ParameterHandler for MyPlugin {
+//!     type Parameters = ParameterStore<&'static str>;
+//!     fn parameters(&self) -> Arc<Self::Parameters> {
+//!         self.parameters.clone()
+//!     }
+//! }
+//!
+//! let plugin = MyPlugin::new();
+//! // The parameters can be read and written from any thread.
+//! let parameters = plugin.parameters();
+//! parameters.set_plain_value(0, -6.0);
+//! assert_eq!(parameters.plain_value(0), -6.0);
+//! ```
+//!
+//! [`Parameters`]: ./trait.Parameters.html
+//! [`ParameterHandler`]: ../trait.ParameterHandler.html
+//! [`ParameterRange`]: ./struct.ParameterRange.html
+//! [`ParameterStore`]: ./struct.ParameterStore.html
+use crate::meta::Name;
+use std::fmt::{Error, Write};
+#[cfg(any(feature = "backend-vst", feature = "backend-jack"))]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Define the parameters of a plugin or application.
+///
+/// All methods take `&self`, so that the parameters can be shared between threads
+/// (e.g. by wrapping them in an `Arc`).
+/// Setting a value therefore requires interior mutability, e.g. by using atomics.
+/// You can use the [`ParameterStore`] struct if you don't want to implement this yourself.
+///
+/// Parameters are identified by their index, which is strictly smaller than
+/// `self.number_of_parameters()`.
+///
+/// [`ParameterStore`]: ./struct.ParameterStore.html
+pub trait Parameters: Send + Sync {
+    /// The number of parameters.
+    /// This method should return the same value every time it is called.
+    fn number_of_parameters(&self) -> usize;
+
+    /// Write the name of the parameter with the given index to the given buffer.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`.
+    fn parameter_name<W: Write>(&self, buffer: &mut W, index: usize) -> Result<(), Error> {
+        write!(buffer, "parameter {}", index)
+    }
+
+    /// Write the label (the unit, e.g. "dB" or "Hz") of the parameter with the given index
+    /// to the given buffer.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`.
+    ///
+    /// The default implementation writes nothing.
+    fn parameter_label<W: Write>(&self, _buffer: &mut W, _index: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The range of the plain values of the parameter with the given index.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`.
+    ///
+    /// The default implementation returns the range from `0.0` to `1.0`.
+    fn parameter_range(&self, _index: usize) -> ParameterRange {
+        ParameterRange::default()
+    }
+
+    /// The default normalized value of the parameter with the given index.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`.
+    ///
+    /// The default implementation returns `0.0`.
+    fn default_normalized_value(&self, _index: usize) -> f32 {
+        0.0
+    }
+
+    /// Write a human readable representation of the given normalized value of the parameter
+    /// with the given index to the given buffer.
+    /// The label should not be included.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`.
+    ///
+    /// The default implementation writes the plain value with two decimals.
+    fn write_value<W: Write>(
+        &self,
+        buffer: &mut W,
+        index: usize,
+        normalized_value: f32,
+    ) -> Result<(), Error> {
+        write!(
+            buffer,
+            "{:.2}",
+            self.parameter_range(index).denormalize(normalized_value)
+        )
+    }
+
+    /// Get the current normalized value of the parameter with the given index.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`.
+    fn normalized_value(&self, index: usize) -> f32;
+
+    /// Set the normalized value of the parameter with the given index.
+    /// You can assume that `index` is strictly smaller than `self.number_of_parameters()`
+    /// and that `0.0 <= value <= 1.0`.
+    fn set_normalized_value(&self, index: usize, value: f32);
+
+    /// Get the current plain value of the parameter with the given index.
+    fn plain_value(&self, index: usize) -> f32 {
+        self.parameter_range(index)
+            .denormalize(self.normalized_value(index))
+    }
+
+    /// Set the plain value of the parameter with the given index.
+    /// Values outside the range of the parameter are clamped.
+    fn set_plain_value(&self, index: usize, value: f32) {
+        let normalized_value = self.parameter_range(index).normalize(value);
+        self.set_normalized_value(index, normalized_value);
+    }
+}
+
+/// The range of the plain values of a parameter.
+///
+/// A normalized value of `0.0` corresponds to `min` and a normalized value of `1.0`
+/// corresponds to `max`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParameterRange {
+    /// The plain value that corresponds to the normalized value `0.0`.
+    pub min: f32,
+    /// The plain value that corresponds to the normalized value `1.0`.
+    pub max: f32,
+}
+
+impl ParameterRange {
+    /// Create a new `ParameterRange`.
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    /// Convert a plain value to a normalized value.
+    /// Values outside the range are clamped.
+    pub fn normalize(&self, plain_value: f32) -> f32 {
+        if self.max == self.min {
+            return 0.0;
+        }
+        let normalized = (plain_value - self.min) / (self.max - self.min);
+        normalized.clamp(0.0, 1.0)
+    }
+
+    /// Convert a normalized value to a plain value.
+    /// Normalized values outside the range from `0.0` to `1.0` are clamped.
+    pub fn denormalize(&self, normalized_value: f32) -> f32 {
+        let normalized_value = normalized_value.clamp(0.0, 1.0);
+        self.min + normalized_value * (self.max - self.min)
+    }
+}
+
+impl Default for ParameterRange {
+    fn default() -> Self {
+        Self { min: 0.0, max: 1.0 }
+    }
+}
+
+#[test]
+fn parameter_range_normalize_works() {
+    let range = ParameterRange::new(-10.0, 30.0);
+    assert_eq!(range.normalize(-10.0), 0.0);
+    assert_eq!(range.normalize(10.0), 0.5);
+    assert_eq!(range.normalize(30.0), 1.0);
+    assert_eq!(range.normalize(-20.0), 0.0);
+    assert_eq!(range.normalize(40.0), 1.0);
+}
+
+#[test]
+fn parameter_range_denormalize_works() {
+    let range = ParameterRange::new(-10.0, 30.0);
+    assert_eq!(range.denormalize(0.0), -10.0);
+    assert_eq!(range.denormalize(0.5), 10.0);
+    assert_eq!(range.denormalize(1.0), 30.0);
+    assert_eq!(range.denormalize(2.0), 30.0);
+}
+
+/// Describes a parameter that is stored in a [`ParameterStore`].
+///
+/// The type parameter `N` is the data type used for the name and the label,
+/// e.g. `&'static str` or `String`.
+///
+/// [`ParameterStore`]: ./struct.ParameterStore.html
+pub struct ParameterInfo<N> {
+    /// The name of the parameter.
+    pub name: N,
+    /// The label (unit) of the parameter, e.g. "dB" or "Hz".
+    pub label: N,
+    /// The range of the plain values.
+    pub range: ParameterRange,
+    /// The default value, as a plain value.
+    pub default_value: f32,
+}
+
+/// Stores the values of parameters in atomics, so that they can be read and
+/// written from different threads without locking.
+///
+/// Normalized values outside of the range `0.0..=1.0` are clamped when they are set;
+/// values that are not a number are ignored.
+///
+/// See the [module level documentation] for an example.
+///
+/// [module level documentation]: ./index.html
+pub struct ParameterStore<N> {
+    infos: Vec<ParameterInfo<N>>,
+    values: Vec<AtomicU32>,
+}
+
+impl<N> ParameterStore<N> {
+    /// Create a new `ParameterStore` with the given parameters.
+    /// The value of each parameter is initialized to its default value.
+    pub fn new(infos: Vec<ParameterInfo<N>>) -> Self {
+        let values = infos
+            .iter()
+            .map(|info| AtomicU32::new(info.range.normalize(info.default_value).to_bits()))
+            .collect();
+        Self { infos, values }
+    }
+
+    /// Get the descriptions of the parameters.
+    pub fn infos(&self) -> &[ParameterInfo<N>] {
+        &self.infos
+    }
+}
+
+impl<N> Parameters for ParameterStore<N>
+where
+    N: Name + Send + Sync,
+{
+    fn number_of_parameters(&self) -> usize {
+        self.infos.len()
+    }
+
+    fn parameter_name<W: Write>(&self, buffer: &mut W, index: usize) -> Result<(), Error> {
+        self.infos[index].name.write_name(buffer)
+    }
+
+    fn parameter_label<W: Write>(&self, buffer: &mut W, index: usize) -> Result<(), Error> {
+        self.infos[index].label.write_name(buffer)
+    }
+
+    fn parameter_range(&self, index: usize) -> ParameterRange {
+        self.infos[index].range
+    }
+
+    fn default_normalized_value(&self, index: usize) -> f32 {
+        let info = &self.infos[index];
+        info.range.normalize(info.default_value)
+    }
+
+    fn normalized_value(&self, index: usize) -> f32 {
+        f32::from_bits(self.values[index].load(Ordering::Relaxed))
+    }
+
+    fn set_normalized_value(&self, index: usize, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        self.values[index].store(value.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Keeps track of which parameters have been changed by the host or the user, so that the
+/// changes can be delivered to the plugin as events.
+///
+/// The parameters may be changed from any thread, so this uses atomics.
+#[cfg(any(feature = "backend-vst", feature = "backend-jack"))]
+pub(crate) struct ChangedParameters {
+    any_changed: AtomicBool,
+    changed: Vec<AtomicBool>,
+}
+
+#[cfg(any(feature = "backend-vst", feature = "backend-jack"))]
+impl ChangedParameters {
+    pub(crate) fn new(number_of_parameters: usize) -> Self {
+        Self {
+            any_changed: AtomicBool::new(false),
+            changed: (0..number_of_parameters)
+                .map(|_| AtomicBool::new(false))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn mark_changed(&self, index: usize) {
+        if let Some(changed) = self.changed.get(index) {
+            changed.store(true, Ordering::Release);
+            self.any_changed.store(true, Ordering::Release);
+        }
+    }
+
+    #[cfg(feature = "backend-vst")]
+    pub(crate) fn mark_all_changed(&self) {
+        for changed in self.changed.iter() {
+            changed.store(true, Ordering::Release);
+        }
+        self.any_changed.store(true, Ordering::Release);
+    }
+
+    /// Call `f` with the index of each parameter that has been changed since the previous call.
+    pub(crate) fn drain<F>(&self, mut f: F)
+    where
+        F: FnMut(usize),
+    {
+        if !self.any_changed.swap(false, Ordering::Acquire) {
+            return;
+        }
+        for (index, changed) in self.changed.iter().enumerate() {
+            if changed.swap(false, Ordering::Acquire) {
+                f(index);
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "backend-vst", feature = "backend-jack"))]
+#[test]
+fn changed_parameters_reports_each_changed_parameter_once() {
+    let changed_parameters = ChangedParameters::new(3);
+    changed_parameters.mark_changed(2);
+    changed_parameters.mark_changed(0);
+    changed_parameters.mark_changed(2);
+    // Out of bounds: ignored.
+    changed_parameters.mark_changed(3);
+    let mut changed = Vec::new();
+    changed_parameters.drain(|index| changed.push(index));
+    assert_eq!(changed, vec![0, 2]);
+    changed_parameters.drain(|index| changed.push(index));
+    assert_eq!(changed, vec![0, 2]);
+}
+
+#[test]
+fn parameter_store_initializes_to_default_values() {
+    let store = ParameterStore::new(vec![
+        ParameterInfo {
+            name: "a",
+            label: "",
+            range: ParameterRange::new(0.0, 10.0),
+            default_value: 5.0,
+        },
+        ParameterInfo {
+            name: "b",
+            label: "Hz",
+            range: ParameterRange::new(20.0, 20020.0),
+            default_value: 20.0,
+        },
+    ]);
+    assert_eq!(store.number_of_parameters(), 2);
+    assert_eq!(store.normalized_value(0), 0.5);
+    assert_eq!(store.plain_value(0), 5.0);
+    assert_eq!(store.normalized_value(1), 0.0);
+}
+
+#[test]
+fn parameter_store_set_and_get_works() {
+    let store = ParameterStore::new(vec![ParameterInfo {
+        name: "a",
+        label: "dB",
+        range: ParameterRange::new(0.0, 10.0),
+        default_value: 0.0,
+    }]);
+    store.set_normalized_value(0, 0.25);
+    assert_eq!(store.normalized_value(0), 0.25);
+    assert_eq!(store.plain_value(0), 2.5);
+    store.set_plain_value(0, 20.0);
+    assert_eq!(store.normalized_value(0), 1.0);
+    store.set_normalized_value(0, -0.5);
+    assert_eq!(store.normalized_value(0), 0.0);
+    store.set_normalized_value(0, f32::NAN);
+    assert_eq!(store.normalized_value(0), 0.0);
+    store.set_normalized_value(0, f32::INFINITY);
+    assert_eq!(store.normalized_value(0), 1.0);
+
+    let mut name = String::new();
+    store.parameter_name(&mut name, 0).unwrap();
+    assert_eq!(name, "a");
+    let mut value = String::new();
+    store.write_value(&mut value, 0, 0.5).unwrap();
+    assert_eq!(value, "5.00");
+}
+
+/// Parameters for a plugin or application that has no parameters.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct NoParameters;
+
+impl Parameters for NoParameters {
+    fn number_of_parameters(&self) -> usize {
+        0
+    }
+
+    fn normalized_value(&self, _index: usize) -> f32 {
+        0.0
+    }
+
+    fn set_normalized_value(&self, _index: usize, _value: f32) {}
+}
@@ -0,0 +1,832 @@
+//! Run a plugin as a standalone application (behind the `standalone` feature).
+//!
+//! The examples in the `examples` folder each wire up a backend by hand. This module does
+//! this once: the [`standalone_main!`] macro defines a `main` function that parses the
+//! command line arguments and runs the plugin either live with Jack or offline, rendering a
+//! midi file to a `.wav` file with [`combined::run`].
+//!
+//! Support is only enabled if you compile with the "standalone" feature, see
+//! [the cargo reference] for more information on setting cargo features.
+//! Running live requires the "backend-jack" feature as well.
+//!
+//! # Command line arguments
+//! ```text
+//! --backend <jack|offline>  the backend to use (default: offline if --output is given, jack otherwise)
+//! --sample-rate <frames>    the sample rate for offline rendering (default: 44100)
+//! --buffer-size <frames>    the buffer size for offline rendering (default: 256)
+//! --midi <file>             the midi file to render offline
+//! --output <file>           the .wav file to write when rendering offline
+//! --client-name <name>      the Jack client name (default: the name of the plugin)
+//! --help                    print the usage and exit
+//! ```
+//! With Jack, the sample rate and the buffer size are determined by the Jack server.
+//!
+//! When rendering offline, the events of all tracks of the midi file are merged and
+//! rendered: midi events, system exclusive messages and meta events are delivered to the plugin
+//! with input index `0`, and the tempo and time signature of the midi file are available
+//! as transport information. The output is one second longer than the midi file, so that notes
+//! can fade out.
+//!
+//! # Example
+//! ```no_run
+//! # #[macro_use] extern crate rsynth;
+//! # use rsynth::meta::{InOut, Meta, MetaData};
+//! # use rsynth::event::{ContextualEventHandler, Indexed, MetaEvent, ParameterChange, RawMidiEvent, SysExEvent, Timed};
+//! # use rsynth::buffer::AudioBufferInOut;
+//! # use rsynth::parameters::NoParameters;
+//! # use rsynth::{AudioHandler, ContextualAudioRenderer, ParameterHandler};
+//! # use std::sync::Arc;
+//! struct MyPlugin {
+//!     meta: MetaData<&'static str, &'static str, &'static str>,
+//!     // Define other fields here
+//! }
+//!
+//! # impl Meta for MyPlugin {
+//! #     type MetaData = MetaData<&'static str, &'static str, &'static str>;
+//! #     fn meta(&self) -> &Self::MetaData { &self.meta }
+//! # }
+//! # impl AudioHandler for MyPlugin {
+//! #     fn set_sample_rate(&mut self, sample_rate: f64) {}
+//! # }
+//! # impl<H> ContextualAudioRenderer<f32, H> for MyPlugin {
+//! #     fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, context: &mut H) {}
+//! # }
+//! # impl<H> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, H> for MyPlugin {
+//! #     fn handle_event(&mut self, event: Indexed<Timed<RawMidiEvent>>, context: &mut H) {}
+//! # }
+//! # impl<'a, H> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, H> for MyPlugin {
+//! #     fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, context: &mut H) {}
+//! # }
+//! # impl<'a, H> ContextualEventHandler<Indexed<Timed<MetaEvent<'a>>>, H> for MyPlugin {
+//! #     fn handle_event(&mut self, event: Indexed<Timed<MetaEvent<'a>>>, context: &mut H) {}
+//! # }
+//! # impl ParameterHandler for MyPlugin {
+//! #     type Parameters = NoParameters;
+//! #     fn parameters(&self) -> Arc<NoParameters> { Arc::new(NoParameters) }
+//! # }
+//! # impl<H> ContextualEventHandler<Timed<ParameterChange>, H> for MyPlugin {
+//! #     fn handle_event(&mut self, event: Timed<ParameterChange>, context: &mut H) {}
+//! # }
+//! // Implement the traits of the `StandalonePlugin` trait here.
+//!
+//! standalone_main!(
+//!     fn init() -> MyPlugin {
+//!         MyPlugin {
+//!             meta: MetaData {
+//!                 general_meta: "my_synth",
+//!                 audio_port_meta: InOut {
+//!                     inputs: Vec::new(),
+//!                     outputs: vec!["left", "right"],
+//!                 },
+//!                 midi_port_meta: InOut {
+//!                     inputs: vec!["midi in"],
+//!                     outputs: Vec::new(),
+//!                 },
+//!             },
+//!         }
+//!     }
+//! );
+//! ```
+//! The application can then be used as follows:
+//! ```text
+//! my_synth --client-name my_synth
+//! my_synth --midi song.mid --output song.wav --sample-rate 48000
+//! ```
+//!
+//! [`standalone_main!`]: ../macro.standalone_main.html
+//! [`combined::run`]: ../backend/combined/fn.run.html
+//! [the cargo reference]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
+use crate::backend::combined::dummy::{AudioDummy, MidiDummy};
+use crate::backend::combined::hound::{HoundAudioError, HoundAudioWriter};
+use crate::backend::combined::midly::midly::{self, Smf};
+use crate::backend::combined::midly::{
+    synthetic_transport, MidlyMidiReaderError, MidlyMultiTrackReader,
+};
+use crate::backend::combined::{self, CombinedError, MidiWriterWrapper, RunOptions};
+use crate::event::{ContextualEventHandler, Indexed, MetaEvent, RawMidiEvent, SysExEvent, Timed};
+use crate::{
+    AudioHandler, CommonAudioPortMeta, CommonMidiPortMeta, CommonPluginMeta,
+    ContextualAudioRenderer,
+};
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[cfg(feature = "backend-jack")]
+use crate::backend::jack_backend::{self, jack, JackConfig, JackHost};
+#[cfg(feature = "backend-jack")]
+use crate::event::ParameterChange;
+#[cfg(feature = "backend-jack")]
+use crate::ParameterHandler;
+
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const DEFAULT_BUFFER_SIZE_IN_FRAMES: usize = 256;
+const TAIL_IN_SECONDS: u32 = 1;
+const MICROSECONDS_PER_SECOND: u64 = 1_000_000;
+
+/// The backend that is used to run the plugin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// Run the plugin live with Jack (requires the "backend-jack" feature).
+    Jack,
+    /// Render a midi file to a `.wav` file.
+    Offline,
+}
+
+/// The options of a standalone application, usually parsed from the command line arguments
+/// with the [`parse`] method.
+///
+/// [`parse`]: #method.parse
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StandaloneOptions {
+    /// The backend, or `None` to choose the backend based on the other options.
+    pub backend: Option<Backend>,
+    /// The sample rate for offline rendering, or `None` to use 44100 frames per second.
+    pub sample_rate: Option<u32>,
+    /// The buffer size for offline rendering, or `None` to use 256 frames.
+    pub buffer_size_in_frames: Option<usize>,
+    /// The midi file to render offline.
+    pub midi_file: Option<PathBuf>,
+    /// The `.wav` file to write when rendering offline.
+    pub output_file: Option<PathBuf>,
+    /// The Jack client name, or `None` to use the name of the plugin.
+    pub client_name: Option<String>,
+    /// `true` if the usage should be printed instead of running the plugin.
+    pub help: bool,
+}
+
+impl StandaloneOptions {
+    /// Parse the options from the command line arguments (without the name of the program).
+    pub fn parse<I, S>(arguments: I) -> Result<Self, StandaloneError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut options = StandaloneOptions::default();
+        let mut arguments = arguments.into_iter().map(Into::into);
+        while let Some(argument) = arguments.next() {
+            if argument == "--help" || argument == "-h" {
+                options.help = true;
+                continue;
+            }
+            let mut value = || {
+                arguments.next().ok_or_else(|| {
+                    StandaloneError::InvalidArguments(format!("missing value for {}", argument))
+                })
+            };
+            match argument.as_str() {
+                "--backend" => {
+                    options.backend = Some(match value()?.as_str() {
+                        "jack" => Backend::Jack,
+                        "offline" => Backend::Offline,
+                        other => {
+                            return Err(StandaloneError::InvalidArguments(format!(
+                                "unknown backend: {}",
+                                other
+                            )));
+                        }
+                    })
+                }
+                "--sample-rate" => options.sample_rate = Some(parse_number(&argument, value()?)?),
+                "--buffer-size" => {
+                    options.buffer_size_in_frames = Some(parse_number(&argument, value()?)?)
+                }
+                "--midi" => options.midi_file = Some(PathBuf::from(value()?)),
+                "--output" => options.output_file = Some(PathBuf::from(value()?)),
+                "--client-name" => options.client_name = Some(value()?),
+                _ => {
+                    return Err(StandaloneError::InvalidArguments(format!(
+                        "unknown argument: {}",
+                        argument
+                    )));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// The usage, to be printed when the user passes `--help` or invalid arguments.
+    pub fn usage(program_name: &str) -> String {
+        format!(
+            "Usage: {} [options]\n\
+             \n\
+             Options:\n\
+             \x20 --backend <jack|offline>  the backend to use (default: offline if --output is given, jack otherwise)\n\
+             \x20 --sample-rate <frames>    the sample rate for offline rendering (default: {})\n\
+             \x20 --buffer-size <frames>    the buffer size for offline rendering (default: {})\n\
+             \x20 --midi <file>             the midi file to render offline\n\
+             \x20 --output <file>           the .wav file to write when rendering offline\n\
+             \x20 --client-name <name>      the Jack client name (default: the name of the plugin)\n\
+             \x20 --help                    print this message and exit\n",
+            program_name, DEFAULT_SAMPLE_RATE, DEFAULT_BUFFER_SIZE_IN_FRAMES
+        )
+    }
+
+    /// The backend to use: the backend that was chosen explicitly, otherwise offline rendering
+    /// if an output file is given and Jack if not.
+    pub fn backend(&self) -> Backend {
+        match (self.backend, &self.output_file) {
+            (Some(backend), _) => backend,
+            (None, Some(_)) => Backend::Offline,
+            (None, None) => Backend::Jack,
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(argument: &str, value: String) -> Result<T, StandaloneError> {
+    value.parse().map_err(|_| {
+        StandaloneError::InvalidArguments(format!("invalid value for {}: {}", argument, value))
+    })
+}
+
+/// Error that can occur when running a standalone application.
+#[derive(Debug)]
+pub enum StandaloneError {
+    /// The command line arguments are invalid.
+    InvalidArguments(String),
+    /// The backend is not available because `rsynth` was compiled without the corresponding
+    /// feature.
+    BackendNotAvailable(Backend),
+    /// The plugin has no audio outputs, so there is nothing to write to the `.wav` file.
+    NoAudioOutputs,
+    /// Failed to read the midi file.
+    Io(io::Error),
+    /// Failed to parse the midi file.
+    Midi(midly::Error),
+    /// The timing of the midi file is not supported.
+    MidiTiming(MidlyMidiReaderError),
+    /// Failed to write the `.wav` file.
+    Wav(hound::Error),
+    /// The format of the `.wav` file is not supported.
+    WavFormat(HoundAudioError),
+    /// An error occurred in Jack.
+    #[cfg(feature = "backend-jack")]
+    Jack(jack::Error),
+}
+
+impl Display for StandaloneError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            StandaloneError::InvalidArguments(message) => write!(f, "{}", message),
+            StandaloneError::BackendNotAvailable(backend) => write!(
+                f,
+                "the {:?} backend is not available; it was not enabled when compiling",
+                backend
+            ),
+            StandaloneError::NoAudioOutputs => write!(f, "the plugin has no audio outputs"),
+            StandaloneError::Io(e) => write!(f, "failed to read the midi file: {}", e),
+            StandaloneError::Midi(e) => write!(f, "failed to parse the midi file: {}", e),
+            StandaloneError::MidiTiming(e) => write!(f, "failed to read the midi file: {}", e),
+            StandaloneError::Wav(e) => write!(f, "failed to write the wav file: {}", e),
+            StandaloneError::WavFormat(e) => write!(f, "failed to write the wav file: {}", e),
+            #[cfg(feature = "backend-jack")]
+            StandaloneError::Jack(e) => write!(f, "jack error: {}", e),
+        }
+    }
+}
+
+impl Error for StandaloneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StandaloneError::Io(e) => Some(e),
+            StandaloneError::Midi(e) => Some(e),
+            StandaloneError::MidiTiming(e) => Some(e),
+            StandaloneError::Wav(e) => Some(e),
+            StandaloneError::WavFormat(e) => Some(e),
+            #[cfg(feature = "backend-jack")]
+            StandaloneError::Jack(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StandaloneError {
+    fn from(e: io::Error) -> Self {
+        StandaloneError::Io(e)
+    }
+}
+
+impl From<midly::Error> for StandaloneError {
+    fn from(e: midly::Error) -> Self {
+        StandaloneError::Midi(e)
+    }
+}
+
+impl From<MidlyMidiReaderError> for StandaloneError {
+    fn from(e: MidlyMidiReaderError) -> Self {
+        StandaloneError::MidiTiming(e)
+    }
+}
+
+impl From<hound::Error> for StandaloneError {
+    fn from(e: hound::Error) -> Self {
+        StandaloneError::Wav(e)
+    }
+}
+
+impl From<HoundAudioError> for StandaloneError {
+    fn from(e: HoundAudioError) -> Self {
+        StandaloneError::WavFormat(e)
+    }
+}
+
+impl From<CombinedError<Infallible, hound::Error>> for StandaloneError {
+    fn from(e: CombinedError<Infallible, hound::Error>) -> Self {
+        match e {
+            CombinedError::AudioInError(e) => match e {},
+            CombinedError::AudioOutError(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(feature = "backend-jack")]
+impl From<jack::Error> for StandaloneError {
+    fn from(e: jack::Error) -> Self {
+        StandaloneError::Jack(e)
+    }
+}
+
+/// The traits that a plugin needs to implement in order to be run live.
+///
+/// When `rsynth` is compiled with the "backend-jack" feature, these are the traits that are
+/// required by the [`jack_backend::run`] function. Otherwise, this trait is implemented for
+/// every type.
+///
+/// [`jack_backend::run`]: ../backend/jack_backend/fn.run.html
+#[cfg(feature = "backend-jack")]
+pub trait LivePlugin:
+    ParameterHandler
+    + Send
+    + Sync
+    + 'static
+    + for<'c, 'mp, 'mw> ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>
+    + for<'c, 'mp, 'mw> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, JackHost<'c, 'mp, 'mw>>
+    + for<'c, 'mp, 'mw> ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>
+    + for<'c, 'mp, 'mw, 'a> ContextualEventHandler<
+        Indexed<Timed<SysExEvent<'a>>>,
+        JackHost<'c, 'mp, 'mw>,
+    >
+{
+}
+
+#[cfg(feature = "backend-jack")]
+impl<P> LivePlugin for P where
+    P: ParameterHandler
+        + Send
+        + Sync
+        + 'static
+        + for<'c, 'mp, 'mw> ContextualAudioRenderer<f32, JackHost<'c, 'mp, 'mw>>
+        + for<'c, 'mp, 'mw> ContextualEventHandler<
+            Indexed<Timed<RawMidiEvent>>,
+            JackHost<'c, 'mp, 'mw>,
+        >
+        + for<'c, 'mp, 'mw> ContextualEventHandler<Timed<ParameterChange>, JackHost<'c, 'mp, 'mw>>
+        + for<'c, 'mp, 'mw, 'a> ContextualEventHandler<
+            Indexed<Timed<SysExEvent<'a>>>,
+            JackHost<'c, 'mp, 'mw>,
+        >
+{
+}
+
+/// The traits that a plugin needs to implement in order to be run live.
+///
+/// When `rsynth` is compiled with the "backend-jack" feature, these are the traits that are
+/// required by the [`jack_backend::run`] function. Otherwise, this trait is implemented for
+/// every type.
+///
+/// [`jack_backend::run`]: ../backend/jack_backend/fn.run.html
+#[cfg(not(feature = "backend-jack"))]
+pub trait LivePlugin {}
+
+#[cfg(not(feature = "backend-jack"))]
+impl<P> LivePlugin for P {}
+
+/// The traits that a plugin needs to implement in order to be run with the
+/// [`standalone_main!`] macro: the traits for offline rendering with
+/// [`combined::run`] and the traits of [`LivePlugin`].
+///
+/// This trait is implemented for every type that implements these traits.
+///
+/// [`standalone_main!`]: ../macro.standalone_main.html
+/// [`combined::run`]: ../backend/combined/fn.run.html
+/// [`LivePlugin`]: ./trait.LivePlugin.html
+pub trait StandalonePlugin:
+    CommonPluginMeta
+    + AudioHandler
+    + CommonAudioPortMeta
+    + CommonMidiPortMeta
+    + ContextualAudioRenderer<f32, MidiWriterWrapper<MidiDummy>>
+    + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, MidiWriterWrapper<MidiDummy>>
+    + for<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, MidiWriterWrapper<MidiDummy>>
+    + for<'a> ContextualEventHandler<Indexed<Timed<MetaEvent<'a>>>, MidiWriterWrapper<MidiDummy>>
+    + LivePlugin
+{
+}
+
+impl<P> StandalonePlugin for P where
+    P: CommonPluginMeta
+        + AudioHandler
+        + CommonAudioPortMeta
+        + CommonMidiPortMeta
+        + ContextualAudioRenderer<f32, MidiWriterWrapper<MidiDummy>>
+        + ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, MidiWriterWrapper<MidiDummy>>
+        + for<'a> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, MidiWriterWrapper<MidiDummy>>
+        + for<'a> ContextualEventHandler<Indexed<Timed<MetaEvent<'a>>>, MidiWriterWrapper<MidiDummy>>
+        + LivePlugin
+{
+}
+
+/// Run the plugin with the backend and the settings from the options.
+pub fn run<P>(plugin: P, options: &StandaloneOptions) -> Result<P, StandaloneError>
+where
+    P: StandalonePlugin,
+{
+    match options.backend() {
+        Backend::Jack => run_live(plugin, options),
+        Backend::Offline => {
+            let mut plugin = plugin;
+            run_offline(&mut plugin, options)?;
+            Ok(plugin)
+        }
+    }
+}
+
+#[cfg(feature = "backend-jack")]
+fn run_live<P>(plugin: P, options: &StandaloneOptions) -> Result<P, StandaloneError>
+where
+    P: StandalonePlugin,
+{
+    if options.sample_rate.is_some() || options.buffer_size_in_frames.is_some() {
+        warn!("The sample rate and buffer size are determined by the Jack server; ignoring them.");
+    }
+    let mut config = JackConfig::new();
+    if let Some(ref client_name) = options.client_name {
+        config = config.client_name(client_name.clone());
+    }
+    Ok(jack_backend::run_with_config(plugin, config)?)
+}
+
+#[cfg(not(feature = "backend-jack"))]
+fn run_live<P>(_plugin: P, _options: &StandaloneOptions) -> Result<P, StandaloneError>
+where
+    P: StandalonePlugin,
+{
+    Err(StandaloneError::BackendNotAvailable(Backend::Jack))
+}
+
+/// Render the midi file from the options to the `.wav` file from the options.
+pub fn run_offline<P>(plugin: &mut P, options: &StandaloneOptions) -> Result<(), StandaloneError>
+where
+    P: StandalonePlugin,
+{
+    let midi_file = options.midi_file.as_ref().ok_or_else(|| {
+        StandaloneError::InvalidArguments("offline rendering requires --midi".to_string())
+    })?;
+    let output_file = options.output_file.as_ref().ok_or_else(|| {
+        StandaloneError::InvalidArguments("offline rendering requires --output".to_string())
+    })?;
+    let sample_rate = options.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+    let buffer_size_in_frames = options
+        .buffer_size_in_frames
+        .unwrap_or(DEFAULT_BUFFER_SIZE_IN_FRAMES);
+    if sample_rate == 0 || buffer_size_in_frames == 0 {
+        return Err(StandaloneError::InvalidArguments(
+            "the sample rate and the buffer size must be positive".to_string(),
+        ));
+    }
+    let number_of_channels = plugin.max_number_of_audio_outputs();
+    if number_of_channels == 0 {
+        return Err(StandaloneError::NoAudioOutputs);
+    }
+
+    info!("Reading midi file {}.", midi_file.display());
+    let midi_data = fs::read(midi_file)?;
+    let smf = Smf::parse(&midi_data)?;
+
+    let duration_in_microseconds: u64 = MidlyMultiTrackReader::try_new(smf.header, &smf.tracks)?
+        .map(|event| event.microseconds_since_previous_event)
+        .sum();
+    let length_in_frames = (duration_in_microseconds * sample_rate as u64 / MICROSECONDS_PER_SECOND)
+        as usize
+        + (TAIL_IN_SECONDS * sample_rate) as usize;
+
+    let spec = hound::WavSpec {
+        channels: number_of_channels as u16,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut wav_writer = hound::WavWriter::create(output_file, spec)?;
+    info!(
+        "Rendering {} frames to {}.",
+        length_in_frames,
+        output_file.display()
+    );
+    plugin.set_sample_rate(sample_rate as f64);
+    {
+        let audio_out = HoundAudioWriter::<f32>::new(&mut wav_writer)?;
+        let audio_in = AudioDummy::with_sample_rate_and_length(sample_rate, length_in_frames);
+        let events = MidlyMultiTrackReader::try_new(smf.header, &smf.tracks)?
+            .events()
+            .with_meta_events();
+        combined::run(
+            plugin,
+            buffer_size_in_frames,
+            audio_in,
+            audio_out,
+            MidiDummy::new(),
+            RunOptions::new()
+                .midi_input(events)
+                .transport(synthetic_transport(smf.header, &smf.tracks)?)
+                .with_input_index(),
+        )?;
+    }
+    wav_writer.finalize()?;
+    Ok(())
+}
+
+/// Parse the command line arguments (including the name of the program), create the plugin
+/// and run it. Returns the exit code of the application.
+///
+/// This function is used by the [`standalone_main!`] macro.
+///
+/// [`standalone_main!`]: ../macro.standalone_main.html
+pub fn run_from_args<P, F, I>(create_plugin: F, arguments: I) -> i32
+where
+    P: StandalonePlugin,
+    F: FnOnce() -> P,
+    I: IntoIterator<Item = String>,
+{
+    let mut arguments = arguments.into_iter();
+    let program_name = arguments.next().unwrap_or_else(|| "standalone".to_string());
+    let options = match StandaloneOptions::parse(arguments) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n", e);
+            eprint!("{}", StandaloneOptions::usage(&program_name));
+            return 2;
+        }
+    };
+    if options.help {
+        print!("{}", StandaloneOptions::usage(&program_name));
+        return 0;
+    }
+    match run(create_plugin(), &options) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// Define the `main` function of a standalone application that runs the plugin
+/// with the backend and settings from the command line arguments.
+///
+/// The plugin needs to implement the [`StandalonePlugin`] trait.
+/// See the documentation of the [`standalone`] module for the command line arguments and
+/// an example.
+///
+/// [`StandalonePlugin`]: ./standalone/trait.StandalonePlugin.html
+/// [`standalone`]: ./standalone/index.html
+//
+// As with `vst_init`, we use a macro so that plugins do not have to implement the `Default`
+// trait.
+#[macro_export]
+macro_rules! standalone_main {
+    (fn $function_name:ident() -> $return_type:ty
+        $body:block
+    ) => {
+        fn $function_name () -> $return_type
+        $body
+
+        fn main() {
+            ::std::process::exit($crate::standalone::run_from_args(
+                $function_name,
+                ::std::env::args(),
+            ));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::midly::num::{u15, u28, u4, u7};
+    use super::midly::{Format, Header, MidiMessage, Timing, TrackEvent, TrackEventKind};
+    use super::*;
+    use crate::buffer::AudioBufferInOut;
+    use crate::event::ParameterChange;
+    use crate::meta::{InOut, Meta, MetaData};
+    use crate::parameters::NoParameters;
+    use crate::ParameterHandler;
+    use std::sync::Arc;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_without_arguments_returns_the_defaults() {
+        let options = StandaloneOptions::parse(Vec::<String>::new()).unwrap();
+        assert_eq!(options, StandaloneOptions::default());
+        assert_eq!(options.backend(), Backend::Jack);
+    }
+
+    #[test]
+    fn parse_reads_all_options() {
+        let options = StandaloneOptions::parse(arguments(&[
+            "--backend",
+            "offline",
+            "--sample-rate",
+            "48000",
+            "--buffer-size",
+            "128",
+            "--midi",
+            "in.mid",
+            "--output",
+            "out.wav",
+            "--client-name",
+            "synth",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options,
+            StandaloneOptions {
+                backend: Some(Backend::Offline),
+                sample_rate: Some(48000),
+                buffer_size_in_frames: Some(128),
+                midi_file: Some(PathBuf::from("in.mid")),
+                output_file: Some(PathBuf::from("out.wav")),
+                client_name: Some("synth".to_string()),
+                help: false,
+            }
+        );
+    }
+
+    #[test]
+    fn backend_is_offline_when_an_output_file_is_given() {
+        let options = StandaloneOptions::parse(arguments(&["--output", "out.wav"])).unwrap();
+        assert_eq!(options.backend(), Backend::Offline);
+        let options =
+            StandaloneOptions::parse(arguments(&["--output", "out.wav", "--backend", "jack"]))
+                .unwrap();
+        assert_eq!(options.backend(), Backend::Jack);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_arguments() {
+        for invalid in &[
+            &["--sample-rate"][..],
+            &["--sample-rate", "fast"][..],
+            &["--backend", "vst"][..],
+            &["--unknown"][..],
+        ] {
+            match StandaloneOptions::parse(arguments(invalid)) {
+                Err(StandaloneError::InvalidArguments(_)) => {}
+                other => panic!("Expected an error for {:?}, got {:?}", invalid, other),
+            }
+        }
+        assert!(
+            StandaloneOptions::parse(arguments(&["--help"]))
+                .unwrap()
+                .help
+        );
+    }
+
+    struct TestPlugin {
+        meta: MetaData<&'static str, &'static str, &'static str>,
+        sample_rate: f64,
+        level: f32,
+        sysex: Vec<Vec<u8>>,
+    }
+
+    impl Meta for TestPlugin {
+        type MetaData = MetaData<&'static str, &'static str, &'static str>;
+        fn meta(&self) -> &Self::MetaData {
+            &self.meta
+        }
+    }
+
+    impl AudioHandler for TestPlugin {
+        fn set_sample_rate(&mut self, sample_rate: f64) {
+            self.sample_rate = sample_rate;
+        }
+    }
+
+    impl<H> ContextualAudioRenderer<f32, H> for TestPlugin {
+        fn render_buffer(&mut self, buffer: &mut AudioBufferInOut<f32>, _context: &mut H) {
+            for channel in buffer.outputs().channel_iter_mut() {
+                for sample in channel.iter_mut() {
+                    *sample = self.level;
+                }
+            }
+        }
+    }
+
+    impl<H> ContextualEventHandler<Indexed<Timed<RawMidiEvent>>, H> for TestPlugin {
+        fn handle_event(&mut self, _event: Indexed<Timed<RawMidiEvent>>, _context: &mut H) {
+            self.level = 0.5;
+        }
+    }
+
+    impl<'a, H> ContextualEventHandler<Indexed<Timed<SysExEvent<'a>>>, H> for TestPlugin {
+        fn handle_event(&mut self, event: Indexed<Timed<SysExEvent<'a>>>, _context: &mut H) {
+            self.sysex.push(event.event.event.data().to_vec());
+        }
+    }
+
+    impl<'a, H> ContextualEventHandler<Indexed<Timed<MetaEvent<'a>>>, H> for TestPlugin {
+        fn handle_event(&mut self, _event: Indexed<Timed<MetaEvent<'a>>>, _context: &mut H) {}
+    }
+
+    impl ParameterHandler for TestPlugin {
+        type Parameters = NoParameters;
+        fn parameters(&self) -> Arc<NoParameters> {
+            Arc::new(NoParameters)
+        }
+    }
+
+    impl<H> ContextualEventHandler<Timed<ParameterChange>, H> for TestPlugin {
+        fn handle_event(&mut self, _event: Timed<ParameterChange>, _context: &mut H) {}
+    }
+
+    #[test]
+    fn run_offline_renders_the_midi_file_to_a_wav_file() {
+        let directory =
+            std::env::temp_dir().join(format!("rsynth-standalone-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let midi_file = directory.join("in.mid");
+        let output_file = directory.join("out.wav");
+
+        // A system exclusive message at the start and one note on after one beat,
+        // which is half a second at the default tempo.
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::from(96)),
+        ));
+        smf.tracks.push(vec![
+            TrackEvent {
+                delta: u28::from(0),
+                kind: TrackEventKind::SysEx(&[0x01, 0x02, 0xF7]),
+            },
+            TrackEvent {
+                delta: u28::from(96),
+                kind: TrackEventKind::Midi {
+                    channel: u4::from(0),
+                    message: MidiMessage::NoteOn {
+                        key: u7::from(60),
+                        vel: u7::from(100),
+                    },
+                },
+            },
+        ]);
+        smf.save(&midi_file).unwrap();
+
+        let mut plugin = TestPlugin {
+            meta: MetaData {
+                general_meta: "test plugin",
+                audio_port_meta: InOut {
+                    inputs: vec![],
+                    outputs: vec!["left", "right"],
+                },
+                midi_port_meta: InOut {
+                    inputs: vec!["midi in"],
+                    outputs: vec![],
+                },
+            },
+            sample_rate: 0.0,
+            level: 0.0,
+            sysex: Vec::new(),
+        };
+        let options = StandaloneOptions::parse(vec![
+            "--midi".to_string(),
+            midi_file.to_string_lossy().into_owned(),
+            "--output".to_string(),
+            output_file.to_string_lossy().into_owned(),
+            "--sample-rate".to_string(),
+            "1000".to_string(),
+            "--buffer-size".to_string(),
+            "100".to_string(),
+        ])
+        .unwrap();
+        run_offline(&mut plugin, &options).unwrap();
+        assert_eq!(plugin.sample_rate, 1000.0);
+        assert_eq!(plugin.sysex, vec![vec![0xF0, 0x01, 0x02, 0xF7]]);
+
+        let mut reader = hound::WavReader::open(&output_file).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 1000);
+        let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        // Half a second of midi file and one second of tail.
+        assert_eq!(samples.len(), 2 * 1500);
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(samples[2 * 1499], 0.5);
+        fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,310 @@
+//! Saving and restoring the state of a plugin or application.
+//!
+//! The state of a plugin (e.g. the values of its parameters) can be serialized to bytes
+//! and restored from bytes by implementing the [`PluginState`] trait.
+//! Both [`ParameterStore`] and [`NoParameters`] implement [`PluginState`].
+//!
+//! The [`save`] and [`load`] functions add and check a small header that contains the version
+//! of the format, so that a plugin can still restore a state that was saved by an older version
+//! of the plugin.
+//!
+//! How the state is used depends on the backend:
+//! * VST 2.4: the state is used for the preset and bank "chunks" that the host stores,
+//!   e.g. in the project file.
+//! * Jack: the state can be saved to a file and loaded from a file, see the [`save_to_file`]
+//!   and [`load_from_file`] functions.
+//! * Offline rendering: the state can be restored before rendering, see the
+//!   [`restore_state`] function of the `combined` backend.
+//!
+//! Example
+//! -------
+//! ```
+//! use rsynth::parameters::{Parameters, ParameterStore, ParameterInfo, ParameterRange};
+//! use rsynth::state;
+//!
+//! let make_store = || ParameterStore::new(vec![
+//!     ParameterInfo {
+//!         name: "gain",
+//!         label: "dB",
+//!         range: ParameterRange::new(-60.0, 6.0),
+//!         default_value: 0.0,
+//!     }
+//! ]);
+//! let original = make_store();
+//! original.set_plain_value(0, -6.0);
+//! let bytes = state::save(&original);
+//!
+//! let restored = make_store();
+//! state::load(&restored, &bytes).expect("the state should be valid");
+//! assert_eq!(restored.plain_value(0), -6.0);
+//! ```
+//!
+//! [`PluginState`]: ./trait.PluginState.html
+//! [`ParameterStore`]: ../parameters/struct.ParameterStore.html
+//! [`NoParameters`]: ../parameters/struct.NoParameters.html
+//! [`save`]: ./fn.save.html
+//! [`load`]: ./fn.load.html
+//! [`save_to_file`]: ./fn.save_to_file.html
+//! [`load_from_file`]: ./fn.load_from_file.html
+//! [`restore_state`]: ../backend/combined/fn.restore_state.html
+use crate::meta::Name;
+use crate::parameters::{NoParameters, ParameterStore, Parameters};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The bytes with which a serialized state starts.
+const MAGIC: &[u8; 4] = b"RSST";
+const HEADER_LENGTH: usize = 8;
+
+/// Serialize and restore the state of a plugin or application.
+///
+/// All methods take `&self`, so that the state can be saved and restored from any thread
+/// (VST hosts e.g. typically do this from the GUI thread).
+/// This is the same approach as for the [`Parameters`] trait and typically, the same
+/// data structure implements both traits.
+///
+/// [`Parameters`]: ../parameters/trait.Parameters.html
+pub trait PluginState {
+    /// The version of the format that is written by `save_state`.
+    ///
+    /// Increase this number when the format changes, so that `load_state` can
+    /// still restore states that have been saved in an older format.
+    /// The default implementation returns `0`.
+    fn state_version(&self) -> u32 {
+        0
+    }
+
+    /// Write the current state to the given buffer.
+    fn save_state(&self, buffer: &mut Vec<u8>);
+
+    /// Restore the state from the given data.
+    /// `version` is the value that `state_version` returned when the state was saved.
+    fn load_state(&self, version: u32, data: &[u8]) -> Result<(), StateError>;
+}
+
+/// An error that can occur when restoring the state.
+#[derive(Debug)]
+pub enum StateError {
+    /// The data does not start with a valid header.
+    InvalidHeader,
+    /// The state has been saved in a version of the format that is not supported.
+    UnsupportedVersion(u32),
+    /// The data is not valid.
+    InvalidData(&'static str),
+    /// An I/O error occurred while reading or writing the state.
+    Io(io::Error),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            StateError::InvalidHeader => write!(f, "the state does not start with a valid header"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "version {} of the state is not supported", version)
+            }
+            StateError::InvalidData(reason) => write!(f, "invalid state: {}", reason),
+            StateError::Io(ref e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl Error for StateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StateError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> Self {
+        StateError::Io(e)
+    }
+}
+
+/// Serialize the state, including a header with the version of the format.
+pub fn save<P>(state: &P) -> Vec<u8>
+where
+    P: PluginState + ?Sized,
+{
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&state.state_version().to_le_bytes());
+    state.save_state(&mut buffer);
+    buffer
+}
+
+/// Restore the state from data that has been created with the [`save`] function.
+///
+/// [`save`]: ./fn.save.html
+pub fn load<P>(state: &P, data: &[u8]) -> Result<(), StateError>
+where
+    P: PluginState + ?Sized,
+{
+    if data.len() < HEADER_LENGTH || &data[0..MAGIC.len()] != MAGIC {
+        return Err(StateError::InvalidHeader);
+    }
+    let version = u32::from_le_bytes(
+        data[MAGIC.len()..HEADER_LENGTH]
+            .try_into()
+            .expect("slice to have length 4"),
+    );
+    state.load_state(version, &data[HEADER_LENGTH..])
+}
+
+/// Save the state to the file with the given path.
+/// If the file already exists, it is overwritten.
+pub fn save_to_file<P, F>(state: &P, path: F) -> Result<(), StateError>
+where
+    P: PluginState + ?Sized,
+    F: AsRef<Path>,
+{
+    fs::write(path, save(state))?;
+    Ok(())
+}
+
+/// Restore the state from the file with the given path.
+/// The file should have been created with the [`save_to_file`] function.
+///
+/// [`save_to_file`]: ./fn.save_to_file.html
+pub fn load_from_file<P, F>(state: &P, path: F) -> Result<(), StateError>
+where
+    P: PluginState + ?Sized,
+    F: AsRef<Path>,
+{
+    let data = fs::read(path)?;
+    load(state, &data)
+}
+
+/// Saves the normalized values of all parameters.
+///
+/// When restoring, parameters that are not in the saved state keep their current value
+/// and values of parameters that no longer exist are ignored.
+impl<N> PluginState for ParameterStore<N>
+where
+    N: Name + Send + Sync,
+{
+    fn save_state(&self, buffer: &mut Vec<u8>) {
+        let number_of_parameters = self.number_of_parameters();
+        buffer.extend_from_slice(&(number_of_parameters as u32).to_le_bytes());
+        for index in 0..number_of_parameters {
+            buffer.extend_from_slice(&self.normalized_value(index).to_le_bytes());
+        }
+    }
+
+    fn load_state(&self, version: u32, data: &[u8]) -> Result<(), StateError> {
+        if version != 0 {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if data.len() < 4 {
+            return Err(StateError::InvalidData("missing number of parameters"));
+        }
+        let (number_of_parameters, values) = data.split_at(4);
+        let number_of_parameters =
+            u32::from_le_bytes(number_of_parameters.try_into().expect("length to be 4")) as usize;
+        if values.len() != number_of_parameters * 4 {
+            return Err(StateError::InvalidData(
+                "length does not match the number of parameters",
+            ));
+        }
+        let values = values
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes(value.try_into().expect("length to be 4")));
+        // Check all values first, so that invalid data does not change any parameter.
+        if values.clone().any(|value| !value.is_finite()) {
+            return Err(StateError::InvalidData("parameter value is not finite"));
+        }
+        for (index, value) in values.take(self.number_of_parameters()).enumerate() {
+            self.set_normalized_value(index, value);
+        }
+        Ok(())
+    }
+}
+
+impl PluginState for NoParameters {
+    fn save_state(&self, _buffer: &mut Vec<u8>) {}
+
+    fn load_state(&self, _version: u32, _data: &[u8]) -> Result<(), StateError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, save, StateError};
+    use crate::parameters::{ParameterInfo, ParameterRange, ParameterStore, Parameters};
+
+    fn store(number_of_parameters: usize) -> ParameterStore<&'static str> {
+        ParameterStore::new(
+            (0..number_of_parameters)
+                .map(|_| ParameterInfo {
+                    name: "p",
+                    label: "",
+                    range: ParameterRange::new(0.0, 10.0),
+                    default_value: 0.0,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parameter_store_round_trip_works() {
+        let original = store(2);
+        original.set_normalized_value(0, 0.25);
+        original.set_normalized_value(1, 0.75);
+        let data = save(&original);
+
+        let restored = store(2);
+        load(&restored, &data).expect("Unexpected error.");
+        assert_eq!(restored.normalized_value(0), 0.25);
+        assert_eq!(restored.normalized_value(1), 0.75);
+    }
+
+    #[test]
+    fn parameter_store_ignores_parameters_that_no_longer_exist() {
+        let original = store(2);
+        original.set_normalized_value(0, 0.25);
+        original.set_normalized_value(1, 0.75);
+        let data = save(&original);
+
+        let restored = store(1);
+        load(&restored, &data).expect("Unexpected error.");
+        assert_eq!(restored.normalized_value(0), 0.25);
+    }
+
+    #[test]
+    fn load_rejects_invalid_data() {
+        let restored = store(1);
+        match load(&restored, b"nonsense") {
+            Err(StateError::InvalidHeader) => {}
+            other => panic!("Expected an invalid header, got {:?}", other),
+        }
+        match load(&restored, b"RSST\x01\x00\x00\x00") {
+            Err(StateError::UnsupportedVersion(1)) => {}
+            other => panic!("Expected an unsupported version, got {:?}", other),
+        }
+        match load(&restored, b"RSST\x00\x00\x00\x00\x01\x00\x00\x00") {
+            Err(StateError::InvalidData(_)) => {}
+            other => panic!("Expected invalid data, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_rejects_values_that_are_not_finite() {
+        let restored = store(2);
+        restored.set_normalized_value(0, 0.25);
+        let mut data = b"RSST\x00\x00\x00\x00\x02\x00\x00\x00".to_vec();
+        data.extend_from_slice(&0.5f32.to_le_bytes());
+        data.extend_from_slice(&f32::NAN.to_le_bytes());
+        match load(&restored, &data) {
+            Err(StateError::InvalidData(_)) => {}
+            other => panic!("Expected invalid data, got {:?}", other),
+        }
+        assert_eq!(restored.normalized_value(0), 0.25);
+    }
+}